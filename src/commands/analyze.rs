@@ -0,0 +1,105 @@
+use ::Server;
+use ::commands::{Command, CommandResult, ExecutionError};
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command for collecting the statistics of one or more tables, which are stored in each
+/// table's header page for the planner to use.
+pub struct AnalyzeCommand {
+    table_names: Vec<String>,
+}
+
+impl AnalyzeCommand {
+    /// Creates a new analyze command.
+    ///
+    /// # Arguments
+    /// * table_names - The names of the tables to analyze.
+    pub fn new(table_names: Vec<String>) -> AnalyzeCommand {
+        AnalyzeCommand { table_names: table_names }
+    }
+}
+
+impl Command for AnalyzeCommand {
+    fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
+        // Check every table before analyzing any of them.
+        for table_name in self.table_names.iter() {
+            if !server.table_manager.table_exists(&server.file_manager, table_name.as_str()) {
+                return Err(ExecutionError::TableDoesNotExist(table_name.clone()));
+            }
+        }
+
+        for table_name in self.table_names.iter() {
+            let table = try!(server.table_manager
+                .get_table(&server.file_manager, table_name.as_str())
+                .map_err(|e| ExecutionError::CouldNotOpenTable(table_name.clone(), e)));
+            let stats = try!(table.analyze()
+                .map_err(|e| ExecutionError::CouldNotAnalyzeTable(table_name.clone(), e)));
+            println!("Analyzed table {}: {} tuples in {} pages.",
+                     table_name,
+                     stats.num_tuples,
+                     stats.num_data_pages);
+        }
+        Ok(None)
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::expressions::Literal;
+    use ::parser::statements;
+
+    #[test]
+    fn test_analyze() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        let sql = b"CREATE TABLE foo (a INTEGER, b VARCHAR(20)); \
+                    INSERT INTO foo VALUES (3, 'x'); \
+                    INSERT INTO foo VALUES (1, NULL); \
+                    INSERT INTO foo VALUES (3, 'y'); \
+                    CREATE TABLE bar (c INTEGER) ORDER BY (c); \
+                    INSERT INTO bar VALUES (5)";
+        for stmt in statements(sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        // A table that hasn't been analyzed has no statistics yet.
+        let table = server.table_manager.get_table(&server.file_manager, "FOO").unwrap();
+        assert_eq!(0, table.get_stats().num_tuples);
+        assert_eq!(None, table.get_stats().column_stats[0].num_unique_values);
+
+        for stmt in statements(b"ANALYZE foo, bar").unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        let stats = table.get_stats();
+        assert_eq!(3, stats.num_tuples);
+        assert_eq!(1, stats.num_data_pages);
+        assert!(stats.avg_tuple_size > 4.0);
+        assert_eq!(Some(2), stats.column_stats[0].num_unique_values);
+        assert_eq!(Some(0), stats.column_stats[0].num_null_values);
+        assert_eq!(Literal::Int(1), stats.column_stats[0].min_value);
+        assert_eq!(Literal::Int(3), stats.column_stats[0].max_value);
+        assert_eq!(Some(2), stats.column_stats[1].num_unique_values);
+        assert_eq!(Some(1), stats.column_stats[1].num_null_values);
+        assert_eq!(Literal::String("y".into()), stats.column_stats[1].max_value);
+
+        let bar = server.table_manager.get_table(&server.file_manager, "BAR").unwrap();
+        assert_eq!(1, bar.get_stats().num_tuples);
+        assert_eq!(Literal::Int(5), bar.get_stats().column_stats[0].min_value);
+
+        // The statistics are read back from the header page when the table is reopened.
+        let reopened = Server::with_data_path(dir.path());
+        let table = reopened.table_manager.get_table(&reopened.file_manager, "FOO").unwrap();
+        assert_eq!(stats, table.get_stats());
+
+        let mut command = AnalyzeCommand::new(vec!["FOO".into(), "BAZ".into()]);
+        assert_eq!(Err(ExecutionError::TableDoesNotExist("BAZ".into())),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+    }
+}
@@ -1,5 +1,6 @@
 use ::{ColumnInfo, ColumnType, Schema, Server};
 use ::commands::{Command, CommandResult, ExecutionError};
+use ::expressions::MAX_NUMERIC_PRECISION;
 
 #[derive(Debug, Clone, PartialEq)]
 /// A command for creating a new database object.
@@ -14,6 +15,20 @@ pub enum CreateCommand {
         if_not_exists: bool,
         /// Column declarations.
         decls: Vec<(String, ColumnType)>,
+        /// The columns the table's tuples are kept ordered by, if any. These must be the leading
+        /// columns of the declarations, in order.
+        order_by: Vec<String>,
+    },
+    /// A command variant for creating a new index on a table.
+    Index {
+        /// The name of the index.
+        name: String,
+        /// The name of the table to index.
+        table: String,
+        /// The names of the columns to index, in order.
+        columns: Vec<String>,
+        /// Whether the index only allows one tuple for each set of values.
+        unique: bool,
     },
     /// A command variant for creating a new view on a table or other view.
     View,
@@ -22,14 +37,27 @@ pub enum CreateCommand {
 impl Command for CreateCommand {
     fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
         match *self {
-            CreateCommand::Table { ref name, ref decls, .. } => {
+            CreateCommand::Table { ref name, ref decls, ref order_by, .. } => {
+                for &(ref col_name, col_type) in decls.iter() {
+                    if let ColumnType::Numeric { scale, precision } = col_type {
+                        if precision == 0 || precision > MAX_NUMERIC_PRECISION || scale > precision {
+                            return Err(ExecutionError::InvalidColumnType(col_name.clone(), col_type));
+                        }
+                    }
+                }
+                for (i, col_name) in order_by.iter().enumerate() {
+                    if decls.get(i).map(|decl| &decl.0) != Some(col_name) {
+                        return Err(ExecutionError::InvalidOrderingKey(col_name.clone()));
+                    }
+                }
+
                 let column_infos: Vec<ColumnInfo> = decls.iter()
-                    .map(|decl| ColumnInfo::with_table_name(decl.1, decl.0.as_ref(), name.as_ref()))
+                    .map(|decl| ColumnInfo::with_table_name(decl.1, decl.0.as_str(), name.as_str()))
                     .collect();
                 let schema = try!(Schema::with_columns(column_infos));
                 debug!("Creating the new table {} on disk.", &name);
                 match server.table_manager
-                    .create_table(&server.file_manager, name.as_ref(), schema) {
+                    .create_table_with_key(&server.file_manager, name.as_str(), schema, order_by.len()) {
                     Ok(_) => {
                         debug!("New table {} was created.", &name);
                         println!("Created table {}.", &name);
@@ -38,6 +66,20 @@ impl Command for CreateCommand {
                     Err(e) => Err(ExecutionError::CouldNotCreateTable(e)),
                 }
             }
+            CreateCommand::Index { ref name, ref table, ref columns, unique } => {
+                if !server.table_manager.table_exists(&server.file_manager, table.as_str()) {
+                    return Err(ExecutionError::TableDoesNotExist(table.clone()));
+                }
+                debug!("Creating the new index {} on table {}.", &name, &table);
+                match server.table_manager
+                    .create_index(&server.file_manager, table.as_str(), name.as_str(), columns, unique) {
+                    Ok(_) => {
+                        println!("Created index {} on table {}.", &name, &table);
+                        Ok(None)
+                    }
+                    Err(e) => Err(ExecutionError::CouldNotCreateIndex(e)),
+                }
+            }
             CreateCommand::View => Err(ExecutionError::Unimplemented),
         }
     }
@@ -53,7 +95,9 @@ mod tests {
 
     use super::*;
     use ::{Server, ColumnType};
-    use ::commands::Command;
+    use ::commands::{Command, InsertCommand};
+    use ::expressions::Expression;
+    use ::storage::table_manager;
 
     #[test]
     fn test_table() {
@@ -64,8 +108,110 @@ mod tests {
             temp: false,
             if_not_exists: false,
             decls: vec![("A".into(), ColumnType::Integer)],
+            order_by: vec![],
+        };
+
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_table_numeric() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        for &(precision, scale, valid) in &[(18, 2, true), (19, 2, false), (5, 6, false), (0, 0, false)] {
+            let col_type = ColumnType::Numeric { scale: scale, precision: precision };
+            let mut command = CreateCommand::Table {
+                name: format!("foo{}", precision),
+                temp: false,
+                if_not_exists: false,
+                decls: vec![("A".into(), col_type)],
+                order_by: vec![],
+            };
+            let expected = if valid {
+                Ok(None)
+            } else {
+                Err(ExecutionError::InvalidColumnType("A".into(), col_type))
+            };
+            assert_eq!(expected, command.execute(&mut server, &mut ::std::io::sink()));
+        }
+    }
+
+    #[test]
+    fn test_table_order_by() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        let decls = vec![("A".into(), ColumnType::Integer), ("B".into(), ColumnType::Integer)];
+
+        let mut command = CreateCommand::Table {
+            name: "foo".into(),
+            temp: false,
+            if_not_exists: false,
+            decls: decls.clone(),
+            order_by: vec!["B".into()],
+        };
+        assert_eq!(Err(ExecutionError::InvalidOrderingKey("B".into())),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+
+        let mut command = CreateCommand::Table {
+            name: "foo".into(),
+            temp: false,
+            if_not_exists: false,
+            decls: decls,
+            order_by: vec!["A".into()],
+        };
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_index() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        let mut command = CreateCommand::Table {
+            name: "FOO".into(),
+            temp: false,
+            if_not_exists: false,
+            decls: vec![("A".into(), ColumnType::Integer), ("B".into(), ColumnType::Integer)],
+            order_by: vec![],
         };
+        command.execute(&mut server, &mut ::std::io::sink()).unwrap();
 
+        let insert = |server: &mut Server, a: i32, b: i32| {
+            let values = vec![Expression::Int(a), Expression::Int(b)];
+            InsertCommand::new("FOO".into(), vec!["A".into(), "B".into()], values)
+                .execute(server, &mut ::std::io::sink())
+        };
+        assert_eq!(Ok(None), insert(&mut server, 1, 10));
+        assert_eq!(Ok(None), insert(&mut server, 2, 10));
+
+        let mut command = CreateCommand::Index {
+            name: "IDX_B".into(),
+            table: "FOO".into(),
+            columns: vec!["B".into()],
+            unique: true,
+        };
+        assert_eq!(Err(ExecutionError::CouldNotCreateIndex(table_manager::Error::UniqueViolation("IDX_B".into()))),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+        assert!(!dir.path().join("FOO.IDX_B.idx").exists());
+
+        let mut command = CreateCommand::Index {
+            name: "IDX_A".into(),
+            table: "FOO".into(),
+            columns: vec!["A".into()],
+            unique: true,
+        };
         assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        assert!(dir.path().join("FOO.IDX_A.idx").exists());
+        assert_eq!(Ok(None), insert(&mut server, 3, 10));
+        assert_eq!(Err(ExecutionError::CouldNotInsertTuple(table_manager::Error::UniqueViolation("IDX_A".into()))),
+                   insert(&mut server, 2, 20));
+
+        // The index is found again when the table is reopened.
+        let mut server = Server::with_data_path(dir.path());
+        let table = server.table_manager.get_table(&server.file_manager, "FOO").unwrap();
+        assert_eq!(vec!["IDX_A".to_string()],
+                   table.get_indexes().into_iter().map(|info| info.name).collect::<Vec<_>>());
+        assert_eq!(Err(ExecutionError::CouldNotInsertTuple(table_manager::Error::UniqueViolation("IDX_A".into()))),
+                   insert(&mut server, 3, 30));
+        assert_eq!(Ok(None), insert(&mut server, 4, 40));
     }
 }
@@ -0,0 +1,119 @@
+use ::Server;
+use ::commands::{Command, CommandResult, ExecutionError};
+use ::expressions::{Expression, Literal};
+use ::queries::make_simple_select;
+use ::storage::TupleLiteral;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command for deleting rows from a table.
+pub struct DeleteCommand {
+    table_name: String,
+    where_expr: Option<Expression>,
+}
+
+impl DeleteCommand {
+    /// Creates a new delete command.
+    ///
+    /// # Arguments
+    /// * table_name - The name of the table to delete rows from.
+    /// * where_expr - Optionally, a predicate that the deleted rows must satisfy. If there is no
+    ///   predicate, every row is deleted.
+    pub fn new(table_name: String, where_expr: Option<Expression>) -> DeleteCommand {
+        DeleteCommand {
+            table_name: table_name,
+            where_expr: where_expr,
+        }
+    }
+}
+
+impl Command for DeleteCommand {
+    fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
+        if !server.table_manager.table_exists(&server.file_manager, self.table_name.as_str()) {
+            return Err(ExecutionError::TableDoesNotExist(self.table_name.clone()));
+        }
+        let table = try!(server.table_manager
+            .get_table(&server.file_manager, self.table_name.as_str())
+            .map_err(|e| ExecutionError::CouldNotOpenTable(self.table_name.clone(), e)));
+
+        // Find all of the tuples to delete before deleting any of them, so that removing tuples
+        // doesn't disturb the scan.
+        let to_delete = {
+            let mut plan = try!(make_simple_select(&server.file_manager,
+                                                   &server.table_manager,
+                                                   self.table_name.as_str(),
+                                                   self.where_expr.clone())
+                .map_err(ExecutionError::CouldNotExecutePlan));
+            plan.initialize();
+
+            let mut to_delete: Vec<(Literal, TupleLiteral)> = Vec::new();
+            while let Some(tuple) = try!(plan.get_next_tuple().map_err(ExecutionError::CouldNotGetNextTuple)) {
+                let values = TupleLiteral::from_tuple(tuple);
+                let pointer = tuple.get_external_reference().unwrap_or(Literal::Null);
+                try!(tuple.unpin());
+                to_delete.push((pointer, values));
+            }
+            to_delete
+        };
+
+        let mut num_deleted = 0;
+        for (pointer, values) in to_delete {
+            if try!(table.delete_tuple(&pointer, &values).map_err(ExecutionError::CouldNotDeleteTuple)) {
+                num_deleted += 1;
+            }
+        }
+        println!("Deleted {} rows.", num_deleted);
+        Ok(None)
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::parser::statements;
+
+    fn select_all(server: &mut Server, sql: &[u8]) -> Vec<TupleLiteral> {
+        let ref mut select_command = statements(sql).unwrap().1[0];
+        select_command.execute(server, &mut ::std::io::sink()).unwrap().unwrap_or(vec![])
+    }
+
+    #[test]
+    fn test_delete() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let mut sql = String::from("CREATE TABLE foo (a integer, b varchar(20)); CREATE INDEX idx ON foo (a);");
+        for i in 0..100 {
+            sql += &format!("INSERT INTO foo VALUES ({}, 'value {}');", i % 10, i);
+        }
+        for stmt in statements(sql.as_bytes()).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        let ref mut command = statements(b"DELETE FROM foo WHERE a = 3;").unwrap().1[0];
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        assert_eq!(90, select_all(&mut server, b"SELECT * FROM foo;").len());
+        assert!(select_all(&mut server, b"SELECT * FROM foo WHERE a = 3;").is_empty());
+        assert_eq!(10, select_all(&mut server, b"SELECT * FROM foo WHERE a = 4;").len());
+
+        // The space of deleted tuples is reused, and the index stays consistent.
+        for stmt in statements(b"INSERT INTO foo VALUES (3, 'new');").unwrap().1 {
+            server.handle_command(stmt);
+        }
+        assert_eq!(vec![TupleLiteral::from_iter(vec![Literal::Int(3), Literal::from("new")])],
+                   select_all(&mut server, b"SELECT * FROM foo WHERE a = 3;"));
+
+        let mut command = DeleteCommand::new("FOO".into(), None);
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        assert!(select_all(&mut server, b"SELECT * FROM foo;").is_empty());
+
+        let mut command = DeleteCommand::new("BAR".into(), None);
+        assert_eq!(Err(ExecutionError::TableDoesNotExist("BAR".into())),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+    }
+}
@@ -1,12 +1,18 @@
 use ::Server;
 use ::commands::{Command, CommandResult, ExecutionError};
-use ::storage::table_manager::get_table_file_name;
 
 #[derive(Debug, Clone, PartialEq)]
 /// A command for destroying a database object.
 pub enum DropCommand {
     /// A command variant for dropping a table.
     Table(String),
+    /// A command variant for dropping an index on a table.
+    Index {
+        /// The name of the index.
+        name: String,
+        /// The name of the indexed table.
+        table: String,
+    },
 }
 
 impl Command for DropCommand {
@@ -15,8 +21,7 @@ impl Command for DropCommand {
             DropCommand::Table(ref table_name) => {
                 let table_exists = server.table_manager.table_exists(&server.file_manager, table_name.as_str());
                 if table_exists {
-                    match server.file_manager
-                        .remove_dbfile(get_table_file_name(table_name.as_str())) {
+                    match server.table_manager.drop_table(&server.file_manager, table_name.as_str()) {
                         Ok(_) => Ok(None),
                         Err(e) => Err(ExecutionError::CouldNotDeleteTable(e))
                     }
@@ -24,6 +29,18 @@ impl Command for DropCommand {
                     Err(ExecutionError::TableDoesNotExist(table_name.clone()))
                 }
             }
+            DropCommand::Index { ref name, ref table } => {
+                if !server.table_manager.table_exists(&server.file_manager, table.as_str()) {
+                    return Err(ExecutionError::TableDoesNotExist(table.clone()));
+                }
+                match server.table_manager.drop_index(&server.file_manager, table.as_str(), name.as_str()) {
+                    Ok(_) => {
+                        println!("Dropped index {} on table {}.", name, table);
+                        Ok(None)
+                    }
+                    Err(e) => Err(ExecutionError::CouldNotDropIndex(e)),
+                }
+            }
         }
     }
 
@@ -53,6 +70,7 @@ mod tests {
                 temp: false,
                 if_not_exists: false,
                 decls: vec![("A".into(), ColumnType::Integer)],
+                order_by: vec![],
             };
             command.execute(&mut server, &mut ::std::io::sink()).unwrap();
         }
@@ -79,4 +97,43 @@ mod tests {
                        command.execute(&mut server, &mut ::std::io::sink()));
         }
     }
+
+    #[test]
+    fn test_index() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        {
+            let mut command = CreateCommand::Table {
+                name: "FOO".into(),
+                temp: false,
+                if_not_exists: false,
+                decls: vec![("A".into(), ColumnType::Integer)],
+                order_by: vec![],
+            };
+            command.execute(&mut server, &mut ::std::io::sink()).unwrap();
+        }
+        for index_name in vec!["IDX1", "IDX2"] {
+            let mut command = CreateCommand::Index {
+                name: index_name.into(),
+                table: "FOO".into(),
+                columns: vec!["A".into()],
+                unique: false,
+            };
+            command.execute(&mut server, &mut ::std::io::sink()).unwrap();
+        }
+
+        let idx1_path = dir.path().join("FOO.IDX1.idx");
+        let idx2_path = dir.path().join("FOO.IDX2.idx");
+        assert!(idx1_path.exists() && idx2_path.exists());
+
+        let mut command = DropCommand::Index { name: "IDX1".into(), table: "FOO".into() };
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        assert!(!idx1_path.exists());
+        assert!(command.execute(&mut server, &mut ::std::io::sink()).is_err());
+
+        // Dropping the table drops its remaining indexes too.
+        let mut command = DropCommand::Table("FOO".into());
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        assert!(!idx2_path.exists());
+    }
 }
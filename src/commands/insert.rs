@@ -26,7 +26,7 @@ impl InsertCommand {
 
 impl Command for InsertCommand {
     fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
-        match server.table_manager.get_table(&server.file_manager, self.table_name.as_ref()) {
+        match server.table_manager.get_table(&server.file_manager, self.table_name.as_str()) {
             Ok(ref mut table) => {
                 // Try to evaluate expressions.
                 let expr_values = {
@@ -37,12 +37,19 @@ impl Command for InsertCommand {
                     }
                     expr_values
                 };
-                // Verify all columns exist in the schema.
-                for (i, col_name) in self.col_names.iter().enumerate() {
-                    match table.get_schema().get_column(col_name.as_ref()) {
+                // Verify all columns exist in the schema. Without a column list, the values are
+                // for the table's columns in order.
+                let col_names: Vec<String> = if self.col_names.is_empty() {
+                    table.get_schema().iter().filter_map(|info| info.name.clone()).collect()
+                } else {
+                    self.col_names.clone()
+                };
+                if col_names.len() != expr_values.len() {
+                    return Err(ExecutionError::WrongNumberOfValues(col_names.len(), expr_values.len()));
+                }
+                for (col_name, (expr_value, expr)) in col_names.iter().zip(expr_values.iter().zip(self.values.iter())) {
+                    match table.get_schema().get_column(col_name.as_str()) {
                         Some(column) => {
-                            let ref expr_value = expr_values[i];
-                            let ref expr = self.values[i];
                             if !column.column_type.can_store_literal(expr_value.clone()) {
                                 return Err(ExecutionError::CannotStoreExpression(col_name.clone(), expr.clone()));
                             }
@@ -62,10 +69,7 @@ impl Command for InsertCommand {
                         try!(page_tuple.unpin());
                         Ok(None)
                     }
-                    Err(e) => {
-                        error!("{:?}", e);
-                        Err(ExecutionError::Unimplemented)
-                    }
+                    Err(e) => Err(ExecutionError::CouldNotInsertTuple(e)),
                 }
             }
             Err(e) => Err(ExecutionError::CouldNotOpenTable(self.table_name.clone(), e)),
@@ -76,3 +80,158 @@ impl Command for InsertCommand {
         self
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::expressions::{Decimal, Literal};
+    use ::parser::statements;
+
+    fn select(server: &mut Server, sql: &[u8]) -> Vec<TupleLiteral> {
+        let ref mut select_command = statements(sql).unwrap().1[0];
+        select_command.execute(server, &mut ::std::io::sink()).unwrap().unwrap_or(vec![])
+    }
+
+    #[test]
+    fn test_insert_numeric() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        {
+            let mut server = Server::with_data_path(dir.path());
+            let sql = b"CREATE TABLE foo (price NUMERIC(8, 2), qty integer); \
+                        INSERT INTO foo VALUES (DECIMAL '19.99', 3); \
+                        INSERT INTO foo VALUES (5, 1); \
+                        INSERT INTO foo VALUES (0.125, 2);";
+            for stmt in statements(sql).unwrap().1 {
+                server.handle_command(stmt);
+            }
+
+            // Values with too many digits before the decimal point can't be stored.
+            let ref mut command = statements(b"INSERT INTO foo VALUES (1234567.8, 1);").unwrap().1[0];
+            assert_eq!(Err(ExecutionError::CannotStoreExpression("PRICE".into(), Expression::Decimal(Decimal::new(12345678, 1)))),
+                       command.execute(&mut server, &mut ::std::io::sink()));
+        }
+
+        // The precision and scale are kept in the table's schema, and values are rounded to the
+        // scale when they are stored.
+        let mut server = Server::with_data_path(dir.path());
+        let prices: Vec<Literal> = select(&mut server, b"SELECT price FROM foo;").into_iter()
+            .map(|tuple| tuple[0].clone())
+            .collect();
+        assert_eq!(vec![Literal::Decimal(Decimal::new(1999, 2)),
+                        Literal::Decimal(Decimal::new(500, 2)),
+                        Literal::Decimal(Decimal::new(13, 2))],
+                   prices);
+        assert_eq!("5.00", format!("{}", prices[1]));
+
+        let totals: Vec<String> = select(&mut server, b"SELECT price * qty + 0.01 FROM foo;").into_iter()
+            .map(|tuple| format!("{}", tuple[0]))
+            .collect();
+        assert_eq!(vec!["59.98", "5.01", "0.27"], totals);
+
+        // Numbers with a decimal point are exact, even without the DECIMAL keyword.
+        let sums: Vec<String> = select(&mut server, b"SELECT 0.1 + 0.2, price + 0.1 FROM foo WHERE qty = 3;").into_iter()
+            .map(|tuple| format!("{}, {}", tuple[0], tuple[1]))
+            .collect();
+        assert_eq!(vec!["0.3, 20.09"], sums);
+    }
+
+    #[test]
+    fn test_insert_temporal() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        {
+            let mut server = Server::with_data_path(dir.path());
+            let sql = b"CREATE TABLE events (d DATE, t TIME, dt DATETIME, ts TIMESTAMP, iv INTERVAL); \
+                        INSERT INTO events VALUES (DATE '2024-01-31', TIME '08:30:15', \
+                            DATETIME '2024-01-31 08:30:15.5', TIMESTAMP '2024-01-31 08:30:15.25', INTERVAL '3' DAY); \
+                        INSERT INTO events VALUES ('2024-02-29', '23:59:59', '2024-03-01', \
+                            '2024-03-01 12:00:00', '1 year 2 months');";
+            for stmt in statements(sql).unwrap().1 {
+                server.handle_command(stmt);
+            }
+
+            // Dates are checked when they are stored, and integers are not dates.
+            let ref mut command = statements(b"INSERT INTO events VALUES ('2023-02-29', NULL, NULL, NULL, NULL);")
+                .unwrap().1[0];
+            assert_eq!(Err(ExecutionError::CannotStoreExpression("D".into(), Expression::String("2023-02-29".into()))),
+                       command.execute(&mut server, &mut ::std::io::sink()));
+            let ref mut command = statements(b"INSERT INTO events VALUES (5, NULL, NULL, NULL, NULL);").unwrap().1[0];
+            assert_eq!(Err(ExecutionError::CannotStoreExpression("D".into(), Expression::Int(5))),
+                       command.execute(&mut server, &mut ::std::io::sink()));
+        }
+
+        let mut server = Server::with_data_path(dir.path());
+        let rows: Vec<Vec<String>> = select(&mut server, b"SELECT * FROM events;").into_iter()
+            .map(Into::into)
+            .collect();
+        // DATETIME values only keep whole seconds, unlike TIMESTAMP values.
+        assert_eq!(vec!["2024-01-31", "08:30:15", "2024-01-31 08:30:15", "2024-01-31 08:30:15.25", "3 days"],
+                   rows[0]);
+        assert_eq!(vec!["2024-02-29", "23:59:59", "2024-03-01 00:00:00", "2024-03-01 12:00:00", "1 year 2 months"],
+                   rows[1]);
+
+        let sql = b"SELECT d + 1, d + iv, ts - dt, t + INTERVAL '1' HOUR FROM events WHERE d > '2024-02-01';";
+        let rows: Vec<Vec<String>> = select(&mut server, sql).into_iter().map(Into::into).collect();
+        assert_eq!(vec![vec!["2024-03-01", "2025-04-29 00:00:00", "12:00:00", "00:59:59"]], rows);
+    }
+
+    #[test]
+    fn test_insert_checks_values() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        let sql = b"CREATE TABLE nums (i integer, l bigint, f float, d double);";
+        for stmt in statements(sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        // Without a column list, the values are checked against the table's columns in order.
+        // Integers can be stored in any wider numeric column.
+        let ref mut command = statements(b"INSERT INTO nums VALUES (1, 2, 3, 4);").unwrap().1[0];
+        assert_eq!(Ok(None), command.execute(&mut server, &mut ::std::io::sink()));
+        let ref mut command = statements(b"INSERT INTO nums VALUES (1, 2, 'three', 4);").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::CannotStoreExpression("F".into(), Expression::String("three".into()))),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+        let ref mut command = statements(b"INSERT INTO nums VALUES (5L, 2, 3, 4);").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::CannotStoreExpression("I".into(), Expression::Long(5))),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+
+        let ref mut command = statements(b"INSERT INTO nums VALUES (1, 2, 3);").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::WrongNumberOfValues(4, 3)),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+        let ref mut command = statements(b"INSERT INTO nums (i, l) VALUES (1, 2, 3);").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::WrongNumberOfValues(2, 3)),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+
+        let rows: Vec<Vec<String>> = select(&mut server, b"SELECT * FROM nums;").into_iter()
+            .map(Into::into)
+            .collect();
+        assert_eq!(vec![vec!["1", "2", "3", "4"]], rows);
+    }
+
+    #[test]
+    fn test_insert_blob() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        let sql = b"CREATE TABLE files (name varchar(20), data blob); \
+                    INSERT INTO files VALUES ('empty', X''); \
+                    INSERT INTO files VALUES ('binary', X'00FFC328');";
+        for stmt in statements(sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        // Byte strings can only be stored in BLOB columns.
+        let ref mut command = statements(b"INSERT INTO files VALUES (X'41', X'41');").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::CannotStoreExpression("NAME".into(), Expression::Bytes(vec![0x41]))),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+
+        let rows: Vec<Vec<String>> = select(&mut server, b"SELECT * FROM files WHERE data <> X'';").into_iter()
+            .map(Into::into)
+            .collect();
+        assert_eq!(vec![vec!["binary", "X'00FFC328'"]], rows);
+        let data: Vec<Literal> = select(&mut server, b"SELECT data FROM files;").into_iter()
+            .map(|tuple| tuple[0].clone())
+            .collect();
+        assert_eq!(vec![Literal::Bytes(vec![]), Literal::Bytes(vec![0x00, 0xFF, 0xC3, 0x28])], data);
+    }
+}
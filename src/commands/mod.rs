@@ -18,48 +18,63 @@
 //! NanoDB, these inherit from a `QueryCommand` base class. These may or may not do so in the
 //! future.* The supported commands are as follows:
 //!
-//! - `DELETE ...` - [`DeleteCommand`]()
+//! - `DELETE ...` - [`DeleteCommand`](struct.DeleteCommand.html)
 //! - `INSERT ...` - [`InsertCommand`]()
 //! - `SELECT ...` - [`SelectCommand`]()
-//! - `UPDATE ...` - [`UpdateCommand`]()
+//! - `UPDATE ...` - [`UpdateCommand`](struct.UpdateCommand.html)
 //!
 //! # Transaction-Demarcation Commands
 //!
 //! These commands provide control over when a transaction is started or ended:
 //!
-//! - `BEGIN [WORK]` | `START TRANSACTION` - [`BeginTransactionCommand`]()
-//! - `COMMIT [WORK]` - [`CommitTransactionCommand`]()
-//! - `ROLLBACK [WORK]` - [`RollbackTransactionCommand`]()
+//! - `BEGIN [WORK]` | `START TRANSACTION` - [`TransactionCommand`](enum.TransactionCommand.html#variant.Begin)
+//! - `COMMIT [WORK]` - [`TransactionCommand`](enum.TransactionCommand.html#variant.Commit)
+//! - `ROLLBACK [WORK]` - [`TransactionCommand`](enum.TransactionCommand.html#variant.Rollback)
+//!
+//! Commands issued outside of a transaction each run in their own transaction. Tables and indexes
+//! cannot be created or dropped inside a transaction.
 //!
 //! # Utility Commands
 //!
 //! These commands perform various utility operations:
 //!
-//! - `ANALYZE ...` - [`AnalyzeCommand`]()
+//! - `ANALYZE ...` - [`AnalyzeCommand`](struct.AnalyzeCommand.html)
 //! - `EXPLAIN ...` - [`ExplainCommand`]()
+//! - `SET ...` - [`SetCommand`](struct.SetCommand.html)
 //! - `EXIT` | `QUIT` - [`ExitCommand`]()
 
+mod analyze;
 mod select;
+mod set;
 mod show;
 mod create;
+mod delete;
 mod insert;
 mod drop;
+mod transaction;
+mod update;
 mod utils;
 
+pub use self::analyze::AnalyzeCommand;
 pub use self::create::CreateCommand;
+pub use self::delete::DeleteCommand;
 pub use self::drop::DropCommand;
 pub use self::insert::InsertCommand;
 pub use self::select::SelectCommand;
+pub use self::set::SetCommand;
 pub use self::show::ShowCommand;
+pub use self::transaction::TransactionCommand;
+pub use self::update::UpdateCommand;
 
 use std::any::Any;
 use std::io::Write;
 
-use ::{Server};
-use ::expressions::{Expression, ExpressionError, SelectValue};
+use ::{ColumnType, Server};
+use ::expressions::{Expression, ExpressionError, Literal, SelectValue};
 use ::queries::PlanError;
 use ::relations::SchemaError;
 use ::storage::{PinError, TupleLiteral, file_manager, table_manager};
+use ::transactions;
 
 /// An enum describing the side of a join being handled.
 #[derive(Clone, Copy, Debug, PartialEq)]
@@ -134,6 +149,11 @@ pub enum ExecutionError {
     CouldNotOpenTable(String, table_manager::Error),
     /// The command was unable to create the table.
     CouldNotCreateTable(table_manager::Error),
+    /// The column was declared with a type that can't be stored, such as a `NUMERIC` type with
+    /// too many digits.
+    InvalidColumnType(String, ColumnType),
+    /// The column named in a table's ordering key is not the next leading column of the table.
+    InvalidOrderingKey(String),
     /// The command could not list tables successfully.
     CouldNotListTables(file_manager::Error),
     /// Could not get another tuple in the plan.
@@ -146,10 +166,25 @@ pub enum ExecutionError {
     ColumnDoesNotExist(String),
     /// The column type does not support the expression passed in.
     CannotStoreExpression(String, Expression),
+    /// An `INSERT` gave a different number of values than it has columns. Format is (number of
+    /// columns, number of values).
+    WrongNumberOfValues(usize, usize),
     /// Parsing the expression resulted in an error.
     ExpressionError(ExpressionError),
     /// The table could not be deleted.
-    CouldNotDeleteTable(file_manager::Error),
+    CouldNotDeleteTable(table_manager::Error),
+    /// The tuple could not be added to the table.
+    CouldNotInsertTuple(table_manager::Error),
+    /// The tuple could not be deleted from the table.
+    CouldNotDeleteTuple(table_manager::Error),
+    /// The tuple could not be updated.
+    CouldNotUpdateTuple(table_manager::Error),
+    /// The index could not be created.
+    CouldNotCreateIndex(table_manager::Error),
+    /// The index could not be deleted.
+    CouldNotDropIndex(table_manager::Error),
+    /// The statistics of the table could not be collected.
+    CouldNotAnalyzeTable(String, table_manager::Error),
     /// When trying to create a scalar select, a select value was provided that could not be a
     /// scalar (e.g. `SELECT 1 + a`).
     ExpectedScalarValue(SelectValue),
@@ -158,6 +193,14 @@ pub enum ExecutionError {
     /// An error occurred while trying to print the results of a query. This error would be an
     /// io::Error, so we have to take the description.
     PrintError(String),
+    /// A transaction could not be started, committed or rolled back.
+    TransactionError(transactions::Error),
+    /// The command creates or drops a table or index, which can't be done inside a transaction.
+    DDLInTransaction,
+    /// The setting named by a `SET` command does not exist.
+    UnknownSetting(String),
+    /// The value given to a setting by a `SET` command can't be used for it.
+    InvalidSettingValue(String, Literal),
     /// The command has not been fully implemented.
     Unimplemented,
 }
@@ -196,6 +239,9 @@ impl ::std::fmt::Display for ExecutionError {
                        expr,
                        column)
             }
+            ExecutionError::WrongNumberOfValues(num_columns, num_values) => {
+                write!(f, "Expected {} values to insert, but got {}.", num_columns, num_values)
+            }
             ExecutionError::ColumnDoesNotExist(ref column) => {
                 write!(f,
                        "The column {} does not exist in the schema of the table.",
@@ -204,11 +250,33 @@ impl ::std::fmt::Display for ExecutionError {
             ExecutionError::CouldNotCreateSchema(ref e) => write!(f, "Unable to create schema. {}", e),
             ExecutionError::CouldNotComputeSchema(ref e) => write!(f, "Unable to compute schema. {}", e),
             ExecutionError::CouldNotCreateTable(ref e) => write!(f, "Unable to create table. {}", e),
+            ExecutionError::InvalidColumnType(ref column, ref column_type) => {
+                write!(f, "The column {} cannot have the type {}.", column, column_type)
+            }
+            ExecutionError::InvalidOrderingKey(ref column) => {
+                write!(f,
+                       "The ordering key must be a prefix of the table's columns, but got {}.",
+                       column)
+            }
             ExecutionError::CouldNotDeleteTable(ref e) => write!(f, "Unable to delete table. {}", e),
+            ExecutionError::CouldNotInsertTuple(ref e) => write!(f, "Unable to insert tuple. {}", e),
+            ExecutionError::CouldNotDeleteTuple(ref e) => write!(f, "Unable to delete tuple. {}", e),
+            ExecutionError::CouldNotUpdateTuple(ref e) => write!(f, "Unable to update tuple. {}", e),
+            ExecutionError::CouldNotCreateIndex(ref e) => write!(f, "Unable to create index. {}", e),
+            ExecutionError::CouldNotDropIndex(ref e) => write!(f, "Unable to drop index. {}", e),
+            ExecutionError::CouldNotAnalyzeTable(ref name, ref e) => write!(f, "Unable to analyze table {}. {}", name, e),
             ExecutionError::CouldNotListTables(ref e) => write!(f, "Unable to list tables. {}", e),
             ExecutionError::CouldNotOpenTable(ref name, ref e) => write!(f, "Unable to open table {}. {}", name, e),
             ExecutionError::CouldNotGetNextTuple(ref e) => write!(f, "Unable to retrieve another tuple. {}", e),
             ExecutionError::CouldNotExecutePlan(ref e) => write!(f, "Unable to execute plan. {}", e),
+            ExecutionError::TransactionError(ref e) => write!(f, "{}", e),
+            ExecutionError::DDLInTransaction => {
+                write!(f, "Tables and indexes cannot be created or dropped inside a transaction.")
+            }
+            ExecutionError::UnknownSetting(ref name) => write!(f, "There is no setting named {}.", name),
+            ExecutionError::InvalidSettingValue(ref name, ref value) => {
+                write!(f, "The value {} cannot be used for the setting {}.", value, name)
+            }
             ExecutionError::Unimplemented => write!(f, "The requested command is not yet implemented."),
             ExecutionError::TableDoesNotExist(ref name) => write!(f, "The table {} does not exist.", name),
             ExecutionError::ExpectedScalarValue(ref value) => write!(f, "The select value {} cannot be evaluated to a scalar.", value),
@@ -4,7 +4,7 @@ use ::Server;
 use ::commands::{Command, CommandResult, ExecutionError};
 use ::commands::utils::print_table;
 use ::expressions::SelectClause;
-use ::queries::{Planner, SimplePlanner, get_plan_results};
+use ::queries::get_plan_results;
 use ::relations::column_name_to_string;
 
 #[derive(Debug, Clone, PartialEq)]
@@ -35,7 +35,7 @@ impl Command for SelectCommand {
         debug!("Prepared SelectClause:\n{}", self.clause);
         debug!("Result schema: {}", result_schema);
 
-        let planner = SimplePlanner::new(&server.file_manager, &mut server.table_manager);
+        let planner = server.planner_type.make_planner(&server.file_manager, &server.table_manager, server.sort_memory);
         let mut plan = try!(planner.make_plan(self.clause.clone()).map_err(ExecutionError::CouldNotExecutePlan));
 
         let col_names: Vec<String> = plan.get_schema().iter().map(|col_info| column_name_to_string(&col_info.get_column_name())).collect();
@@ -65,8 +65,11 @@ mod tests {
 
     use super::*;
 
-    use ::commands::ExecutionError;
+    use ::commands::{CommandResult, ExecutionError};
+    use ::expressions::Literal;
+    use ::queries::PlanError;
     use ::parser::statements;
+    use ::queries::PlannerType;
     use ::storage::TupleLiteral;
 
     #[test]
@@ -91,4 +94,589 @@ mod tests {
         assert_eq!(Err(ExecutionError::TableDoesNotExist("BAR".into())),
         select_command.execute(&mut server, &mut ::std::io::sink()));
     }
+
+    #[test]
+    fn test_select_order_by() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE foo (a integer, b integer); \
+        INSERT INTO foo VALUES (1, 20); INSERT INTO foo VALUES (2, NULL); \
+        INSERT INTO foo VALUES (3, 10); INSERT INTO foo VALUES (4, 20);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        // Sorting on a column that isn't selected, with ties kept in their original order.
+        let ref mut select_command = statements(b"SELECT a FROM foo ORDER BY b DESC NULLS LAST;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![1.into()]),
+                                TupleLiteral::from_iter(vec![4.into()]),
+                                TupleLiteral::from_iter(vec![3.into()]),
+                                TupleLiteral::from_iter(vec![2.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // Sorting on the alias of a select-value.
+        let ref mut select_command = statements(b"SELECT a * -1 AS c FROM foo ORDER BY c;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![(-4).into()]),
+                                TupleLiteral::from_iter(vec![(-3).into()]),
+                                TupleLiteral::from_iter(vec![(-2).into()]),
+                                TupleLiteral::from_iter(vec![(-1).into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // Sorting on an aggregate.
+        let ref mut select_command = statements(b"SELECT b FROM foo GROUP BY b ORDER BY COUNT(a), b;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![10.into()]),
+                                TupleLiteral::from_iter(vec![Literal::Null]),
+                                TupleLiteral::from_iter(vec![20.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // Sorting on the positions of select-values, including an aggregate.
+        let ref mut select_command = statements(b"SELECT b, a FROM foo ORDER BY 2 DESC;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![20.into(), 4.into()]),
+                                TupleLiteral::from_iter(vec![10.into(), 3.into()]),
+                                TupleLiteral::from_iter(vec![Literal::Null, 2.into()]),
+                                TupleLiteral::from_iter(vec![20.into(), 1.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+        let ref mut select_command = statements(b"SELECT b, COUNT(a) FROM foo GROUP BY b ORDER BY 2 DESC, 1;")
+            .unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![20.into(), 2.into()]),
+                                TupleLiteral::from_iter(vec![10.into(), 1.into()]),
+                                TupleLiteral::from_iter(vec![Literal::Null, 1.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // With a wildcard, and after a set operation, positions refer to the columns of the results.
+        let ref mut select_command = statements(b"SELECT * FROM foo ORDER BY 2, 1;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![3.into(), 10.into()]),
+                                TupleLiteral::from_iter(vec![1.into(), 20.into()]),
+                                TupleLiteral::from_iter(vec![4.into(), 20.into()]),
+                                TupleLiteral::from_iter(vec![2.into(), Literal::Null])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+        let ref mut select_command = statements(b"SELECT a FROM foo WHERE a < 3 UNION SELECT b FROM foo WHERE a <> 2 \
+                                                  ORDER BY 1 DESC;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![20.into()]),
+                                TupleLiteral::from_iter(vec![10.into()]),
+                                TupleLiteral::from_iter(vec![2.into()]),
+                                TupleLiteral::from_iter(vec![1.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        let sqls: Vec<(&[u8], i32)> = vec![
+            (b"SELECT a FROM foo ORDER BY 2;", 2),
+            (b"SELECT * FROM foo ORDER BY 3;", 3),
+            (b"SELECT a FROM foo UNION SELECT b FROM foo ORDER BY 0;", 0),
+        ];
+        for (sql, position) in sqls {
+            let ref mut select_command = statements(sql).unwrap().1[0];
+            assert_eq!(Err(ExecutionError::CouldNotExecutePlan(PlanError::InvalidOrderByPosition(position))),
+                       select_command.execute(&mut server, &mut ::std::io::sink()));
+        }
+    }
+
+    #[test]
+    fn test_select_limit_offset() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE foo (a integer); INSERT INTO foo VALUES (3); \
+        INSERT INTO foo VALUES (1); INSERT INTO foo VALUES (4); INSERT INTO foo VALUES (2);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let ref mut select_command = statements(b"SELECT a FROM foo ORDER BY a LIMIT 2 OFFSET 1;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![2.into()]),
+                                TupleLiteral::from_iter(vec![3.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        let ref mut select_command = statements(b"SELECT a FROM foo LIMIT 1;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![3.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // LIMIT 0 produces no rows, rather than being ignored.
+        let ref mut select_command = statements(b"SELECT a FROM foo ORDER BY a LIMIT 0 OFFSET 0;").unwrap().1[0];
+        assert_eq!(Ok(None), select_command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_select_distinct() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), dept varchar(10)); \
+        INSERT INTO emp VALUES ('a', 'sales'); INSERT INTO emp VALUES ('b', 'eng'); \
+        INSERT INTO emp VALUES ('c', 'sales'); INSERT INTO emp VALUES ('d', NULL); \
+        INSERT INTO emp VALUES ('e', NULL);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let ref mut select_command = statements(b"SELECT DISTINCT dept FROM emp;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec!["sales".into()]),
+                                TupleLiteral::from_iter(vec!["eng".into()]),
+                                TupleLiteral::from_iter(vec![Literal::Null])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        let ref mut select_command = statements(b"SELECT DISTINCT dept FROM emp ORDER BY dept LIMIT 2;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec!["eng".into()]),
+                                TupleLiteral::from_iter(vec!["sales".into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_select_having() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), dept varchar(10), salary integer); \
+        INSERT INTO emp VALUES ('a', 'sales', 10); INSERT INTO emp VALUES ('b', 'eng', 30); \
+        INSERT INTO emp VALUES ('c', 'sales', 20); INSERT INTO emp VALUES ('d', 'ops', 5); \
+        INSERT INTO emp VALUES ('e', 'eng', 40); INSERT INTO emp VALUES ('f', 'sales', 30);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let ref mut select_command =
+            statements(b"SELECT dept FROM emp GROUP BY dept HAVING COUNT(*) > 1 ORDER BY dept;").unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec!["eng".into()]),
+                                TupleLiteral::from_iter(vec!["sales".into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // The HAVING clause can use aggregates that aren't selected.
+        let ref mut select_command =
+            statements(b"SELECT dept, MAX(salary) FROM emp GROUP BY dept HAVING SUM(salary) >= 60 AND MIN(salary) < 20;")
+                .unwrap().1[0];
+        assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec!["sales".into(), 30.into()])])),
+        select_command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_select_group_by_expressions() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE foo (a integer); INSERT INTO foo VALUES (3); \
+        INSERT INTO foo VALUES (14); INSERT INTO foo VALUES (7); INSERT INTO foo VALUES (25); \
+        INSERT INTO foo VALUES (11);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let expected = Ok(Some(vec![TupleLiteral::from_iter(vec![0.into(), 2.into()]),
+                                    TupleLiteral::from_iter(vec![10.into(), 2.into()]),
+                                    TupleLiteral::from_iter(vec![20.into(), 1.into()])]));
+
+        // The select-value and the ORDER BY term resolve to the grouped value.
+        let ref mut select_command =
+            statements(b"SELECT a / 10 * 10, COUNT(*) FROM foo GROUP BY a / 10 ORDER BY a / 10;").unwrap().1[0];
+        assert_eq!(expected, select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        // Positional references use the select-value's expression.
+        let ref mut select_command =
+            statements(b"SELECT a / 10 * 10 AS bucket, COUNT(*) FROM foo GROUP BY 1 ORDER BY bucket;").unwrap().1[0];
+        assert_eq!(expected, select_command.execute(&mut server, &mut ::std::io::sink()));
+
+        let ref mut select_command = statements(b"SELECT a, COUNT(*) FROM foo GROUP BY 3;").unwrap().1[0];
+        assert_eq!(Err(ExecutionError::CouldNotExecutePlan(PlanError::InvalidGroupByPosition(3))),
+                   select_command.execute(&mut server, &mut ::std::io::sink()));
+    }
+
+    #[test]
+    fn test_select_window_functions() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE sales (region varchar(10), month integer, amount integer); \
+        INSERT INTO sales VALUES ('east', 1, 10); INSERT INTO sales VALUES ('east', 2, 20); \
+        INSERT INTO sales VALUES ('east', 3, 20); INSERT INTO sales VALUES ('east', 4, 5); \
+        INSERT INTO sales VALUES ('west', 1, 7); INSERT INTO sales VALUES ('west', 2, 3);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        // Each row is the region and month followed by the given values.
+        let rows = |values: &[&[Literal]]| -> CommandResult {
+            let keys = [("east", 1), ("east", 2), ("east", 3), ("east", 4), ("west", 1), ("west", 2)];
+            Ok(Some(keys.iter()
+                .zip(values.iter())
+                .map(|(&(region, month), values)| {
+                    let mut tuple = TupleLiteral::from_iter(vec![region.into(), Literal::Int(month)]);
+                    for value in values.iter() {
+                        tuple.add_value(value.clone());
+                    }
+                    tuple
+                })
+                .collect()))
+        };
+        let int = |n: i32| Literal::Int(n);
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            // Running totals, and sums over a sliding frame.
+            assert_eq!(rows(&[&[int(10), int(30)], &[int(30), int(50)], &[int(50), int(45)],
+                              &[int(55), int(25)], &[int(7), int(10)], &[int(10), int(10)]]),
+                       run(b"SELECT region, month, \
+                             SUM(amount) OVER (PARTITION BY region ORDER BY month \
+                                               ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW) AS total, \
+                             SUM(amount) OVER (PARTITION BY region ORDER BY month \
+                                               ROWS BETWEEN 1 PRECEDING AND 1 FOLLOWING) AS nearby \
+                             FROM sales ORDER BY region, month;"));
+
+            // Rankings, where ties are broken by the order the rows were produced in.
+            assert_eq!(rows(&[&[int(3), int(3), int(2)], &[int(1), int(1), int(1)], &[int(2), int(1), int(1)],
+                              &[int(4), int(4), int(4)], &[int(1), int(1), int(3)], &[int(2), int(2), int(5)]]),
+                       run(b"SELECT region, month, \
+                             ROW_NUMBER() OVER (PARTITION BY region ORDER BY amount DESC), \
+                             RANK() OVER (PARTITION BY region ORDER BY amount DESC), \
+                             DENSE_RANK() OVER (ORDER BY amount DESC) \
+                             FROM sales ORDER BY region, month;"));
+
+            assert_eq!(rows(&[&[Literal::Null, int(20), int(5)], &[int(10), int(20), int(5)],
+                              &[int(20), int(5), int(5)], &[int(20), int(0), int(5)],
+                              &[Literal::Null, int(3), int(3)], &[int(7), int(0), int(3)]]),
+                       run(b"SELECT region, month, \
+                             LAG(amount) OVER (PARTITION BY region ORDER BY month), \
+                             LEAD(amount, 1, 0) OVER (PARTITION BY region ORDER BY month), \
+                             FIRST_VALUE(amount) OVER (PARTITION BY region ORDER BY amount) \
+                             FROM sales ORDER BY region, month;"));
+
+            // Without a frame, the rows with the same month are summed together, and without an
+            // ordering the whole partition is counted.
+            assert_eq!(rows(&[&[int(17), int(4)], &[int(40), int(4)], &[int(60), int(4)],
+                              &[int(65), int(4)], &[int(17), int(2)], &[int(40), int(2)]]),
+                       run(b"SELECT region, month, SUM(amount) OVER (ORDER BY month), \
+                             COUNT(*) OVER (PARTITION BY region) FROM sales ORDER BY region, month;"));
+
+            // Window functions are computed after grouping.
+            assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec!["east".into(), int(55), int(1)]),
+                                    TupleLiteral::from_iter(vec!["west".into(), int(10), int(2)])])),
+                       run(b"SELECT region, SUM(amount) AS total, RANK() OVER (ORDER BY SUM(amount) DESC) AS r \
+                             FROM sales GROUP BY region ORDER BY r;"));
+
+            match run(b"SELECT month FROM sales WHERE ROW_NUMBER() OVER (ORDER BY month) > 2;") {
+                Err(ExecutionError::CouldNotExecutePlan(PlanError::WindowFunctionsNotAllowed(..))) => {}
+                result => panic!("Expected window functions not to be allowed, got {:?}", result),
+            }
+            assert_eq!(Err(ExecutionError::CouldNotExecutePlan(PlanError::NotWindowFunction("ABS".into()))),
+                       run(b"SELECT ABS(amount) OVER () FROM sales;"));
+            assert!(run(b"SELECT RANK() FROM sales;").is_err());
+        }
+    }
+
+    #[test]
+    fn test_select_subquery_tests() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), dept_id integer); \
+        CREATE TABLE dept (id integer, name varchar(10)); CREATE TABLE closed (id integer); \
+        INSERT INTO emp VALUES ('a', 1); INSERT INTO emp VALUES ('b', 2); \
+        INSERT INTO emp VALUES ('c', 3); INSERT INTO emp VALUES ('d', NULL); \
+        INSERT INTO dept VALUES (1, 'sales'); INSERT INTO dept VALUES (3, 'eng'); \
+        INSERT INTO dept VALUES (NULL, 'ops');").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let names = |names: &[&str]| -> CommandResult {
+            Ok(Some(names.iter().map(|name| TupleLiteral::from_iter(vec![(*name).into()])).collect()))
+        };
+        let mut run = |sql: &[u8]| {
+            let ref mut select_command = statements(sql).unwrap().1[0];
+            select_command.execute(&mut server, &mut ::std::io::sink())
+        };
+
+        assert_eq!(names(&["a", "c"]), run(b"SELECT name FROM emp WHERE dept_id IN (SELECT id FROM dept);"));
+        assert_eq!(names(&["c"]),
+                   run(b"SELECT name FROM emp WHERE dept_id IN (SELECT id FROM dept WHERE name = 'eng');"));
+        assert_eq!(names(&["a"]),
+                   run(b"SELECT name FROM emp WHERE name <> 'c' AND dept_id IN (SELECT id FROM dept);"));
+
+        // The subquery produces a NULL, so no value is known to be outside of it.
+        assert_eq!(Ok(None), run(b"SELECT name FROM emp WHERE dept_id NOT IN (SELECT id FROM dept);"));
+        assert_eq!(names(&["b"]),
+                   run(b"SELECT name FROM emp WHERE dept_id NOT IN (SELECT id FROM dept WHERE id IS NOT NULL);"));
+        // Nothing is in an empty subquery, not even NULL.
+        assert_eq!(names(&["a", "b", "c", "d"]),
+                   run(b"SELECT name FROM emp WHERE dept_id NOT IN (SELECT id FROM closed);"));
+
+        assert_eq!(names(&["a", "b", "c", "d"]),
+                   run(b"SELECT name FROM emp WHERE EXISTS (SELECT id FROM dept);"));
+        assert_eq!(Ok(None), run(b"SELECT name FROM emp WHERE EXISTS (SELECT id FROM closed);"));
+        assert_eq!(names(&["a", "b", "c", "d"]),
+                   run(b"SELECT name FROM emp WHERE NOT EXISTS (SELECT id FROM closed);"));
+    }
+
+    #[test]
+    fn test_select_correlated_subqueries() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), dept_id integer, salary integer); \
+        CREATE TABLE dept (id integer, name varchar(10)); \
+        INSERT INTO emp VALUES ('a', 1, 100); INSERT INTO emp VALUES ('b', 1, 200); \
+        INSERT INTO emp VALUES ('c', 2, 150); INSERT INTO emp VALUES ('d', 2, 150); \
+        INSERT INTO emp VALUES ('e', 3, 50); \
+        INSERT INTO dept VALUES (1, 'sales'); INSERT INTO dept VALUES (2, 'eng'); \
+        INSERT INTO dept VALUES (3, 'ops'); INSERT INTO dept VALUES (4, 'hr');").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let names = |names: &[&str]| -> CommandResult {
+            Ok(Some(names.iter().map(|name| TupleLiteral::from_iter(vec![(*name).into()])).collect()))
+        };
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            // Employees earning more than the average of their department.
+            assert_eq!(names(&["b"]),
+                       run(b"SELECT name FROM emp AS e WHERE salary > \
+                             (SELECT AVG(salary) FROM emp AS e2 WHERE e2.dept_id = e.dept_id);"));
+            // Employees earning the most in their department.
+            assert_eq!(names(&["b", "c", "d", "e"]),
+                       run(b"SELECT name FROM emp AS e WHERE salary IN \
+                             (SELECT MAX(salary) FROM emp AS e2 WHERE e2.dept_id = e.dept_id);"));
+
+            assert_eq!(names(&["sales", "eng", "ops"]),
+                       run(b"SELECT name FROM dept AS d WHERE EXISTS (SELECT * FROM emp WHERE emp.dept_id = d.id);"));
+            assert_eq!(names(&["hr"]),
+                       run(b"SELECT name FROM dept AS d WHERE NOT EXISTS (SELECT * FROM emp WHERE emp.dept_id = d.id);"));
+            // Subquery tests that aren't done with joins are evaluated for each row.
+            assert_eq!(names(&["sales", "eng", "hr"]),
+                       run(b"SELECT name FROM dept AS d WHERE d.id = 4 OR \
+                             EXISTS (SELECT * FROM emp WHERE emp.dept_id = d.id AND salary > 120);"));
+
+            let pairs = [("a", "sales"), ("b", "sales"), ("c", "eng"), ("d", "eng"), ("e", "ops")];
+            let expected = pairs.iter()
+                .map(|&(emp, dept)| TupleLiteral::from_iter(vec![emp.into(), dept.into()]))
+                .collect();
+            assert_eq!(Ok(Some(expected)),
+                       run(b"SELECT name, (SELECT name FROM dept WHERE dept.id = emp.dept_id) FROM emp;"));
+        }
+    }
+
+    #[test]
+    fn test_select_empty_subqueries() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (id integer, name varchar(10), mgr integer); \
+        INSERT INTO emp VALUES (1, 'a', 0); INSERT INTO emp VALUES (2, 'b', 1); \
+        INSERT INTO emp VALUES (3, 'c', 1); INSERT INTO emp VALUES (4, 'd', 2);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let rows = |rows: Vec<(&str, Literal)>| -> CommandResult {
+            Ok(Some(rows.into_iter().map(|(name, value)| TupleLiteral::from_iter(vec![name.into(), value])).collect()))
+        };
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            // An aggregate without grouping produces one row even when there are no rows to
+            // aggregate, so employees without reports count 0 of them.
+            assert_eq!(rows(vec![("a", 2.into()), ("b", 1.into()), ("c", 0.into()), ("d", 0.into())]),
+                       run(b"SELECT name, (SELECT COUNT(*) FROM emp AS f WHERE f.mgr = e.id) FROM emp AS e;"));
+            // A scalar subquery that produces no rows is NULL.
+            assert_eq!(rows(vec![("a", Literal::Null), ("b", "a".into()), ("c", "a".into()), ("d", "b".into())]),
+                       run(b"SELECT name, (SELECT name FROM emp AS m WHERE m.id = e.mgr) FROM emp AS e;"));
+
+            assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![0.into(), Literal::Null])])),
+                       run(b"SELECT COUNT(*), MAX(id) FROM emp WHERE id > 10;"));
+            assert_eq!(Ok(None), run(b"SELECT mgr, COUNT(*) FROM emp WHERE id > 10 GROUP BY mgr;"));
+        }
+    }
+
+    #[test]
+    fn test_select_derived_tables() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), dept_id integer, salary integer); \
+        CREATE TABLE dept (id integer, name varchar(10)); \
+        INSERT INTO emp VALUES ('a', 1, 100); INSERT INTO emp VALUES ('b', 1, 200); \
+        INSERT INTO emp VALUES ('c', 2, 150); INSERT INTO emp VALUES ('d', 2, 150); \
+        INSERT INTO emp VALUES ('e', 3, 50); \
+        INSERT INTO dept VALUES (1, 'sales'); INSERT INTO dept VALUES (2, 'eng'); \
+        INSERT INTO dept VALUES (3, 'ops'); INSERT INTO dept VALUES (4, 'hr');").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let names = |names: &[&str]| -> CommandResult {
+            Ok(Some(names.iter().map(|name| TupleLiteral::from_iter(vec![(*name).into()])).collect()))
+        };
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            // Aggregating before joining.
+            assert_eq!(names(&["eng", "sales"]),
+                       run(b"SELECT dept.name FROM dept JOIN \
+                             (SELECT dept_id, AVG(salary) AS avg_salary FROM emp GROUP BY dept_id) AS s \
+                             ON dept.id = s.dept_id WHERE s.avg_salary > 100 ORDER BY dept.name;"));
+
+            assert_eq!(names(&["b", "c", "d"]),
+                       run(b"SELECT t.name FROM (SELECT name, salary FROM emp WHERE salary > 100) AS t \
+                             ORDER BY t.salary DESC, t.name;"));
+            // The columns of a derived table are named after its select-values.
+            assert_eq!(names(&["hr"]), run(b"SELECT n FROM (SELECT name AS n FROM dept) AS d WHERE d.n = 'hr';"));
+            assert_eq!(names(&["eng"]),
+                       run(b"SELECT x FROM (SELECT n AS x FROM (SELECT name AS n FROM dept) AS d1) AS d2 \
+                             WHERE x = 'eng';"));
+        }
+    }
+
+    #[test]
+    fn test_select_set_operations() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let stmts = statements(b"CREATE TABLE foo (a integer, b varchar(10)); \
+        CREATE TABLE bar (c integer, d varchar(20)); \
+        INSERT INTO foo VALUES (1, 'x'); INSERT INTO foo VALUES (2, 'y'); \
+        INSERT INTO foo VALUES (2, 'y'); INSERT INTO foo VALUES (3, 'z'); \
+        INSERT INTO bar VALUES (2, 'y'); INSERT INTO bar VALUES (3, 'w'); \
+        INSERT INTO bar VALUES (4, 'v');").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let ints = |values: &[i32]| -> CommandResult {
+            Ok(Some(values.iter().map(|value| TupleLiteral::from_iter(vec![Literal::Int(*value)])).collect()))
+        };
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            assert_eq!(ints(&[1, 2, 3]), run(b"SELECT a FROM foo UNION SELECT a FROM foo ORDER BY a;"));
+            assert_eq!(ints(&[1, 2, 2, 2, 3, 3, 4]),
+                       run(b"SELECT a FROM foo UNION ALL SELECT c FROM bar ORDER BY a;"));
+            assert_eq!(ints(&[2, 3]), run(b"SELECT a FROM foo INTERSECT SELECT c FROM bar ORDER BY a;"));
+            assert_eq!(ints(&[2]), run(b"SELECT a FROM foo INTERSECT ALL SELECT a FROM foo WHERE a = 2 LIMIT 1;"));
+            assert_eq!(ints(&[1]), run(b"SELECT a FROM foo EXCEPT SELECT c FROM bar;"));
+            assert_eq!(ints(&[1, 2]), run(b"SELECT a FROM foo EXCEPT ALL SELECT c FROM bar ORDER BY a;"));
+            assert_eq!(ints(&[4, 3]), run(b"SELECT a FROM foo UNION SELECT c FROM bar ORDER BY a DESC LIMIT 2;"));
+
+            // Rows are compared on all of their columns.
+            let result = run(b"SELECT a, b FROM foo INTERSECT SELECT c, d FROM bar;");
+            assert_eq!(Ok(Some(vec![TupleLiteral::from_iter(vec![Literal::Int(2), "y".into()])])), result);
+
+            // Set operations can be nested in subqueries.
+            assert_eq!(ints(&[1, 3]),
+                       run(b"SELECT a FROM foo WHERE a IN (SELECT 1 UNION SELECT c FROM bar WHERE d = 'w') ORDER BY a;"));
+            assert_eq!(ints(&[2, 3]),
+                       run(b"SELECT t.a FROM (SELECT a FROM foo INTERSECT SELECT c FROM bar) AS t ORDER BY t.a;"));
+
+            // The queries must produce compatible columns.
+            assert_eq!(Err(ExecutionError::CouldNotExecutePlan(PlanError::SetOperandArity(2, 1))),
+                       run(b"SELECT a, b FROM foo UNION SELECT c FROM bar;"));
+            match run(b"SELECT a FROM foo UNION SELECT d FROM bar;") {
+                Err(ExecutionError::CouldNotExecutePlan(PlanError::IncompatibleSetOperandTypes(..))) => {}
+                result => panic!("Expected incompatible column types, got {:?}", result),
+            }
+        }
+    }
+
+    #[test]
+    fn test_select_common_table_expressions() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        // An org chart, where 'a' manages 'b' and 'c', and 'c' manages 'd', and some parts with
+        // their subparts.
+        let stmts = statements(b"CREATE TABLE emp (name varchar(10), manager varchar(10), salary integer); \
+        CREATE TABLE parts (part varchar(10), subpart varchar(10), quantity integer); \
+        INSERT INTO emp VALUES ('a', '', 300); INSERT INTO emp VALUES ('b', 'a', 100); \
+        INSERT INTO emp VALUES ('c', 'a', 200); INSERT INTO emp VALUES ('d', 'c', 50); \
+        INSERT INTO parts VALUES ('bike', 'wheel', 2); INSERT INTO parts VALUES ('wheel', 'spoke', 30); \
+        INSERT INTO parts VALUES ('wheel', 'rim', 1); INSERT INTO parts VALUES ('bike', 'frame', 1);").unwrap().1;
+        for stmt in stmts {
+            server.handle_command(stmt);
+        }
+
+        let names = |names: &[&str]| -> CommandResult {
+            Ok(Some(names.iter().map(|name| TupleLiteral::from_iter(vec![(*name).into()])).collect()))
+        };
+
+        for &planner_type in [PlannerType::Simple, PlannerType::CostBased].iter() {
+            server.planner_type = planner_type;
+            let mut run = |sql: &[u8]| {
+                let ref mut select_command = statements(sql).unwrap().1[0];
+                select_command.execute(&mut server, &mut ::std::io::sink())
+            };
+
+            assert_eq!(names(&["a", "c"]),
+                       run(b"WITH rich AS (SELECT name FROM emp WHERE salary > 150) \
+                             SELECT name FROM rich ORDER BY name;"));
+            // The columns can be renamed, and later queries can refer to earlier ones.
+            assert_eq!(names(&["c"]),
+                       run(b"WITH rich (n) AS (SELECT name FROM emp WHERE salary > 150), \
+                                  managers (n) AS (SELECT manager FROM emp WHERE manager <> '') \
+                             SELECT rich.n FROM rich JOIN managers ON rich.n = managers.n \
+                             WHERE rich.n <> 'a';"));
+            // A query can be referred to more than once, and from subqueries.
+            assert_eq!(names(&["b", "c"]),
+                       run(b"WITH e AS (SELECT name, manager FROM emp) \
+                             SELECT e1.name FROM e AS e1 JOIN e AS e2 ON e1.manager = e2.name \
+                             WHERE e2.name IN (SELECT name FROM e WHERE manager = '') ORDER BY e1.name;"));
+            // The name of a query hides that of a table.
+            assert_eq!(names(&["d"]),
+                       run(b"WITH emp AS (SELECT name FROM emp WHERE salary < 100) SELECT name FROM emp;"));
+
+            // Everyone who reports to 'a', directly or indirectly.
+            assert_eq!(names(&["b", "c", "d"]),
+                       run(b"WITH RECURSIVE reports (name) AS ( \
+                                 SELECT name FROM emp WHERE manager = 'a' \
+                                 UNION ALL \
+                                 SELECT emp.name FROM emp JOIN reports ON emp.manager = reports.name) \
+                             SELECT name FROM reports ORDER BY name;"));
+
+            // The number of each basic part needed for a bike.
+            let result = run(b"WITH RECURSIVE needed (part, quantity) AS ( \
+                                   SELECT subpart, quantity FROM parts WHERE part = 'bike' \
+                                   UNION ALL \
+                                   SELECT p.subpart, p.quantity * n.quantity FROM parts AS p JOIN needed AS n \
+                                   ON p.part = n.part) \
+                               SELECT part, quantity FROM needed WHERE part NOT IN (SELECT part FROM parts) \
+                               ORDER BY part;");
+            let expected: Vec<TupleLiteral> = vec![("frame", 1), ("rim", 2), ("spoke", 60)]
+                .into_iter()
+                .map(|(part, quantity)| TupleLiteral::from_iter(vec![part.into(), Literal::Int(quantity)]))
+                .collect();
+            assert_eq!(Ok(Some(expected)), result);
+
+            // Counting up to a limit, without any tables.
+            let result = run(b"WITH RECURSIVE t (n) AS (SELECT 1 UNION ALL SELECT n + 1 FROM t WHERE n < 5) \
+                               SELECT n FROM t;");
+            assert_eq!(Ok(Some((1..6).map(|n| TupleLiteral::from_iter(vec![Literal::Int(n)])).collect())), result);
+
+            // Without ALL, rows that have already been produced are discarded, so cycles end.
+            let result = run(b"WITH RECURSIVE t (n) AS (SELECT 1 UNION SELECT n % 3 + 1 FROM t) \
+                               SELECT n FROM t ORDER BY n;");
+            assert_eq!(Ok(Some((1..4).map(|n| TupleLiteral::from_iter(vec![Literal::Int(n)])).collect())), result);
+        }
+    }
 }
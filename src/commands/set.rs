@@ -0,0 +1,133 @@
+use ::Server;
+use ::commands::{Command, CommandResult, ExecutionError};
+use ::expressions::Literal;
+use ::queries::PlannerType;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command for changing a setting of the server for the rest of the session, e.g.
+/// `SET PLANNER = COST_BASED`. The supported settings are:
+///
+/// - `PLANNER` - the planner used to plan `SELECT` statements, either `SIMPLE` or `COST_BASED`.
+/// - `SORT_MEMORY` - the number of bytes of tuples each sort may keep in memory, which must be
+///   positive.
+pub struct SetCommand {
+    name: String,
+    value: Literal,
+}
+
+impl SetCommand {
+    /// Creates a new set command.
+    ///
+    /// # Arguments
+    /// * name - The name of the setting, in upper case.
+    /// * value - The new value of the setting. Names given as values are passed as strings.
+    pub fn new(name: String, value: Literal) -> SetCommand {
+        SetCommand {
+            name: name,
+            value: value,
+        }
+    }
+
+    fn invalid_value(&self) -> ExecutionError {
+        ExecutionError::InvalidSettingValue(self.name.clone(), self.value.clone())
+    }
+}
+
+impl Command for SetCommand {
+    fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
+        match self.name.as_str() {
+            "PLANNER" => {
+                server.planner_type = try!(self.value
+                    .as_string()
+                    .and_then(|name| name.parse::<PlannerType>().ok())
+                    .ok_or_else(|| self.invalid_value()));
+            }
+            "SORT_MEMORY" => {
+                server.sort_memory = match self.value {
+                    Literal::Int(i) if i > 0 => i as usize,
+                    Literal::Long(l) if l > 0 => l as usize,
+                    _ => return Err(self.invalid_value()),
+                };
+            }
+            _ => return Err(ExecutionError::UnknownSetting(self.name.clone())),
+        }
+        Ok(None)
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::parser::statements;
+
+    fn execute(server: &mut Server, sql: &[u8]) -> CommandResult {
+        let ref mut command = statements(sql).unwrap().1[0];
+        command.execute(server, &mut ::std::io::sink())
+    }
+
+    #[test]
+    fn test_set_planner() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        assert_eq!(PlannerType::Simple, server.planner_type);
+
+        assert_eq!(Ok(None), execute(&mut server, b"SET PLANNER = cost_based;"));
+        assert_eq!(PlannerType::CostBased, server.planner_type);
+        assert_eq!(Ok(None), execute(&mut server, b"set planner to 'SIMPLE';"));
+        assert_eq!(PlannerType::Simple, server.planner_type);
+
+        assert_eq!(Err(ExecutionError::InvalidSettingValue("PLANNER".into(), Literal::String("FASTEST".into()))),
+                   execute(&mut server, b"SET PLANNER = fastest;"));
+        assert_eq!(Err(ExecutionError::InvalidSettingValue("PLANNER".into(), Literal::Int(1))),
+                   execute(&mut server, b"SET PLANNER = 1;"));
+        assert_eq!(Err(ExecutionError::UnknownSetting("COLOR".into())), execute(&mut server, b"SET COLOR = red;"));
+        assert_eq!(PlannerType::Simple, server.planner_type);
+
+        // The planner that was set is used for the following queries.
+        let sql = b"CREATE TABLE foo (a integer); INSERT INTO foo VALUES (1); SET PLANNER = COST_BASED;";
+        for stmt in statements(sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+        assert_eq!(PlannerType::CostBased, server.planner_type);
+        let rows = execute(&mut server, b"SELECT a FROM foo;").unwrap().unwrap();
+        assert_eq!(1, rows.len());
+    }
+
+    #[test]
+    fn test_set_sort_memory() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        assert_eq!(::queries::DEFAULT_SORT_MEMORY, server.sort_memory);
+
+        assert_eq!(Ok(None), execute(&mut server, b"SET SORT_MEMORY = 100;"));
+        assert_eq!(100, server.sort_memory);
+        assert_eq!(Err(ExecutionError::InvalidSettingValue("SORT_MEMORY".into(), Literal::Int(0))),
+                   execute(&mut server, b"SET SORT_MEMORY = 0;"));
+        assert_eq!(Err(ExecutionError::InvalidSettingValue("SORT_MEMORY".into(), Literal::String("LOTS".into()))),
+                   execute(&mut server, b"SET SORT_MEMORY = lots;"));
+        assert_eq!(100, server.sort_memory);
+
+        // With such a small budget, the sorts of both planners have to merge run files.
+        let mut sql = b"CREATE TABLE foo (a integer, b varchar(10));".to_vec();
+        for i in 0..50 {
+            sql.extend(format!(" INSERT INTO foo VALUES ({}, 'row {}');", (i * 17) % 50, i).into_bytes());
+        }
+        for stmt in statements(&sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+        for planner in vec![&b"SET PLANNER = SIMPLE;"[..], &b"SET PLANNER = COST_BASED;"[..]] {
+            assert_eq!(Ok(None), execute(&mut server, planner));
+            let values: Vec<Literal> = execute(&mut server, b"SELECT a FROM foo ORDER BY a DESC;").unwrap().unwrap()
+                .into_iter()
+                .map(|tuple| tuple[0].clone())
+                .collect();
+            assert_eq!((0..50).rev().map(Literal::Int).collect::<Vec<_>>(), values);
+        }
+    }
+}
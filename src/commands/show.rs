@@ -22,7 +22,11 @@ impl Command for ShowCommand {
                 match server.file_manager.get_file_paths() {
                     Ok(paths) => {
                         let header = vec!["TABLE NAME"];
-                        let mut table_names: Vec<String> = paths.iter().map(|p| {
+                        // Only table files are listed, not index files or the write-ahead log.
+                        let table_paths = paths.iter().filter(|p| {
+                            p.extension().map(|ext| ext == "tbl") == Some(true)
+                        });
+                        let mut table_names: Vec<String> = table_paths.map(|p| {
                             p.as_path()
                                 .file_stem().unwrap()
                                 .to_str().unwrap()
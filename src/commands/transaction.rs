@@ -0,0 +1,82 @@
+use ::Server;
+use ::commands::{Command, CommandResult, ExecutionError};
+use ::transactions::transaction_manager;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command for starting or ending a transaction.
+pub enum TransactionCommand {
+    /// Start a transaction, which lasts until it is committed or rolled back.
+    Begin,
+    /// Commit the current transaction.
+    Commit,
+    /// Roll back the current transaction, undoing all of its changes.
+    Rollback,
+}
+
+impl Command for TransactionCommand {
+    fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
+        let data_dir = server.file_manager.get_base_dir().to_path_buf();
+        let result = match *self {
+            TransactionCommand::Begin => transaction_manager::begin_transaction(&data_dir).map(|_| ()),
+            TransactionCommand::Commit => transaction_manager::commit_transaction(&data_dir),
+            TransactionCommand::Rollback => transaction_manager::rollback_transaction(&data_dir),
+        };
+        result.map(|_| None).map_err(ExecutionError::TransactionError)
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::expressions::Literal;
+    use ::parser::statements;
+    use ::storage::TupleLiteral;
+    use ::transactions::{LogSequenceNumber, TransactionStateFile};
+
+    fn run(server: &mut Server, sql: &[u8]) {
+        for stmt in statements(sql).unwrap().1 {
+            server.handle_command(stmt);
+        }
+    }
+
+    fn select_all(server: &mut Server) -> Vec<TupleLiteral> {
+        let ref mut select_command = statements(b"SELECT * FROM foo;").unwrap().1[0];
+        select_command.execute(server, &mut ::std::io::sink()).unwrap().unwrap_or(vec![])
+    }
+
+    #[test]
+    fn test_transactions() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+        run(&mut server, b"CREATE TABLE foo (a integer); CREATE UNIQUE INDEX idx ON foo (a);");
+
+        run(&mut server, b"BEGIN; INSERT INTO foo VALUES (1); INSERT INTO foo VALUES (2); ROLLBACK;");
+        assert!(select_all(&mut server).is_empty());
+
+        // A failed statement only undoes its own changes, and the transaction stays in progress.
+        run(&mut server, b"START TRANSACTION; INSERT INTO foo VALUES (1); INSERT INTO foo VALUES (1);");
+        run(&mut server, b"INSERT INTO foo VALUES (2); COMMIT WORK;");
+        assert_eq!(vec![TupleLiteral::from_iter(vec![Literal::Int(1)]),
+                        TupleLiteral::from_iter(vec![Literal::Int(2)])],
+                   select_all(&mut server));
+
+        // Tables can't be created or dropped inside a transaction.
+        run(&mut server, b"BEGIN WORK; DROP TABLE foo; CREATE TABLE bar (a integer); COMMIT;");
+        assert_eq!(2, select_all(&mut server).len());
+        assert!(!dir.path().join("BAR.tbl").exists());
+
+        let mut command = TransactionCommand::Commit;
+        assert_eq!(Err(ExecutionError::TransactionError(::transactions::Error::NoTransactionInProgress)),
+                   command.execute(&mut server, &mut ::std::io::sink()));
+
+        let state = TransactionStateFile::open(dir.path()).unwrap().unwrap();
+        assert!(state.next_txn_id > 1);
+        assert!(state.last_committed_lsn > LogSequenceNumber::default());
+    }
+}
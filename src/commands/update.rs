@@ -0,0 +1,165 @@
+use ::Server;
+use ::commands::{Command, CommandResult, ExecutionError};
+use ::expressions::{Environment, Expression, Literal};
+use ::queries::make_simple_select;
+use ::storage::TupleLiteral;
+
+#[derive(Debug, Clone, PartialEq)]
+/// A command for changing the values of rows in a table.
+pub struct UpdateCommand {
+    table_name: String,
+    values: Vec<(String, Expression)>,
+    where_expr: Option<Expression>,
+}
+
+impl UpdateCommand {
+    /// Creates a new update command.
+    ///
+    /// # Arguments
+    /// * table_name - The name of the table to update rows in.
+    /// * values - The columns to change, along with the expressions computing their new values.
+    /// * where_expr - Optionally, a predicate that the updated rows must satisfy. If there is no
+    ///   predicate, every row is updated.
+    pub fn new(table_name: String,
+               values: Vec<(String, Expression)>,
+               where_expr: Option<Expression>)
+               -> UpdateCommand {
+        UpdateCommand {
+            table_name: table_name,
+            values: values,
+            where_expr: where_expr,
+        }
+    }
+}
+
+impl Command for UpdateCommand {
+    fn execute(&mut self, server: &mut Server, _out: &mut ::std::io::Write) -> CommandResult {
+        if !server.table_manager.table_exists(&server.file_manager, self.table_name.as_str()) {
+            return Err(ExecutionError::TableDoesNotExist(self.table_name.clone()));
+        }
+        let table = try!(server.table_manager
+            .get_table(&server.file_manager, self.table_name.as_str())
+            .map_err(|e| ExecutionError::CouldNotOpenTable(self.table_name.clone(), e)));
+        let schema = table.get_schema();
+
+        // Verify all columns exist in the schema.
+        let mut column_indexes = Vec::with_capacity(self.values.len());
+        for &(ref col_name, _) in self.values.iter() {
+            match schema.find_columns(&(None, Some(col_name.clone()))).first() {
+                Some(&(index, _)) => column_indexes.push(index),
+                None => return Err(ExecutionError::ColumnDoesNotExist(col_name.clone())),
+            }
+        }
+
+        // Find all of the tuples to update before changing any of them, so that updated tuples
+        // aren't found by the scan again.
+        let to_update = {
+            let mut plan = try!(make_simple_select(&server.file_manager,
+                                                   &server.table_manager,
+                                                   self.table_name.as_str(),
+                                                   self.where_expr.clone())
+                .map_err(ExecutionError::CouldNotExecutePlan));
+            plan.initialize();
+
+            let mut to_update: Vec<(Literal, TupleLiteral)> = Vec::new();
+            while let Some(tuple) = try!(plan.get_next_tuple().map_err(ExecutionError::CouldNotGetNextTuple)) {
+                let values = TupleLiteral::from_tuple(tuple);
+                let pointer = tuple.get_external_reference().unwrap_or(Literal::Null);
+                try!(tuple.unpin());
+                to_update.push((pointer, values));
+            }
+            to_update
+        };
+
+        let mut num_updated = 0;
+        for (pointer, old_values) in to_update {
+            // The new values are computed from the old values of the row.
+            let mut env = Environment::new();
+            env.add_tuple(schema.clone(), old_values.clone());
+
+            let mut new_values: Vec<Literal> = old_values.iter().cloned().collect();
+            for (&(ref col_name, ref expr), &index) in self.values.iter().zip(column_indexes.iter()) {
+                let value = try!(expr.evaluate(&mut Some(&mut env), &None));
+                if !schema[index].column_type.can_store_literal(value.clone()) {
+                    return Err(ExecutionError::CannotStoreExpression(col_name.clone(), expr.clone()));
+                }
+                new_values[index] = value;
+            }
+
+            let new_values = TupleLiteral::from_iter(new_values);
+            if try!(table.update_tuple(&pointer, &old_values, &new_values)
+                .map_err(ExecutionError::CouldNotUpdateTuple)) {
+                num_updated += 1;
+            }
+        }
+        println!("Updated {} rows.", num_updated);
+        Ok(None)
+    }
+
+    fn as_any(&self) -> &::std::any::Any {
+        self
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::parser::statements;
+    use ::storage::table_manager;
+
+    fn run(server: &mut Server, sql: &[u8]) -> CommandResult {
+        let ref mut command = statements(sql).unwrap().1[0];
+        command.execute(server, &mut ::std::io::sink())
+    }
+
+    fn select_all(server: &mut Server, sql: &[u8]) -> Vec<TupleLiteral> {
+        run(server, sql).unwrap().unwrap_or(vec![])
+    }
+
+    #[test]
+    fn test_update() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let mut sql = String::from("CREATE TABLE foo (a integer, b varchar(200)); \
+                                    CREATE UNIQUE INDEX idx ON foo (a);");
+        // Fill most of the first data page, so that growing tuples have to move.
+        let value = |i: i32| format!("{} {}", "v".repeat(100), i);
+        for i in 0..70 {
+            sql += &format!("INSERT INTO foo VALUES ({}, '{}');", i, value(i));
+        }
+        for stmt in statements(sql.as_bytes()).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        // Fixed-size columns are updated in place, and the index follows the new values.
+        assert_eq!(Ok(None), run(&mut server, b"UPDATE foo SET a = a + 100 WHERE a < 10;"));
+        assert!(select_all(&mut server, b"SELECT * FROM foo WHERE a < 10;").is_empty());
+        assert_eq!(vec![TupleLiteral::from_iter(vec![Literal::Int(103), Literal::from(value(3))])],
+                   select_all(&mut server, b"SELECT * FROM foo WHERE a = 103;"));
+
+        // Growing values past the room left in their page moves them to other pages.
+        let long_value = "x".repeat(200);
+        let update = format!("UPDATE foo SET b = '{}' WHERE a >= 20 AND a < 40;", long_value);
+        assert_eq!(Ok(None), run(&mut server, update.as_bytes()));
+        let results = select_all(&mut server, b"SELECT * FROM foo WHERE a > 19 AND a < 40;");
+        assert_eq!(20, results.len());
+        assert!(results.iter().all(|tuple| tuple[1] == Literal::from(long_value.as_str())));
+
+        // Shrinking values works as well, and the other tuples are unchanged.
+        assert_eq!(Ok(None), run(&mut server, b"UPDATE foo SET b = 'short' WHERE a = 25;"));
+        assert_eq!(vec![TupleLiteral::from_iter(vec![Literal::Int(25), Literal::from("short")])],
+                   select_all(&mut server, b"SELECT * FROM foo WHERE a = 25;"));
+        assert_eq!(70, select_all(&mut server, b"SELECT * FROM foo;").len());
+        assert_eq!(vec![TupleLiteral::from_iter(vec![Literal::Int(15), Literal::from(value(15))])],
+                   select_all(&mut server, b"SELECT * FROM foo WHERE a = 15;"));
+
+        // Updates that would violate a unique index are rejected.
+        assert_eq!(Err(ExecutionError::CouldNotUpdateTuple(table_manager::Error::UniqueViolation("IDX".into()))),
+                   run(&mut server, b"UPDATE foo SET a = 15 WHERE a = 16;"));
+        assert_eq!(Err(ExecutionError::ColumnDoesNotExist("C".into())),
+                   run(&mut server, b"UPDATE foo SET c = 1;"));
+    }
+}
@@ -0,0 +1,398 @@
+//! This module contains the exact fixed-point number type used for `NUMERIC` and `DECIMAL` values.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::hash::{Hash, Hasher};
+use std::str::FromStr;
+
+use ::expressions::{ArithmeticType, ExpressionError};
+use ::relations::ColumnType;
+
+/// The largest precision (total number of digits) a `NUMERIC` value can have.
+pub const MAX_NUMERIC_PRECISION: u16 = 18;
+
+/// The number of digits after the decimal point that the result of a division has at least.
+const MIN_DIVIDE_SCALE: u16 = 6;
+
+fn pow10(exp: u16) -> i128 {
+    10i128.pow(exp as u32)
+}
+
+/// Divides two numbers, rounding halfway cases away from zero.
+fn div_round(numerator: i128, denominator: i128) -> i128 {
+    let quotient = numerator / denominator;
+    let remainder = numerator % denominator;
+    if remainder.abs() * 2 >= denominator.abs() {
+        if (numerator < 0) == (denominator < 0) { quotient + 1 } else { quotient - 1 }
+    } else {
+        quotient
+    }
+}
+
+/// An exact decimal number, stored as an unscaled integer value along with a scale, so that the
+/// number's value is `unscaled / 10^scale`. All arithmetic on decimals is exact, except that
+/// division rounds to the scale of its result.
+///
+/// Decimals are compared by value, so `1.5` and `1.50` are equal even though their scales differ.
+/// A decimal never has more than [`MAX_NUMERIC_PRECISION`](constant.MAX_NUMERIC_PRECISION.html)
+/// digits; arithmetic that would produce a larger value fails.
+#[derive(Debug, Clone, Copy)]
+pub struct Decimal {
+    unscaled: i64,
+    scale: u16,
+}
+
+impl Decimal {
+    /// Instantiate a new decimal with the value `unscaled / 10^scale`.
+    ///
+    /// # Arguments
+    /// * unscaled - The digits of the decimal, as an integer.
+    /// * scale - The number of digits after the decimal point.
+    pub fn new(unscaled: i64, scale: u16) -> Decimal {
+        Decimal {
+            unscaled: unscaled,
+            scale: scale,
+        }
+    }
+
+    /// Creates a decimal from a 128-bit unscaled value, failing if it has too many digits.
+    fn from_i128(unscaled: i128, scale: u16) -> Result<Decimal, ExpressionError> {
+        if unscaled.abs() >= pow10(MAX_NUMERIC_PRECISION) || scale > MAX_NUMERIC_PRECISION {
+            Err(ExpressionError::NumericOverflow)
+        } else {
+            Ok(Decimal::new(unscaled as i64, scale))
+        }
+    }
+
+    /// Converts a floating-point number to the decimal with the shortest representation that
+    /// converts back to the same number, so that e.g. `0.1` becomes exactly `0.1`. Returns `None`
+    /// if the number is not finite or has too many digits.
+    ///
+    /// # Arguments
+    /// * value - The number to convert.
+    pub fn from_f64(value: f64) -> Option<Decimal> {
+        if !value.is_finite() {
+            return None;
+        }
+        Decimal::from_str(&format!("{}", value)).ok()
+    }
+
+    /// The digits of the decimal, as an integer.
+    pub fn get_unscaled(&self) -> i64 {
+        self.unscaled
+    }
+
+    /// The number of digits after the decimal point.
+    pub fn get_scale(&self) -> u16 {
+        self.scale
+    }
+
+    /// The total number of digits needed to store the decimal at its scale.
+    pub fn get_precision(&self) -> u16 {
+        let mut digits = 1;
+        let mut value = self.unscaled.abs() / 10;
+        while value > 0 {
+            digits += 1;
+            value /= 10;
+        }
+        ::std::cmp::max(digits, self.scale)
+    }
+
+    /// Converts the decimal to the closest floating-point number.
+    pub fn to_f64(&self) -> f64 {
+        self.unscaled as f64 / 10f64.powi(self.scale as i32)
+    }
+
+    /// Converts the decimal to an integer, truncating any digits after the decimal point.
+    pub fn to_i64(&self) -> i64 {
+        (self.unscaled as i128 / pow10(self.scale)) as i64
+    }
+
+    /// Returns the same value with a different scale. When the scale is reduced, the value is
+    /// rounded, with halfway cases rounded away from zero.
+    ///
+    /// # Arguments
+    /// * scale - The new scale.
+    ///
+    /// # Errors
+    /// This will fail if the value would have too many digits at the new scale.
+    pub fn rescale(&self, scale: u16) -> Result<Decimal, ExpressionError> {
+        if scale >= self.scale {
+            if scale - self.scale > MAX_NUMERIC_PRECISION {
+                return Err(ExpressionError::NumericOverflow);
+            }
+            Decimal::from_i128(self.unscaled as i128 * pow10(scale - self.scale), scale)
+        } else {
+            Decimal::from_i128(div_round(self.unscaled as i128, pow10(self.scale - scale)), scale)
+        }
+    }
+
+    /// Checks whether the decimal can be stored in a `NUMERIC` column with the given precision and
+    /// scale, once it is rounded to the column's scale.
+    ///
+    /// # Arguments
+    /// * precision - The total number of digits of the column.
+    /// * scale - The number of digits after the decimal point of the column.
+    pub fn fits(&self, precision: u16, scale: u16) -> bool {
+        match self.rescale(scale) {
+            Ok(value) => value.get_precision() <= precision,
+            Err(_) => false,
+        }
+    }
+
+    /// Returns the scale of the result of an arithmetic operation on two decimals. Sums and
+    /// differences keep the larger scale and products add the scales, while quotients get at
+    /// least six digits after the decimal point.
+    ///
+    /// # Arguments
+    /// * op - The arithmetic operation.
+    /// * left - The scale of the left operand.
+    /// * right - The scale of the right operand.
+    pub fn get_result_scale(op: ArithmeticType, left: u16, right: u16) -> u16 {
+        let scale = match op {
+            ArithmeticType::Multiply => left + right,
+            ArithmeticType::Divide => {
+                ::std::cmp::max(MIN_DIVIDE_SCALE, ::std::cmp::max(left, right))
+            }
+            _ => ::std::cmp::max(left, right),
+        };
+        ::std::cmp::min(scale, MAX_NUMERIC_PRECISION)
+    }
+
+    /// Returns the column type of a `NUMERIC` column that can hold the decimal.
+    pub fn get_column_type(&self) -> ColumnType {
+        ColumnType::Numeric {
+            scale: self.scale,
+            precision: self.get_precision(),
+        }
+    }
+
+    /// Performs exact arithmetic on two decimals.
+    ///
+    /// # Arguments
+    /// * other - The right operand.
+    /// * op - The arithmetic operation.
+    ///
+    /// # Errors
+    /// This will fail if the result has too many digits, or on division by zero.
+    pub fn arithmetic(&self, other: &Decimal, op: ArithmeticType) -> Result<Decimal, ExpressionError> {
+        let scale = Decimal::get_result_scale(op, self.scale, other.scale);
+        let (left, right) = (self.unscaled as i128, other.unscaled as i128);
+        match op {
+            ArithmeticType::Plus | ArithmeticType::Minus | ArithmeticType::Remainder => {
+                let left = left * pow10(scale - self.scale);
+                let right = right * pow10(scale - other.scale);
+                match op {
+                    ArithmeticType::Plus => Decimal::from_i128(left + right, scale),
+                    ArithmeticType::Minus => Decimal::from_i128(left - right, scale),
+                    _ if right == 0 => Err(ExpressionError::DivisionByZero),
+                    _ => Decimal::from_i128(left % right, scale),
+                }
+            }
+            ArithmeticType::Multiply => {
+                let product = left * right;
+                let product_scale = self.scale + other.scale;
+                Decimal::from_i128(div_round(product, pow10(product_scale - scale)), scale)
+            }
+            ArithmeticType::Divide => {
+                if right == 0 {
+                    return Err(ExpressionError::DivisionByZero);
+                }
+                // The quotient's unscaled value is left * 10^(scale + other.scale - self.scale) /
+                // right, where the exponent may be negative.
+                let exp = scale as i32 + other.scale as i32 - self.scale as i32;
+                let (numerator, denominator) = if exp >= 0 {
+                    match left.checked_mul(pow10(exp as u16)) {
+                        Some(numerator) => (numerator, right),
+                        None => return Err(ExpressionError::NumericOverflow),
+                    }
+                } else {
+                    (left, right * pow10((-exp) as u16))
+                };
+                Decimal::from_i128(div_round(numerator, denominator), scale)
+            }
+            ArithmeticType::Min => Ok(if *self <= *other { *self } else { *other }),
+            ArithmeticType::Max => Ok(if *self >= *other { *self } else { *other }),
+        }
+    }
+
+    /// Returns the same value with the trailing zeros after the decimal point removed.
+    fn normalize(&self) -> Decimal {
+        let mut result = *self;
+        while result.scale > 0 && result.unscaled % 10 == 0 {
+            result.unscaled /= 10;
+            result.scale -= 1;
+        }
+        result
+    }
+}
+
+impl FromStr for Decimal {
+    type Err = ExpressionError;
+
+    /// Parses a decimal such as `-123.4500`. The scale of the result is the number of digits
+    /// given after the decimal point.
+    fn from_str(s: &str) -> Result<Decimal, ExpressionError> {
+        let invalid = || ExpressionError::InvalidLiteral("NUMERIC".into(), s.into());
+        let trimmed = s.trim();
+        let (negative, digits) = if trimmed.starts_with('-') {
+            (true, &trimmed[1..])
+        } else if trimmed.starts_with('+') {
+            (false, &trimmed[1..])
+        } else {
+            (false, trimmed)
+        };
+        let (ipart, dpart) = match digits.find('.') {
+            Some(index) => (&digits[..index], &digits[(index + 1)..]),
+            None => (digits, ""),
+        };
+        if ipart.is_empty() && dpart.is_empty() {
+            return Err(invalid());
+        }
+
+        let mut unscaled: i128 = 0;
+        for c in ipart.chars().chain(dpart.chars()) {
+            match c.to_digit(10) {
+                Some(digit) => {
+                    unscaled = unscaled * 10 + digit as i128;
+                    if unscaled >= pow10(MAX_NUMERIC_PRECISION) {
+                        return Err(ExpressionError::NumericOverflow);
+                    }
+                }
+                None => return Err(invalid()),
+            }
+        }
+        if dpart.len() > MAX_NUMERIC_PRECISION as usize {
+            return Err(ExpressionError::NumericOverflow);
+        }
+        Decimal::from_i128(if negative { -unscaled } else { unscaled }, dpart.len() as u16)
+    }
+}
+
+impl PartialEq for Decimal {
+    fn eq(&self, other: &Decimal) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl Eq for Decimal {}
+
+impl Ord for Decimal {
+    fn cmp(&self, other: &Decimal) -> Ordering {
+        let scale = ::std::cmp::max(self.scale, other.scale);
+        let left = self.unscaled as i128 * pow10(scale - self.scale);
+        let right = other.unscaled as i128 * pow10(scale - other.scale);
+        left.cmp(&right)
+    }
+}
+
+impl PartialOrd for Decimal {
+    fn partial_cmp(&self, other: &Decimal) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Hash for Decimal {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        // Equal values must hash the same regardless of their scale.
+        let normalized = self.normalize();
+        normalized.unscaled.hash(state);
+        normalized.scale.hash(state);
+    }
+}
+
+impl fmt::Display for Decimal {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let sign = if self.unscaled < 0 { "-" } else { "" };
+        let magnitude = (self.unscaled as i128).abs();
+        if self.scale == 0 {
+            return write!(f, "{}{}", sign, magnitude);
+        }
+        let divisor = pow10(self.scale);
+        write!(f, "{}{}.{:0width$}", sign, magnitude / divisor, magnitude % divisor,
+               width = self.scale as usize)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn dec(s: &str) -> Decimal {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_parse_and_display() {
+        assert_eq!(Decimal::new(12345, 2), dec("123.45"));
+        assert_eq!(2, dec("123.45").get_scale());
+        assert_eq!(Decimal::new(-5, 3), dec("-0.005"));
+        assert_eq!("-0.005", format!("{}", dec("-0.005")));
+        assert_eq!("12.50", format!("{}", dec("12.50")));
+        assert_eq!("7", format!("{}", dec("+7")));
+        assert_eq!("0.5", format!("{}", dec(".5")));
+        assert!("1.2.3".parse::<Decimal>().is_err());
+        assert!("abc".parse::<Decimal>().is_err());
+        assert!(".".parse::<Decimal>().is_err());
+        assert_eq!(Err(ExpressionError::NumericOverflow), "1234567890123456789".parse::<Decimal>());
+
+        assert_eq!(Some(dec("0.1")), Decimal::from_f64(0.1));
+        assert_eq!(None, Decimal::from_f64(::std::f64::NAN));
+    }
+
+    #[test]
+    fn test_compare() {
+        assert_eq!(dec("1.5"), dec("1.500"));
+        assert!(dec("1.49") < dec("1.5"));
+        assert!(dec("-2") < dec("-1.99"));
+
+        use std::collections::hash_map::DefaultHasher;
+        let hash = |d: Decimal| {
+            let mut hasher = DefaultHasher::new();
+            d.hash(&mut hasher);
+            hasher.finish()
+        };
+        assert_eq!(hash(dec("1.5")), hash(dec("1.500")));
+    }
+
+    #[test]
+    fn test_precision_and_scale() {
+        assert_eq!(5, dec("123.45").get_precision());
+        assert_eq!(3, dec("0.005").get_precision());
+        assert_eq!(1, dec("0").get_precision());
+
+        assert_eq!(Ok(dec("1.24")), dec("1.235").rescale(2));
+        assert_eq!(Ok(dec("-1.24")), dec("-1.235").rescale(2));
+        assert_eq!(2, dec("1.235").rescale(2).unwrap().get_scale());
+        assert_eq!(Ok(Decimal::new(1500, 3)), dec("1.5").rescale(3));
+
+        assert!(dec("999.99").fits(5, 2));
+        assert!(dec("99.999").fits(6, 2));
+        assert!(!dec("999.999").fits(5, 2));
+        assert!(!dec("1000").fits(5, 2));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        // The classic floating-point failure is exact with decimals.
+        assert_eq!(Ok(dec("0.3")), dec("0.1").arithmetic(&dec("0.2"), ArithmeticType::Plus));
+        assert_eq!(Ok(dec("-0.15")), dec("0.1").arithmetic(&dec("0.25"), ArithmeticType::Minus));
+
+        let product = dec("19.99").arithmetic(&dec("3"), ArithmeticType::Multiply).unwrap();
+        assert_eq!((5997, 2), (product.get_unscaled(), product.get_scale()));
+        assert_eq!(Ok(dec("1.2345")), dec("1.5").arithmetic(&dec("0.823"), ArithmeticType::Multiply));
+
+        let quotient = dec("10").arithmetic(&dec("3"), ArithmeticType::Divide).unwrap();
+        assert_eq!("3.333333", format!("{}", quotient));
+        let quotient = dec("2.00").arithmetic(&dec("3"), ArithmeticType::Divide).unwrap();
+        assert_eq!("0.666667", format!("{}", quotient));
+        assert_eq!(Err(ExpressionError::DivisionByZero),
+                   dec("1").arithmetic(&dec("0.00"), ArithmeticType::Divide));
+
+        assert_eq!(Ok(dec("1.5")), dec("7.5").arithmetic(&dec("2"), ArithmeticType::Remainder));
+        assert_eq!(Ok(dec("2")), dec("7.5").arithmetic(&dec("2"), ArithmeticType::Min));
+
+        assert_eq!(Err(ExpressionError::NumericOverflow),
+                   dec("999999999999999999").arithmetic(&dec("1"), ArithmeticType::Plus));
+    }
+}
@@ -54,6 +54,19 @@ impl Environment {
     /// Instantiate a new environment.
     pub fn new() -> Environment { Default::default() }
 
+    /// Instantiate a new environment, which looks up the symbols it can't resolve itself in the
+    /// parent environment if one is given.
+    ///
+    /// # Arguments
+    /// * parent - The environment to add as a parent, if any.
+    pub fn with_parent(parent: Option<&Environment>) -> Environment {
+        let mut env = Environment::new();
+        if let Some(parent) = parent {
+            env.add_parent_env(parent.clone());
+        }
+        env
+    }
+
     /// Reset the environment.
     pub fn clear(&mut self) {
         self.current_schemas.clear();
@@ -254,4 +267,33 @@ mod tests {
         assert_eq!(Ok(Int(2)), env3.get_column_value(&foo_w));
         assert_eq!(Err(AmbiguousColumnName(bar_w.clone())), env3.get_column_value(&bar_w));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_parent_env() {
+        let outer_schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO"),
+        ]).unwrap();
+        let inner_schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "BAR"),
+        ]).unwrap();
+
+        let mut outer = Environment::new();
+        outer.add_tuple(outer_schema, TupleLiteral::from_iter(vec![Int(1), Int(2)]));
+        let mut inner = Environment::with_parent(Some(&outer));
+        inner.add_tuple(inner_schema, TupleLiteral::from_iter(vec![Int(3)]));
+
+        let col_a: ColumnName = (None, Some("A".into()));
+        let col_b: ColumnName = (None, Some("B".into()));
+        let col_c: ColumnName = (None, Some("C".into()));
+        let foo_a: ColumnName = (Some("FOO".into()), Some("A".into()));
+
+        // The inner environment's own columns hide the parent's.
+        assert_eq!(Ok(Int(3)), inner.get_column_value(&col_a));
+        assert_eq!(Ok(Int(2)), inner.get_column_value(&col_b));
+        assert_eq!(Ok(Int(1)), inner.get_column_value(&foo_a));
+        assert_eq!(Err(CouldNotResolve(col_c.clone())), inner.get_column_value(&col_c));
+        assert_eq!(Err(CouldNotResolve(col_b.clone())),
+                   Environment::with_parent(None).get_column_value(&col_b));
+    }
+}
@@ -1,16 +1,21 @@
 //! This module contains utilities for dealing with expressions, including the `Expression` struct.
 
-use ::expressions::{ArithmeticType, CompareType, Environment, ExpressionError, Literal,
-                    ExpressionProcessor, SelectClause};
+use std::cmp::Ordering;
+
+use ::expressions::{ArithmeticType, CompareType, Date, Decimal, Environment, ExpressionError, Interval,
+                    Literal, ExpressionProcessor, MAX_NUMERIC_PRECISION, SelectClause, Time, Timestamp,
+                    WindowSpec};
+use ::expressions::temporal::{temporal_arithmetic, temporal_compare, temporal_result_type};
 use ::functions::Directory;
 use ::queries::{Planner, get_plan_results};
-use ::relations::{EMPTY_NUMERIC, ColumnInfo, ColumnName, ColumnType, Schema, column_name_to_string};
+use ::relations::{ColumnInfo, ColumnName, ColumnType, Schema, column_name_to_string};
+use ::storage::TupleLiteral;
 
 lazy_static! {
     static ref DIRECTORY: Directory = Directory::new();
 }
 
-static TYPE_ORDER: &'static [ColumnType] = &[EMPTY_NUMERIC, ColumnType::Double, ColumnType::Float,
+static TYPE_ORDER: &'static [ColumnType] = &[ColumnType::Double, ColumnType::Float,
     ColumnType::BigInt, ColumnType::Integer, ColumnType::SmallInt, ColumnType::TinyInt];
 
 fn arithmetic_result_type(op: ArithmeticType, left: ColumnType, right: ColumnType) -> ColumnType {
@@ -18,15 +23,18 @@ fn arithmetic_result_type(op: ArithmeticType, left: ColumnType, right: ColumnTyp
     assert!(left.is_numeric());
     assert!(right.is_numeric());
 
-    // We don't care what the precision/scale are.
-    let left = match left {
-        ColumnType::Numeric { .. } => EMPTY_NUMERIC,
-        _ => left
-    };
-    let right = match right {
-        ColumnType::Numeric { .. } => EMPTY_NUMERIC,
-        _ => right
+    // Arithmetic on a NUMERIC value is exact, so the result is NUMERIC with the scale that the
+    // arithmetic produces. The other operand is converted to a decimal, so it adds no scale.
+    let scale = |t: ColumnType| match t {
+        ColumnType::Numeric { scale, .. } => Some(scale),
+        _ => None,
     };
+    if scale(left).is_some() || scale(right).is_some() {
+        return ColumnType::Numeric {
+            scale: Decimal::get_result_scale(op, scale(left).unwrap_or(0), scale(right).unwrap_or(0)),
+            precision: MAX_NUMERIC_PRECISION,
+        };
+    }
 
     match op {
         ArithmeticType::Divide => ColumnType::Double,
@@ -45,7 +53,14 @@ fn arithmetic_result_type(op: ArithmeticType, left: ColumnType, right: ColumnTyp
 
 fn coerce_literals(left: &Literal, right: &Literal) -> (Literal, Literal) {
     // WE ASSUME THAT BOTH LITERALS ARE ARITHMETIC HERE.
-    if left.is_double() || right.is_double() {
+    if left.is_decimal() || right.is_decimal() {
+        // If either is a decimal, coerce both to decimals so the arithmetic is exact, unless the
+        // other is a floating-point value that no decimal can hold.
+        if let (Some(l), Some(r)) = (left.as_decimal(), right.as_decimal()) {
+            return (Literal::Decimal(l), Literal::Decimal(r));
+        }
+        (left.as_double().unwrap(), right.as_double().unwrap())
+    } else if left.is_double() || right.is_double() {
         // If either is a double, coerce both to doubles.
         (left.as_double().unwrap(), right.as_double().unwrap())
     } else if left.is_float() || right.is_float() {
@@ -62,7 +77,13 @@ fn coerce_literals(left: &Literal, right: &Literal) -> (Literal, Literal) {
 /// Perform arithmetic on literals given an arithmetic operator. This handles coercion of literals
 /// properly.
 pub fn literal_arithmetic(left: &Literal, right: &Literal, op: ArithmeticType) -> Result<Literal, ExpressionError> {
+    if left.is_temporal() || right.is_temporal() {
+        return temporal_arithmetic(left, right, op);
+    }
     let (left, right) = coerce_literals(left, right);
+    if let (&Literal::Decimal(l), &Literal::Decimal(r)) = (&left, &right) {
+        return l.arithmetic(&r, op).map(Literal::Decimal);
+    }
     match op {
         ArithmeticType::Plus => {
             match (left, right) {
@@ -130,6 +151,61 @@ pub fn literal_arithmetic(left: &Literal, right: &Literal, op: ArithmeticType) -
     }
 }
 
+/// Returns whether the ordering of two values satisfies a comparison.
+fn ordering_matches(ordering: Ordering, op: CompareType) -> bool {
+    match op {
+        CompareType::GreaterThan => ordering == Ordering::Greater,
+        CompareType::GreaterThanEqual => ordering != Ordering::Less,
+        CompareType::LessThan => ordering == Ordering::Less,
+        CompareType::LessThanEqual => ordering != Ordering::Greater,
+        CompareType::Equals => ordering == Ordering::Equal,
+        CompareType::NotEquals => ordering != Ordering::Equal,
+    }
+}
+
+/// Plans and runs a subquery, returning all of its results. The subquery is run with the current
+/// environment as its parent, so that a correlated subquery can refer to the enclosing query's
+/// current row.
+fn evaluate_subquery(clause: &SelectClause,
+                     env: &Option<&mut Environment>,
+                     planner: &Option<&Planner>)
+                     -> Result<Vec<TupleLiteral>, ExpressionError> {
+    match *planner {
+        Some(ref planner) => {
+            let mut plan = try!(planner.make_plan(clause.clone())
+                .map_err(|e| ExpressionError::CouldNotEvaluateSubquery(clause.clone(), Box::new(e))));
+            if let Some(ref env) = *env {
+                plan.set_parent_env((**env).clone());
+            }
+            get_plan_results(&mut *plan)
+                .map_err(|e| ExpressionError::CouldNotEvaluateSubquery(clause.clone(), Box::new(e)))
+        }
+        None => Err(ExpressionError::SubqueryNeedsPlanner),
+    }
+}
+
+/// Checks whether a value is in the results of a subquery, following SQL's rules for `NULL`: the
+/// result is `NULL` rather than false if there is no match but either the value or one of the
+/// results is `NULL`.
+fn evaluate_in(value: &Literal, clause: &SelectClause, results: &[TupleLiteral]) -> Result<Literal, ExpressionError> {
+    let mut found_null = *value == Literal::Null;
+    for tuple in results {
+        if tuple.len() != 1 {
+            return Err(ExpressionError::SubqueryNotScalar(clause.clone()));
+        }
+        if tuple[0] == Literal::Null {
+            found_null = true;
+        } else if *value != Literal::Null && value.compare(&tuple[0]) == Ordering::Equal {
+            return Ok(Literal::True);
+        }
+    }
+    Ok(if found_null && !results.is_empty() {
+        Literal::Null
+    } else {
+        Literal::False
+    })
+}
+
 /// A SQL-supported expression.
 #[derive(Debug, Clone, PartialEq)]
 pub enum Expression {
@@ -144,6 +220,19 @@ pub enum Expression {
         /// The list of one or more arguments for the function call.
         args: Vec<Expression>
     },
+    /// A window function call, e.g. `RANK() OVER (ORDER BY a)`, which computes a value for each row
+    /// from the other rows of the row's partition.
+    WindowFunction {
+        /// The string name of the function as specified in the original SQL.
+        name: String,
+        /// Whether the `DISTINCT` keyword was used in the function invocation. As with other
+        /// function calls, this is only used for aggregate functions.
+        distinct: bool,
+        /// The list of arguments for the function call.
+        args: Vec<Expression>,
+        /// The window the function is computed over.
+        window: Box<WindowSpec>,
+    },
     /// A Boolean OR expression
     OR(Vec<Expression>),
     /// A Boolean AND expression
@@ -160,6 +249,22 @@ pub enum Expression {
     ColumnValue(ColumnName),
     /// A subquery expression
     Subquery(Box<SelectClause>),
+    /// An `EXISTS` or `NOT EXISTS` test of whether a subquery produces any rows.
+    Exists {
+        /// The subquery to test.
+        subquery: Box<SelectClause>,
+        /// Whether this is a `NOT EXISTS` test.
+        negated: bool,
+    },
+    /// An `IN` or `NOT IN` test of whether a value is one of the values a subquery produces.
+    InSubquery {
+        /// The value to look for.
+        expr: Box<Expression>,
+        /// The subquery producing the values to look in. It must produce a single column.
+        subquery: Box<SelectClause>,
+        /// Whether this is a `NOT IN` test.
+        negated: bool,
+    },
     /// NULL
     Null,
     /// TRUE
@@ -174,8 +279,20 @@ pub enum Expression {
     Float(f32),
     /// A double
     Double(f64),
+    /// An exact decimal number
+    Decimal(Decimal),
     /// A string
     String(String),
+    /// A byte string
+    Bytes(Vec<u8>),
+    /// A date
+    Date(Date),
+    /// A time of day
+    Time(Time),
+    /// A date and time of day
+    Timestamp(Timestamp),
+    /// An interval of time
+    Interval(Interval),
 }
 
 impl From<Literal> for Expression {
@@ -185,7 +302,13 @@ impl From<Literal> for Expression {
             Literal::Long(l) => Expression::Long(l),
             Literal::Float(f) => Expression::Float(f),
             Literal::Double(d) => Expression::Double(d),
+            Literal::Decimal(d) => Expression::Decimal(d),
             Literal::String(s) => Expression::String(s),
+            Literal::Bytes(b) => Expression::Bytes(b),
+            Literal::Date(d) => Expression::Date(d),
+            Literal::Time(t) => Expression::Time(t),
+            Literal::Timestamp(t) => Expression::Timestamp(t),
+            Literal::Interval(i) => Expression::Interval(i),
             Literal::Null => Expression::Null,
             Literal::True => Expression::True,
             Literal::False => Expression::False,
@@ -221,7 +344,13 @@ impl Expression {
             Expression::Long(l) => Literal::Long(l).into(),
             Expression::Float(f) => Literal::Float(f).into(),
             Expression::Double(d) => Literal::Double(d).into(),
+            Expression::Decimal(d) => Literal::Decimal(d).into(),
             Expression::String(ref s) => Literal::String(s.clone()).into(),
+            Expression::Bytes(ref b) => Literal::Bytes(b.clone()).into(),
+            Expression::Date(d) => Literal::Date(d).into(),
+            Expression::Time(t) => Literal::Time(t).into(),
+            Expression::Timestamp(t) => Literal::Timestamp(t).into(),
+            Expression::Interval(i) => Literal::Interval(i).into(),
             Expression::Null => Literal::Null.into(),
             Expression::True => Literal::True.into(),
             Expression::False => Literal::False.into(),
@@ -315,28 +444,36 @@ impl Expression {
                     Err(ExpressionError::CouldNotResolve(name.clone()))
                 }
             }
-            Expression::Function { ref name, ref args, .. } => {
-                let func = try!(DIRECTORY.get(name.as_ref()));
+            Expression::Function { ref name, ref args, .. } |
+            Expression::WindowFunction { ref name, ref args, .. } => {
+                let func = try!(DIRECTORY.get(name.as_str()));
                 func.evaluate(&mut env, args.to_vec(), planner).map_err(Into::into)
             }
             Expression::Subquery(ref clause) => {
-                match *planner {
-                    Some(ref planner) => {
-                        let mut plan = try!(planner.make_plan(*clause.clone())
-                            .map_err(|e| ExpressionError::CouldNotEvaluateSubquery(*clause.clone(), Box::new(e))));
-                        let results = try!(get_plan_results(&mut *plan)
-                            .map_err(|e| ExpressionError::CouldNotEvaluateSubquery(*clause.clone(), Box::new(e))));
-                        if results.is_empty() {
-                            Err(ExpressionError::SubqueryEmpty(*clause.clone()))
-                        } else if results.len() > 1 || results[0].len() > 1 {
-                            Err(ExpressionError::SubqueryNotScalar(*clause.clone()))
-                        } else {
-                            Ok(results[0][0].clone().into())
-                        }
-                    }
-                    None => Err(ExpressionError::SubqueryNeedsPlanner)
+                // A scalar subquery that produces no rows evaluates to NULL.
+                let results = try!(evaluate_subquery(clause, env, planner));
+                if results.is_empty() {
+                    Ok(Literal::Null)
+                } else if results.len() > 1 || results[0].len() > 1 {
+                    Err(ExpressionError::SubqueryNotScalar(*clause.clone()))
+                } else {
+                    Ok(results[0][0].clone().into())
                 }
             }
+            Expression::Exists { ref subquery, negated } => {
+                let results = try!(evaluate_subquery(subquery, env, planner));
+                Ok((results.is_empty() == negated).into())
+            }
+            Expression::InSubquery { ref expr, ref subquery, negated } => {
+                let value = try!(expr.evaluate(env, planner));
+                let results = try!(evaluate_subquery(subquery, env, planner));
+                let result = try!(evaluate_in(&value, subquery, &results));
+                Ok(match result {
+                    Literal::True if negated => Literal::False,
+                    Literal::False if negated => Literal::True,
+                    result => result,
+                })
+            }
             _ => Err(ExpressionError::Unimplemented),
         }
     }
@@ -350,6 +487,9 @@ impl Expression {
                            -> Result<Literal, ExpressionError> {
         let left_val = try!(left.evaluate(&mut env, planner));
         let right_val = try!(right.evaluate(&mut env, planner));
+        if left_val.is_temporal() || right_val.is_temporal() {
+            return temporal_arithmetic(&left_val, &right_val, op);
+        }
         if !left_val.is_numeric() {
             return Err(ExpressionError::NotNumeric(left_val.clone()));
         }
@@ -368,6 +508,16 @@ impl Expression {
                         -> Result<Literal, ExpressionError> {
         let left_val = try!(left.evaluate(&mut env, planner));
         let right_val = try!(right.evaluate(&mut env, planner));
+        if left_val.is_temporal() || right_val.is_temporal() {
+            let ordering = try!(temporal_compare(&left_val, &right_val));
+            return Ok(ordering_matches(ordering, op).into());
+        }
+        if let (&Literal::String(ref l), &Literal::String(ref r)) = (&left_val, &right_val) {
+            return Ok(ordering_matches(l.cmp(r), op).into());
+        }
+        if let (&Literal::Bytes(ref l), &Literal::Bytes(ref r)) = (&left_val, &right_val) {
+            return Ok(ordering_matches(l.cmp(r), op).into());
+        }
         if !left_val.is_numeric() {
             return Err(ExpressionError::NotNumeric(left_val.clone()));
         }
@@ -375,6 +525,9 @@ impl Expression {
             return Err(ExpressionError::NotNumeric(right_val.clone()));
         }
         let (left_val, right_val) = coerce_literals(&left_val, &right_val);
+        if let (&Literal::Decimal(l), &Literal::Decimal(r)) = (&left_val, &right_val) {
+            return Ok(ordering_matches(l.cmp(&r), op).into());
+        }
         match op {
             CompareType::GreaterThan => {
                 match (left_val, right_val) {
@@ -474,13 +627,31 @@ impl Expression {
                     args[i] = e;
                 }
             }
-            Expression::Subquery(_) => {
+            Expression::WindowFunction { ref mut args, ref mut window, .. } => {
+                for i in 0..args.len() {
+                    let e = try!(args[i].traverse(processor));
+                    args[i] = e;
+                }
+                for i in 0..window.partition_by.len() {
+                    let e = try!(window.partition_by[i].traverse(processor));
+                    window.partition_by[i] = e;
+                }
+                for term in window.order_by.iter_mut() {
+                    term.expression = try!(term.expression.traverse(processor));
+                }
+            }
+            Expression::Subquery(_) | Expression::Exists { .. } => {
                 // We do not traverse the subquery; it is treated as a "black box" by the
                 // expression-traversal mechanism.
             }
+            Expression::InSubquery { ref mut expr, .. } => {
+                *expr = Box::new(try!(expr.traverse(processor)));
+            }
             Expression::Null | Expression::True | Expression::False | Expression::Int(_)
             | Expression::Long(_) | Expression::Float(_) | Expression::Double(_)
-            | Expression::String(_) => {
+            | Expression::Decimal(_) | Expression::String(_) | Expression::Bytes(_)
+            | Expression::Date(_) | Expression::Time(_) | Expression::Timestamp(_)
+            | Expression::Interval(_) => {
                 // These are literals so there's nothing else to do.
             }
         }
@@ -493,8 +664,9 @@ impl Expression {
     /// [`ColumnInfo`]: ../relations/relations/struct.ColumnInfo.html
     pub fn get_column_type(&self, schema: &Schema) -> Result<ColumnType, ExpressionError> {
         match *self {
-            Expression::Function { ref name, ref args, .. } => {
-                let func = try!(DIRECTORY.get(name.as_ref()));
+            Expression::Function { ref name, ref args, .. } |
+            Expression::WindowFunction { ref name, ref args, .. } => {
+                let func = try!(DIRECTORY.get(name.as_str()));
                 match func.get_as_scalar() {
                     Some(scalar_func) => {
                         let result = try!(scalar_func.get_return_type(args.clone(), schema));
@@ -522,7 +694,13 @@ impl Expression {
             Expression::Long(_) => Ok(ColumnType::BigInt),
             Expression::Float(_) => Ok(ColumnType::Float),
             Expression::Double(_) => Ok(ColumnType::Double),
+            Expression::Decimal(ref d) => Ok(d.get_column_type()),
             Expression::String(ref s) => Ok(ColumnType::VarChar { length: s.len() as u16 }),
+            Expression::Bytes(_) => Ok(ColumnType::Blob),
+            Expression::Date(_) => Ok(ColumnType::Date),
+            Expression::Time(_) => Ok(ColumnType::Time),
+            Expression::Timestamp(_) => Ok(ColumnType::Timestamp),
+            Expression::Interval(_) => Ok(ColumnType::Interval),
             Expression::ColumnValue(ref name) => {
                 let columns = schema.find_columns(name);
                 if columns.len() != 1 {
@@ -547,13 +725,17 @@ impl Expression {
                     t => Err(ExpressionError::NotBooleanExpr(*inner.clone(), t)),
                 }
             }
-            Expression::IsNull(_) => Ok(ColumnType::TinyInt),
+            Expression::IsNull(_) | Expression::Exists { .. } | Expression::InSubquery { .. } => {
+                Ok(ColumnType::TinyInt)
+            }
             // TODO: Compare should actually check if they're comparable.
             Expression::Compare(_, _, _) => Ok(ColumnType::TinyInt),
             Expression::Arithmetic(ref left, op, ref right) => {
                 let left_type = try!(left.get_column_type(schema));
                 let right_type = try!(right.get_column_type(schema));
-                if !left_type.is_numeric() {
+                if left_type.is_temporal() || right_type.is_temporal() {
+                    temporal_result_type(op, left_type, right_type)
+                } else if !left_type.is_numeric() {
                     Err(ExpressionError::NotNumericExpr(*left.clone(), left_type))
                 } else if !right_type.is_numeric() {
                     Err(ExpressionError::NotNumericExpr(*right.clone(), right_type))
@@ -588,6 +770,14 @@ impl ::std::fmt::Display for Expression {
                 let arg_vals: Vec<String> = args.iter().map(|expr| format!("{}", expr)).collect();
                 write!(f, "{})", arg_vals.join(", "))
             }
+            Expression::WindowFunction { ref name, ref distinct, ref args, ref window } => {
+                try!(write!(f, "{}(", name));
+                if *distinct {
+                    try!(write!(f, "DISTINCT "));
+                }
+                let arg_vals: Vec<String> = args.iter().map(|expr| format!("{}", expr)).collect();
+                write!(f, "{}) OVER {}", arg_vals.join(", "), window)
+            }
             Expression::True => write!(f, "TRUE"),
             Expression::False => write!(f, "FALSE"),
             Expression::Null => write!(f, "NULL"),
@@ -595,9 +785,22 @@ impl ::std::fmt::Display for Expression {
             Expression::Long(num) => write!(f, "{}", num),
             Expression::Float(num) => write!(f, "{}", num),
             Expression::Double(num) => write!(f, "{}", num),
+            Expression::Decimal(num) => write!(f, "{}", num),
             Expression::String(ref s) => write!(f, "\'{}\'", s),
+            Expression::Bytes(ref b) => write!(f, "{}", Literal::Bytes(b.clone())),
+            Expression::Date(d) => write!(f, "DATE '{}'", d),
+            Expression::Time(t) => write!(f, "TIME '{}'", t),
+            Expression::Timestamp(t) => write!(f, "TIMESTAMP '{}'", t),
+            Expression::Interval(i) => write!(f, "INTERVAL '{}'", i),
             Expression::ColumnValue(ref name) => write!(f, "{}", column_name_to_string(name)),
             Expression::Subquery(ref clause) => write!(f, "({})", clause),
+            Expression::Exists { ref subquery, negated } => {
+                write!(f, "{}EXISTS ({})", if negated { "NOT " } else { "" }, subquery)
+            }
+            Expression::InSubquery { ref expr, ref subquery, negated } => {
+                try!(write_expr_parens(f, expr));
+                write!(f, " {}IN ({})", if negated { "NOT " } else { "" }, subquery)
+            }
             Expression::OR(ref exprs) => {
                 let r: Vec<_> = exprs.iter().map(|e| wrap_expr_parens(e)).collect();
                 write!(f, "{}", r.join(" OR "))
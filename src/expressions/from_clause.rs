@@ -4,7 +4,9 @@ use std::collections::HashSet;
 use std::default::Default;
 
 use ::commands::{ExecutionError, InvalidSchemaError, JoinSide};
-use ::expressions::{CompareType, Expression, SelectValue};
+use ::expressions::{CompareType, Expression, SelectClause, SelectValue};
+use ::expressions::select_clause::CommonTableBinding;
+use ::queries::{Planner, SimplePlanner, WorkingTable};
 use ::relations::{ColumnInfo, Schema};
 use ::storage::{FileManager, TableManager};
 
@@ -94,7 +96,7 @@ impl ::std::ops::Deref for FromClause {
 
 fn check_join_column<S: Into<String>>(schema: &Schema, side: JoinSide, name: S) -> Result<(), InvalidSchemaError> {
     let name = name.into();
-    let count = schema.num_columns_with_name(name.as_ref());
+    let count = schema.num_columns_with_name(name.as_str());
     if count == 0 {
         Err(InvalidSchemaError::MissingJoinColumn(name.clone(), side))
     } else if count > 1 {
@@ -123,13 +125,13 @@ fn build_join_schema(left: Schema,
         // Handle the shared columns.  We need to check that the
         // names aren't ambiguous on one or the other side.
         for name in common.iter() {
-            try!(check_join_column(&left, JoinSide::Left, name.as_ref()));
-            try!(check_join_column(&right, JoinSide::Right, name.as_ref()));
+            try!(check_join_column(&left, JoinSide::Left, name.as_str()));
+            try!(check_join_column(&right, JoinSide::Right, name.as_str()));
 
-            let left_info = left.get_column(name.as_ref()).unwrap();
-            let right_info = right.get_column(name.as_ref()).unwrap();
+            let left_info = left.get_column(name.as_str()).unwrap();
+            let right_info = right.get_column(name.as_str()).unwrap();
 
-            try!(result.add_column(ColumnInfo::with_name(left_info.column_type, name.as_ref())).map_err(ExecutionError::CouldNotCreateSchema));
+            try!(result.add_column(ColumnInfo::with_name(left_info.column_type, name.as_str())).map_err(ExecutionError::CouldNotCreateSchema));
 
             let compare_expr = Expression::Compare(Box::new(Expression::ColumnValue(left_info.get_column_name())),
                                                    CompareType::Equals,
@@ -227,6 +229,23 @@ pub enum FromClauseType {
         /// The join condition type.
         condition_type: JoinConditionType,
     },
+    /// A `FROM` clause that selects from the results of a subquery, i.e. a derived table.
+    Subquery {
+        /// The subquery producing the rows.
+        subquery: Box<SelectClause>,
+        /// The name the subquery's results are referred to by.
+        alias: String,
+        /// The names given to the subquery's columns, if any.
+        column_names: Option<Vec<String>>,
+    },
+    /// A `FROM` clause that selects from the working table of a recursive query. These replace the
+    /// references to a recursive query from its own recursive part.
+    WorkingTable {
+        /// The working table.
+        table: WorkingTable,
+        /// An optional alias to rename the table with.
+        alias: Option<String>,
+    },
 }
 
 impl ::std::fmt::Display for FromClauseType {
@@ -234,6 +253,8 @@ impl ::std::fmt::Display for FromClauseType {
         match *self {
             FromClauseType::BaseTable { .. } => write!(f, "BaseTable"),
             FromClauseType::JoinExpression { .. } => write!(f, "JoinExpression"),
+            FromClauseType::Subquery { .. } => write!(f, "Subquery"),
+            FromClauseType::WorkingTable { .. } => write!(f, "WorkingTable"),
         }
     }
 }
@@ -272,6 +293,33 @@ impl FromClause {
         }
     }
 
+    /// Instantiate a FROM clause that selects from a subquery.
+    pub fn subquery(subquery: SelectClause, alias: String, column_names: Option<Vec<String>>) -> FromClause {
+        FromClause {
+            clause_type: FromClauseType::Subquery {
+                subquery: Box::new(subquery),
+                alias: alias,
+                column_names: column_names,
+            },
+            computed_schema: None,
+            computed_join_expr: None,
+            computed_select_values: None,
+        }
+    }
+
+    /// Instantiate a FROM clause that selects from the working table of a recursive query.
+    pub fn working_table(table: WorkingTable, alias: Option<String>) -> FromClause {
+        FromClause {
+            clause_type: FromClauseType::WorkingTable {
+                table: table,
+                alias: alias,
+            },
+            computed_schema: None,
+            computed_join_expr: None,
+            computed_select_values: None,
+        }
+    }
+
     /// Retrieve the computed join expression.
     pub fn get_computed_join_expr(&self) -> Option<Expression> {
         self.computed_join_expr.clone()
@@ -282,6 +330,35 @@ impl FromClause {
         self.computed_select_values.clone()
     }
 
+    /// Replaces the references to common tables in the FROM clause with the queries they name. A
+    /// base table is only looked up as a table if there is no common table with its name.
+    ///
+    /// # Arguments
+    /// * scope - The common tables that the FROM clause can refer to.
+    pub fn resolve_common_tables(&mut self, scope: &[CommonTableBinding]) {
+        let resolved = match self.clause_type {
+            FromClauseType::BaseTable { ref table, ref alias } => {
+                // Later bindings hide earlier ones with the same name.
+                scope.iter()
+                    .rposition(|binding| binding.get_name() == *table)
+                    .map(|index| scope[index].make_from_clause(&scope[..index], alias.clone()))
+            }
+            FromClauseType::JoinExpression { ref mut left, ref mut right, .. } => {
+                left.resolve_common_tables(scope);
+                right.resolve_common_tables(scope);
+                None
+            }
+            FromClauseType::Subquery { ref mut subquery, .. } => {
+                subquery.resolve_common_tables(scope);
+                None
+            }
+            FromClauseType::WorkingTable { .. } => None,
+        };
+        if let Some(clause) = resolved {
+            *self = clause;
+        }
+    }
+
     /// Calculate the schema and computed join expression for the FROM clause.
     pub fn compute_schema(&mut self,
                           file_manager: &FileManager,
@@ -291,7 +368,7 @@ impl FromClause {
             FromClauseType::BaseTable { ref table, ref alias } => {
                 debug!("Preparing BASE_TABLE from-clause.");
 
-                if !table_manager.table_exists(file_manager, table.as_ref()) {
+                if !table_manager.table_exists(file_manager, table.as_str()) {
                     return Err(ExecutionError::TableDoesNotExist(table.clone()));
                 }
 
@@ -299,7 +376,7 @@ impl FromClause {
                 let mut schema = table.get_schema();
 
                 if let Some(ref name) = *alias {
-                    try!(schema.set_table_name(name.as_ref())
+                    try!(schema.set_table_name(name.as_str())
                                .map_err(ExecutionError::CouldNotCreateSchema));
                 }
 
@@ -356,6 +433,35 @@ impl FromClause {
                 self.computed_schema = Some(schema.clone());
                 schema.clone()
             }
+            FromClauseType::Subquery { ref mut subquery, ref alias, ref column_names } => {
+                debug!("Preparing SELECT_SUBQUERY from-clause.");
+
+                // The subquery's schema depends on what it selects, so it has to be planned to
+                // find it.
+                try!(subquery.compute_schema(file_manager, table_manager));
+                let planner = SimplePlanner::new(file_manager, table_manager);
+                let plan = try!(planner.make_plan((**subquery).clone()).map_err(ExecutionError::CouldNotExecutePlan));
+                let mut schema = plan.get_schema();
+                if let Some(ref names) = *column_names {
+                    try!(schema.set_column_names(names).map_err(ExecutionError::CouldNotCreateSchema));
+                }
+                try!(schema.set_table_name(alias.as_str()).map_err(ExecutionError::CouldNotCreateSchema));
+
+                self.computed_schema = Some(schema.clone());
+                schema
+            }
+            FromClauseType::WorkingTable { ref table, ref alias } => {
+                debug!("Preparing WORKING_TABLE from-clause.");
+
+                // The schema is set once the non-recursive part of the query has been planned.
+                let mut schema = try!(table.get_schema().ok_or(ExecutionError::TableDoesNotExist(table.get_name())));
+                if let Some(ref name) = *alias {
+                    try!(schema.set_table_name(name.as_str()).map_err(ExecutionError::CouldNotCreateSchema));
+                }
+
+                self.computed_schema = Some(schema.clone());
+                schema
+            }
         };
         Ok(result)
     }
@@ -393,6 +499,18 @@ impl ::std::fmt::Display for FromClause {
                 try!(write!(f, ", left_child={}", left));
                 try!(write!(f, ", right_child={}", right));
             }
+            FromClauseType::Subquery { ref subquery, ref alias, ref column_names } => {
+                try!(write!(f, ", subquery={} AS {}", subquery, alias));
+                if let Some(ref names) = *column_names {
+                    try!(write!(f, " ({})", names.join(", ")));
+                }
+            }
+            FromClauseType::WorkingTable { ref table, ref alias } => {
+                try!(write!(f, ", working_table={}", table.get_name()));
+                if let Some(ref name) = *alias {
+                    try!(write!(f, " AS {}", name));
+                }
+            }
         }
         write!(f, "]")
     }
@@ -1,6 +1,12 @@
 //! This module contains utilities and classes for SQL literals.
 
+use std::io;
+
+use byteorder::BigEndian;
+
 use ::ColumnType;
+use ::expressions::{Date, Decimal, Interval, Time, Timestamp};
+use ::storage::{ReadNanoDBExt, WriteNanoDBExt};
 
 /// An enum representing a SQL literal.
 #[derive(Debug, Clone, PartialEq)]
@@ -13,8 +19,20 @@ pub enum Literal {
     Double(f64),
     /// A float
     Float(f32),
+    /// An exact decimal number, for `NUMERIC` values
+    Decimal(Decimal),
     /// A string
     String(String),
+    /// A byte string, for `BLOB` values
+    Bytes(Vec<u8>),
+    /// A date, for `DATE` values
+    Date(Date),
+    /// A time of day, for `TIME` values
+    Time(Time),
+    /// A date and time of day, for `DATETIME` and `TIMESTAMP` values
+    Timestamp(Timestamp),
+    /// An interval of time
+    Interval(Interval),
     /// A `NULL` value
     Null,
     /// A `TRUE` value
@@ -46,6 +64,10 @@ impl ::std::hash::Hash for Literal {
                 s.hash(state);
                 state.write_u8(1u8);
             },
+            Literal::Bytes(ref b) => {
+                b.hash(state);
+                state.write_u8(3u8);
+            },
             Literal::Null => {
                 0.hash(state);
                 state.write_u8(0u8);
@@ -66,6 +88,26 @@ impl ::std::hash::Hash for Literal {
                 (f as u32).hash(state);
                 state.write_u8(1u8);
             },
+            Literal::Decimal(d) => {
+                d.hash(state);
+                state.write_u8(1u8);
+            },
+            Literal::Date(d) => {
+                d.hash(state);
+                state.write_u8(2u8);
+            },
+            Literal::Time(t) => {
+                t.hash(state);
+                state.write_u8(2u8);
+            },
+            Literal::Timestamp(t) => {
+                t.hash(state);
+                state.write_u8(2u8);
+            },
+            Literal::Interval(i) => {
+                i.hash(state);
+                state.write_u8(2u8);
+            },
             Literal::FilePointer { page_no, offset } => {
                 page_no.hash(state);
                 offset.hash(state);
@@ -87,7 +129,19 @@ impl ::std::fmt::Display for Literal {
             Literal::Long(num) => write!(f, "{}", num),
             Literal::Float(num) => write!(f, "{}", num),
             Literal::Double(num) => write!(f, "{}", num),
+            Literal::Decimal(num) => write!(f, "{}", num),
             Literal::String(ref s) => write!(f, "\'{}\'", s),
+            Literal::Bytes(ref b) => {
+                try!(write!(f, "X'"));
+                for byte in b {
+                    try!(write!(f, "{:02X}", byte));
+                }
+                write!(f, "'")
+            }
+            Literal::Date(d) => write!(f, "DATE '{}'", d),
+            Literal::Time(t) => write!(f, "TIME '{}'", t),
+            Literal::Timestamp(t) => write!(f, "TIMESTAMP '{}'", t),
+            Literal::Interval(i) => write!(f, "INTERVAL '{}'", i),
             Literal::FilePointer { page_no, offset } => write!(f, "FP({}, {})", page_no, offset),
         }
     }
@@ -123,6 +177,36 @@ impl From<f64> for Literal {
     }
 }
 
+impl From<Decimal> for Literal {
+    fn from(value: Decimal) -> Self {
+        Literal::Decimal(value)
+    }
+}
+
+impl From<Date> for Literal {
+    fn from(value: Date) -> Self {
+        Literal::Date(value)
+    }
+}
+
+impl From<Time> for Literal {
+    fn from(value: Time) -> Self {
+        Literal::Time(value)
+    }
+}
+
+impl From<Timestamp> for Literal {
+    fn from(value: Timestamp) -> Self {
+        Literal::Timestamp(value)
+    }
+}
+
+impl From<Interval> for Literal {
+    fn from(value: Interval) -> Self {
+        Literal::Interval(value)
+    }
+}
+
 impl<'a> From<&'a str> for Literal {
     fn from(value: &str) -> Self {
         Literal::String(value.into())
@@ -135,11 +219,18 @@ impl From<String> for Literal {
     }
 }
 
+impl From<Vec<u8>> for Literal {
+    fn from(value: Vec<u8>) -> Self {
+        Literal::Bytes(value)
+    }
+}
+
 impl Literal {
     /// A utility function for determining if the literal is numeric.
     pub fn is_numeric(&self) -> bool {
         match *self {
             Literal::Double(_) |
+            Literal::Decimal(_) |
             Literal::Int(_) |
             Literal::Float(_) |
             Literal::Long(_) => true,
@@ -160,6 +251,23 @@ impl Literal {
             _ => false,
         }
     }
+    /// A utility function for determining if the literal is a NUMERIC.
+    pub fn is_decimal(&self) -> bool {
+        match *self {
+            Literal::Decimal(_) => true,
+            _ => false,
+        }
+    }
+    /// A utility function for determining if the literal is a date, time, timestamp or interval.
+    pub fn is_temporal(&self) -> bool {
+        match *self {
+            Literal::Date(_) |
+            Literal::Time(_) |
+            Literal::Timestamp(_) |
+            Literal::Interval(_) => true,
+            _ => false,
+        }
+    }
     /// A utility function for determining if the literal is a LONG.
     pub fn is_long(&self) -> bool {
         match *self {
@@ -174,6 +282,7 @@ impl Literal {
             Literal::Float(f) => Some(Literal::Double(f as f64)),
             Literal::Int(i) => Some(Literal::Double(i as f64)),
             Literal::Long(l) => Some(Literal::Double(l as f64)),
+            Literal::Decimal(d) => Some(Literal::Double(d.to_f64())),
             _ => None,
         }
     }
@@ -184,6 +293,7 @@ impl Literal {
             Literal::Float(f) => Some(Literal::Float(f as f32)),
             Literal::Int(i) => Some(Literal::Float(i as f32)),
             Literal::Long(l) => Some(Literal::Float(l as f32)),
+            Literal::Decimal(d) => Some(Literal::Float(d.to_f64() as f32)),
             _ => None,
         }
     }
@@ -194,6 +304,7 @@ impl Literal {
             Literal::Float(f) => Some(Literal::Long(f as i64)),
             Literal::Int(i) => Some(Literal::Long(i as i64)),
             Literal::Long(l) => Some(Literal::Long(l)),
+            Literal::Decimal(d) => Some(Literal::Long(d.to_i64())),
             _ => None,
         }
     }
@@ -204,6 +315,20 @@ impl Literal {
             Literal::Float(f) => Some(Literal::Int(f as i32)),
             Literal::Int(i) => Some(Literal::Int(i)),
             Literal::Long(l) => Some(Literal::Int(l as i32)),
+            Literal::Decimal(d) => Some(Literal::Int(d.to_i64() as i32)),
+            _ => None,
+        }
+    }
+    /// A utility function for converting to a NUMERIC if possible. Floating-point values are
+    /// converted to the decimal with the shortest representation that converts back to the same
+    /// value. If the literal isn't numeric or doesn't fit in a decimal, returns None.
+    pub fn as_decimal(&self) -> Option<Decimal> {
+        match *self {
+            Literal::Decimal(d) => Some(d),
+            Literal::Int(i) => Some(Decimal::new(i as i64, 0)),
+            Literal::Long(l) => Decimal::new(l, 0).rescale(0).ok(),
+            Literal::Double(d) => Decimal::from_f64(d),
+            Literal::Float(f) => Decimal::from_f64(f as f64),
             _ => None,
         }
     }
@@ -214,6 +339,15 @@ impl Literal {
             _ => None,
         }
     }
+    /// A utility function for getting the bytes of a byte string, or the UTF-8 bytes of a string.
+    /// For other literals, returns None.
+    pub fn as_bytes(&self) -> Option<&[u8]> {
+        match *self {
+            Literal::Bytes(ref b) => Some(b),
+            Literal::String(ref s) => Some(s.as_bytes()),
+            _ => None,
+        }
+    }
 
     /// A utility function for getting a column type based on the literal.
     pub fn get_column_type(&self) -> ColumnType {
@@ -222,10 +356,218 @@ impl Literal {
             Literal::Long(_) => ColumnType::BigInt,
             Literal::Float(_) => ColumnType::Float,
             Literal::Double(_) => ColumnType::Double,
+            Literal::Decimal(ref d) => d.get_column_type(),
             Literal::String(ref s) => ColumnType::VarChar { length: s.len() as u16 },
+            Literal::Bytes(_) => ColumnType::Blob,
+            Literal::Date(_) => ColumnType::Date,
+            Literal::Time(_) => ColumnType::Time,
+            Literal::Timestamp(_) => ColumnType::Timestamp,
+            Literal::Interval(_) => ColumnType::Interval,
             Literal::Null => ColumnType::Null,
             Literal::True | Literal::False => ColumnType::TinyInt,
             Literal::FilePointer { .. } => ColumnType::FilePointer,
         }
     }
+
+    /// The rank of the literal's kind, used to order literals of different types.
+    fn type_rank(&self) -> u8 {
+        match *self {
+            Literal::Null => 0,
+            Literal::False | Literal::True => 1,
+            Literal::Int(_) | Literal::Long(_) | Literal::Float(_) | Literal::Double(_) |
+            Literal::Decimal(_) => 2,
+            Literal::String(_) => 3,
+            Literal::FilePointer { .. } => 4,
+            Literal::Date(_) | Literal::Timestamp(_) => 5,
+            Literal::Time(_) => 6,
+            Literal::Interval(_) => 7,
+            Literal::Bytes(_) => 8,
+        }
+    }
+
+    /// Compares two literals with a total ordering, for use in sorted structures such as B-tree
+    /// tuple files. `NULL` sorts before everything else, and numeric values of different types are
+    /// compared by value. Values of unrelated types are ordered by type.
+    pub fn compare(&self, other: &Literal) -> ::std::cmp::Ordering {
+        use std::cmp::Ordering;
+
+        let (rank1, rank2) = (self.type_rank(), other.type_rank());
+        if rank1 != rank2 {
+            return rank1.cmp(&rank2);
+        }
+
+        match (self, other) {
+            (&Literal::String(ref s1), &Literal::String(ref s2)) => s1.cmp(s2),
+            (&Literal::Bytes(ref b1), &Literal::Bytes(ref b2)) => b1.cmp(b2),
+            (&Literal::FilePointer { page_no: p1, offset: o1 },
+             &Literal::FilePointer { page_no: p2, offset: o2 }) => (p1, o1).cmp(&(p2, o2)),
+            _ if self.is_temporal() => {
+                ::expressions::temporal::temporal_compare(self, other).unwrap_or(Ordering::Equal)
+            }
+            (&Literal::False, &Literal::True) => Ordering::Less,
+            (&Literal::True, &Literal::False) => Ordering::Greater,
+            _ if self.is_numeric() => {
+                let integral = |l: &Literal| match *l {
+                    Literal::Int(_) | Literal::Long(_) => true,
+                    _ => false,
+                };
+                let exact = |l: &Literal| integral(l) || l.is_decimal();
+                if integral(self) && integral(other) {
+                    match (self.as_long(), other.as_long()) {
+                        (Some(Literal::Long(l1)), Some(Literal::Long(l2))) => l1.cmp(&l2),
+                        _ => Ordering::Equal,
+                    }
+                } else if exact(self) && exact(other) {
+                    match (self.as_decimal(), other.as_decimal()) {
+                        (Some(d1), Some(d2)) => d1.cmp(&d2),
+                        _ => Ordering::Equal,
+                    }
+                } else {
+                    match (self.as_double(), other.as_double()) {
+                        (Some(Literal::Double(d1)), Some(Literal::Double(d2))) => {
+                            d1.partial_cmp(&d2).unwrap_or(Ordering::Equal)
+                        }
+                        _ => Ordering::Equal,
+                    }
+                }
+            }
+            _ => Ordering::Equal,
+        }
+    }
+    /// Writes the literal to some output, preceded by a byte giving its type, so that it can be read
+    /// back without knowing its type.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to write to the given output, or if a
+    /// string or byte string is longer than 65535 bytes.
+    pub fn write_tagged<W: WriteNanoDBExt>(&self, output: &mut W) -> Result<(), io::Error> {
+        match *self {
+            Literal::Int(i) => {
+                try!(output.write_u8(1));
+                output.write_i32::<BigEndian>(i)
+            }
+            Literal::Long(l) => {
+                try!(output.write_u8(2));
+                output.write_i64::<BigEndian>(l)
+            }
+            Literal::Float(f) => {
+                try!(output.write_u8(3));
+                output.write_f32::<BigEndian>(f)
+            }
+            Literal::Double(d) => {
+                try!(output.write_u8(4));
+                output.write_f64::<BigEndian>(d)
+            }
+            Literal::Decimal(d) => {
+                try!(output.write_u8(5));
+                try!(output.write_i64::<BigEndian>(d.get_unscaled()));
+                output.write_u16::<BigEndian>(d.get_scale())
+            }
+            Literal::String(ref s) => {
+                try!(output.write_u8(6));
+                output.write_varchar65535(s.clone())
+            }
+            Literal::Date(d) => {
+                try!(output.write_u8(7));
+                output.write_i32::<BigEndian>(d.get_days())
+            }
+            Literal::Time(t) => {
+                try!(output.write_u8(8));
+                output.write_i64::<BigEndian>(t.get_micros())
+            }
+            Literal::Timestamp(t) => {
+                try!(output.write_u8(9));
+                output.write_i64::<BigEndian>(t.get_micros())
+            }
+            Literal::Interval(i) => {
+                try!(output.write_u8(10));
+                try!(output.write_i32::<BigEndian>(i.months));
+                try!(output.write_i32::<BigEndian>(i.days));
+                output.write_i64::<BigEndian>(i.micros)
+            }
+            Literal::True => output.write_u8(11),
+            Literal::False => output.write_u8(12),
+            Literal::FilePointer { page_no, offset } => {
+                try!(output.write_u8(13));
+                try!(output.write_u16::<BigEndian>(page_no));
+                output.write_u16::<BigEndian>(offset)
+            }
+            Literal::Bytes(ref b) => {
+                try!(output.write_u8(14));
+                if b.len() > 65535 {
+                    return Err(io::Error::new(io::ErrorKind::InvalidInput, "byte string is too long"));
+                }
+                try!(output.write_u16::<BigEndian>(b.len() as u16));
+                output.write_all(b)
+            }
+            Literal::Null => output.write_u8(0),
+        }
+    }
+
+    /// Reads a literal written by [`write_tagged`](#method.write_tagged) from some input.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to read from the given input.
+    pub fn read_tagged<R: ReadNanoDBExt>(input: &mut R) -> Result<Literal, io::Error> {
+        Ok(match try!(input.read_u8()) {
+            1 => Literal::Int(try!(input.read_i32::<BigEndian>())),
+            2 => Literal::Long(try!(input.read_i64::<BigEndian>())),
+            3 => Literal::Float(try!(input.read_f32::<BigEndian>())),
+            4 => Literal::Double(try!(input.read_f64::<BigEndian>())),
+            5 => {
+                let unscaled = try!(input.read_i64::<BigEndian>());
+                Literal::Decimal(Decimal::new(unscaled, try!(input.read_u16::<BigEndian>())))
+            }
+            6 => Literal::String(try!(input.read_varchar65535())),
+            7 => Date::from_days(try!(input.read_i32::<BigEndian>()) as i64).map(Literal::Date).unwrap_or(Literal::Null),
+            8 => Literal::Time(Time::from_micros(try!(input.read_i64::<BigEndian>()))),
+            9 => Timestamp::from_micros(try!(input.read_i64::<BigEndian>())).map(Literal::Timestamp).unwrap_or(Literal::Null),
+            10 => {
+                let months = try!(input.read_i32::<BigEndian>());
+                let days = try!(input.read_i32::<BigEndian>());
+                Literal::Interval(Interval::new(months, days, try!(input.read_i64::<BigEndian>())))
+            }
+            11 => Literal::True,
+            12 => Literal::False,
+            13 => {
+                let page_no = try!(input.read_u16::<BigEndian>());
+                Literal::FilePointer { page_no: page_no, offset: try!(input.read_u16::<BigEndian>()) }
+            }
+            14 => {
+                let mut bytes = vec![0; try!(input.read_u16::<BigEndian>()) as usize];
+                try!(input.read_exact(&mut bytes));
+                Literal::Bytes(bytes)
+            }
+            _ => Literal::Null,
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_tagged_round_trip() {
+        // Table statistics and the runs of external sorts both store literals in this format.
+        let date = Date::from_ymd(2024, 1, 31).unwrap();
+        let time = Time::from_hms_micro(12, 30, 0, 5).unwrap();
+        let values = vec![Literal::Int(-3), Literal::Long(1 << 40), Literal::Float(1.5), Literal::Double(-2.25),
+                          Literal::Decimal(Decimal::new(1999, 2)), Literal::String("héllo".into()),
+                          Literal::Bytes(vec![0x00, 0xFF, 0xC3]), Literal::Date(date), Literal::Time(time),
+                          Literal::Timestamp(Timestamp::new(date, time)), Literal::Interval(Interval::new(14, 3, 60)),
+                          Literal::True, Literal::False, Literal::FilePointer { page_no: 7, offset: 12 },
+                          Literal::Null];
+        let mut buffer = Cursor::new(Vec::new());
+        for value in values.iter() {
+            value.write_tagged(&mut buffer).unwrap();
+        }
+        buffer.set_position(0);
+        let read: Vec<Literal> = values.iter().map(|_| Literal::read_tagged(&mut buffer).unwrap()).collect();
+        assert_eq!(values, read);
+
+        assert!(Literal::Bytes(vec![0; 65536]).write_tagged(&mut Cursor::new(Vec::new())).is_err());
+    }
 }
@@ -1,21 +1,29 @@
 //! A module containing classes for representing and evaluating arithmetic and logical expressions.
 
+pub mod decimal;
 pub mod expression;
 pub mod processor;
 pub mod environment;
 pub mod literal;
+pub mod order_by_expression;
 pub mod select_clause;
 pub mod from_clause;
 pub mod select_value;
+pub mod temporal;
+pub mod window;
 
 pub use self::Error as ExpressionError;
+pub use self::decimal::{Decimal, MAX_NUMERIC_PRECISION};
 pub use self::environment::Environment;
 pub use self::expression::Expression;
 pub use self::from_clause::{FromClause, FromClauseType, JoinConditionType, JoinType};
 pub use self::literal::Literal;
+pub use self::order_by_expression::OrderByExpression;
 pub use self::processor::Processor as ExpressionProcessor;
-pub use self::select_clause::SelectClause;
+pub use self::select_clause::{CommonTableExpression, SelectClause, SetOperation, SetOperator, WithClause};
 pub use self::select_value::SelectValue;
+pub use self::temporal::{Date, Interval, Time, Timestamp};
+pub use self::window::{FrameBound, WindowFrame, WindowSpec};
 
 use ::ColumnName;
 use ::functions::FunctionError;
@@ -167,6 +175,23 @@ pub enum Error {
         /// The expression given.
         received: Expression
     },
+    /// Window function calls cannot be nested.
+    NestedWindowFunction {
+        /// The parent call (i.e. the already traversed one).
+        parent: Expression,
+        /// The nested call.
+        nested: Expression
+    },
+    /// The result of some arithmetic had more digits than a `NUMERIC` value can hold.
+    NumericOverflow,
+    /// A `NUMERIC` value was divided by zero.
+    DivisionByZero,
+    /// A literal of the named type could not be parsed from the string.
+    InvalidLiteral(String, String),
+    /// The two values cannot be used together in an operation.
+    IncompatibleOperands(Literal, Literal),
+    /// The result of some date or time arithmetic was outside the supported range of dates.
+    TemporalOverflow,
     /// This expression's evaluation has not been implemented yet.
     Unimplemented,
 }
@@ -229,6 +254,19 @@ impl ::std::fmt::Display for Error {
             Error::UnexpectedAggregate { ref expected, ref received } => {
                 write!(f, "Expected to find aggregate {} but found {} instead.", expected, received)
             }
+            Error::NestedWindowFunction { ref parent, ref nested } => {
+                write!(f, "Found window function call {} nested within another window function call {}",
+                       nested, parent)
+            }
+            Error::NumericOverflow => {
+                write!(f, "The numeric value has more than {} digits.", MAX_NUMERIC_PRECISION)
+            }
+            Error::DivisionByZero => write!(f, "Division by zero."),
+            Error::InvalidLiteral(ref t, ref s) => write!(f, "'{}' is not a valid {} value.", s, t),
+            Error::IncompatibleOperands(ref left, ref right) => {
+                write!(f, "The values {} and {} cannot be used together.", left, right)
+            }
+            Error::TemporalOverflow => write!(f, "The date is outside the range of supported dates."),
             Error::Unimplemented => {
                 write!(f,
                        "The expression's evaluation has not yet been implemented.")
@@ -0,0 +1,57 @@
+//! This module contains the expressions of `ORDER BY` clauses.
+
+use std::cmp::Ordering;
+
+use ::expressions::{Expression, Literal};
+
+/// A single term of an `ORDER BY` clause: an expression to sort on, along with the direction to
+/// sort it in and where `NULL` values go.
+#[derive(Clone, Debug, PartialEq)]
+pub struct OrderByExpression {
+    /// The expression to sort on.
+    pub expression: Expression,
+    /// Whether the expression is sorted in ascending order, rather than descending order.
+    pub ascending: bool,
+    /// Whether `NULL` values come before all other values, rather than after them.
+    pub nulls_first: bool,
+}
+
+impl OrderByExpression {
+    /// Creates a new `ORDER BY` term.
+    ///
+    /// # Arguments
+    /// * expression - The expression to sort on.
+    /// * ascending - Whether to sort in ascending order.
+    /// * nulls_first - Whether `NULL` values come first. If this isn't given, `NULL` values sort as
+    ///   if they were larger than all other values, i.e. last in ascending order and first in
+    ///   descending order.
+    pub fn new(expression: Expression, ascending: bool, nulls_first: Option<bool>) -> OrderByExpression {
+        OrderByExpression {
+            expression: expression,
+            ascending: ascending,
+            nulls_first: nulls_first.unwrap_or(!ascending),
+        }
+    }
+
+    /// Compares two values of the expression in the order this term specifies.
+    pub fn compare(&self, left: &Literal, right: &Literal) -> Ordering {
+        match (left, right) {
+            (&Literal::Null, &Literal::Null) => Ordering::Equal,
+            (&Literal::Null, _) => if self.nulls_first { Ordering::Less } else { Ordering::Greater },
+            (_, &Literal::Null) => if self.nulls_first { Ordering::Greater } else { Ordering::Less },
+            _ => {
+                let result = left.compare(right);
+                if self.ascending { result } else { result.reverse() }
+            }
+        }
+    }
+}
+
+impl ::std::fmt::Display for OrderByExpression {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "{} {} NULLS {}",
+               self.expression,
+               if self.ascending { "ASC" } else { "DESC" },
+               if self.nulls_first { "FIRST" } else { "LAST" })
+    }
+}
@@ -3,10 +3,165 @@
 use std::default::Default;
 
 use ::commands::ExecutionError;
-use ::expressions::{Expression, FromClause, SelectValue};
+use ::expressions::{Expression, ExpressionError, ExpressionProcessor, FromClause, OrderByExpression,
+                    SelectValue};
+use ::queries::{Planner, SimplePlanner, WorkingTable};
 use ::relations::Schema;
 use ::storage::{FileManager, TableManager};
 
+/// The set operations that combine the results of two queries.
+#[derive(Copy, Clone, Debug, PartialEq)]
+pub enum SetOperator {
+    /// `UNION`, which produces the rows of both queries.
+    Union,
+    /// `INTERSECT`, which produces the rows of the left query that are also in the right query.
+    Intersect,
+    /// `EXCEPT`, which produces the rows of the left query that aren't in the right query.
+    Except,
+}
+
+impl ::std::fmt::Display for SetOperator {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            SetOperator::Union => write!(f, "UNION"),
+            SetOperator::Intersect => write!(f, "INTERSECT"),
+            SetOperator::Except => write!(f, "EXCEPT"),
+        }
+    }
+}
+
+/// A set operation combining the results of two queries, as in `SELECT ... UNION SELECT ...`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct SetOperation {
+    /// The set operation to perform.
+    pub operator: SetOperator,
+    /// Whether duplicate rows are kept (`ALL`), rather than removed.
+    pub all: bool,
+    /// The left query.
+    pub left: Box<SelectClause>,
+    /// The right query.
+    pub right: Box<SelectClause>,
+    /// The working table read by the right query, if this is the `UNION` of a recursive query.
+    pub working_table: Option<WorkingTable>,
+}
+
+/// A common table expression, i.e. a query named by a `WITH` clause, like `t (a, b) AS (SELECT ...)`.
+#[derive(Clone, Debug, PartialEq)]
+pub struct CommonTableExpression {
+    /// The name of the query.
+    pub name: String,
+    /// The names given to the query's columns, if any.
+    pub column_names: Option<Vec<String>>,
+    /// The query.
+    pub query: Box<SelectClause>,
+}
+
+impl CommonTableExpression {
+    /// Returns the query, with the common tables it refers to resolved.
+    ///
+    /// A query of the form `anchor UNION [ALL] recursive` in a `WITH RECURSIVE` clause, where the
+    /// recursive part refers to the query itself, is given a working table for those references.
+    ///
+    /// # Arguments
+    /// * scope - The common tables the query can refer to.
+    /// * recursive - Whether the query was defined by a `WITH RECURSIVE` clause.
+    fn resolve_query(&self, scope: &[CommonTableBinding], recursive: bool) -> SelectClause {
+        let mut query = (*self.query).clone();
+        if recursive && query.with_clause.is_none() {
+            if let Some(ref mut operation) = query.set_operation {
+                if operation.operator == SetOperator::Union {
+                    let table = WorkingTable::new(self.name.clone(), self.column_names.clone());
+                    let mut recursive_scope = scope.to_vec();
+                    recursive_scope.push(CommonTableBinding::WorkingTable(table.clone()));
+
+                    let mut right = operation.right.clone();
+                    right.resolve_common_tables(&recursive_scope);
+                    if table.is_referenced() {
+                        operation.right = right;
+                        operation.working_table = Some(table);
+                    }
+                }
+            }
+        }
+        query.resolve_common_tables(scope);
+        query
+    }
+}
+
+/// A `WITH` clause, which names queries for the rest of the statement to refer to.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WithClause {
+    /// Whether the queries may refer to themselves, i.e. whether this is a `WITH RECURSIVE` clause.
+    pub recursive: bool,
+    /// The named queries, which may refer to the ones before them.
+    pub tables: Vec<CommonTableExpression>,
+}
+
+/// A name that a `FROM` clause can refer to instead of a table.
+#[derive(Clone, Debug)]
+pub enum CommonTableBinding {
+    /// A query named by a `WITH` clause.
+    Query {
+        /// The named query.
+        table: CommonTableExpression,
+        /// Whether the query was defined by a `WITH RECURSIVE` clause.
+        recursive: bool,
+    },
+    /// The working table of a recursive query, which its recursive part refers to.
+    WorkingTable(WorkingTable),
+}
+
+impl CommonTableBinding {
+    /// Retrieves the name that the binding is referred to by.
+    pub fn get_name(&self) -> String {
+        match *self {
+            CommonTableBinding::Query { ref table, .. } => table.name.clone(),
+            CommonTableBinding::WorkingTable(ref table) => table.get_name(),
+        }
+    }
+
+    /// Creates the `FROM` clause that a reference to the binding is replaced with.
+    ///
+    /// # Arguments
+    /// * scope - The bindings that were visible where this binding was defined.
+    /// * alias - The alias the reference gives to the binding, if any.
+    pub fn make_from_clause(&self, scope: &[CommonTableBinding], alias: Option<String>) -> FromClause {
+        match *self {
+            CommonTableBinding::Query { ref table, recursive } => {
+                let query = table.resolve_query(scope, recursive);
+                FromClause::subquery(query, alias.unwrap_or(table.name.clone()), table.column_names.clone())
+            }
+            CommonTableBinding::WorkingTable(ref table) => {
+                table.set_referenced();
+                FromClause::working_table(table.clone(), alias)
+            }
+        }
+    }
+}
+
+/// Resolves the common tables referred to by the subqueries in an expression.
+struct CommonTableResolver<'a> {
+    scope: &'a [CommonTableBinding],
+}
+
+impl<'a> ExpressionProcessor for CommonTableResolver<'a> {
+    fn enter(&mut self, _node: &Expression) -> Result<(), ExpressionError> {
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        let mut node = node.clone();
+        match node {
+            Expression::Subquery(ref mut subquery) | Expression::Exists { ref mut subquery, .. }
+            | Expression::InSubquery { ref mut subquery, .. } => {
+                subquery.resolve_common_tables(self.scope);
+            }
+            _ => {}
+        }
+        Ok(node)
+    }
+}
+
 /// This class represents a single `SELECT ...` statement or clause. `SELECT` statements can appear
 /// as clauses within other expressions, so the class is written to be used easily within other
 /// classes.
@@ -28,6 +183,13 @@ pub struct SelectClause {
     pub group_by_exprs: Option<Vec<Expression>>,
     /// Optional HAVING expression.
     pub having: Option<Expression>,
+    /// The terms of the ORDER BY clause, if there is one.
+    pub order_by_exprs: Vec<OrderByExpression>,
+    /// The set operation producing the rows, if this is a compound query. A compound query selects
+    /// from the combined results of its queries, so only its ORDER BY, LIMIT and OFFSET are used.
+    pub set_operation: Option<SetOperation>,
+    /// The WITH clause, if there is one. It is removed once the names it defines are resolved.
+    pub with_clause: Option<WithClause>,
     from_schema: Option<Schema>,
 }
 
@@ -43,6 +205,9 @@ impl Default for SelectClause {
             where_expr: None,
             group_by_exprs: None,
             having: None,
+            order_by_exprs: vec![],
+            set_operation: None,
+            with_clause: None,
             from_schema: None,
         }
     }
@@ -95,6 +260,28 @@ impl SelectClause {
         }
     }
 
+    /// Creates a new compound select clause, which combines the results of two queries with a set
+    /// operation.
+    ///
+    /// # Arguments
+    /// * operator - The set operation to perform.
+    /// * all - Whether duplicate rows are kept.
+    /// * left - The left query.
+    /// * right - The right query.
+    pub fn compound(operator: SetOperator, all: bool, left: SelectClause, right: SelectClause) -> SelectClause {
+        SelectClause {
+            values: vec![SelectValue::WildcardColumn { table: None }],
+            set_operation: Some(SetOperation {
+                operator: operator,
+                all: all,
+                left: Box::new(left),
+                right: Box::new(right),
+                working_table: None,
+            }),
+            ..Default::default()
+        }
+    }
+
     /// Checks if the projection is trivial.
     pub fn is_trivial_project(&self) -> bool {
         if self.values.len() == 1 {
@@ -107,6 +294,53 @@ impl SelectClause {
         false
     }
 
+    /// Replaces the references to common tables, i.e. the queries named by `WITH` clauses, with
+    /// the queries they name. This is done for the whole statement, including its subqueries, before
+    /// any tables are looked up, so that the names of common tables hide those of tables.
+    ///
+    /// # Arguments
+    /// * scope - The common tables defined by the enclosing statements.
+    pub fn resolve_common_tables(&mut self, scope: &[CommonTableBinding]) {
+        let mut scope = scope.to_vec();
+        if let Some(with_clause) = self.with_clause.take() {
+            for table in with_clause.tables {
+                scope.push(CommonTableBinding::Query {
+                    table: table,
+                    recursive: with_clause.recursive,
+                });
+            }
+        }
+
+        if let Some(ref mut from_clause) = self.from_clause {
+            from_clause.resolve_common_tables(&scope);
+        }
+
+        if let Some(ref mut operation) = self.set_operation {
+            operation.left.resolve_common_tables(&scope);
+            // The recursive part of a recursive query has already been resolved.
+            if operation.working_table.is_none() {
+                operation.right.resolve_common_tables(&scope);
+            }
+        }
+
+        // The resolver itself never fails.
+        let mut resolver = CommonTableResolver { scope: &scope };
+        for value in self.values.iter_mut() {
+            if let SelectValue::Expression { ref mut expression, .. } = *value {
+                *expression = expression.traverse(&mut resolver).unwrap();
+            }
+        }
+        for expr in self.where_expr.iter_mut().chain(self.having.iter_mut()) {
+            *expr = expr.traverse(&mut resolver).unwrap();
+        }
+        for expr in self.group_by_exprs.iter_mut().flat_map(|exprs| exprs.iter_mut()) {
+            *expr = expr.traverse(&mut resolver).unwrap();
+        }
+        for term in self.order_by_exprs.iter_mut() {
+            term.expression = term.expression.traverse(&mut resolver).unwrap();
+        }
+    }
+
     /// Compute the schema for this select clause.
     pub fn compute_schema(&mut self,
                           file_manager: &FileManager,
@@ -114,12 +348,28 @@ impl SelectClause {
                           -> Result<Schema, ExecutionError> {
         // TODO
         // For now, just return the from clause schema.
-        let schema = match self.from_clause {
-            Some(ref mut clause) => {
-                try!(clause.compute_schema(file_manager, table_manager))
+        self.resolve_common_tables(&[]);
+
+        let schema = if let Some(ref mut operation) = self.set_operation {
+            // The columns of a compound query are named after those of its left query.
+            let schema = try!(operation.left.compute_schema(file_manager, table_manager));
+            if let Some(ref table) = operation.working_table {
+                // The working table holds the results of the non-recursive part of the query, so
+                // it has to be planned to find the working table's schema.
+                let planner = SimplePlanner::new(file_manager, table_manager);
+                let plan = try!(planner.make_plan((*operation.left).clone()).map_err(ExecutionError::CouldNotExecutePlan));
+                try!(table.set_schema(plan.get_schema()).map_err(ExecutionError::CouldNotCreateSchema));
             }
-            None => {
-                try!(Schema::from_select_values(self.values.clone(), &mut None))
+            try!(operation.right.compute_schema(file_manager, table_manager));
+            schema
+        } else {
+            match self.from_clause {
+                Some(ref mut clause) => {
+                    try!(clause.compute_schema(file_manager, table_manager))
+                }
+                None => {
+                    try!(Schema::from_select_values(self.values.clone(), &mut None))
+                }
             }
         };
         self.from_schema = Some(schema.clone());
@@ -130,6 +380,17 @@ impl SelectClause {
 impl ::std::fmt::Display for SelectClause {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         try!(write!(f, "SelectClause[\n"));
+        if let Some(ref with_clause) = self.with_clause {
+            let tables: Vec<String> = with_clause.tables
+                .iter()
+                .map(|table| match table.column_names {
+                    Some(ref names) => format!("{} ({}) AS {}", table.name, names.join(", "), table.query),
+                    None => format!("{} AS {}", table.name, table.query),
+                })
+                .collect();
+            try!(write!(f, "\twith{}={}\n", if with_clause.recursive { "_recursive" } else { "" }, tables.join(", ")));
+        }
+
         if !self.is_trivial_project() {
             let values: Vec<String> = self.values.iter().map(|f| format!("{}", f)).collect();
             try!(write!(f, "\tvalues={}\n", values.join(", ")));
@@ -139,6 +400,12 @@ impl ::std::fmt::Display for SelectClause {
             try!(write!(f, "\tfrom={}\n", clause));
         }
 
+        if let Some(ref operation) = self.set_operation {
+            try!(write!(f, "\tset_operation={}{}\n", operation.operator, if operation.all { " ALL" } else { "" }));
+            try!(write!(f, "\tleft={}\n", operation.left));
+            try!(write!(f, "\tright={}\n", operation.right));
+        }
+
         if let Some(ref expr) = self.where_expr {
             try!(write!(f, "\twhere={}\n", expr));
         }
@@ -151,6 +418,11 @@ impl ::std::fmt::Display for SelectClause {
             }
         }
 
+        if !self.order_by_exprs.is_empty() {
+            let values: Vec<String> = self.order_by_exprs.iter().map(|e| format!("{}", e)).collect();
+            try!(write!(f, "\torder_by={}\n", values.join(", ")));
+        }
+
         if let Some(limit) = self.limit {
             try!(write!(f, "\tlimit={}\n", limit));
         }
@@ -159,7 +431,7 @@ impl ::std::fmt::Display for SelectClause {
             try!(write!(f, "\toffset={}\n", offset));
         }
 
-        // TODO: ORDER BY, correlated with?
+        // TODO: correlated with?
         write!(f, "]")
     }
 }
@@ -0,0 +1,753 @@
+//! This module contains the date and time types used for `DATE`, `TIME`, `DATETIME` and
+//! `TIMESTAMP` values, along with intervals of time, and the arithmetic between them.
+//!
+//! Dates are in the proleptic Gregorian calendar and may be in the years 1 through 9999. Times of
+//! day have microsecond precision, and no time zones are supported.
+
+use std::cmp::Ordering;
+use std::fmt;
+use std::str::FromStr;
+
+use ::expressions::{ArithmeticType, ExpressionError, Literal};
+use ::relations::ColumnType;
+
+/// The number of microseconds in a second.
+pub const MICROS_PER_SECOND: i64 = 1_000_000;
+
+/// The number of microseconds in a day.
+pub const MICROS_PER_DAY: i64 = 86_400 * MICROS_PER_SECOND;
+
+/// The earliest supported year.
+const MIN_YEAR: i64 = 1;
+
+/// The latest supported year.
+const MAX_YEAR: i64 = 9999;
+
+/// Returns the number of days from 1970-01-01 to the given date.
+fn days_from_civil(year: i64, month: u32, day: u32) -> i64 {
+    // This algorithm is due to Howard Hinnant; it treats March as the first month of the year so
+    // that leap days fall at the end of a year.
+    let year = if month <= 2 { year - 1 } else { year };
+    let era = if year >= 0 { year } else { year - 399 } / 400;
+    let year_of_era = year - era * 400;
+    let month_index = (month as i64 + 9) % 12;
+    let day_of_year = (153 * month_index + 2) / 5 + day as i64 - 1;
+    let day_of_era = year_of_era * 365 + year_of_era / 4 - year_of_era / 100 + day_of_year;
+    era * 146097 + day_of_era - 719468
+}
+
+/// Returns the year, month and day of the date the given number of days after 1970-01-01.
+fn civil_from_days(days: i64) -> (i64, u32, u32) {
+    let days = days + 719468;
+    let era = if days >= 0 { days } else { days - 146096 } / 146097;
+    let day_of_era = days - era * 146097;
+    let year_of_era = (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let month_index = (5 * day_of_year + 2) / 153;
+    let day = (day_of_year - (153 * month_index + 2) / 5 + 1) as u32;
+    let month = if month_index < 10 { month_index + 3 } else { month_index - 9 } as u32;
+    let year = year_of_era + era * 400;
+    (if month <= 2 { year + 1 } else { year }, month, day)
+}
+
+fn is_leap_year(year: i64) -> bool {
+    (year % 4 == 0 && year % 100 != 0) || year % 400 == 0
+}
+
+fn days_in_month(year: i64, month: u32) -> u32 {
+    match month {
+        2 => if is_leap_year(year) { 29 } else { 28 },
+        4 | 6 | 9 | 11 => 30,
+        _ => 31,
+    }
+}
+
+/// Parses an unsigned number made only of ASCII digits.
+fn parse_digits(s: &str) -> Option<i64> {
+    if s.is_empty() || s.len() > 9 || !s.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    s.parse().ok()
+}
+
+/// Formats a number of microseconds since midnight as `HH:MM:SS`, followed by the fraction of a
+/// second if there is one.
+fn format_time_of_day(f: &mut fmt::Formatter, micros: i64) -> fmt::Result {
+    let seconds = micros / MICROS_PER_SECOND;
+    try!(write!(f, "{:02}:{:02}:{:02}", seconds / 3600, seconds / 60 % 60, seconds % 60));
+    let fraction = micros % MICROS_PER_SECOND;
+    if fraction != 0 {
+        let digits = format!("{:06}", fraction);
+        try!(write!(f, ".{}", digits.trim_right_matches('0')));
+    }
+    Ok(())
+}
+
+/// A calendar date, stored as the number of days since 1970-01-01.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Date {
+    days: i32,
+}
+
+impl Date {
+    /// Creates a date from its year, month and day, returning `None` if there is no such date.
+    ///
+    /// # Arguments
+    /// * year - The year, from 1 to 9999.
+    /// * month - The month, from 1 to 12.
+    /// * day - The day of the month, starting at 1.
+    pub fn from_ymd(year: i64, month: u32, day: u32) -> Option<Date> {
+        if year < MIN_YEAR || year > MAX_YEAR || month < 1 || month > 12 || day < 1 ||
+           day > days_in_month(year, month) {
+            return None;
+        }
+        Some(Date { days: days_from_civil(year, month, day) as i32 })
+    }
+
+    /// Creates the date the given number of days after 1970-01-01, returning `None` if it is out
+    /// of the supported range.
+    ///
+    /// # Arguments
+    /// * days - The number of days since 1970-01-01.
+    pub fn from_days(days: i64) -> Option<Date> {
+        if days < days_from_civil(MIN_YEAR, 1, 1) || days > days_from_civil(MAX_YEAR, 12, 31) {
+            return None;
+        }
+        Some(Date { days: days as i32 })
+    }
+
+    /// The number of days since 1970-01-01.
+    pub fn get_days(&self) -> i32 {
+        self.days
+    }
+
+    /// Returns the year, month and day of the date.
+    pub fn to_ymd(&self) -> (i64, u32, u32) {
+        civil_from_days(self.days as i64)
+    }
+
+    /// Returns the date a number of months later (or earlier), moving the day back to the end of
+    /// the month if the month is shorter. Returns `None` if the result is out of range.
+    ///
+    /// # Arguments
+    /// * months - The number of months to add.
+    pub fn add_months(&self, months: i64) -> Option<Date> {
+        let (year, month, day) = self.to_ymd();
+        let month_index = year * 12 + (month as i64 - 1) + months;
+        let (year, month) = (month_index.div_euclid(12), (month_index.rem_euclid(12) + 1) as u32);
+        if year < MIN_YEAR || year > MAX_YEAR {
+            return None;
+        }
+        Date::from_ymd(year, month, ::std::cmp::min(day, days_in_month(year, month)))
+    }
+}
+
+impl FromStr for Date {
+    type Err = ExpressionError;
+
+    /// Parses a date written as `YYYY-MM-DD`.
+    fn from_str(s: &str) -> Result<Date, ExpressionError> {
+        let invalid = || ExpressionError::InvalidLiteral("DATE".into(), s.into());
+        let parts: Vec<&str> = s.trim().split('-').collect();
+        if parts.len() != 3 {
+            return Err(invalid());
+        }
+        match (parse_digits(parts[0]), parse_digits(parts[1]), parse_digits(parts[2])) {
+            (Some(year), Some(month), Some(day)) => {
+                Date::from_ymd(year, month as u32, day as u32).ok_or_else(invalid)
+            }
+            _ => Err(invalid()),
+        }
+    }
+}
+
+impl fmt::Display for Date {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let (year, month, day) = self.to_ymd();
+        write!(f, "{:04}-{:02}-{:02}", year, month, day)
+    }
+}
+
+/// A time of day, stored as the number of microseconds since midnight.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Time {
+    micros: i64,
+}
+
+impl Time {
+    /// Creates a time of day from the number of microseconds since midnight. The value wraps
+    /// around, so that e.g. 25 hours after midnight is 1 AM.
+    ///
+    /// # Arguments
+    /// * micros - The number of microseconds since midnight.
+    pub fn from_micros(micros: i64) -> Time {
+        Time { micros: micros.rem_euclid(MICROS_PER_DAY) }
+    }
+
+    /// Creates a time of day from its fields, returning `None` if any of them is out of range.
+    ///
+    /// # Arguments
+    /// * hour - The hour, from 0 to 23.
+    /// * minute - The minute, from 0 to 59.
+    /// * second - The second, from 0 to 59.
+    /// * micro - The fraction of the second, in microseconds.
+    pub fn from_hms_micro(hour: u32, minute: u32, second: u32, micro: u32) -> Option<Time> {
+        if hour > 23 || minute > 59 || second > 59 || micro as i64 >= MICROS_PER_SECOND {
+            return None;
+        }
+        let seconds = (hour * 3600 + minute * 60 + second) as i64;
+        Some(Time { micros: seconds * MICROS_PER_SECOND + micro as i64 })
+    }
+
+    /// The number of microseconds since midnight.
+    pub fn get_micros(&self) -> i64 {
+        self.micros
+    }
+}
+
+impl FromStr for Time {
+    type Err = ExpressionError;
+
+    /// Parses a time written as `HH:MM`, `HH:MM:SS` or `HH:MM:SS.ffffff`.
+    fn from_str(s: &str) -> Result<Time, ExpressionError> {
+        let invalid = || ExpressionError::InvalidLiteral("TIME".into(), s.into());
+        let (hms, fraction) = match s.trim().find('.') {
+            Some(index) => (&s.trim()[..index], Some(&s.trim()[(index + 1)..])),
+            None => (s.trim(), None),
+        };
+        let parts: Vec<Option<i64>> = hms.split(':').map(parse_digits).collect();
+        let (hour, minute, second) = match parts.as_slice() {
+            &[Some(h), Some(m)] if fraction.is_none() => (h, m, 0),
+            &[Some(h), Some(m), Some(s)] => (h, m, s),
+            _ => return Err(invalid()),
+        };
+        let micro = match fraction {
+            Some(digits) if digits.len() <= 6 => {
+                try!(parse_digits(digits).ok_or_else(invalid)) * 10i64.pow(6 - digits.len() as u32)
+            }
+            Some(_) => return Err(invalid()),
+            None => 0,
+        };
+        if hour > 23 || minute > 59 || second > 59 {
+            return Err(invalid());
+        }
+        Time::from_hms_micro(hour as u32, minute as u32, second as u32, micro as u32).ok_or_else(invalid)
+    }
+}
+
+impl fmt::Display for Time {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        format_time_of_day(f, self.micros)
+    }
+}
+
+/// A date and time of day, stored as the number of microseconds since 1970-01-01 00:00:00.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash)]
+pub struct Timestamp {
+    micros: i64,
+}
+
+impl Timestamp {
+    /// Creates a timestamp from a date and a time of day.
+    ///
+    /// # Arguments
+    /// * date - The date.
+    /// * time - The time of day.
+    pub fn new(date: Date, time: Time) -> Timestamp {
+        Timestamp { micros: date.days as i64 * MICROS_PER_DAY + time.micros }
+    }
+
+    /// Creates a timestamp from the number of microseconds since 1970-01-01 00:00:00, returning
+    /// `None` if it is out of the supported range.
+    ///
+    /// # Arguments
+    /// * micros - The number of microseconds since 1970-01-01 00:00:00.
+    pub fn from_micros(micros: i64) -> Option<Timestamp> {
+        Date::from_days(micros.div_euclid(MICROS_PER_DAY)).map(|_| Timestamp { micros: micros })
+    }
+
+    /// The number of microseconds since 1970-01-01 00:00:00.
+    pub fn get_micros(&self) -> i64 {
+        self.micros
+    }
+
+    /// The date part of the timestamp.
+    pub fn get_date(&self) -> Date {
+        Date { days: self.micros.div_euclid(MICROS_PER_DAY) as i32 }
+    }
+
+    /// The time-of-day part of the timestamp.
+    pub fn get_time(&self) -> Time {
+        Time::from_micros(self.micros)
+    }
+
+    /// Returns the timestamp with any fraction of a second removed.
+    pub fn truncate_to_seconds(&self) -> Timestamp {
+        Timestamp { micros: self.micros - self.micros.rem_euclid(MICROS_PER_SECOND) }
+    }
+
+    /// Returns the timestamp an interval later, returning `None` if the result is out of range.
+    /// The months of the interval are added first, then its days, and then its time.
+    ///
+    /// # Arguments
+    /// * interval - The interval to add.
+    pub fn add_interval(&self, interval: &Interval) -> Option<Timestamp> {
+        let date = match self.get_date().add_months(interval.months as i64) {
+            Some(date) => date,
+            None => return None,
+        };
+        let days = date.days as i64 + interval.days as i64;
+        let micros = days.checked_mul(MICROS_PER_DAY)
+            .and_then(|micros| micros.checked_add(self.get_time().micros))
+            .and_then(|micros| micros.checked_add(interval.micros));
+        micros.and_then(Timestamp::from_micros)
+    }
+}
+
+impl From<Date> for Timestamp {
+    fn from(date: Date) -> Timestamp {
+        Timestamp::new(date, Time::from_micros(0))
+    }
+}
+
+impl FromStr for Timestamp {
+    type Err = ExpressionError;
+
+    /// Parses a timestamp written as a date and a time separated by a space or a `T`, or just a
+    /// date for midnight of that day.
+    fn from_str(s: &str) -> Result<Timestamp, ExpressionError> {
+        let invalid = || ExpressionError::InvalidLiteral("TIMESTAMP".into(), s.into());
+        let s = s.trim();
+        let (date, time) = match s.find(|c| c == ' ' || c == 'T') {
+            Some(index) => (&s[..index], Some(&s[(index + 1)..])),
+            None => (s, None),
+        };
+        let date: Date = try!(date.parse().map_err(|_| invalid()));
+        let time = match time {
+            Some(time) => try!(time.parse().map_err(|_| invalid())),
+            None => Time::from_micros(0),
+        };
+        Ok(Timestamp::new(date, time))
+    }
+}
+
+impl fmt::Display for Timestamp {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        try!(write!(f, "{} ", self.get_date()));
+        format_time_of_day(f, self.get_time().micros)
+    }
+}
+
+/// An interval of time. Months and days are kept separate from the time, since the length of a
+/// month or a day in microseconds depends on the date the interval is added to.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub struct Interval {
+    /// The number of months.
+    pub months: i32,
+    /// The number of days.
+    pub days: i32,
+    /// The number of microseconds.
+    pub micros: i64,
+}
+
+impl Interval {
+    /// Instantiate a new interval.
+    ///
+    /// # Arguments
+    /// * months - The number of months.
+    /// * days - The number of days.
+    /// * micros - The number of microseconds.
+    pub fn new(months: i32, days: i32, micros: i64) -> Interval {
+        Interval {
+            months: months,
+            days: days,
+            micros: micros,
+        }
+    }
+
+    /// Creates an interval of a number of the given units, which may be `YEAR`, `MONTH`, `WEEK`,
+    /// `DAY`, `HOUR`, `MINUTE` or `SECOND` (in any case, and optionally plural). Returns `None` if
+    /// the unit isn't known.
+    ///
+    /// # Arguments
+    /// * amount - The number of units.
+    /// * unit - The name of the unit.
+    pub fn from_unit(amount: i64, unit: &str) -> Option<Interval> {
+        let unit = unit.to_lowercase();
+        let unit = unit.trim_right_matches('s');
+        let (months, days, micros) = match unit {
+            "year" => (amount * 12, 0, 0),
+            "month" | "mon" => (amount, 0, 0),
+            "week" => (0, amount * 7, 0),
+            "day" => (0, amount, 0),
+            "hour" => (0, 0, amount * 3600 * MICROS_PER_SECOND),
+            "minute" | "min" => (0, 0, amount * 60 * MICROS_PER_SECOND),
+            "second" | "sec" => (0, 0, amount * MICROS_PER_SECOND),
+            _ => return None,
+        };
+        Some(Interval::new(months as i32, days as i32, micros))
+    }
+
+    /// The approximate length of the interval in microseconds, counting a month as 30 days. This
+    /// is used to compare intervals.
+    fn approximate_micros(&self) -> i128 {
+        (self.months as i128 * 30 + self.days as i128) * MICROS_PER_DAY as i128 + self.micros as i128
+    }
+
+    /// Returns the sum of two intervals, or their difference if `negate` is set.
+    fn add(&self, other: &Interval, negate: bool) -> Option<Interval> {
+        let sign = if negate { -1 } else { 1 };
+        let months = self.months.checked_add(sign * other.months);
+        let days = self.days.checked_add(sign * other.days);
+        let micros = self.micros.checked_add(sign as i64 * other.micros);
+        match (months, days, micros) {
+            (Some(months), Some(days), Some(micros)) => Some(Interval::new(months, days, micros)),
+            _ => None,
+        }
+    }
+
+    /// Returns the interval multiplied by a whole number.
+    fn multiply(&self, factor: i64) -> Option<Interval> {
+        let months = (self.months as i64).checked_mul(factor);
+        let days = (self.days as i64).checked_mul(factor);
+        let micros = self.micros.checked_mul(factor);
+        match (months, days, micros) {
+            (Some(months), Some(days), Some(micros))
+                if months.abs() <= ::std::i32::MAX as i64 && days.abs() <= ::std::i32::MAX as i64 => {
+                Some(Interval::new(months as i32, days as i32, micros))
+            }
+            _ => None,
+        }
+    }
+}
+
+impl FromStr for Interval {
+    type Err = ExpressionError;
+
+    /// Parses an interval written as a list of amounts and units, optionally followed by a time,
+    /// e.g. `1 year 2 months 3 days 04:05:06`. This is the same format intervals are displayed in.
+    fn from_str(s: &str) -> Result<Interval, ExpressionError> {
+        let invalid = || ExpressionError::InvalidLiteral("INTERVAL".into(), s.into());
+        let mut result = Interval::new(0, 0, 0);
+        let mut tokens = s.split_whitespace();
+        let mut any = false;
+        while let Some(token) = tokens.next() {
+            any = true;
+            let (negative, digits) = if token.starts_with('-') {
+                (true, &token[1..])
+            } else {
+                (false, token)
+            };
+            let part = if digits.contains(':') {
+                let time: Time = try!(digits.parse().map_err(|_| invalid()));
+                Interval::new(0, 0, time.micros)
+            } else {
+                let amount = try!(parse_digits(digits).ok_or_else(invalid));
+                let unit = try!(tokens.next().ok_or_else(invalid));
+                try!(Interval::from_unit(amount, unit).ok_or_else(invalid))
+            };
+            result = try!(result.add(&part, negative).ok_or_else(invalid));
+        }
+        if any { Ok(result) } else { Err(invalid()) }
+    }
+}
+
+impl fmt::Display for Interval {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let mut parts = Vec::new();
+        let (years, months) = (self.months / 12, self.months % 12);
+        for &(amount, unit) in &[(years, "year"), (months, "month"), (self.days, "day")] {
+            if amount != 0 {
+                parts.push(format!("{} {}{}", amount, unit, if amount.abs() == 1 { "" } else { "s" }));
+            }
+        }
+        if self.micros != 0 || parts.is_empty() {
+            let sign = if self.micros < 0 { "-" } else { "" };
+            parts.push(format!("{}{}", sign, Time { micros: self.micros.abs() % MICROS_PER_DAY }));
+            if self.micros.abs() >= MICROS_PER_DAY {
+                // Times of a day or more are shown in hours, e.g. 25:00:00.
+                let hours = self.micros.abs() / (3600 * MICROS_PER_SECOND);
+                let rest = format!("{}", Time { micros: self.micros.abs() % (3600 * MICROS_PER_SECOND) });
+                parts.pop();
+                parts.push(format!("{}{}:{}", sign, hours, &rest[3..]));
+            }
+        }
+        write!(f, "{}", parts.join(" "))
+    }
+}
+
+/// Converts a literal to a date, parsing it if it is a string.
+pub fn as_date(value: &Literal) -> Option<Date> {
+    match *value {
+        Literal::Date(date) => Some(date),
+        Literal::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Converts a literal to a time of day, parsing it if it is a string.
+pub fn as_time(value: &Literal) -> Option<Time> {
+    match *value {
+        Literal::Time(time) => Some(time),
+        Literal::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Converts a literal to a timestamp, parsing it if it is a string. Dates are converted to
+/// midnight of that day.
+pub fn as_timestamp(value: &Literal) -> Option<Timestamp> {
+    match *value {
+        Literal::Timestamp(timestamp) => Some(timestamp),
+        Literal::Date(date) => Some(date.into()),
+        Literal::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Converts a literal to an interval, parsing it if it is a string.
+pub fn as_interval(value: &Literal) -> Option<Interval> {
+    match *value {
+        Literal::Interval(interval) => Some(interval),
+        Literal::String(ref s) => s.parse().ok(),
+        _ => None,
+    }
+}
+
+/// Compares two literals, at least one of which is a date, time, timestamp or interval. Dates are
+/// compared with timestamps as midnight of that day, and strings are parsed as the type of the
+/// other value.
+///
+/// # Errors
+/// This will fail if the values can't be compared.
+pub fn temporal_compare(left: &Literal, right: &Literal) -> Result<Ordering, ExpressionError> {
+    let incompatible = || ExpressionError::IncompatibleOperands(left.clone(), right.clone());
+    let ordering = match (left, right) {
+        (&Literal::Date(_), &Literal::Date(_)) |
+        (&Literal::Date(_), &Literal::String(_)) |
+        (&Literal::String(_), &Literal::Date(_)) => {
+            as_date(left).and_then(|l| as_date(right).map(|r| l.cmp(&r)))
+        }
+        (&Literal::Timestamp(_), _) | (_, &Literal::Timestamp(_)) => {
+            as_timestamp(left).and_then(|l| as_timestamp(right).map(|r| l.cmp(&r)))
+        }
+        (&Literal::Time(_), _) | (_, &Literal::Time(_)) => {
+            as_time(left).and_then(|l| as_time(right).map(|r| l.cmp(&r)))
+        }
+        (&Literal::Interval(_), _) | (_, &Literal::Interval(_)) => {
+            as_interval(left).and_then(|l| {
+                as_interval(right).map(|r| l.approximate_micros().cmp(&r.approximate_micros()))
+            })
+        }
+        _ => None,
+    };
+    ordering.ok_or_else(incompatible)
+}
+
+/// Performs arithmetic on two literals, at least one of which is a date, time, timestamp or
+/// interval. The supported operations are:
+///
+/// - date `+`/`-` integer, for a date that many days later or earlier
+/// - date `-` date, for the number of days between them
+/// - date `+` time, for a timestamp
+/// - date or timestamp `+`/`-` interval, for a timestamp
+/// - timestamp `-` timestamp, for an interval
+/// - time `+`/`-` interval, for a time (wrapping around midnight)
+/// - time `-` time, for an interval
+/// - interval `+`/`-` interval, and interval `*` integer, for an interval
+///
+/// `MIN` and `MAX` are supported on values that can be compared.
+///
+/// # Errors
+/// This will fail if the operation isn't supported on the values, or the result is out of range.
+pub fn temporal_arithmetic(left: &Literal, right: &Literal, op: ArithmeticType) -> Result<Literal, ExpressionError> {
+    use expressions::ArithmeticType::*;
+
+    let incompatible = || ExpressionError::IncompatibleOperands(left.clone(), right.clone());
+    let out_of_range = || ExpressionError::TemporalOverflow;
+
+    if op == Min || op == Max {
+        let ordering = try!(temporal_compare(left, right));
+        let use_left = (ordering != Ordering::Greater) == (op == Min);
+        return Ok(if use_left { left.clone() } else { right.clone() });
+    }
+
+    let integer = |value: &Literal| match *value {
+        Literal::Int(i) => Some(i as i64),
+        Literal::Long(l) => Some(l),
+        _ => None,
+    };
+
+    match (left, op, right) {
+        (&Literal::Date(date), Plus, other) | (other, Plus, &Literal::Date(date)) if integer(other).is_some() => {
+            Date::from_days(date.days as i64 + integer(other).unwrap()).map(Literal::Date).ok_or_else(out_of_range)
+        }
+        (&Literal::Date(date), Minus, other) if integer(other).is_some() => {
+            Date::from_days(date.days as i64 - integer(other).unwrap()).map(Literal::Date).ok_or_else(out_of_range)
+        }
+        (&Literal::Date(l), Minus, &Literal::Date(r)) => Ok(Literal::Int(l.days - r.days)),
+        (&Literal::Date(date), Plus, &Literal::Time(time)) | (&Literal::Time(time), Plus, &Literal::Date(date)) => {
+            Ok(Literal::Timestamp(Timestamp::new(date, time)))
+        }
+        (&Literal::Date(_), Plus, &Literal::Interval(interval)) |
+        (&Literal::Timestamp(_), Plus, &Literal::Interval(interval)) |
+        (&Literal::Interval(interval), Plus, &Literal::Date(_)) |
+        (&Literal::Interval(interval), Plus, &Literal::Timestamp(_)) => {
+            let timestamp = match *left {
+                Literal::Interval(_) => as_timestamp(right).unwrap(),
+                _ => as_timestamp(left).unwrap(),
+            };
+            timestamp.add_interval(&interval).map(Literal::Timestamp).ok_or_else(out_of_range)
+        }
+        (&Literal::Date(_), Minus, &Literal::Interval(interval)) |
+        (&Literal::Timestamp(_), Minus, &Literal::Interval(interval)) => {
+            let negated = try!(interval.multiply(-1).ok_or_else(out_of_range));
+            as_timestamp(left).unwrap().add_interval(&negated).map(Literal::Timestamp).ok_or_else(out_of_range)
+        }
+        (&Literal::Timestamp(_), Minus, &Literal::Timestamp(_)) |
+        (&Literal::Timestamp(_), Minus, &Literal::Date(_)) |
+        (&Literal::Date(_), Minus, &Literal::Timestamp(_)) => {
+            let micros = as_timestamp(left).unwrap().micros - as_timestamp(right).unwrap().micros;
+            let days = micros / MICROS_PER_DAY;
+            Ok(Literal::Interval(Interval::new(0, days as i32, micros - days * MICROS_PER_DAY)))
+        }
+        (&Literal::Time(time), Plus, &Literal::Interval(interval)) |
+        (&Literal::Interval(interval), Plus, &Literal::Time(time)) => {
+            Ok(Literal::Time(Time::from_micros(time.micros + interval.micros % MICROS_PER_DAY)))
+        }
+        (&Literal::Time(time), Minus, &Literal::Interval(interval)) => {
+            Ok(Literal::Time(Time::from_micros(time.micros - interval.micros % MICROS_PER_DAY)))
+        }
+        (&Literal::Time(l), Minus, &Literal::Time(r)) => Ok(Literal::Interval(Interval::new(0, 0, l.micros - r.micros))),
+        (&Literal::Interval(l), Plus, &Literal::Interval(r)) => {
+            l.add(&r, false).map(Literal::Interval).ok_or_else(out_of_range)
+        }
+        (&Literal::Interval(l), Minus, &Literal::Interval(r)) => {
+            l.add(&r, true).map(Literal::Interval).ok_or_else(out_of_range)
+        }
+        (&Literal::Interval(interval), Multiply, other) |
+        (other, Multiply, &Literal::Interval(interval)) if integer(other).is_some() => {
+            interval.multiply(integer(other).unwrap()).map(Literal::Interval).ok_or_else(out_of_range)
+        }
+        _ => Err(incompatible()),
+    }
+}
+
+/// Returns the type of the result of arithmetic on values of the given types, at least one of
+/// which is a date or time type.
+///
+/// # Errors
+/// This will fail if the operation isn't supported on values of these types.
+pub fn temporal_result_type(op: ArithmeticType, left: ColumnType, right: ColumnType) -> Result<ColumnType, ExpressionError> {
+    // The result type only depends on the operand types, so it can be found by doing the
+    // arithmetic on the types' default values.
+    temporal_arithmetic(&left.default_literal(), &right.default_literal(), op).map(|value| value.get_column_type())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn date(s: &str) -> Date {
+        s.parse().unwrap()
+    }
+
+    fn timestamp(s: &str) -> Timestamp {
+        s.parse().unwrap()
+    }
+
+    #[test]
+    fn test_date() {
+        assert_eq!(0, date("1970-01-01").get_days());
+        assert_eq!(19753, date("2024-01-31").get_days());
+        assert_eq!(-719162, date("0001-01-01").get_days());
+        assert_eq!((2024, 2, 29), date("2024-02-29").to_ymd());
+        assert_eq!("2024-02-29", format!("{}", date("2024-2-29")));
+        assert!("2023-02-29".parse::<Date>().is_err());
+        assert!("2024-13-01".parse::<Date>().is_err());
+        assert!("2024-01".parse::<Date>().is_err());
+        assert!("10000-01-01".parse::<Date>().is_err());
+
+        assert_eq!(Some(date("2024-02-29")), date("2024-01-31").add_months(1));
+        assert_eq!(Some(date("2023-11-30")), date("2024-01-31").add_months(-2));
+        assert_eq!(Some(date("2025-01-31")), date("2024-01-31").add_months(12));
+    }
+
+    #[test]
+    fn test_time_and_timestamp() {
+        let time: Time = "12:34:56.5".parse().unwrap();
+        assert_eq!(Time::from_hms_micro(12, 34, 56, 500000), Some(time));
+        assert_eq!("12:34:56.5", format!("{}", time));
+        assert_eq!("08:05:00", format!("{}", "8:05".parse::<Time>().unwrap()));
+        assert!("24:00:00".parse::<Time>().is_err());
+        assert!("12:60".parse::<Time>().is_err());
+
+        let ts = timestamp("2024-01-31 23:59:59.000001");
+        assert_eq!(date("2024-01-31"), ts.get_date());
+        assert_eq!("2024-01-31 23:59:59.000001", format!("{}", ts));
+        assert_eq!("2024-01-31 23:59:59", format!("{}", ts.truncate_to_seconds()));
+        assert_eq!(timestamp("2024-01-31T00:00:00"), timestamp("2024-01-31"));
+        assert_eq!("1969-12-31 23:00:00", format!("{}", timestamp("1969-12-31 23:00")));
+    }
+
+    #[test]
+    fn test_interval() {
+        let interval: Interval = "1 year 2 months 3 days 04:05:06".parse().unwrap();
+        assert_eq!(Interval::new(14, 3, (4 * 3600 + 5 * 60 + 6) * MICROS_PER_SECOND), interval);
+        assert_eq!("1 year 2 months 3 days 04:05:06", format!("{}", interval));
+        assert_eq!("1 day", format!("{}", Interval::new(0, 1, 0)));
+        assert_eq!("-2 days -01:00:00", format!("{}", "-2 days -1 hour".parse::<Interval>().unwrap()));
+        assert_eq!("00:00:00", format!("{}", Interval::new(0, 0, 0)));
+        assert_eq!(Some(Interval::new(0, 21, 0)), Interval::from_unit(3, "WEEKS"));
+        assert!("3 fortnights".parse::<Interval>().is_err());
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let d = Literal::Date(date("2024-01-31"));
+        let ts = Literal::Timestamp(timestamp("2024-01-31 12:00:00"));
+        let month = Literal::Interval(Interval::new(1, 0, 0));
+        let hours = Literal::Interval(Interval::from_unit(36, "hours").unwrap());
+
+        assert_eq!(Ok(Literal::Date(date("2024-02-10"))), temporal_arithmetic(&d, &10.into(), ArithmeticType::Plus));
+        assert_eq!(Ok(Literal::Date(date("2024-01-21"))), temporal_arithmetic(&d, &10.into(), ArithmeticType::Minus));
+        assert_eq!(Ok(Literal::Int(31)),
+                   temporal_arithmetic(&d, &Literal::Date(date("2023-12-31")), ArithmeticType::Minus));
+        assert_eq!(Ok(Literal::Timestamp(timestamp("2024-02-29"))),
+                   temporal_arithmetic(&d, &month, ArithmeticType::Plus));
+        assert_eq!(Ok(Literal::Timestamp(timestamp("2024-02-02 00:00:00"))),
+                   temporal_arithmetic(&hours, &ts, ArithmeticType::Plus));
+        assert_eq!(Ok(Literal::Timestamp(timestamp("2023-12-31 12:00:00"))),
+                   temporal_arithmetic(&ts, &month, ArithmeticType::Minus));
+        assert_eq!(Ok(Literal::Interval(Interval::new(0, 1, 12 * 3600 * MICROS_PER_SECOND))),
+                   temporal_arithmetic(&Literal::Timestamp(timestamp("2024-02-02")), &ts, ArithmeticType::Minus));
+        assert_eq!(Ok(Literal::Time("00:30:00".parse().unwrap())),
+                   temporal_arithmetic(&Literal::Time("12:30:00".parse().unwrap()), &hours, ArithmeticType::Plus));
+        assert_eq!(Ok(Literal::Interval(Interval::new(3, 0, 0))),
+                   temporal_arithmetic(&month, &3.into(), ArithmeticType::Multiply));
+        assert_eq!(Ok(ts.clone()), temporal_arithmetic(&d, &ts, ArithmeticType::Max));
+
+        assert_eq!(Err(ExpressionError::IncompatibleOperands(d.clone(), ts.clone())),
+                   temporal_arithmetic(&d, &ts, ArithmeticType::Plus));
+        assert_eq!(Err(ExpressionError::TemporalOverflow),
+                   temporal_arithmetic(&Literal::Date(date("9999-12-31")), &1.into(), ArithmeticType::Plus));
+
+        assert_eq!(Ok(ColumnType::Timestamp),
+                   temporal_result_type(ArithmeticType::Plus, ColumnType::Date, ColumnType::Interval));
+        assert_eq!(Ok(ColumnType::Integer),
+                   temporal_result_type(ArithmeticType::Minus, ColumnType::Date, ColumnType::Date));
+    }
+
+    #[test]
+    fn test_compare() {
+        let d = Literal::Date(date("2024-01-31"));
+        assert_eq!(Ok(Ordering::Less),
+                   temporal_compare(&d, &Literal::Timestamp(timestamp("2024-01-31 00:00:01"))));
+        assert_eq!(Ok(Ordering::Equal), temporal_compare(&d, &"2024-01-31".into()));
+        assert_eq!(Ok(Ordering::Greater),
+                   temporal_compare(&Literal::Interval(Interval::new(1, 0, 0)),
+                                    &Literal::Interval(Interval::new(0, 29, 0))));
+        assert!(temporal_compare(&d, &"yesterday".into()).is_err());
+        assert!(temporal_compare(&d, &5.into()).is_err());
+    }
+}
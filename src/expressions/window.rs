@@ -0,0 +1,160 @@
+//! This module contains the window specifications of window function calls, i.e. the
+//! `OVER (PARTITION BY ... ORDER BY ... ROWS BETWEEN ...)` part of the call.
+
+use std::cmp::{max, min};
+use std::ops::Range;
+
+use ::expressions::{Expression, OrderByExpression};
+
+/// One end of a window frame, given relative to the row that the frame belongs to.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum FrameBound {
+    /// The first row of the partition.
+    UnboundedPreceding,
+    /// The row the given number of rows before the current row.
+    Preceding(usize),
+    /// The current row.
+    CurrentRow,
+    /// The row the given number of rows after the current row.
+    Following(usize),
+    /// The last row of the partition.
+    UnboundedFollowing,
+}
+
+impl ::std::fmt::Display for FrameBound {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            FrameBound::UnboundedPreceding => write!(f, "UNBOUNDED PRECEDING"),
+            FrameBound::Preceding(n) => write!(f, "{} PRECEDING", n),
+            FrameBound::CurrentRow => write!(f, "CURRENT ROW"),
+            FrameBound::Following(n) => write!(f, "{} FOLLOWING", n),
+            FrameBound::UnboundedFollowing => write!(f, "UNBOUNDED FOLLOWING"),
+        }
+    }
+}
+
+/// A `ROWS BETWEEN start AND end` window frame, which is the set of rows of a partition that an
+/// aggregate used as a window function is computed over for each row.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct WindowFrame {
+    /// The first row of the frame.
+    pub start: FrameBound,
+    /// The last row of the frame.
+    pub end: FrameBound,
+}
+
+impl WindowFrame {
+    /// Creates a new window frame.
+    pub fn new(start: FrameBound, end: FrameBound) -> WindowFrame {
+        WindowFrame {
+            start: start,
+            end: end,
+        }
+    }
+
+    /// Returns the positions of the rows in the frame of a row.
+    ///
+    /// # Arguments
+    /// * index - The position of the row in the partition.
+    /// * size - The number of rows in the partition.
+    pub fn get_rows(&self, index: usize, size: usize) -> Range<usize> {
+        let start = match self.start {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(n) => index.saturating_sub(n),
+            FrameBound::CurrentRow => index,
+            FrameBound::Following(n) => index.saturating_add(n),
+            FrameBound::UnboundedFollowing => size,
+        };
+        // The end bound includes the row it refers to.
+        let end = match self.end {
+            FrameBound::UnboundedPreceding => 0,
+            FrameBound::Preceding(n) => (index + 1).saturating_sub(n),
+            FrameBound::CurrentRow => index + 1,
+            FrameBound::Following(n) => (index + 1).saturating_add(n),
+            FrameBound::UnboundedFollowing => size,
+        };
+        let start = min(start, size);
+        start..max(start, min(end, size))
+    }
+}
+
+impl ::std::fmt::Display for WindowFrame {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "ROWS BETWEEN {} AND {}", self.start, self.end)
+    }
+}
+
+/// The window of a window function call, which says how the rows are split into partitions, the
+/// order of the rows in each partition and, for aggregates, the frame of rows to aggregate.
+#[derive(Clone, Debug, PartialEq)]
+pub struct WindowSpec {
+    /// The expressions whose values split the rows into partitions.
+    pub partition_by: Vec<Expression>,
+    /// The order of the rows in each partition.
+    pub order_by: Vec<OrderByExpression>,
+    /// The frame of rows that an aggregate is computed over. If this isn't given, the frame is
+    /// every row up to the last row that is equal to the current row according to the ordering,
+    /// or the whole partition if there is no ordering.
+    pub frame: Option<WindowFrame>,
+}
+
+impl WindowSpec {
+    /// Creates a new window specification.
+    pub fn new(partition_by: Vec<Expression>,
+               order_by: Vec<OrderByExpression>,
+               frame: Option<WindowFrame>)
+               -> WindowSpec {
+        WindowSpec {
+            partition_by: partition_by,
+            order_by: order_by,
+            frame: frame,
+        }
+    }
+}
+
+impl ::std::fmt::Display for WindowSpec {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        let mut parts: Vec<String> = Vec::new();
+        if !self.partition_by.is_empty() {
+            let exprs: Vec<String> = self.partition_by.iter().map(|e| format!("{}", e)).collect();
+            parts.push(format!("PARTITION BY {}", exprs.join(", ")));
+        }
+        if !self.order_by.is_empty() {
+            let terms: Vec<String> = self.order_by.iter().map(|t| format!("{}", t)).collect();
+            parts.push(format!("ORDER BY {}", terms.join(", ")));
+        }
+        if let Some(ref frame) = self.frame {
+            parts.push(format!("{}", frame));
+        }
+        write!(f, "({})", parts.join(" "))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_frame_rows() {
+        let frame = WindowFrame::new(FrameBound::UnboundedPreceding, FrameBound::CurrentRow);
+        assert_eq!(0..1, frame.get_rows(0, 5));
+        assert_eq!(0..4, frame.get_rows(3, 5));
+
+        let frame = WindowFrame::new(FrameBound::Preceding(1), FrameBound::Following(1));
+        assert_eq!(0..2, frame.get_rows(0, 5));
+        assert_eq!(1..4, frame.get_rows(2, 5));
+        assert_eq!(3..5, frame.get_rows(4, 5));
+
+        let frame = WindowFrame::new(FrameBound::CurrentRow, FrameBound::UnboundedFollowing);
+        assert_eq!(2..5, frame.get_rows(2, 5));
+
+        // Frames that end before they start are empty.
+        let frame = WindowFrame::new(FrameBound::Following(1), FrameBound::Preceding(1));
+        assert_eq!(3..3, frame.get_rows(2, 5));
+        let frame = WindowFrame::new(FrameBound::Following(2), FrameBound::Following(3));
+        assert_eq!(5..5, frame.get_rows(4, 5));
+        let frame = WindowFrame::new(FrameBound::Preceding(2), FrameBound::Preceding(1));
+        assert_eq!(0..0, frame.get_rows(0, 5));
+        assert_eq!(0..2, frame.get_rows(2, 5));
+    }
+}
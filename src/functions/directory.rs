@@ -5,6 +5,7 @@ use super::arithmetic::*;
 use super::count::*;
 use super::stats::*;
 use super::trig::*;
+use super::window::*;
 use super::{Function, FunctionError};
 
 use std::collections::HashMap;
@@ -61,6 +62,13 @@ impl Directory {
         self.add_function("VARIANCE", Box::new(StdDevVariance::variance));
         self.add_function("STDDEVP", Box::new(StdDevVariance::std_dev_population));
         self.add_function("VARIANCEP", Box::new(StdDevVariance::variance_population));
+
+        self.add_function("ROW_NUMBER", Box::new(RowNumber::new));
+        self.add_function("RANK", Box::new(Rank::rank));
+        self.add_function("DENSE_RANK", Box::new(Rank::dense_rank));
+        self.add_function("LAG", Box::new(LagLead::lag));
+        self.add_function("LEAD", Box::new(LagLead::lead));
+        self.add_function("FIRST_VALUE", Box::new(FirstValue::new));
     }
 
     fn add_function<S: Into<String>, F: Fn() -> Box<Function> + 'static + Sync>(&mut self, name: S, f: Box<F>) {
@@ -11,9 +11,11 @@ mod coalesce;
 mod count;
 mod stats;
 mod trig;
+mod window;
 
 pub use self::directory::Directory;
 pub use self::{Error as FunctionError};
+pub use self::window::WindowRow;
 
 use ::expressions::{Environment, Expression, ExpressionError, Literal};
 use ::relations::{ColumnType, Schema};
@@ -39,11 +41,17 @@ pub trait Function: Sync + ::std::fmt::Debug {
     /// Returns the function as an AggregateFunction if possible. By default this doesn't work.
     fn get_as_aggregate(&self) -> Option<Box<AggregateFunction>> { None }
 
+    /// Returns the function as a WindowFunction if possible. By default this doesn't work.
+    fn get_as_window(&self) -> Option<Box<WindowFunction>> { None }
+
     /// Whether the function can be taken as a scalar function.
     fn is_scalar(&self) -> bool { false }
 
     /// Whether the function can be taken as an aggregate function.
     fn is_aggregate(&self) -> bool { false }
+
+    /// Whether the function can be taken as a window function.
+    fn is_window(&self) -> bool { false }
 }
 
 /// This is a function that returns a scalar, and thus has a specific return column type.
@@ -73,6 +81,15 @@ pub trait AggregateFunction: ScalarFunction {
     fn get_result(&self) -> Literal;
 }
 
+/// This is a function that can only be called with an `OVER` clause, because it computes its value
+/// for a row from the row's position among the other rows of its window partition, e.g. `RANK`.
+/// (Aggregate functions can be called with an `OVER` clause too, in which case they are computed
+/// over the row's window frame.)
+pub trait WindowFunction: ScalarFunction {
+    /// Computes the function's value for a row of a window partition.
+    fn get_window_result(&self, row: &WindowRow) -> FunctionResult;
+}
+
 /// An error that can occur while calling or retrieving a function.
 #[derive(Clone, Debug, PartialEq)]
 pub enum Error {
@@ -96,6 +113,8 @@ pub enum Error {
     ColumnValueNotInSchema(Expression),
     /// The function using a column value found multiple possibilities in the input schema.
     ColumnValueAmbiguous(Expression),
+    /// The window function was called without an `OVER` clause.
+    NeedsWindow(String),
     /// The function has not been implemented yet.
     Unimplemented(String),
 }
@@ -133,6 +152,9 @@ impl ::std::fmt::Display for Error {
             Error::ColumnValueAmbiguous(ref expr) => {
                 write!(f, "The column value {} is ambiguous.", expr)
             },
+            Error::NeedsWindow(ref name) => {
+                write!(f, "The function {} can only be called with an OVER clause.", name)
+            },
             Error::Unimplemented(ref name) => {
                 write!(f, "The function {} is not implmented.", name)
             }
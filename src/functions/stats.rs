@@ -404,6 +404,9 @@ impl AggregateFunction for StdDevVariance {
             return;
         }
 
+        // The variance is computed with doubles, even for exact NUMERIC values.
+        let value = if value.is_decimal() { value.as_double().unwrap() } else { value };
+
         // This algorithm is due to Welford, 1962.
         self.count += 1;
         let delta = literal_arithmetic(&value, &Literal::Double(self.mean), ArithmeticType::Minus).unwrap();
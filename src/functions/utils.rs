@@ -7,6 +7,10 @@ macro_rules! return_arithmetic_eval {
                     Literal::Long($int_name) => Ok($int_expr.into()),
                     Literal::Double($dec_name) => Ok($dec_expr.into()),
                     Literal::Float($dec_name) => Ok($dec_expr.into()),
+                    Literal::Decimal(d) => {
+                        let $dec_name = d.to_f64();
+                        Ok($dec_expr.into())
+                    }
                     _ => Err(FunctionError::ExpressionNotNumeric($to_eval.clone())),
                 }
             }
@@ -23,6 +27,10 @@ macro_rules! return_arithmetic_eval {
                     Literal::Long($var_name) => Ok($expr.into()),
                     Literal::Double($var_name) => Ok($expr.into()),
                     Literal::Float($var_name) => Ok($expr.into()),
+                    Literal::Decimal(d) => {
+                        let $var_name = d.to_f64();
+                        Ok($expr.into())
+                    }
                     _ => Err(FunctionError::ExpressionNotNumeric($to_eval.clone())),
                 }
             }
@@ -46,6 +54,7 @@ macro_rules! as_double {
             Literal::Long(l) => l as f64,
             Literal::Float(f) => f as f64,
             Literal::Double(d) => d,
+            Literal::Decimal(d) => d.to_f64(),
             _ => unreachable!()
         }
     }};
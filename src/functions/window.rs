@@ -0,0 +1,281 @@
+use std::ops::Range;
+
+use ::expressions::{Environment, Expression, Literal};
+use ::functions::{Function, FunctionError, FunctionResult, ScalarFunction, WindowFunction};
+use ::queries::Planner;
+use ::relations::{ColumnType, Schema};
+
+/// A row of a window partition, along with everything about the row's position in the partition
+/// that window functions compute their values from.
+pub struct WindowRow<'a> {
+    /// The values of the function's arguments for each row of the partition, in the order of the
+    /// partition.
+    pub args: &'a [Vec<Literal>],
+    /// The position of the row in the partition.
+    pub index: usize,
+    /// The positions of the rows in the row's window frame.
+    pub frame: Range<usize>,
+    /// The positions of the row's peers, i.e. the rows that are equal to it according to the
+    /// ordering of the partition. This includes the row itself.
+    pub peers: Range<usize>,
+    /// The number of groups of peers before the row's own group of peers.
+    pub peer_group: usize,
+}
+
+fn check_num_args(name: &str, args: &[Expression], min: usize, max: usize) -> Result<(), FunctionError> {
+    if args.len() < min {
+        if args.is_empty() {
+            Err(FunctionError::NeedsArguments(name.into()))
+        } else {
+            Err(FunctionError::NeedsMoreArguments(name.into(), min, args.len()))
+        }
+    } else if args.len() > max {
+        Err(FunctionError::TakesArguments(name.into(), max, args.len()))
+    } else {
+        Ok(())
+    }
+}
+
+fn get_arg_type(args: &[Expression], schema: &Schema) -> Result<ColumnType, FunctionError> {
+    args[0].get_column_type(schema).map_err(|e| {
+        FunctionError::CouldNotRetrieveExpressionColumnType(args[0].clone(), Box::new(e))
+    })
+}
+
+macro_rules! impl_window_func {
+    ($name:ident) => {
+        impl Function for $name {
+            fn clone(&self) -> Self where Self: Sized {
+                Clone::clone(self)
+            }
+
+            fn evaluate(&self, _env: &mut Option<&mut Environment>, _args: Vec<Expression>, _planner: &Option<&Planner>) -> FunctionResult {
+                Err(FunctionError::NeedsWindow(self.get_name().into()))
+            }
+
+            fn get_as_scalar(&self) -> Option<Box<ScalarFunction>> {
+                Some(Box::new(Clone::clone(self)))
+            }
+
+            fn get_as_window(&self) -> Option<Box<WindowFunction>> {
+                Some(Box::new(Clone::clone(self)))
+            }
+
+            fn is_window(&self) -> bool { true }
+        }
+    }
+}
+
+/// The `ROW_NUMBER()` function, which numbers the rows of each partition from 1.
+#[derive(Debug, Clone)]
+pub struct RowNumber;
+
+impl RowNumber {
+    pub fn new() -> Box<Function> {
+        Box::new(RowNumber)
+    }
+
+    fn get_name(&self) -> &'static str { "ROW_NUMBER" }
+}
+
+impl_window_func!(RowNumber);
+
+impl ScalarFunction for RowNumber {
+    fn get_return_type(&self, args: Vec<Expression>, _schema: &Schema) -> Result<ColumnType, FunctionError> {
+        try!(check_num_args(self.get_name(), &args, 0, 0));
+        Ok(ColumnType::Integer)
+    }
+}
+
+impl WindowFunction for RowNumber {
+    fn get_window_result(&self, row: &WindowRow) -> FunctionResult {
+        Ok(Literal::Int(row.index as i32 + 1))
+    }
+}
+
+/// The `RANK()` and `DENSE_RANK()` functions. The rank of a row is one more than the number of
+/// rows before its peers, so ties leave gaps in the ranks. The dense rank is one more than the
+/// number of groups of peers before the row, so there are no gaps.
+#[derive(Debug, Clone)]
+pub struct Rank {
+    dense: bool,
+}
+
+impl Rank {
+    pub fn rank() -> Box<Function> {
+        Box::new(Rank { dense: false })
+    }
+
+    pub fn dense_rank() -> Box<Function> {
+        Box::new(Rank { dense: true })
+    }
+
+    fn get_name(&self) -> &'static str { if self.dense { "DENSE_RANK" } else { "RANK" } }
+}
+
+impl_window_func!(Rank);
+
+impl ScalarFunction for Rank {
+    fn get_return_type(&self, args: Vec<Expression>, _schema: &Schema) -> Result<ColumnType, FunctionError> {
+        try!(check_num_args(self.get_name(), &args, 0, 0));
+        Ok(ColumnType::Integer)
+    }
+}
+
+impl WindowFunction for Rank {
+    fn get_window_result(&self, row: &WindowRow) -> FunctionResult {
+        Ok(Literal::Int(if self.dense { row.peer_group } else { row.peers.start } as i32 + 1))
+    }
+}
+
+/// The `LAG(value [, offset [, default]])` and `LEAD(value [, offset [, default]])` functions,
+/// which return the value for the row the given number of rows before or after the current row in
+/// its partition. The offset is 1 if it isn't given, and if there is no such row the default value
+/// is returned, or `NULL` if there is no default.
+#[derive(Debug, Clone)]
+pub struct LagLead {
+    lead: bool,
+}
+
+impl LagLead {
+    pub fn lag() -> Box<Function> {
+        Box::new(LagLead { lead: false })
+    }
+
+    pub fn lead() -> Box<Function> {
+        Box::new(LagLead { lead: true })
+    }
+
+    fn get_name(&self) -> &'static str { if self.lead { "LEAD" } else { "LAG" } }
+}
+
+impl_window_func!(LagLead);
+
+impl ScalarFunction for LagLead {
+    fn get_return_type(&self, args: Vec<Expression>, schema: &Schema) -> Result<ColumnType, FunctionError> {
+        try!(check_num_args(self.get_name(), &args, 1, 3));
+        if args.len() > 1 {
+            let offset_type = try!(get_arg_type(&args[1..], schema));
+            match offset_type {
+                ColumnType::TinyInt | ColumnType::SmallInt | ColumnType::Integer | ColumnType::BigInt => {}
+                _ => return Err(FunctionError::ExpressionNotNumeric(args[1].clone())),
+            }
+        }
+        get_arg_type(&args, schema)
+    }
+}
+
+impl WindowFunction for LagLead {
+    fn get_window_result(&self, row: &WindowRow) -> FunctionResult {
+        let args = &row.args[row.index];
+        let offset = match args.get(1) {
+            None => 1,
+            Some(&Literal::Int(offset)) => offset as i64,
+            Some(&Literal::Long(offset)) => offset,
+            // A NULL offset makes the result NULL.
+            Some(_) => return Ok(Literal::Null),
+        };
+        let target = if self.lead { row.index as i64 + offset } else { row.index as i64 - offset };
+
+        if target >= 0 && (target as usize) < row.args.len() {
+            Ok(row.args[target as usize][0].clone())
+        } else {
+            Ok(args.get(2).cloned().unwrap_or(Literal::Null))
+        }
+    }
+}
+
+/// The `FIRST_VALUE(value)` function, which returns the value for the first row of the current
+/// row's window frame.
+#[derive(Debug, Clone)]
+pub struct FirstValue;
+
+impl FirstValue {
+    pub fn new() -> Box<Function> {
+        Box::new(FirstValue)
+    }
+
+    fn get_name(&self) -> &'static str { "FIRST_VALUE" }
+}
+
+impl_window_func!(FirstValue);
+
+impl ScalarFunction for FirstValue {
+    fn get_return_type(&self, args: Vec<Expression>, schema: &Schema) -> Result<ColumnType, FunctionError> {
+        try!(check_num_args(self.get_name(), &args, 1, 1));
+        get_arg_type(&args, schema)
+    }
+}
+
+impl WindowFunction for FirstValue {
+    fn get_window_result(&self, row: &WindowRow) -> FunctionResult {
+        if row.frame.start < row.frame.end {
+            Ok(row.args[row.frame.start][0].clone())
+        } else {
+            Ok(Literal::Null)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn make_row<'a>(args: &'a [Vec<Literal>], index: usize, peers: Range<usize>, peer_group: usize) -> WindowRow<'a> {
+        WindowRow {
+            args: args,
+            index: index,
+            frame: 0..(index + 1),
+            peers: peers,
+            peer_group: peer_group,
+        }
+    }
+
+    #[test]
+    fn test_ranking_functions() {
+        // The rows are ordered by a value which is the same for the second and third rows.
+        let args = vec![vec![]; 4];
+        let rows = vec![make_row(&args, 0, 0..1, 0),
+                        make_row(&args, 1, 1..3, 1),
+                        make_row(&args, 2, 1..3, 1),
+                        make_row(&args, 3, 3..4, 2)];
+
+        let results = |func: Box<Function>| -> Vec<Literal> {
+            let func = func.get_as_window().unwrap();
+            rows.iter().map(|row| func.get_window_result(row).unwrap()).collect()
+        };
+        assert_eq!(vec![Literal::Int(1), Literal::Int(2), Literal::Int(3), Literal::Int(4)],
+                   results(RowNumber::new()));
+        assert_eq!(vec![Literal::Int(1), Literal::Int(2), Literal::Int(2), Literal::Int(4)],
+                   results(Rank::rank()));
+        assert_eq!(vec![Literal::Int(1), Literal::Int(2), Literal::Int(2), Literal::Int(3)],
+                   results(Rank::dense_rank()));
+
+        assert_eq!(Err(FunctionError::NeedsWindow("RANK".into())),
+                   Rank::rank().evaluate(&mut None, vec![], &None));
+        assert_eq!(Err(FunctionError::TakesArguments("ROW_NUMBER".into(), 0, 1)),
+                   RowNumber::new().get_as_scalar().unwrap().get_return_type(vec![Expression::Int(1)], &Schema::new()));
+    }
+
+    #[test]
+    fn test_value_functions() {
+        let args: Vec<Vec<Literal>> = (1..4).map(|i| vec![Literal::Int(i * 10), Literal::Int(2), Literal::Int(-1)]).collect();
+        let lag = LagLead::lag().get_as_window().unwrap();
+        let lead = LagLead::lead().get_as_window().unwrap();
+        let first = FirstValue::new().get_as_window().unwrap();
+
+        // With the default offset and no default value.
+        let short_args: Vec<Vec<Literal>> = args.iter().map(|values| vec![values[0].clone()]).collect();
+        assert_eq!(Ok(Literal::Null), lag.get_window_result(&make_row(&short_args, 0, 0..1, 0)));
+        assert_eq!(Ok(Literal::Int(10)), lag.get_window_result(&make_row(&short_args, 1, 1..2, 1)));
+        assert_eq!(Ok(Literal::Int(30)), lead.get_window_result(&make_row(&short_args, 1, 1..2, 1)));
+        assert_eq!(Ok(Literal::Null), lead.get_window_result(&make_row(&short_args, 2, 2..3, 2)));
+
+        // With an offset of 2 and a default value of -1.
+        assert_eq!(Ok(Literal::Int(-1)), lag.get_window_result(&make_row(&args, 1, 1..2, 1)));
+        assert_eq!(Ok(Literal::Int(10)), lag.get_window_result(&make_row(&args, 2, 2..3, 2)));
+        assert_eq!(Ok(Literal::Int(30)), lead.get_window_result(&make_row(&args, 0, 0..1, 0)));
+
+        assert_eq!(Ok(Literal::Int(10)), first.get_window_result(&make_row(&args, 2, 2..3, 2)));
+    }
+}
@@ -0,0 +1,188 @@
+//! This module contains the classes for secondary indexes on tables.
+//!
+//! An index is stored as a B<sup>+</sup> tree tuple file alongside the table's file. Each entry in
+//! the index holds the values of the indexed columns of one tuple, followed by a file pointer to
+//! the tuple in the table file. Every column of the entry (including the file pointer) is part of
+//! the B<sup>+</sup> tree's key, so entries for tuples with equal indexed values are still
+//! distinct.
+//!
+//! The list of indexes on a table is stored in the table's header page, directly after the
+//! table's schema.
+
+use std::io;
+
+use ::{ColumnInfo, ColumnType, Schema, SchemaError};
+use ::expressions::Literal;
+use ::storage::{ReadNanoDBExt, TupleLiteral, WriteNanoDBExt};
+
+/// The name of the column in an index's schema that holds the file pointer to the indexed tuple.
+pub const TUPLE_POINTER_COLUMN: &'static str = "#TUPLE_FP";
+
+/// Given the name of a table and of an index on it, return the file name which will correspond to
+/// the index in the data directory.
+///
+/// # Arguments
+/// * table_name - The name of the table.
+/// * index_name - The name of the index.
+#[inline]
+pub fn get_index_file_name<S1: Into<String>, S2: Into<String>>(table_name: S1, index_name: S2) -> String {
+    table_name.into() + "." + &index_name.into() + ".idx"
+}
+
+/// This class describes an index on a table: its name, whether it enforces uniqueness, and which
+/// of the table's columns it indexes.
+#[derive(Debug, Clone, PartialEq)]
+pub struct IndexInfo {
+    /// The name of the index.
+    pub name: String,
+    /// Whether the index only allows one tuple for each combination of (non-`NULL`) values.
+    pub unique: bool,
+    /// The indexes of the indexed columns in the table's schema, in the order they are indexed.
+    pub column_indexes: Vec<usize>,
+}
+
+impl IndexInfo {
+    /// Creates a new index description.
+    ///
+    /// # Arguments
+    /// * name - The name of the index.
+    /// * unique - Whether the index enforces uniqueness.
+    /// * column_indexes - The indexes of the indexed columns in the table's schema.
+    pub fn new<S: Into<String>>(name: S, unique: bool, column_indexes: Vec<usize>) -> IndexInfo {
+        IndexInfo {
+            name: name.into(),
+            unique: unique,
+            column_indexes: column_indexes,
+        }
+    }
+
+    /// Returns the schema of the index's entries: the indexed columns of the table, followed by the
+    /// file pointer to the tuple.
+    ///
+    /// # Arguments
+    /// * table_schema - The schema of the indexed table.
+    pub fn get_schema(&self, table_schema: &Schema) -> Result<Schema, SchemaError> {
+        let mut columns: Vec<ColumnInfo> = self.column_indexes
+            .iter()
+            .map(|i| table_schema[*i].clone())
+            .collect();
+        columns.push(ColumnInfo::with_table_name(ColumnType::FilePointer,
+                                                 TUPLE_POINTER_COLUMN,
+                                                 self.name.as_str()));
+        Schema::with_columns(columns)
+    }
+
+    /// Returns the number of columns in the index's key, which is every column of the entry.
+    pub fn get_num_key_columns(&self) -> usize {
+        self.column_indexes.len() + 1
+    }
+
+    /// Returns the values of the indexed columns of a tuple of the table.
+    ///
+    /// # Arguments
+    /// * tuple - The values of the table's tuple.
+    pub fn get_key(&self, tuple: &TupleLiteral) -> TupleLiteral {
+        TupleLiteral::from_iter(self.column_indexes.iter().map(|i| tuple[*i].clone()))
+    }
+
+    /// Returns the index entry for a tuple of the table.
+    ///
+    /// # Arguments
+    /// * tuple - The values of the table's tuple.
+    /// * pointer - The file pointer to the tuple in the table file.
+    pub fn get_entry(&self, tuple: &TupleLiteral, pointer: Literal) -> TupleLiteral {
+        let mut entry = self.get_key(tuple);
+        entry.add_value(pointer);
+        entry
+    }
+
+    /// Write the index description to some output.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to write to the given output.
+    pub fn write<W: WriteNanoDBExt>(&self, output: &mut W) -> Result<(), io::Error> {
+        try!(output.write_varchar255(self.name.clone()));
+        try!(output.write_u8(if self.unique { 1 } else { 0 }));
+        try!(output.write_u8(self.column_indexes.len() as u8));
+        for i in self.column_indexes.iter() {
+            try!(output.write_u8(*i as u8));
+        }
+        Ok(())
+    }
+
+    /// Reads an index description from some input.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to read from the given input.
+    pub fn read<R: ReadNanoDBExt>(input: &mut R) -> Result<IndexInfo, io::Error> {
+        let name = try!(input.read_varchar255());
+        let unique = try!(input.read_u8()) != 0;
+        let num_columns = try!(input.read_u8());
+        let mut column_indexes = Vec::with_capacity(num_columns as usize);
+        for _ in 0..num_columns {
+            column_indexes.push(try!(input.read_u8()) as usize);
+        }
+        Ok(IndexInfo::new(name, unique, column_indexes))
+    }
+}
+
+/// Writes a list of index descriptions to some output, preceded by their count.
+///
+/// # Errors
+/// This function can fail if anything goes wrong trying to write to the given output.
+pub fn write_indexes<W: WriteNanoDBExt>(output: &mut W, indexes: &[IndexInfo]) -> Result<(), io::Error> {
+    try!(output.write_u8(indexes.len() as u8));
+    for index in indexes.iter() {
+        try!(index.write(output));
+    }
+    Ok(())
+}
+
+/// Reads a list of index descriptions written by [`write_indexes`](fn.write_indexes.html).
+///
+/// # Errors
+/// This function can fail if anything goes wrong trying to read from the given input.
+pub fn read_indexes<R: ReadNanoDBExt>(input: &mut R) -> Result<Vec<IndexInfo>, io::Error> {
+    let num_indexes = try!(input.read_u8());
+    let mut result = Vec::with_capacity(num_indexes as usize);
+    for _ in 0..num_indexes {
+        result.push(try!(IndexInfo::read(input)));
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_read_write_indexes() {
+        let indexes = vec![IndexInfo::new("IDX_A", false, vec![0]),
+                           IndexInfo::new("IDX_CB", true, vec![2, 1])];
+        let mut cursor = Cursor::new(Vec::new());
+        write_indexes(&mut cursor, &indexes).unwrap();
+
+        cursor.set_position(0);
+        assert_eq!(indexes, read_indexes(&mut cursor).unwrap());
+    }
+
+    #[test]
+    fn test_entries() {
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::VarChar { length: 10 }, "B", "FOO"),
+        ]).unwrap();
+        let index = IndexInfo::new("IDX", false, vec![1]);
+
+        let index_schema = index.get_schema(&schema).unwrap();
+        assert_eq!(2, index_schema.num_columns());
+        assert_eq!(ColumnType::FilePointer, index_schema[1].column_type);
+
+        let tuple = TupleLiteral::from_iter(vec![Literal::Int(3), Literal::from("three")]);
+        let pointer = Literal::FilePointer { page_no: 1, offset: 4 };
+        assert_eq!(TupleLiteral::from_iter(vec![Literal::from("three"), pointer.clone()]),
+                   index.get_entry(&tuple, pointer));
+    }
+}
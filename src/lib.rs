@@ -16,11 +16,13 @@ extern crate log;
 pub mod commands;
 pub mod expressions;
 pub mod functions;
+pub mod indexes;
 pub mod parser;
 pub mod queries;
 pub mod relations;
 pub mod storage;
 pub mod server;
+pub mod transactions;
 
 pub use relations::{Schema, SchemaError, ColumnInfo, ColumnName, ColumnType};
 pub use server::{Client, Server};
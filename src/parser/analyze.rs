@@ -0,0 +1,28 @@
+use ::commands::AnalyzeCommand;
+use ::parser::utils::*;
+
+named!(pub parse (&[u8]) -> Box<AnalyzeCommand>, do_parse!(
+    ws!(tag_no_case!("ANALYZE")) >>
+    table_names: separated_nonempty_list!(ws!(tag!(",")), dbobj_ident) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(AnalyzeCommand::new(table_names)))
+));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_analyze_parse() {
+        {
+            let (left, output) = parse(b"ANALYZE foo").unwrap();
+            assert_eq!((&b""[..], AnalyzeCommand::new(vec!["FOO".into()])), (left, *output));
+        }
+        {
+            let (left, output) = parse(b"analyze foo , bar").unwrap();
+            assert_eq!((&b""[..], AnalyzeCommand::new(vec!["FOO".into(), "BAR".into()])),
+                       (left, *output));
+        }
+        assert!(!parse(b"ANALYZE").is_done());
+    }
+}
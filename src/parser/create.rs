@@ -11,6 +11,8 @@ named!(col_type_len (&[u8]) -> u16, do_parse!(
 
 named!(col_type (&[u8]) -> ColumnType, do_parse!(
     result: alt_complete!(
+// Intervals, which must come before INT
+        map!(tag_no_case!("INTERVAL"), |_| ColumnType::Interval) |
 // Integers
         map!(tag_no_case!("TINYINT"), |_| ColumnType::TinyInt) |
         map!(tag_no_case!("SMALLINT"), |_| ColumnType::SmallInt) |
@@ -74,6 +76,15 @@ named!(table_col_decls (&[u8]) -> Vec<(String, ColumnType)>, do_parse!(
     (decls)
 ));
 
+named!(table_order_by (&[u8]) -> Vec<String>, do_parse!(
+    ws!(tag_no_case!("ORDER")) >>
+    ws!(tag_no_case!("BY")) >>
+    tag!("(") >>
+    cols: separated_nonempty_list!(tag!(","), ws!(ident)) >>
+    tag!(")") >>
+    (cols)
+));
+
 named!(pub create_table (&[u8]) -> Box<CreateCommand>, do_parse!(
     ws!(tag_no_case!("CREATE")) >>
     temp: opt!(ws!(tag_no_case!("TEMPORARY"))) >>
@@ -86,6 +97,7 @@ named!(pub create_table (&[u8]) -> Box<CreateCommand>, do_parse!(
     )) >>
     table_name: ws!(dbobj_ident) >>
     decls: table_col_decls >>
+    order_by: opt!(complete!(table_order_by)) >>
     alt!(eof!() | peek!(tag!(";"))) >>
     ({
         Box::new(CreateCommand::Table {
@@ -93,6 +105,28 @@ named!(pub create_table (&[u8]) -> Box<CreateCommand>, do_parse!(
             temp: temp.is_some(),
             if_not_exists: if_not_exists.is_some(),
             decls: decls,
+            order_by: order_by.unwrap_or_default(),
+        })
+    })
+));
+
+named!(pub create_index (&[u8]) -> Box<CreateCommand>, do_parse!(
+    ws!(tag_no_case!("CREATE")) >>
+    unique: opt!(ws!(tag_no_case!("UNIQUE"))) >>
+    ws!(tag_no_case!("INDEX")) >>
+    index_name: ws!(dbobj_ident) >>
+    ws!(tag_no_case!("ON")) >>
+    table_name: ws!(dbobj_ident) >>
+    tag!("(") >>
+    columns: separated_nonempty_list!(tag!(","), ws!(ident)) >>
+    tag!(")") >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    ({
+        Box::new(CreateCommand::Index {
+            name: index_name,
+            table: table_name,
+            columns: columns,
+            unique: unique.is_some(),
         })
     })
 ));
@@ -106,7 +140,7 @@ named!(pub create_view (&[u8]) -> Box<CreateCommand>, do_parse!(
     })
 ));
 
-named!(pub parse (&[u8]) -> Box<CreateCommand>, alt_complete!(create_table | create_view));
+named!(pub parse (&[u8]) -> Box<CreateCommand>, alt_complete!(create_table | create_index | create_view));
 
 #[cfg(test)]
 mod tests {
@@ -140,6 +174,7 @@ mod tests {
         assert_eq!(Done(&b""[..], ColumnType::DateTime), col_type(b"datetime"));
         assert_eq!(Done(&b""[..], ColumnType::Time), col_type(b"Time"));
         assert_eq!(Done(&b""[..], ColumnType::Timestamp), col_type(b"TIMEstamp"));
+        assert_eq!(Done(&b""[..], ColumnType::Interval), col_type(b"interval"));
     }
 
     #[test]
@@ -171,6 +206,7 @@ mod tests {
                     ("C".into(), ColumnType::VarChar { length: 50 }),
                     ("D".into(), ColumnType::Numeric { precision: 9, scale: 4 })
                 ],
+                order_by: vec![],
             };
             let (left, output) = create_table(decl).unwrap();
             assert_eq!((&b""[..], expected), (left, *output));
@@ -186,6 +222,7 @@ mod tests {
                 decls: vec![
                     ("A".into(), ColumnType::Integer)
                 ],
+                order_by: vec![],
             };
             let (left, output) = create_table(decl).unwrap();
             assert_eq!((&b""[..], expected), (left, *output));
@@ -199,10 +236,52 @@ mod tests {
                 decls: vec![
                     ("A".into(), ColumnType::Integer)
                 ],
+                order_by: vec![],
+            };
+            let (left, output) = create_table(decl).unwrap();
+            assert_eq!((&b""[..], expected), (left, *output));
+        }
+        {
+            let decl = b"CREATE TABLE foo (a INTEGER, b VARCHAR(20), c INTEGER) ORDER BY (a, b)";
+            let expected = CreateCommand::Table {
+                name: "FOO".into(),
+                temp: false,
+                if_not_exists: false,
+                decls: vec![
+                    ("A".into(), ColumnType::Integer),
+                    ("B".into(), ColumnType::VarChar { length: 20 }),
+                    ("C".into(), ColumnType::Integer)
+                ],
+                order_by: vec!["A".into(), "B".into()],
             };
             let (left, output) = create_table(decl).unwrap();
             assert_eq!((&b""[..], expected), (left, *output));
         }
         assert!(create_table(b"CREATE TABLE (a INTEGER)").is_err());
     }
+
+    #[test]
+    fn test_create_index() {
+        {
+            let expected = CreateCommand::Index {
+                name: "IDX".into(),
+                table: "FOO".into(),
+                columns: vec!["A".into(), "B".into()],
+                unique: false,
+            };
+            let (left, output) = create_index(b"CREATE INDEX idx ON foo (a, b)").unwrap();
+            assert_eq!((&b""[..], expected), (left, *output));
+        }
+        {
+            let expected = CreateCommand::Index {
+                name: "IDX".into(),
+                table: "FOO".into(),
+                columns: vec!["A".into()],
+                unique: true,
+            };
+            let (left, output) = create_index(b"CREATE UNIQUE INDEX idx ON foo (a);").unwrap();
+            assert_eq!((&b";"[..], expected), (left, *output));
+        }
+        assert!(create_index(b"CREATE INDEX idx ON foo ()").is_err());
+    }
 }
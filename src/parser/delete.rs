@@ -0,0 +1,32 @@
+use ::commands::DeleteCommand;
+use ::parser::expression::expression;
+use ::parser::utils::*;
+
+named!(pub parse (&[u8]) -> Box<DeleteCommand>, do_parse!(
+    ws!(tag_no_case!("DELETE")) >>
+    ws!(tag_no_case!("FROM")) >>
+    table_name: ws!(dbobj_ident) >>
+    where_expr: opt!(complete!(preceded!(ws!(tag_no_case!("WHERE")), ws!(expression)))) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(DeleteCommand::new(table_name, where_expr)))
+));
+
+#[cfg(test)]
+mod tests {
+    use nom::IResult::*;
+
+    use super::*;
+    use ::expressions::{CompareType, Expression};
+
+    #[test]
+    fn test_parse() {
+        assert_eq!(Done(&[][..], Box::new(DeleteCommand::new("FOO".into(), None))),
+                   parse(b"DELETE FROM foo"));
+        let predicate = Expression::Compare(Box::new(Expression::ColumnValue((None, Some("A".into())))),
+                                            CompareType::GreaterThan,
+                                            Box::new(Expression::Int(3)));
+        assert_eq!(Done(&b";"[..], Box::new(DeleteCommand::new("FOO".into(), Some(predicate)))),
+                   parse(b"DELETE  FROM foo WHERE a > 3;"));
+        assert!(parse(b"DELETE foo").is_err());
+    }
+}
@@ -1,14 +1,26 @@
 use ::commands::DropCommand;
 use ::parser::utils::*;
 
-named!(pub parse (&[u8]) -> Box<DropCommand>, do_parse!(
+named!(drop_table (&[u8]) -> Box<DropCommand>, do_parse!(
     ws!(tag_no_case!("DROP")) >>
-    result: ws!(tag_no_case!("TABLE")) >>
+    ws!(tag_no_case!("TABLE")) >>
     table_name: ws!(dbobj_ident) >>
     alt!(eof!() | peek!(tag!(";"))) >>
     (Box::new(DropCommand::Table(table_name)))
 ));
 
+named!(drop_index (&[u8]) -> Box<DropCommand>, do_parse!(
+    ws!(tag_no_case!("DROP")) >>
+    ws!(tag_no_case!("INDEX")) >>
+    index_name: ws!(dbobj_ident) >>
+    ws!(tag_no_case!("ON")) >>
+    table_name: ws!(dbobj_ident) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(DropCommand::Index { name: index_name, table: table_name }))
+));
+
+named!(pub parse (&[u8]) -> Box<DropCommand>, alt!(drop_table | drop_index));
+
 #[cfg(test)]
 mod tests {
     use nom::IResult::*;
@@ -22,6 +34,11 @@ mod tests {
             let (left, output) = parse(b"DROP TABLE foo").unwrap();
             assert_eq!((&b""[..], DropCommand::Table("FOO".into())), (left, *output));
         }
+        {
+            let (left, output) = parse(b"DROP INDEX idx ON foo").unwrap();
+            assert_eq!((&b""[..], DropCommand::Index { name: "IDX".into(), table: "FOO".into() }),
+                       (left, *output));
+        }
         assert_eq!(Incomplete(Needed::Size(11)), parse(b"DROP TABLE"));
     }
 }
@@ -1,23 +1,103 @@
-use ::expressions::{ArithmeticType, Expression};
+use ::expressions::{ArithmeticType, Expression, FrameBound, WindowFrame, WindowSpec};
 use ::parser::literal::literal;
-use ::parser::select::select_clause;
+use ::parser::select::{order_by, select_clause};
 use ::parser::utils::*;
 
+named!(over_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("OVER"), keyword_end));
+
+named!(frame_offset (&[u8]) -> usize, map!(ws!(digit_u32), |n| n as usize));
+
+named!(frame_bound (&[u8]) -> FrameBound, alt_complete!(
+    do_parse!(
+        ws!(tag_no_case!("UNBOUNDED")) >>
+        bound: alt_complete!(
+            tag_no_case!("PRECEDING") => { |_| FrameBound::UnboundedPreceding } |
+            tag_no_case!("FOLLOWING") => { |_| FrameBound::UnboundedFollowing }
+        ) >>
+        (bound)
+    ) |
+    do_parse!(
+        ws!(tag_no_case!("CURRENT")) >>
+        tag_no_case!("ROW") >>
+        (FrameBound::CurrentRow)
+    ) |
+    do_parse!(
+        n: frame_offset >>
+        bound: alt_complete!(
+            tag_no_case!("PRECEDING") => { |_| FrameBound::Preceding(n) } |
+            tag_no_case!("FOLLOWING") => { |_| FrameBound::Following(n) }
+        ) >>
+        (bound)
+    )
+));
+
+// A frame given by a single bound ends at the current row.
+named!(window_frame (&[u8]) -> WindowFrame, do_parse!(
+    ws!(tag_no_case!("ROWS")) >>
+    frame: alt_complete!(
+        do_parse!(
+            ws!(tag_no_case!("BETWEEN")) >>
+            start: frame_bound >>
+            ws!(tag_no_case!("AND")) >>
+            end: frame_bound >>
+            (WindowFrame::new(start, end))
+        ) |
+        map!(frame_bound, |start| WindowFrame::new(start, FrameBound::CurrentRow))
+    ) >>
+    (frame)
+));
+
+named!(window_spec (&[u8]) -> WindowSpec, do_parse!(
+    ws!(tag!("(")) >>
+    partition_by: opt!(complete!(do_parse!(
+        ws!(tag_no_case!("PARTITION")) >>
+        ws!(tag_no_case!("BY")) >>
+        exprs: separated_nonempty_list!(ws!(tag!(",")), expression) >>
+        (exprs)
+    ))) >>
+    order_by: opt!(complete!(order_by)) >>
+    frame: opt!(complete!(window_frame)) >>
+    ws!(tag!(")")) >>
+    (WindowSpec::new(partition_by.unwrap_or(vec![]), order_by.unwrap_or(vec![]), frame))
+));
+
 named!(function_call (&[u8]) -> Expression, do_parse!(
     name: dbobj_ident >>
     tag!("(") >>
     distinct: opt!(ws!(tag_no_case!("DISTINCT"))) >>
     args: separated_list!(ws!(tag!(",")), expression) >>
     tag!(")") >>
-    (Expression::Function {
-        name: name,
-        distinct: distinct.is_some(),
-        args: args,
+    window: opt!(complete!(preceded!(ws!(over_keyword), window_spec))) >>
+    ({
+        match window {
+            Some(window) => Expression::WindowFunction {
+                name: name,
+                distinct: distinct.is_some(),
+                args: args,
+                window: Box::new(window),
+            },
+            None => Expression::Function {
+                name: name,
+                distinct: distinct.is_some(),
+                args: args,
+            },
+        }
     })
 ));
 
 named!(subquery_expr (&[u8]) -> Expression, map!(select_clause, |clause| Expression::Subquery(Box::new(clause))));
 
+named!(exists_expr (&[u8]) -> Expression, do_parse!(
+    ws!(tag_no_case!("EXISTS")) >>
+    ws!(tag!("(")) >>
+    subquery: select_clause >>
+    ws!(tag!(")")) >>
+    (Expression::Exists {
+        subquery: Box::new(subquery),
+        negated: false,
+    })
+));
+
 named!(base_expr (&[u8]) -> Expression, alt_complete!(
     function_call |
     literal_expr |
@@ -99,24 +179,51 @@ named!(relational_expr (&[u8]) -> Expression, alt_complete!(
             }
         })
     ) |
+    do_parse!(
+        e: additive_expr >>
+        negated: opt!(ws!(tag_no_case!("NOT"))) >>
+        ws!(tag_no_case!("IN")) >>
+        ws!(tag!("(")) >>
+        subquery: select_clause >>
+        ws!(tag!(")")) >>
+        (Expression::InSubquery {
+            expr: Box::new(e),
+            subquery: Box::new(subquery),
+            negated: negated.is_some(),
+        })
+    ) |
 // TODO: LIKE, etc.
     additive_expr
 ));
 
+named!(not_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("NOT"), keyword_end));
+
 named!(logical_not_expr (&[u8]) -> Expression, do_parse!(
-    not: opt!(tag_no_case!("NOT")) >>
-    inner: relational_expr >> // TODO: Handle exists_expr
+    not: opt!(ws!(not_keyword)) >>
+    inner: alt_complete!(exists_expr | relational_expr) >>
     ({
         if not.is_some() {
-            Expression::NOT(Box::new(inner))
+            // Negated subquery tests are the same as NOT EXISTS and NOT IN.
+            match inner {
+                Expression::Exists { subquery, negated } => {
+                    Expression::Exists { subquery: subquery, negated: !negated }
+                }
+                Expression::InSubquery { expr, subquery, negated } => {
+                    Expression::InSubquery { expr: expr, subquery: subquery, negated: !negated }
+                }
+                inner => Expression::NOT(Box::new(inner)),
+            }
         } else {
             inner
         }
     })
 ));
 
+named!(and_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("AND"), keyword_end));
+named!(or_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("OR"), keyword_end));
+
 named!(logical_and_expr (&[u8]) -> Expression, do_parse!(
-    clauses: separated_nonempty_list!(ws!(tag_no_case!("AND")), logical_not_expr) >>
+    clauses: separated_nonempty_list!(ws!(and_keyword), logical_not_expr) >>
     ({
         let has_plural = clauses.len() > 1;
         if has_plural {
@@ -128,7 +235,7 @@ named!(logical_and_expr (&[u8]) -> Expression, do_parse!(
 ));
 
 named!(logical_or_expr (&[u8]) -> Expression, do_parse!(
-    clauses: separated_nonempty_list!(ws!(tag_no_case!("OR")), logical_and_expr) >>
+    clauses: separated_nonempty_list!(ws!(or_keyword), logical_and_expr) >>
     ({
         let has_plural = clauses.len() > 1;
         if has_plural {
@@ -146,7 +253,8 @@ mod tests {
     use nom::IResult::*;
 
     use super::*;
-    use ::expressions::{ArithmeticType, CompareType, Expression, FromClause, SelectClause, SelectValue};
+    use ::expressions::{ArithmeticType, CompareType, Expression, FrameBound, FromClause, OrderByExpression,
+                        SelectClause, SelectValue, WindowFrame, WindowSpec};
 
     #[test]
     fn test_base_expr() {
@@ -175,6 +283,34 @@ mod tests {
         assert_eq!(Done(&[][..], Expression::OR(vec![Expression::Int(3), Expression::AND(vec![Expression::Int(4), Expression::Int(5)])])), logical_or_expr(b"3 OR 4 AND 5"));
     }
 
+    #[test]
+    fn test_subquery_tests() {
+        let clause = SelectClause::new(FromClause::base_table("BAR".into(), None),
+                                       false,
+                                       vec![SelectValue::Expression {
+                                           expression: Expression::ColumnValue((None,
+                                                                                Some("FOO".into()))),
+                                           alias: None
+                                       }],
+                                       None, None, None, None, None);
+        let a = Box::new(Expression::ColumnValue((None, Some("A".into()))));
+
+        assert_eq!(Done(&[][..], Expression::Exists { subquery: Box::new(clause.clone()), negated: false }),
+                   logical_not_expr(b"EXISTS (SELECT foo FROM bar)"));
+        assert_eq!(Done(&[][..], Expression::Exists { subquery: Box::new(clause.clone()), negated: true }),
+                   logical_not_expr(b"NOT EXISTS (SELECT foo FROM bar)"));
+        assert_eq!(Done(&[][..], Expression::InSubquery { expr: a.clone(), subquery: Box::new(clause.clone()), negated: false }),
+                   logical_not_expr(b"a IN (SELECT foo FROM bar)"));
+        assert_eq!(Done(&[][..], Expression::InSubquery { expr: a.clone(), subquery: Box::new(clause.clone()), negated: true }),
+                   logical_not_expr(b"a NOT IN (SELECT foo FROM bar)"));
+        assert_eq!(Done(&[][..], Expression::InSubquery { expr: a.clone(), subquery: Box::new(clause.clone()), negated: true }),
+                   logical_not_expr(b"NOT a IN (SELECT foo FROM bar)"));
+
+        // Columns whose names start with keywords are still columns.
+        assert_eq!(Done(&[][..], Expression::ColumnValue((None, Some("NOTES".into())))), logical_not_expr(b"notes"));
+        assert_eq!(Done(&[][..], Expression::ColumnValue((None, Some("EXISTSFOO".into())))), logical_not_expr(b"existsfoo"));
+    }
+
     #[test]
     fn test_arithmetic_exprs() {
         let three = Box::new(Expression::Int(3));
@@ -253,4 +389,57 @@ mod tests {
             args: vec![Expression::ColumnValue((Some("FOO".into()), None))]
         }), function_call(b"count(foo.*)"));
     }
+
+    #[test]
+    fn test_window_function_call() {
+        let column = |name: &str| Expression::ColumnValue((None, Some(name.into())));
+        assert_eq!(Done(&[][..], Expression::WindowFunction {
+            name: "ROW_NUMBER".into(),
+            distinct: false,
+            args: vec![],
+            window: Box::new(WindowSpec::new(vec![], vec![], None)),
+        }), function_call(b"row_number() OVER ()"));
+        assert_eq!(Done(&[][..], Expression::WindowFunction {
+            name: "RANK".into(),
+            distinct: false,
+            args: vec![],
+            window: Box::new(WindowSpec::new(vec![column("A"), column("B")],
+                                             vec![OrderByExpression::new(column("C"), false, None)],
+                                             None)),
+        }), function_call(b"rank() over (partition by a, b order by c desc)"));
+        assert_eq!(Done(&[][..], Expression::WindowFunction {
+            name: "SUM".into(),
+            distinct: false,
+            args: vec![column("A")],
+            window: Box::new(WindowSpec::new(vec![],
+                                             vec![OrderByExpression::new(column("B"), true, None)],
+                                             Some(WindowFrame::new(FrameBound::UnboundedPreceding,
+                                                                   FrameBound::CurrentRow)))),
+        }), function_call(b"SUM(a) OVER (ORDER BY b ROWS BETWEEN UNBOUNDED PRECEDING AND CURRENT ROW)"));
+        assert_eq!(Done(&[][..], Expression::WindowFunction {
+            name: "AVG".into(),
+            distinct: false,
+            args: vec![column("A")],
+            window: Box::new(WindowSpec::new(vec![column("B")],
+                                             vec![],
+                                             Some(WindowFrame::new(FrameBound::Preceding(2),
+                                                                   FrameBound::Following(1))))),
+        }), function_call(b"AVG(a) OVER (PARTITION BY b ROWS BETWEEN 2 PRECEDING AND 1 FOLLOWING)"));
+        assert_eq!(Done(&[][..], Expression::WindowFunction {
+            name: "COUNT".into(),
+            distinct: false,
+            args: vec![Expression::ColumnValue((None, None))],
+            window: Box::new(WindowSpec::new(vec![],
+                                             vec![],
+                                             Some(WindowFrame::new(FrameBound::Preceding(3),
+                                                                   FrameBound::CurrentRow)))),
+        }), function_call(b"COUNT(*) OVER (ROWS 3 PRECEDING)"));
+
+        // A column whose name starts with OVER isn't a window.
+        assert_eq!(Done(&b" overall"[..], Expression::Function {
+            name: "ABS".into(),
+            distinct: false,
+            args: vec![Expression::Int(5)],
+        }), function_call(b"ABS(5) overall"));
+    }
 }
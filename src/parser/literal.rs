@@ -2,37 +2,120 @@ use std::str::{self, FromStr};
 
 use nom::digit;
 
-use ::expressions::Literal;
+use ::expressions::{Date, Decimal, ExpressionError, Interval, Literal, Time, Timestamp};
 
-named!(string_literal (&[u8]) -> Literal, do_parse!(
+named!(quoted_string (&[u8]) -> String, do_parse!(
     tag!("'") >>
     str: map!(many0!(none_of!("'\r\n")), |chars: Vec<char>| {
         let result: String = chars.into_iter().collect();
         result
     }) >>
     tag!("'") >>
-    (Literal::String(str))
+    (str)
 ));
 
+named!(string_literal (&[u8]) -> Literal, map!(quoted_string, Literal::String));
+
+fn parse_hex(value: String) -> Result<Vec<u8>, ExpressionError> {
+    let invalid = || ExpressionError::InvalidLiteral("X".into(), value.clone());
+    if value.len() % 2 != 0 {
+        return Err(invalid());
+    }
+    let mut bytes = Vec::with_capacity(value.len() / 2);
+    for i in 0..value.len() / 2 {
+        let byte = try!(value.get(2 * i..2 * i + 2).and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            .ok_or_else(&invalid));
+        bytes.push(byte);
+    }
+    Ok(bytes)
+}
+
+// A byte string is written as hex digits in a string prefixed with `X`, e.g. `X'DEADBEEF'`.
+named!(bytes_literal (&[u8]) -> Literal, do_parse!(
+    tag_no_case!("X") >>
+    value: quoted_string >>
+    bytes: expr_res!(parse_hex(value)) >>
+    (Literal::Bytes(bytes))
+));
+
+// An exact decimal is written as a typed string, e.g. `DECIMAL '19.99'`.
+named!(decimal_literal (&[u8]) -> Literal, do_parse!(
+    alt_complete!(tag_no_case!("DECIMAL") | tag_no_case!("NUMERIC")) >>
+    value: map_res!(ws!(quoted_string), |s: String| s.parse::<Decimal>()) >>
+    (Literal::Decimal(value))
+));
+
+// Dates and times are also written as typed strings, e.g. `DATE '2024-01-31'`. `DATETIME` values
+// are timestamps with whole seconds, so both keywords give a timestamp literal.
+named!(temporal_literal (&[u8]) -> Literal, alt_complete!(
+    do_parse!(
+        alt_complete!(tag_no_case!("TIMESTAMP") | tag_no_case!("DATETIME")) >>
+        value: map_res!(ws!(quoted_string), |s: String| s.parse::<Timestamp>()) >>
+        (Literal::Timestamp(value))
+    ) |
+    do_parse!(
+        tag_no_case!("DATE") >>
+        value: map_res!(ws!(quoted_string), |s: String| s.parse::<Date>()) >>
+        (Literal::Date(value))
+    ) |
+    do_parse!(
+        tag_no_case!("TIME") >>
+        value: map_res!(ws!(quoted_string), |s: String| s.parse::<Time>()) >>
+        (Literal::Time(value))
+    )
+));
+
+fn parse_interval(value: String, unit: Option<&[u8]>) -> Result<Interval, ExpressionError> {
+    match unit {
+        Some(unit) => {
+            let unit = str::from_utf8(unit).unwrap_or("");
+            value.trim().parse::<i64>().ok()
+                .and_then(|amount| Interval::from_unit(amount, unit))
+                .ok_or_else(|| ExpressionError::InvalidLiteral("INTERVAL".into(), value.clone()))
+        }
+        None => value.parse(),
+    }
+}
+
+// An interval is either a number of some unit, e.g. `INTERVAL '3' DAY`, or a list of amounts and
+// units, e.g. `INTERVAL '1 year 2 months'`.
+named!(interval_literal (&[u8]) -> Literal, do_parse!(
+    tag_no_case!("INTERVAL") >>
+    value: ws!(quoted_string) >>
+    unit: opt!(complete!(alt_complete!(
+        tag_no_case!("YEAR") | tag_no_case!("MONTH") | tag_no_case!("WEEK") | tag_no_case!("DAY") |
+        tag_no_case!("HOUR") | tag_no_case!("MINUTE") | tag_no_case!("SECOND")
+    ))) >>
+    interval: expr_res!(parse_interval(value, unit)) >>
+    (Literal::Interval(interval))
+));
+
+fn parse_dotted_number(ipart: Option<&str>, dpart: Option<&str>, float: bool)
+                       -> Result<Literal, ExpressionError> {
+    let ipart = ipart.unwrap_or("0");
+    let string = match dpart {
+        Some(d) => format!("{}.{}", ipart, d),
+        None => ipart.into()
+    };
+    if float {
+        Ok(Literal::Float(f32::from_str(&string).unwrap()))
+    } else {
+        string.parse::<Decimal>().map(Literal::Decimal)
+    }
+}
+
+// A number with a decimal point is an exact decimal, like in standard SQL, so that e.g. `0.1 + 0.2`
+// is exactly `0.3`. A number with too many digits for a decimal is rejected rather than rounded,
+// and an `F` suffix makes the number a float.
 named!(num_literal (&[u8]) -> Literal, alt_complete!(
-// Floats or Doubles
+// Floats or Decimals
     do_parse!(
         ipart: opt!(map_res!(digit, str::from_utf8)) >>
         tag!(".") >>
         dpart: opt!(map_res!(digit, str::from_utf8)) >>
         float: opt!(complete!(tag_no_case!("F"))) >>
-        ({
-            let ipart = ipart.unwrap_or("0");
-            let string = match dpart {
-                Some(d) => format!("{}.{}", ipart, d),
-                None => ipart.into()
-            };
-            if float.is_some() {
-                Literal::Float(f32::from_str(&string).unwrap())
-            } else {
-                Literal::Double(f64::from_str(&string).unwrap())
-            }
-        })
+        value: expr_res!(parse_dotted_number(ipart, dpart, float.is_some())) >>
+        (value)
     ) |
 // Int or Long
     do_parse!(
@@ -52,6 +135,10 @@ named!(pub literal (&[u8]) -> Literal, alt_complete!(
     map!(tag_no_case!("NULL"), |_| Literal::Null) |
     map!(tag_no_case!("TRUE"), |_| Literal::True) |
     map!(tag_no_case!("FALSE"), |_| Literal::False) |
+    decimal_literal |
+    temporal_literal |
+    interval_literal |
+    bytes_literal |
     string_literal |
     num_literal
 ));
@@ -64,12 +151,15 @@ mod tests {
 
     #[test]
     fn test_num_literal() {
-        assert_eq!(Done(&b""[..], Literal::Double(64.21)), num_literal(b"64.21"));
+        assert_eq!(Done(&b""[..], Literal::Decimal(Decimal::new(6421, 2))), num_literal(b"64.21"));
         assert_eq!(Done(&b""[..], Literal::Float(64.21)), num_literal(b"64.21f"));
         assert_eq!(Done(&b""[..], Literal::Float(0.0)), num_literal(b"0.f"));
         assert_eq!(Done(&b""[..], Literal::Float(0.0)), num_literal(b".f"));
         assert_eq!(Done(&b""[..], Literal::Float(0.34)), num_literal(b".34f"));
-        assert_eq!(Done(&b""[..], Literal::Double(0.33)), num_literal(b".33"));
+        assert_eq!(Done(&b""[..], Literal::Decimal(Decimal::new(33, 2))), num_literal(b".33"));
+        // Too many digits for a decimal: only the leading `0` can be parsed, as an integer.
+        assert_eq!(Done(&b".12345678901234567890123"[..], Literal::Int(0)),
+                   num_literal(b"0.12345678901234567890123"));
         assert_eq!(Done(&b""[..], Literal::Int(345)), num_literal(b"345"));
         assert_eq!(Done(&b""[..], Literal::Long(763)), num_literal(b"763L"));
     }
@@ -81,12 +171,57 @@ mod tests {
         assert!(string_literal(b"'\nfoo'").is_err());
     }
 
+    #[test]
+    fn test_bytes_literal() {
+        assert_eq!(Done(&b""[..], Literal::Bytes(vec![0xDE, 0xAD, 0x00, 0xff])), bytes_literal(b"X'DEad00fF'"));
+        assert_eq!(Done(&b""[..], Literal::Bytes(vec![])), bytes_literal(b"x''"));
+        assert!(bytes_literal(b"X'ABC'").is_err());
+        assert!(bytes_literal(b"X'GG'").is_err());
+        assert!(bytes_literal(b"X 'AB'").is_err());
+    }
+
+    #[test]
+    fn test_decimal_literal() {
+        assert_eq!(Done(&b""[..], Literal::Decimal(Decimal::new(1999, 2))), decimal_literal(b"DECIMAL '19.99'"));
+        assert_eq!(Done(&b""[..], Literal::Decimal(Decimal::new(-5, 0))), decimal_literal(b"numeric'-5'"));
+        assert!(decimal_literal(b"DECIMAL 'abc'").is_err());
+        assert!(decimal_literal(b"DECIMAL 19.99").is_err());
+    }
+
+    #[test]
+    fn test_temporal_literal() {
+        let date = Date::from_ymd(2024, 1, 31).unwrap();
+        let time = Time::from_hms_micro(12, 30, 0, 0).unwrap();
+        assert_eq!(Done(&b""[..], Literal::Date(date)), temporal_literal(b"DATE '2024-01-31'"));
+        assert_eq!(Done(&b""[..], Literal::Time(time)), temporal_literal(b"time'12:30'"));
+        assert_eq!(Done(&b""[..], Literal::Timestamp(Timestamp::new(date, time))),
+                   temporal_literal(b"TIMESTAMP '2024-01-31 12:30:00'"));
+        assert_eq!(Done(&b""[..], Literal::Timestamp(Timestamp::new(date, time))),
+                   temporal_literal(b"DateTime '2024-01-31T12:30'"));
+        assert!(temporal_literal(b"DATE '2024-02-30'").is_err());
+        assert!(temporal_literal(b"DATE").is_err());
+    }
+
+    #[test]
+    fn test_interval_literal() {
+        assert_eq!(Done(&b""[..], Literal::Interval(Interval::new(0, 3, 0))), interval_literal(b"INTERVAL '3' DAY"));
+        assert_eq!(Done(&b""[..], Literal::Interval(Interval::new(-24, 0, 0))),
+                   interval_literal(b"interval '-2' year"));
+        assert_eq!(Done(&b""[..], Literal::Interval(Interval::new(14, 0, 60 * 1000000))),
+                   interval_literal(b"INTERVAL '1 year 2 months 00:01:00'"));
+        assert!(interval_literal(b"INTERVAL '1 day' DAY").is_err());
+        assert!(interval_literal(b"INTERVAL '3'").is_err());
+    }
+
     #[test]
     fn test_literal() {
         assert_eq!(Done(&b""[..], Literal::Float(64.21)), literal(b"64.21f"));
         assert_eq!(Done(&b""[..], Literal::String("bar".into())), literal(b"'bar'"));
         assert_eq!(Done(&b""[..], Literal::Long(763)), literal(b"763L"));
         assert_eq!(Done(&b""[..], Literal::Null), literal(b"null"));
+        assert_eq!(Done(&b""[..], Literal::Decimal(Decimal::new(5, 1))), literal(b"Decimal '0.5'"));
+        assert_eq!(Done(&b""[..], Literal::Interval(Interval::new(1, 0, 0))), literal(b"INTERVAL '1' MONTH"));
+        assert_eq!(Done(&b""[..], Literal::Bytes(vec![0x01, 0xAB])), literal(b"X'01AB'"));
         assert_eq!(Done(&b""[..], Literal::True), literal(b"TRUE"));
         assert_eq!(Done(&b""[..], Literal::False), literal(b"FAlse"));
     }
@@ -1,20 +1,30 @@
 #![allow(missing_docs)]
 //! A module for parsing SQL statements used for NanoDB.
 
+pub mod analyze;
 pub mod select;
+pub mod set;
 pub mod utils;
 pub mod drop;
 pub mod show;
 pub mod create;
+pub mod delete;
 pub mod literal;
 pub mod insert;
+pub mod transaction;
+pub mod update;
 pub mod expression;
 
+use self::analyze::parse as analyze_parse;
 use self::create::parse as create_parse;
+use self::delete::parse as delete_parse;
 use self::drop::parse as drop_parse;
 use self::insert::parse as insert_parse;
 use self::select::parse as select_parse;
+use self::set::parse as set_parse;
 use self::show::parse as show_parse;
+use self::transaction::parse as transaction_parse;
+use self::update::parse as update_parse;
 
 use ::commands::Command;
 
@@ -28,7 +38,12 @@ named!(pub statements (&[u8]) -> Vec<Box<Command>>, separated_nonempty_list!(
                   map!(show_parse, as_boxed_command) |
                   map!(create_parse, as_boxed_command) |
                   map!(drop_parse, as_boxed_command) |
-                  map!(insert_parse, as_boxed_command))
+                  map!(insert_parse, as_boxed_command) |
+                  map!(delete_parse, as_boxed_command) |
+                  map!(update_parse, as_boxed_command) |
+                  map!(transaction_parse, as_boxed_command) |
+                  map!(analyze_parse, as_boxed_command) |
+                  map!(set_parse, as_boxed_command))
 ));
 
 #[cfg(test)]
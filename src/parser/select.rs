@@ -3,7 +3,8 @@
 use std::default::Default;
 
 use ::commands::SelectCommand;
-use ::expressions::{Expression, FromClause, JoinConditionType, JoinType, SelectClause, SelectValue};
+use ::expressions::{CommonTableExpression, Expression, FromClause, JoinConditionType, JoinType,
+                    OrderByExpression, SelectClause, SelectValue, SetOperator, WithClause};
 use ::parser::expression::expression;
 use ::parser::utils::*;
 
@@ -39,28 +40,40 @@ named!(select_values (&[u8]) -> Vec<SelectValue>,
     separated_nonempty_list!(tag!(","), ws!(select_value))
 );
 
-named!(limit (&[u8]) -> Option<i32>, do_parse!(
-    ws!(tag_no_case!("LIMIT")) >>
-    limit: signed_int >>
-    ({
-        if limit <= 0 {
-            None
-        } else {
-            Some(limit)
-        }
-    })
+named!(order_by_expr (&[u8]) -> OrderByExpression, do_parse!(
+    expr: ws!(expression) >>
+    ascending: opt!(complete!(ws!(alt_complete!(
+            tag_no_case!("ASC")     => { |_| true }
+        |   tag_no_case!("DESC")    => { |_| false }
+    )))) >>
+    nulls_first: opt!(complete!(do_parse!(
+        ws!(tag_no_case!("NULLS")) >>
+        first: ws!(alt_complete!(
+                tag_no_case!("FIRST")   => { |_| true }
+            |   tag_no_case!("LAST")    => { |_| false }
+        )) >>
+        (first)
+    ))) >>
+    (OrderByExpression::new(expr, ascending.unwrap_or(true), nulls_first))
 ));
 
-named!(offset (&[u8]) -> Option<i32>, do_parse!(
-    ws!(tag_no_case!("OFFSET")) >>
-    offset: signed_int >>
-    ({
-        if offset <= 0 {
-            None
-        } else {
-            Some(offset)
-        }
-    })
+named!(pub order_by (&[u8]) -> Vec<OrderByExpression>, do_parse!(
+    ws!(tag_no_case!("ORDER")) >>
+    ws!(tag_no_case!("BY")) >>
+    exprs: separated_nonempty_list!(ws!(tag!(",")), order_by_expr) >>
+    (exprs)
+));
+
+/// `LIMIT 0` is a valid limit that produces no rows. Negative limits and offsets don't parse.
+named!(limit (&[u8]) -> u32, preceded!(ws!(tag_no_case!("LIMIT")), digit_u32));
+
+named!(offset (&[u8]) -> u32, preceded!(ws!(tag_no_case!("OFFSET")), digit_u32));
+
+named!(column_name_list (&[u8]) -> Vec<String>, do_parse!(
+    ws!(tag!("(")) >>
+    names: separated_nonempty_list!(ws!(tag!(",")), ws!(dbobj_ident)) >>
+    ws!(tag!(")")) >>
+    (names)
 ));
 
 named!(from_expr (&[u8]) -> FromClause, alt!(
@@ -69,6 +82,15 @@ named!(from_expr (&[u8]) -> FromClause, alt!(
         alias: opt!(complete!(preceded!(ws!(tag_no_case!("AS")), dbobj_ident))) >>
         (FromClause::base_table(name, alias))
     ) |
+    do_parse!(
+        ws!(tag!("(")) >>
+        subquery: select_clause >>
+        ws!(tag!(")")) >>
+        ws!(tag_no_case!("AS")) >>
+        alias: dbobj_ident >>
+        column_names: opt!(complete!(column_name_list)) >>
+        (FromClause::subquery(subquery, alias, column_names))
+    ) |
     do_parse!(
         ws!(tag!("(")) >>
         fc: from_clause >>
@@ -163,8 +185,8 @@ named!(from_clause (&[u8]) -> FromClause, do_parse!(
     })
 ));
 
-/// Parses a `SELECT` statement into a `SelectClause`.
-named!(pub select_clause (&[u8]) -> SelectClause, do_parse!(
+/// Parses a single `SELECT` statement, without any set operations, into a `SelectClause`.
+named!(simple_select_clause (&[u8]) -> SelectClause, do_parse!(
     ws!(tag_no_case!("SELECT")) >>
     distinct: opt!(ws!(alt!(
             tag_no_case!("ALL")         => { |_| false }
@@ -185,34 +207,136 @@ named!(pub select_clause (&[u8]) -> SelectClause, do_parse!(
             having: opt!(preceded!(ws!(tag_no_case!("HAVING")), expression)) >>
             (exprs, having)
         ))) >>
+        order_by: opt!(complete!(order_by)) >>
         limit: opt!(complete!(limit)) >>
         offset: opt!(complete!(offset)) >>
         ({
             if let Some((group_exprs, having)) = group_by {
-                (from_clause, where_expr, Some(group_exprs), having, limit, offset)
+                (from_clause, where_expr, Some(group_exprs), having, order_by, limit, offset)
             } else {
-                (from_clause, where_expr, None, None, limit, offset)
+                (from_clause, where_expr, None, None, order_by, limit, offset)
             }
         })
     ))) >>
     ({
-        if let Some((from_clause, where_expr, group_exprs, having, limit, offset)) = from {
-            SelectClause::new(from_clause, match distinct {
+        if let Some((from_clause, where_expr, group_exprs, having, order_by, limit, offset)) = from {
+            let mut clause = SelectClause::new(from_clause, match distinct {
                     Some(modifier) => modifier,
                     None => false
                 }, select_values,
-                limit.and_then(|v| v).map(|v| v as u32),
-                offset.and_then(|v| v).map(|v| v as u32),
+                limit,
+                offset,
                 where_expr,
                 group_exprs,
                 having
-            )
+            );
+            clause.order_by_exprs = order_by.unwrap_or(vec![]);
+            clause
         } else {
             SelectClause::scalar(select_values)
         }
     })
 ));
 
+named!(union_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("UNION"), keyword_end));
+named!(intersect_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("INTERSECT"), keyword_end));
+named!(except_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("EXCEPT"), keyword_end));
+named!(all_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("ALL"), keyword_end));
+
+named!(set_operator (&[u8]) -> (SetOperator, bool), do_parse!(
+    operator: ws!(alt_complete!(
+            union_keyword       => { |_| SetOperator::Union }
+        |   intersect_keyword   => { |_| SetOperator::Intersect }
+        |   except_keyword      => { |_| SetOperator::Except }
+    )) >>
+    all: opt!(complete!(ws!(all_keyword))) >>
+    ((operator, all.is_some()))
+));
+
+named!(with_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("WITH"), keyword_end));
+named!(recursive_keyword (&[u8]) -> &[u8], terminated!(tag_no_case!("RECURSIVE"), keyword_end));
+
+named!(common_table_expr (&[u8]) -> CommonTableExpression, do_parse!(
+    name: ws!(dbobj_ident) >>
+    column_names: opt!(complete!(column_name_list)) >>
+    ws!(tag_no_case!("AS")) >>
+    ws!(tag!("(")) >>
+    query: select_clause >>
+    ws!(tag!(")")) >>
+    (CommonTableExpression {
+        name: name,
+        column_names: column_names,
+        query: Box::new(query),
+    })
+));
+
+named!(with_clause (&[u8]) -> WithClause, do_parse!(
+    ws!(with_keyword) >>
+    recursive: opt!(complete!(ws!(recursive_keyword))) >>
+    tables: separated_nonempty_list!(ws!(tag!(",")), common_table_expr) >>
+    (WithClause {
+        recursive: recursive.is_some(),
+        tables: tables,
+    })
+));
+
+/// Combines the queries of a compound `SELECT` statement. `INTERSECT` takes precedence over `UNION`
+/// and `EXCEPT`, which are applied from left to right. The `ORDER BY`, `LIMIT` and `OFFSET` clauses
+/// at the end of the statement belong to the compound query rather than to its last query.
+fn make_compound_clause(first: SelectClause, rest: Vec<(SetOperator, bool, SelectClause)>) -> SelectClause {
+    if rest.is_empty() {
+        return first;
+    }
+
+    let mut operands = vec![first];
+    let mut operators = vec![];
+    for (operator, all, clause) in rest {
+        if operator == SetOperator::Intersect {
+            let left = operands.pop().unwrap();
+            operands.push(SelectClause::compound(operator, all, left, clause));
+        } else {
+            operators.push((operator, all));
+            operands.push(clause);
+        }
+    }
+
+    let (order_by_exprs, limit, offset) = {
+        let last = operands.last_mut().unwrap();
+        let last = match last.set_operation {
+            Some(ref mut operation) => &mut *operation.right,
+            None => last,
+        };
+        (::std::mem::replace(&mut last.order_by_exprs, vec![]), last.limit.take(), last.offset.take())
+    };
+
+    let mut operands = operands.into_iter();
+    let mut result = operands.next().unwrap();
+    for ((operator, all), right) in operators.into_iter().zip(operands) {
+        result = SelectClause::compound(operator, all, result, right);
+    }
+    result.order_by_exprs = order_by_exprs;
+    result.limit = limit;
+    result.offset = offset;
+    result
+}
+
+/// Parses a `SELECT` statement, which may start with a `WITH` clause and may combine several
+/// queries with `UNION`, `INTERSECT` and `EXCEPT`, into a `SelectClause`.
+named!(pub select_clause (&[u8]) -> SelectClause, do_parse!(
+    with: opt!(complete!(with_clause)) >>
+    first: simple_select_clause >>
+    rest: many0!(complete!(do_parse!(
+        operator: set_operator >>
+        clause: simple_select_clause >>
+        (operator.0, operator.1, clause)
+    ))) >>
+    ({
+        let mut clause = make_compound_clause(first, rest);
+        clause.with_clause = with;
+        clause
+    })
+));
+
 /// Parses a `SELECT` statement into a `SelectCommand`.
 named!(pub parse (&[u8]) -> Box<SelectCommand>, do_parse!(
     clause: select_clause >>
@@ -225,13 +349,22 @@ mod tests {
     use nom::IResult::*;
 
     use super::*;
-    use ::expressions::{Expression, FromClause, JoinConditionType, JoinType, SelectClause, SelectValue};
+    use ::expressions::{Expression, FromClause, JoinConditionType, JoinType, OrderByExpression, SelectClause,
+                        SelectValue};
 
     #[test]
     fn test_from_expr() {
         assert_eq!(Done(&b""[..], FromClause::base_table("FOO".into(), None)), from_expr(b"foo"));
         assert_eq!(Done(&b""[..], FromClause::base_table("FOO".into(), Some("BAR".into()))), from_expr(b"foo as bar"));
         assert_eq!(Done(&b" JOIN"[..], FromClause::base_table("FOO".into(), None)), from_expr(b"foo JOIN"));
+
+        let subquery = select_clause(b"SELECT a FROM foo").unwrap().1;
+        assert_eq!(Done(&b""[..], FromClause::subquery(subquery.clone(), "BAR".into(), None)),
+                   from_expr(b"(SELECT a FROM foo) AS bar"));
+        assert_eq!(Done(&b""[..], FromClause::subquery(subquery, "BAR".into(), Some(vec!["B".into()]))),
+                   from_expr(b"(SELECT a FROM foo) AS bar (b)"));
+        // The subquery's results must be given a name.
+        assert!(!from_expr(b"(SELECT a FROM foo)").is_done());
     }
 
     #[test]
@@ -249,12 +382,33 @@ mod tests {
         assert_eq!(Done(&b""[..], (JoinType::FullOuter, None)), join_type(b"FULL OUTER"));
     }
 
+    #[test]
+    fn test_order_by() {
+        let a: Expression = (None, Some("A".into())).into();
+        let b: Expression = (Some("FOO".into()), Some("B".into())).into();
+        assert_eq!(Done(&b""[..], OrderByExpression::new(a.clone(), true, Some(false))), order_by_expr(b"a"));
+        assert_eq!(Done(&b""[..], OrderByExpression::new(a.clone(), false, Some(true))), order_by_expr(b"a DESC"));
+        assert_eq!(Done(&b""[..], OrderByExpression::new(a.clone(), true, Some(true))),
+                   order_by_expr(b"a asc nulls first"));
+        assert_eq!(Done(&b""[..], vec![OrderByExpression::new(a.clone(), false, Some(false)),
+                                       OrderByExpression::new(b.clone(), true, None)]),
+                   order_by(b"ORDER BY a DESC NULLS LAST, foo.b"));
+
+        let (left, clause) = select_clause(b"SELECT * FROM foo WHERE a > 3 ORDER BY a DESC LIMIT 5").unwrap();
+        assert_eq!(&b""[..], left);
+        assert_eq!(vec![OrderByExpression::new(a.clone(), false, None)], clause.order_by_exprs);
+        assert_eq!(Some(5), clause.limit);
+    }
+
     #[test]
     fn test_limit() {
-        assert_eq!(Done(&b""[..], Some(10)), limit(b"LIMIT 10"));
-        assert_eq!(Done(&b""[..], Some(15)), limit(b"LIMIT   15"));
-        assert_eq!(Done(&b""[..], None), limit(b"LIMIT   0"));
-        assert_eq!(Done(&b""[..], None), limit(b"LIMIT   -5"));
+        assert_eq!(Done(&b""[..], 10), limit(b"LIMIT 10"));
+        assert_eq!(Done(&b""[..], 15), limit(b"LIMIT   15"));
+        assert_eq!(Done(&b""[..], 0), limit(b"LIMIT   0"));
+        assert!(limit(b"LIMIT   -5").is_err());
+        assert_eq!(Done(&b""[..], 0), offset(b"OFFSET 0"));
+        assert!(offset(b"OFFSET -1").is_err());
+        assert!(::parser::statements(b"SELECT * FROM foo LIMIT -5;").is_err());
     }
 
     #[test]
@@ -398,4 +552,77 @@ mod tests {
         // assert_eq!(Error(error_position!(ErrorKind::Alt, &b"4"[..])),
         // parse(b"SELECT * FROM   fo4"));
     }
+
+    #[test]
+    fn test_set_operations() {
+        let a = simple_select_clause(b"SELECT a FROM foo").unwrap().1;
+        let b = simple_select_clause(b"SELECT b FROM bar").unwrap().1;
+        let c = simple_select_clause(b"SELECT c FROM baz").unwrap().1;
+
+        assert_eq!(Done(&b""[..], SelectClause::compound(SetOperator::Union, false, a.clone(), b.clone())),
+                   select_clause(b"SELECT a FROM foo UNION SELECT b FROM bar"));
+        assert_eq!(Done(&b""[..], SelectClause::compound(SetOperator::Except, true, a.clone(), b.clone())),
+                   select_clause(b"SELECT a FROM foo except all SELECT b FROM bar"));
+
+        // UNION and EXCEPT are applied from left to right...
+        let expected = SelectClause::compound(SetOperator::Except, false,
+                                              SelectClause::compound(SetOperator::Union, true, a.clone(), b.clone()),
+                                              c.clone());
+        assert_eq!(Done(&b""[..], expected),
+                   select_clause(b"SELECT a FROM foo UNION ALL SELECT b FROM bar EXCEPT SELECT c FROM baz"));
+
+        // ...but INTERSECT takes precedence over them.
+        let expected = SelectClause::compound(SetOperator::Union, false, a.clone(),
+                                              SelectClause::compound(SetOperator::Intersect, false, b.clone(), c.clone()));
+        assert_eq!(Done(&b""[..], expected),
+                   select_clause(b"SELECT a FROM foo UNION SELECT b FROM bar INTERSECT SELECT c FROM baz"));
+
+        // The ORDER BY and LIMIT clauses at the end apply to the whole compound query.
+        let mut expected = SelectClause::compound(SetOperator::Intersect, false, a.clone(), b.clone());
+        expected.order_by_exprs = vec![OrderByExpression::new(Expression::ColumnValue((None, Some("A".into()))), true, None)];
+        expected.limit = Some(2);
+        assert_eq!(Done(&b""[..], expected),
+                   select_clause(b"SELECT a FROM foo INTERSECT SELECT b FROM bar ORDER BY a LIMIT 2"));
+
+        // A table can't be named after a set operator.
+        assert_eq!(Done(&b"UNIONS SELECT b FROM bar"[..], a.clone()),
+                   select_clause(b"SELECT a FROM foo UNIONS SELECT b FROM bar"));
+    }
+
+    #[test]
+    fn test_with_clause() {
+        let a = simple_select_clause(b"SELECT a FROM foo").unwrap().1;
+        let b = simple_select_clause(b"SELECT b FROM bar").unwrap().1;
+        let t = simple_select_clause(b"SELECT * FROM t").unwrap().1;
+
+        let mut expected = t.clone();
+        expected.with_clause = Some(WithClause {
+            recursive: false,
+            tables: vec![CommonTableExpression {
+                             name: "T".into(),
+                             column_names: None,
+                             query: Box::new(a.clone()),
+                         },
+                         CommonTableExpression {
+                             name: "U".into(),
+                             column_names: Some(vec!["C".into(), "D".into()]),
+                             query: Box::new(b.clone()),
+                         }],
+        });
+        assert_eq!(Done(&b""[..], expected),
+                   select_clause(b"WITH t AS (SELECT a FROM foo), u (c, d) AS (SELECT b FROM bar) SELECT * FROM t"));
+
+        let mut expected = SelectClause::compound(SetOperator::Union, true, t.clone(), t.clone());
+        expected.with_clause = Some(WithClause {
+            recursive: true,
+            tables: vec![CommonTableExpression {
+                             name: "T".into(),
+                             column_names: Some(vec!["A".into()]),
+                             query: Box::new(SelectClause::compound(SetOperator::Union, true, a.clone(), t.clone())),
+                         }],
+        });
+        assert_eq!(Done(&b""[..], expected),
+                   select_clause(b"WITH RECURSIVE t (a) AS (SELECT a FROM foo UNION ALL SELECT * FROM t) \
+                                   SELECT * FROM t UNION ALL SELECT * FROM t"));
+    }
 }
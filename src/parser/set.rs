@@ -0,0 +1,38 @@
+use ::commands::SetCommand;
+use ::expressions::Literal;
+use ::parser::literal::literal;
+use ::parser::utils::*;
+
+named!(pub parse (&[u8]) -> Box<SetCommand>, do_parse!(
+    ws!(tag_no_case!("SET")) >>
+    name: ident >>
+    ws!(alt_complete!(tag!("=") | tag_no_case!("TO"))) >>
+    value: alt_complete!(map!(ident, Literal::String) | literal) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(SetCommand::new(name, value)))
+));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_set_parse() {
+        {
+            let (left, output) = parse(b"SET planner = cost_based").unwrap();
+            assert_eq!((&b""[..], SetCommand::new("PLANNER".into(), Literal::String("COST_BASED".into()))),
+                       (left, *output));
+        }
+        {
+            let (left, output) = parse(b"set SORT_MEMORY to 1024;").unwrap();
+            assert_eq!((&b";"[..], SetCommand::new("SORT_MEMORY".into(), Literal::Int(1024))), (left, *output));
+        }
+        {
+            let (left, output) = parse(b"SET planner='simple'").unwrap();
+            assert_eq!((&b""[..], SetCommand::new("PLANNER".into(), Literal::String("simple".into()))),
+                       (left, *output));
+        }
+        assert!(!parse(b"SET planner").is_done());
+        assert!(!parse(b"SET = simple").is_done());
+    }
+}
@@ -0,0 +1,56 @@
+use ::commands::TransactionCommand;
+
+named!(begin (&[u8]) -> TransactionCommand, do_parse!(
+    alt_complete!(
+            do_parse!(ws!(tag_no_case!("BEGIN")) >> opt!(complete!(ws!(tag_no_case!("WORK")))) >> (()))
+        |   do_parse!(ws!(tag_no_case!("START")) >> ws!(tag_no_case!("TRANSACTION")) >> (()))
+    ) >>
+    (TransactionCommand::Begin)
+));
+
+named!(commit (&[u8]) -> TransactionCommand, do_parse!(
+    ws!(tag_no_case!("COMMIT")) >>
+    opt!(complete!(ws!(tag_no_case!("WORK")))) >>
+    (TransactionCommand::Commit)
+));
+
+named!(rollback (&[u8]) -> TransactionCommand, do_parse!(
+    ws!(tag_no_case!("ROLLBACK")) >>
+    opt!(complete!(ws!(tag_no_case!("WORK")))) >>
+    (TransactionCommand::Rollback)
+));
+
+named!(pub parse (&[u8]) -> Box<TransactionCommand>, do_parse!(
+    result: alt_complete!(begin | commit | rollback) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(result))
+));
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::commands::TransactionCommand;
+
+    #[test]
+    fn test_transaction() {
+        let cases: Vec<(&[u8], TransactionCommand)> = vec![
+            (b"BEGIN", TransactionCommand::Begin),
+            (b"begin work", TransactionCommand::Begin),
+            (b"START TRANSACTION", TransactionCommand::Begin),
+            (b"COMMIT", TransactionCommand::Commit),
+            (b"COMMIT WORK", TransactionCommand::Commit),
+            (b"ROLLBACK", TransactionCommand::Rollback),
+            (b"ROLLBACK  WORK", TransactionCommand::Rollback),
+        ];
+        for (sql, expected) in cases {
+            let (left, output) = parse(sql).unwrap();
+            assert_eq!((&b""[..], expected), (left, *output));
+        }
+        {
+            let (left, output) = parse(b"COMMIT; BEGIN").unwrap();
+            assert_eq!((&b"; BEGIN"[..], TransactionCommand::Commit), (left, *output));
+        }
+        assert!(parse(b"START").is_err());
+        assert!(parse(b"ROLLBACK WORKS").is_err());
+    }
+}
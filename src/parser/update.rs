@@ -0,0 +1,46 @@
+use ::commands::UpdateCommand;
+use ::expressions::Expression;
+use ::parser::expression::expression;
+use ::parser::utils::*;
+
+named!(update_value (&[u8]) -> (String, Expression), do_parse!(
+    column: ws!(dbobj_ident) >>
+    ws!(tag!("=")) >>
+    value: ws!(expression) >>
+    (column, value)
+));
+
+named!(pub parse (&[u8]) -> Box<UpdateCommand>, do_parse!(
+    ws!(tag_no_case!("UPDATE")) >>
+    table_name: ws!(dbobj_ident) >>
+    ws!(tag_no_case!("SET")) >>
+    values: separated_nonempty_list!(ws!(tag!(",")), update_value) >>
+    where_expr: opt!(complete!(preceded!(ws!(tag_no_case!("WHERE")), ws!(expression)))) >>
+    alt!(eof!() | peek!(tag!(";"))) >>
+    (Box::new(UpdateCommand::new(table_name, values, where_expr)))
+));
+
+#[cfg(test)]
+mod tests {
+    use nom::IResult::*;
+
+    use super::*;
+    use ::expressions::{ArithmeticType, CompareType};
+
+    #[test]
+    fn test_parse() {
+        let col = |name: &str| Box::new(Expression::ColumnValue((None, Some(name.into()))));
+        assert_eq!(Done(&[][..], Box::new(UpdateCommand::new("FOO".into(),
+                                                             vec![("A".into(), Expression::Int(3))],
+                                                             None))),
+                   parse(b"UPDATE foo SET a = 3"));
+        let values = vec![
+            ("A".into(), Expression::Arithmetic(col("A"), ArithmeticType::Plus, Box::new(Expression::Int(1)))),
+            ("B".into(), Expression::String("bar".into())),
+        ];
+        let predicate = Expression::Compare(col("A"), CompareType::LessThan, Box::new(Expression::Int(3)));
+        assert_eq!(Done(&b";"[..], Box::new(UpdateCommand::new("FOO".into(), values, Some(predicate)))),
+                   parse(b"UPDATE foo SET a = a + 1, b = 'bar' WHERE a < 3;"));
+        assert!(parse(b"UPDATE foo SET").is_incomplete() || parse(b"UPDATE foo SET").is_err());
+    }
+}
@@ -26,6 +26,10 @@ named!(pub ident (&[u8]) -> String, do_parse!(
     })
 ));
 
+/// Matches the end of a keyword, so that keywords aren't mistaken for the start of an identifier,
+/// e.g. the `OR` in `ORDER`.
+named!(pub keyword_end (&[u8]) -> &[u8], not!(alt!(alpha | digit | tag!("_"))));
+
 named!(pub quoted_ident (&[u8]) -> String, do_parse!(
     tag!("\"") >>
     head: map_res!(alt!(alpha | tag!("_")), str::from_utf8) >>
@@ -54,7 +54,7 @@ impl AggregateFunctionExtractor {
 impl ExpressionProcessor for AggregateFunctionExtractor {
     fn enter(&mut self, node: &Expression) -> Result<(), ExpressionError> {
         if let Expression::Function { ref name, .. } = *node {
-            let func = try!(DIRECTORY.get(name.as_ref()));
+            let func = try!(DIRECTORY.get(name.as_str()));
             if func.is_aggregate() {
                 if let Some(ref aggregate) = self.current_aggregate {
                     return Err(ExpressionError::NestedAggregateCall {
@@ -72,7 +72,7 @@ impl ExpressionProcessor for AggregateFunctionExtractor {
 
     fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
         if let Expression::Function { ref name, .. } = *node {
-            let func = try!(DIRECTORY.get(name.as_ref()));
+            let func = try!(DIRECTORY.get(name.as_str()));
             if func.is_aggregate() {
                 if self.current_aggregate != Some(node.clone()) {
                     // This would be a bug.
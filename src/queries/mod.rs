@@ -4,9 +4,14 @@
 mod aggregate_extractor;
 mod plan_nodes;
 mod planning;
+mod window_extractor;
 
-pub use self::plan_nodes::{NodeResult, FileScanNode, HashedGroupAggregateNode, NestedLoopJoinNode,
-                           PlanNode, ProjectNode, RenameNode};
-pub use self::planning::{PlanError, PlanResult, Planner, SimplePlanner, make_simple_select,
-                         get_plan_results};
-pub use self::aggregate_extractor::AggregateFunctionExtractor;
\ No newline at end of file
+pub use self::plan_nodes::{NodeResult, FileScanNode, HashedDistinctNode, HashedGroupAggregateNode,
+                           HashedSetOperationNode, IndexBound, IndexScanNode, LimitOffsetNode,
+                           NestedLoopJoinNode, PlanNode, ProjectNode, RecursiveUnionNode, RenameNode,
+                           SimpleFilterNode, SortedDistinctNode, SortNode, UnionNode, WindowNode,
+                           WorkingTable, WorkingTableScanNode, DEFAULT_SORT_MEMORY, get_group_by_column_name};
+pub use self::planning::{CostBasedPlanner, PlanCost, PlanError, PlanResult, Planner, PlannerType,
+                         SimplePlanner, make_simple_select, get_plan_results};
+pub use self::aggregate_extractor::AggregateFunctionExtractor;
+pub use self::window_extractor::WindowFunctionExtractor;
\ No newline at end of file
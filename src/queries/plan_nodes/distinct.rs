@@ -0,0 +1,204 @@
+//! This module provides the duplicate-elimination plan nodes used for `SELECT DISTINCT`.
+//!
+//! There are two variants. The [`HashedDistinctNode`] remembers every distinct tuple it has
+//! produced in a hash set, and produces tuples in the order its child does. The
+//! [`SortedDistinctNode`] sorts its child's tuples, which spills to disk when they don't fit in
+//! memory, and then only has to compare each tuple with the one before it. Its output is sorted on
+//! all of the columns.
+//!
+//! [`HashedDistinctNode`]: struct.HashedDistinctNode.html
+//! [`SortedDistinctNode`]: struct.SortedDistinctNode.html
+
+use std::collections::HashSet;
+
+use ::Schema;
+use ::expressions::{Environment, Expression, OrderByExpression};
+use ::queries::plan_nodes::{PlanNode, SortNode, DEFAULT_SORT_MEMORY};
+use ::queries::planning::PlanResult;
+use ::storage::{FileManager, Tuple, TupleLiteral};
+
+/// A plan node that removes duplicate tuples by keeping a hash set of the tuples produced so far.
+/// The first occurrence of each tuple is produced, so the order of the child is kept.
+pub struct HashedDistinctNode<'a> {
+    child: Box<PlanNode + 'a>,
+    seen: HashSet<TupleLiteral>,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl<'a> HashedDistinctNode<'a> {
+    /// Instantiate a new hashed duplicate-elimination node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    pub fn new(child: Box<PlanNode + 'a>) -> HashedDistinctNode<'a> {
+        HashedDistinctNode {
+            child: child,
+            seen: HashSet::new(),
+            current_tuple: None,
+        }
+    }
+}
+
+impl<'a> PlanNode for HashedDistinctNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.child.get_schema()
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        self.current_tuple = None;
+        while let Some(tuple) = try!(self.child.get_next_tuple()) {
+            let tuple = TupleLiteral::from_tuple(tuple);
+            if !self.seen.contains(&tuple) {
+                self.seen.insert(tuple.clone());
+                self.current_tuple = Some(tuple);
+                break;
+            }
+        }
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        self.child.prepare()
+    }
+
+    fn initialize(&mut self) {
+        self.seen.clear();
+        self.current_tuple = None;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.child.set_parent_env(env);
+    }
+}
+
+/// A plan node that removes duplicate tuples by sorting its child's tuples on all of their columns,
+/// so that duplicates are next to each other. This uses a bounded amount of memory, so it is the
+/// better choice when there are too many distinct tuples to keep in a hash set.
+pub struct SortedDistinctNode<'a> {
+    child: SortNode<'a>,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl<'a> SortedDistinctNode<'a> {
+    /// Instantiate a new sort-based duplicate-elimination node, whose sort uses the default memory
+    /// budget.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * file_manager - The file manager for the sort to create its temporary files with.
+    pub fn new(child: Box<PlanNode + 'a>, file_manager: &FileManager) -> SortedDistinctNode<'a> {
+        SortedDistinctNode::with_memory_budget(child, file_manager, DEFAULT_SORT_MEMORY)
+    }
+
+    /// Instantiate a new sort-based duplicate-elimination node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * file_manager - The file manager for the sort to create its temporary files with.
+    /// * max_memory - The number of bytes of tuples to sort in memory.
+    pub fn with_memory_budget(child: Box<PlanNode + 'a>,
+                              file_manager: &FileManager,
+                              max_memory: usize)
+                              -> SortedDistinctNode<'a> {
+        let order_by = child.get_schema()
+            .iter()
+            .map(|col_info| {
+                let name = col_info.get_column_name();
+                OrderByExpression::new(Expression::ColumnValue(name), true, None)
+            })
+            .collect();
+        SortedDistinctNode {
+            child: SortNode::with_memory_budget(child, order_by, file_manager, max_memory),
+            current_tuple: None,
+        }
+    }
+}
+
+impl<'a> PlanNode for SortedDistinctNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.child.get_schema()
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        let mut next = None;
+        while let Some(tuple) = try!(self.child.get_next_tuple()) {
+            let tuple = TupleLiteral::from_tuple(tuple);
+            if self.current_tuple.as_ref() != Some(&tuple) {
+                next = Some(tuple);
+                break;
+            }
+        }
+        self.current_tuple = next;
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        self.child.prepare()
+    }
+
+    fn initialize(&mut self) {
+        self.current_tuple = None;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.child.set_parent_env(env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::expressions::Literal;
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::{ColumnInfo, ColumnType};
+
+    fn make_child<'a>() -> Box<PlanNode + 'a> {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+                                               ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO")])
+            .unwrap();
+        let values = [(Some(2), 1), (Some(1), 1), (Some(2), 1), (None, 3), (Some(1), 2), (None, 3)];
+        let tuples: Vec<TupleLiteral> = values.iter()
+            .map(|&(a, b)| TupleLiteral::from_iter(vec![a.map(Literal::Int).unwrap_or(Literal::Null), Literal::Int(b)]))
+            .collect();
+        Box::new(LiteralNode::from_iter(tuples.into_iter(), schema).unwrap())
+    }
+
+    fn tuple(a: Option<i32>, b: i32) -> TupleLiteral {
+        TupleLiteral::from_iter(vec![a.map(Literal::Int).unwrap_or(Literal::Null), Literal::Int(b)])
+    }
+
+    #[test]
+    fn test_hashed_distinct() {
+        let mut node = HashedDistinctNode::new(make_child());
+        node.prepare().unwrap();
+
+        let expected = vec![tuple(Some(2), 1), tuple(Some(1), 1), tuple(None, 3), tuple(Some(1), 2)];
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+    }
+
+    #[test]
+    fn test_sorted_distinct() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut node = SortedDistinctNode::new(make_child(), &file_manager);
+        node.prepare().unwrap();
+
+        let expected = vec![tuple(Some(1), 1), tuple(Some(1), 2), tuple(Some(2), 1), tuple(None, 3)];
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+    }
+}
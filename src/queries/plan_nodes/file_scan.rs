@@ -6,19 +6,23 @@ use ::queries::plan_nodes::PlanNode;
 use ::queries::planning::{PlanError, PlanResult};
 use ::storage::{Pinnable, Tuple};
 use ::storage::table_manager::Table;
-use ::storage::tuple_files::HeapFilePageTuple;
+use ::storage::tuple_files::FilePageTuple;
 
 /// Checks whether the tuple fits the predicate.
 ///
 /// # Arguments
+/// * predicate - The predicate to check, if any.
+/// * schema - The schema of the tuple.
 /// * tuple - The tuple to verify.
-fn is_tuple_selected(predicate: Option<&Expression>,
-                     schema: Schema,
-                     tuple: &mut HeapFilePageTuple)
-                     -> PlanResult<bool> {
+/// * parent_env - The environment to resolve columns outside of the tuple with, if any.
+pub fn is_tuple_selected(predicate: Option<&Expression>,
+                         schema: Schema,
+                         tuple: &mut FilePageTuple,
+                         parent_env: Option<&Environment>)
+                         -> PlanResult<bool> {
     match predicate {
         Some(ref expr) => {
-            let mut env = Environment::new();
+            let mut env = Environment::with_parent(parent_env);
             env.add_tuple(schema, tuple);
             match expr.evaluate(&mut Some(&mut env), &mut None) {
                 Ok(Literal::True) => Ok(true),
@@ -45,7 +49,8 @@ pub struct FileScanNode {
     done: bool,
     /// The predicate to filter the node with.
     pub predicate: Option<Expression>,
-    current_tuple: Option<Box<HeapFilePageTuple>>,
+    current_tuple: Option<Box<FilePageTuple>>,
+    parent_env: Option<Environment>,
 }
 
 impl FileScanNode {
@@ -61,6 +66,7 @@ impl FileScanNode {
             done: false,
             predicate: predicate,
             current_tuple: None,
+            parent_env: None,
         }
     }
 
@@ -103,7 +109,8 @@ impl FileScanNode {
             let mut boxed_tuple = self.current_tuple.as_mut().unwrap();
             let is_selected = is_tuple_selected(self.predicate.as_ref(),
                                                 self.table.get_schema().clone(),
-                                                &mut *boxed_tuple);
+                                                &mut *boxed_tuple,
+                                                self.parent_env.as_ref());
             // If we found a tuple that satisfies the predicate, break out of the loop!
             if try!(is_selected) {
                 return Ok(());
@@ -129,6 +136,10 @@ impl PlanNode for FileScanNode {
         self.done = false;
     }
 
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env);
+    }
+
     fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
         try!(self.get_next_tuple_helper());
 
@@ -11,7 +11,9 @@ lazy_static! {
     static ref DIRECTORY: Directory = Directory::new();
 }
 
-fn get_aggregate_function<I: Iterator<Item=Expression>>(func_name: &str, mut args: I, distinct: bool) -> Box<Function> {
+/// Retrieves the aggregate function for a call, taking its `DISTINCT` flag and its arguments into
+/// account, e.g. `COUNT(*)` is a different function from `COUNT(a)`.
+pub fn get_aggregate_function<I: Iterator<Item=Expression>>(func_name: &str, mut args: I, distinct: bool) -> Box<Function> {
     // No need to make another allocation if we don't need to update the function name.
     let mut func_name = Cow::from(func_name);
 
@@ -63,6 +65,16 @@ impl Clone for FunctionCall {
     }
 }
 
+/// Returns the name of the column that a grouping expression is computed into. Column references
+/// keep their own name, and anything else is named after the expression itself, so that other
+/// occurrences of the expression can be replaced with a reference to the column.
+pub fn get_group_by_column_name(expr: &Expression) -> String {
+    match *expr {
+        Expression::ColumnValue((_, Some(ref name))) => name.clone(),
+        _ => format!("{}", expr),
+    }
+}
+
 fn evaluate_group_by_exprs<'a, I: Iterator<Item=&'a Expression>>(group_by_exprs: I, mut env: &mut Environment) -> Result<TupleLiteral, ExpressionError> {
     let mut result = TupleLiteral::new();
 
@@ -112,6 +124,7 @@ pub struct HashedGroupAggregateNode<'a> {
     group_idx: usize,
     current_tuple: Option<Box<Tuple>>,
     done: bool,
+    parent_env: Option<Environment>,
 }
 
 impl<'a> PlanNode for HashedGroupAggregateNode<'a> {
@@ -132,17 +145,18 @@ impl<'a> PlanNode for HashedGroupAggregateNode<'a> {
         let mut schema = Schema::new();
 
         for expr in self.group_by_exprs.iter() {
-            if let Expression::ColumnValue(ref col_name) = *expr {
-                let info = ColumnInfo {
-                    column_type: try!(expr.get_column_type(&self.input_schema)
-                        .map_err(PlanError::CouldNotProcessAggregates)),
+            let column_type = try!(expr.get_column_type(&self.input_schema)
+                .map_err(PlanError::CouldNotProcessAggregates));
+            let info = if let Expression::ColumnValue(ref col_name) = *expr {
+                ColumnInfo {
+                    column_type: column_type,
                     table_name: col_name.0.clone(),
                     name: col_name.1.clone(),
-                };
-                try!(schema.add_column(info));
+                }
             } else {
-                return Err(PlanError::SimpleColumnReferenceGroupBy(expr.clone()));
-            }
+                ColumnInfo::with_name(column_type, get_group_by_column_name(expr))
+            };
+            try!(schema.add_column(info));
         }
 
         for name in self.aggregates.keys() {
@@ -158,7 +172,19 @@ impl<'a> PlanNode for HashedGroupAggregateNode<'a> {
     }
 
     fn initialize(&mut self) {
+        // The child's tuples may be different this time, e.g. in a correlated subquery, so the
+        // aggregates are computed again.
+        self.computed_aggregates.clear();
+        self.groups = None;
         self.group_idx = 0;
+        self.current_tuple = None;
+        self.done = false;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        self.child.set_parent_env(env);
     }
 }
 
@@ -211,7 +237,8 @@ impl<'a> HashedGroupAggregateNode<'a> {
             groups: None,
             group_idx: 0,
             current_tuple: None,
-            done: false
+            done: false,
+            parent_env: None,
         })
     }
 
@@ -223,9 +250,9 @@ impl<'a> HashedGroupAggregateNode<'a> {
             result.append_tuple(group);
         }
 
-        // TODO:  Add the aggregate values in an order that matches what
-        //        the grouping/aggregate plan node must output.
-        for name in aggregates.keys() {
+        // Add the aggregate values in the same order as the output schema, which follows the
+        // node's own map of aggregates rather than the group's copy of it.
+        for name in self.aggregates.keys() {
             let call = aggregates.get(name).unwrap();
             result.add_value(call.function.get_result());
         }
@@ -240,7 +267,7 @@ impl<'a> HashedGroupAggregateNode<'a> {
         let mut cur_tuple = try!(self.child.get_next_tuple()).map(TupleLiteral::from_tuple);
         let aggregate_keys: Vec<String> = self.aggregates.keys().map(Clone::clone).collect();
         while let Some(tuple) = cur_tuple {
-            let mut environment = Environment::new();
+            let mut environment = Environment::with_parent(self.parent_env.as_ref());
             environment.add_tuple(self.input_schema.clone(), tuple);
 
             // Get the group values for the current row.
@@ -270,6 +297,12 @@ impl<'a> HashedGroupAggregateNode<'a> {
             try!(update_aggregates(group_aggregates, &mut environment));
             cur_tuple = try!(self.child.get_next_tuple()).map(TupleLiteral::from_tuple);
         }
+
+        // Without grouping, the whole input is a single group even when it's empty, so that e.g.
+        // COUNT(*) produces 0 rather than no rows at all.
+        if self.group_by_exprs.is_empty() && result.is_empty() {
+            result.insert(TupleLiteral::new(), self.aggregates.clone());
+        }
         self.computed_aggregates = result;
         Ok(())
     }
@@ -0,0 +1,300 @@
+//! This module provides the index scan plan node.
+
+use std::cmp::Ordering;
+
+use ::Schema;
+use ::expressions::{Environment, Expression, Literal};
+use ::indexes::IndexInfo;
+use ::queries::plan_nodes::PlanNode;
+use ::queries::plan_nodes::file_scan::is_tuple_selected;
+use ::queries::planning::{PlanError, PlanResult};
+use ::storage::{Pinnable, Tuple, TupleLiteral};
+use ::storage::table_manager::Table;
+use ::storage::tuple_files::{BTreeFilePageTuple, FilePageTuple};
+
+/// A bound on the values of the first indexed column that an index scan will produce.
+#[derive(Clone, Debug, PartialEq)]
+pub struct IndexBound {
+    /// The value of the bound.
+    pub value: Literal,
+    /// Whether values equal to the bound are included in the scan.
+    pub inclusive: bool,
+}
+
+impl IndexBound {
+    /// Instantiate a new bound.
+    ///
+    /// # Arguments
+    /// * value - The value of the bound.
+    /// * inclusive - Whether values equal to the bound are included.
+    pub fn new(value: Literal, inclusive: bool) -> IndexBound {
+        IndexBound {
+            value: value,
+            inclusive: inclusive,
+        }
+    }
+}
+
+/// A select plan-node that uses an index to find the tuples of a table whose first indexed column
+/// lies within a range of values, and then checks the optional predicate against each of those
+/// tuples. An equality lookup is a range whose lower and upper bounds are the same inclusive value.
+///
+/// Index entries whose first indexed column is `NULL` are never produced, since no comparison with
+/// `NULL` can be true.
+pub struct IndexScanNode {
+    table: Table,
+    index: IndexInfo,
+    lower_bound: Option<IndexBound>,
+    upper_bound: Option<IndexBound>,
+    done: bool,
+    /// The predicate to filter the node with.
+    pub predicate: Option<Expression>,
+    current_entry: Option<BTreeFilePageTuple>,
+    current_tuple: Option<Box<FilePageTuple>>,
+    parent_env: Option<Environment>,
+}
+
+impl IndexScanNode {
+    /// Instantiate a new index scan node.
+    ///
+    /// # Arguments
+    /// * table - The table to scan.
+    /// * index - The index on the table to scan with.
+    /// * lower_bound - The lowest value of the first indexed column to produce, if any.
+    /// * upper_bound - The highest value of the first indexed column to produce, if any.
+    /// * predicate - The predicate to filter on if it exists.
+    pub fn new(table: Table,
+               index: IndexInfo,
+               lower_bound: Option<IndexBound>,
+               upper_bound: Option<IndexBound>,
+               predicate: Option<Expression>)
+               -> IndexScanNode {
+        IndexScanNode {
+            table: table,
+            index: index,
+            lower_bound: lower_bound,
+            upper_bound: upper_bound,
+            done: false,
+            predicate: predicate,
+            current_entry: None,
+            current_tuple: None,
+            parent_env: None,
+        }
+    }
+
+    /// Instantiate a new index scan node that finds the tuples whose first indexed column equals a
+    /// value.
+    ///
+    /// # Arguments
+    /// * table - The table to scan.
+    /// * index - The index on the table to scan with.
+    /// * value - The value to look up.
+    /// * predicate - The predicate to filter on if it exists.
+    pub fn equal(table: Table,
+                 index: IndexInfo,
+                 value: Literal,
+                 predicate: Option<Expression>)
+                 -> IndexScanNode {
+        let bound = IndexBound::new(value, true);
+        IndexScanNode::new(table, index, Some(bound.clone()), Some(bound), predicate)
+    }
+
+    /// Moves to the next index entry in the range, returning the file pointer it holds, or `None`
+    /// once the range is exhausted.
+    fn advance_current_entry(&mut self) -> PlanResult<Option<Literal>> {
+        let next_entry = match self.current_entry.take() {
+            Some(ref entry) => self.table.get_next_index_entry(self.index.name.as_str(), entry),
+            None => {
+                // Without a lower bound, start after the entries whose value is NULL.
+                let (value, inclusive) = match self.lower_bound {
+                    Some(ref bound) => (bound.value.clone(), bound.inclusive),
+                    None => (Literal::Null, false),
+                };
+                let key = TupleLiteral::from_iter(vec![value]);
+                self.table.find_index_entry(self.index.name.as_str(), &key, inclusive)
+            }
+        };
+
+        let mut entry = match try!(next_entry) {
+            Some(entry) => entry,
+            None => return Ok(None),
+        };
+
+        let value = try!(entry.get_column_value(0).map_err(PlanError::CouldNotAdvanceTuple));
+        let pointer = try!(entry.get_column_value(self.index.column_indexes.len())
+            .map_err(PlanError::CouldNotAdvanceTuple));
+        try!(entry.unpin());
+
+        let in_range = match self.upper_bound {
+            Some(ref bound) => {
+                match value.compare(&bound.value) {
+                    Ordering::Less => true,
+                    Ordering::Equal => bound.inclusive,
+                    Ordering::Greater => false,
+                }
+            }
+            None => true,
+        };
+        if !in_range {
+            return Ok(None);
+        }
+
+        self.current_entry = Some(entry);
+        Ok(Some(pointer))
+    }
+
+    fn get_next_tuple_helper(&mut self) -> PlanResult<()> {
+        if self.done {
+            return Ok(());
+        }
+
+        // Continue to advance through the index until a tuple is selected by the predicate.
+        loop {
+            let pointer = match try!(self.advance_current_entry()) {
+                Some(pointer) => pointer,
+                None => {
+                    self.current_tuple = None;
+                    self.done = true;
+                    return Ok(());
+                }
+            };
+
+            let tuple = try!(self.table.get_tuple(&pointer).map_err(PlanError::CouldNotAdvanceTuple));
+            self.current_tuple = tuple.map(Box::new);
+
+            if let Some(ref mut boxed_tuple) = self.current_tuple {
+                let is_selected = is_tuple_selected(self.predicate.as_ref(),
+                                                    self.table.get_schema(),
+                                                    &mut *boxed_tuple,
+                                                    self.parent_env.as_ref());
+                // If we found a tuple that satisfies the predicate, we're done.
+                if try!(is_selected) {
+                    return Ok(());
+                }
+
+                try!(boxed_tuple.unpin());
+            }
+        }
+    }
+}
+
+impl PlanNode for IndexScanNode {
+    fn get_schema(&self) -> Schema {
+        self.table.get_schema()
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        self.current_entry = None;
+        self.current_tuple = None;
+        self.done = false;
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env);
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        try!(self.get_next_tuple_helper());
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(boxed_tuple) => Some(&mut **boxed_tuple),
+            _ => None,
+        })
+    }
+
+    #[inline]
+    fn has_predicate(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn get_predicate(&self) -> Option<Expression> {
+        self.predicate.clone()
+    }
+
+    fn set_predicate(&mut self, predicate: Expression) -> PlanResult<()> {
+        self.predicate = Some(predicate);
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::{ColumnInfo, ColumnType};
+    use ::expressions::CompareType;
+    use ::queries::get_plan_results;
+    use ::storage::{FileManager, TableManager};
+
+    fn make_table(file_manager: &FileManager, table_manager: &TableManager) -> Table {
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO"),
+        ]).unwrap();
+        table_manager.create_table(file_manager, "FOO", schema).unwrap();
+        table_manager.create_index(file_manager, "FOO", "IDX_A", &["A".into()], false).unwrap();
+
+        let table = table_manager.get_table(file_manager, "FOO").unwrap();
+        for i in 0..200 {
+            let a = if i % 10 == 0 { Literal::Null } else { Literal::Int((i * 7) % 50) };
+            table.add_tuple(TupleLiteral::from_iter(vec![a, Literal::Int(i)])).unwrap();
+        }
+        table
+    }
+
+    fn get_column_a(node: &mut PlanNode) -> Vec<Literal> {
+        get_plan_results(node).unwrap().iter().map(|tuple| tuple[0].clone()).collect()
+    }
+
+    #[test]
+    fn test_index_scan() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let table_manager = TableManager::new();
+        let table = make_table(&file_manager, &table_manager);
+        let index = table.get_indexes()[0].clone();
+
+        // Equality lookups only produce matching tuples.
+        let mut node = IndexScanNode::equal(table.clone(), index.clone(), Literal::Int(14), None);
+        assert_eq!(vec![Literal::Int(14); 4], get_column_a(&mut node));
+        let mut node = IndexScanNode::equal(table.clone(), index.clone(), Literal::Int(0), None);
+        assert_eq!(Vec::<Literal>::new(), get_column_a(&mut node));
+
+        // Range lookups produce tuples in index order, and skip NULL values.
+        let mut node = IndexScanNode::new(table.clone(),
+                                          index.clone(),
+                                          Some(IndexBound::new(Literal::Int(40), false)),
+                                          None,
+                                          None);
+        let values = get_column_a(&mut node);
+        assert_eq!(36, values.len());
+        assert_eq!(Literal::Int(41), values[0]);
+        assert_eq!(Literal::Int(49), values[35]);
+
+        let mut node = IndexScanNode::new(table.clone(), index.clone(), None,
+                                          Some(IndexBound::new(Literal::Int(3), true)), None);
+        assert_eq!(vec![Literal::Int(1), Literal::Int(1), Literal::Int(1), Literal::Int(1),
+                        Literal::Int(2), Literal::Int(2), Literal::Int(2), Literal::Int(2),
+                        Literal::Int(3), Literal::Int(3), Literal::Int(3), Literal::Int(3)],
+                   get_column_a(&mut node));
+
+        // The predicate is still applied to the tuples the index finds.
+        let predicate = Expression::Compare(Box::new(Expression::ColumnValue((None, Some("B".into())))),
+                                            CompareType::LessThan,
+                                            Box::new(Expression::Int(100)));
+        let mut node = IndexScanNode::equal(table.clone(), index.clone(), Literal::Int(14),
+                                            Some(predicate));
+        let results = get_plan_results(&mut node).unwrap();
+        assert_eq!(2, results.len());
+        assert!(results.iter().all(|tuple| tuple[1].compare(&Literal::Int(100)) == Ordering::Less));
+
+        // Scanning again after initializing produces the same results.
+        assert_eq!(2, get_plan_results(&mut node).unwrap().len());
+    }
+}
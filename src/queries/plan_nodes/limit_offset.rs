@@ -0,0 +1,169 @@
+//! This module provides the limit-offset plan node.
+
+use ::Schema;
+use ::expressions::Environment;
+use ::queries::plan_nodes::PlanNode;
+use ::queries::planning::PlanResult;
+use ::storage::Tuple;
+
+/// A plan node that skips the first `offset` tuples of its child and then produces at most `limit`
+/// tuples. Once the limit is reached, no more tuples are pulled from the child, so the rest of the
+/// child's plan is never evaluated.
+pub struct LimitOffsetNode<'a> {
+    child: Box<PlanNode + 'a>,
+    limit: Option<u32>,
+    offset: u32,
+    /// Whether the first `offset` tuples of the child have been skipped yet.
+    skipped_offset: bool,
+    /// The number of tuples produced since the node was initialized.
+    num_produced: u32,
+}
+
+impl<'a> LimitOffsetNode<'a> {
+    /// Instantiate a new limit-offset node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * limit - Optionally, the most tuples to produce.
+    /// * offset - Optionally, the number of tuples of the child to skip before producing any.
+    pub fn new(child: Box<PlanNode + 'a>, limit: Option<u32>, offset: Option<u32>) -> LimitOffsetNode<'a> {
+        LimitOffsetNode {
+            child: child,
+            limit: limit,
+            offset: offset.unwrap_or(0),
+            skipped_offset: false,
+            num_produced: 0,
+        }
+    }
+}
+
+impl<'a> PlanNode for LimitOffsetNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.child.get_schema()
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        if let Some(limit) = self.limit {
+            if self.num_produced >= limit {
+                return Ok(None);
+            }
+        }
+
+        if !self.skipped_offset {
+            for _ in 0..self.offset {
+                if try!(self.child.get_next_tuple()).is_none() {
+                    break;
+                }
+            }
+            self.skipped_offset = true;
+        }
+
+        let tuple = try!(self.child.get_next_tuple());
+        if tuple.is_some() {
+            self.num_produced += 1;
+        }
+        Ok(tuple)
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        self.child.prepare()
+    }
+
+    fn initialize(&mut self) {
+        self.skipped_offset = false;
+        self.num_produced = 0;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.child.set_parent_env(env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::cell::Cell;
+    use std::rc::Rc;
+
+    use super::*;
+    use ::expressions::Literal;
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::{ColumnInfo, ColumnType};
+    use ::storage::TupleLiteral;
+
+    /// Counts the number of times tuples are requested from a node.
+    struct CountingNode {
+        child: LiteralNode,
+        num_requests: Rc<Cell<usize>>,
+    }
+
+    impl PlanNode for CountingNode {
+        fn get_schema(&self) -> Schema {
+            self.child.get_schema()
+        }
+
+        fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+            self.num_requests.set(self.num_requests.get() + 1);
+            self.child.get_next_tuple()
+        }
+
+        fn prepare(&mut self) -> PlanResult<()> {
+            self.child.prepare()
+        }
+
+        fn initialize(&mut self) {
+            self.child.initialize();
+        }
+    }
+
+    fn make_node<'a>(limit: Option<u32>, offset: Option<u32>) -> (LimitOffsetNode<'a>, Rc<Cell<usize>>) {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO")])
+            .unwrap();
+        let tuples: Vec<TupleLiteral> = (0..100).map(|i| TupleLiteral::from_iter(vec![Literal::Int(i)])).collect();
+        let num_requests = Rc::new(Cell::new(0));
+        let child = CountingNode {
+            child: LiteralNode::from_iter(tuples.into_iter(), schema).unwrap(),
+            num_requests: num_requests.clone(),
+        };
+        let mut node = LimitOffsetNode::new(Box::new(child), limit, offset);
+        node.prepare().unwrap();
+        (node, num_requests)
+    }
+
+    fn ints(range: ::std::ops::Range<i32>) -> Vec<TupleLiteral> {
+        range.map(|i| TupleLiteral::from_iter(vec![Literal::Int(i)])).collect()
+    }
+
+    #[test]
+    fn test_limit_offset() {
+        let (mut node, _) = make_node(Some(3), Some(5));
+        assert_eq!(ints(5..8), get_plan_results(&mut node).unwrap());
+        // The offset and limit are applied again when the node is rescanned.
+        assert_eq!(ints(5..8), get_plan_results(&mut node).unwrap());
+
+        let (mut node, _) = make_node(None, Some(95));
+        assert_eq!(ints(95..100), get_plan_results(&mut node).unwrap());
+
+        let (mut node, _) = make_node(Some(10), Some(200));
+        assert_eq!(ints(0..0), get_plan_results(&mut node).unwrap());
+
+        let (mut node, _) = make_node(None, None);
+        assert_eq!(ints(0..100), get_plan_results(&mut node).unwrap());
+    }
+
+    #[test]
+    fn test_limit_stops_pulling_tuples() {
+        let (mut node, num_requests) = make_node(Some(10), None);
+        assert_eq!(ints(0..10), get_plan_results(&mut node).unwrap());
+        assert_eq!(10, num_requests.get());
+
+        // Asking for more tuples once the limit is reached doesn't touch the child.
+        assert!(node.get_next_tuple().unwrap().is_none());
+        assert_eq!(10, num_requests.get());
+
+        let (mut node, num_requests) = make_node(Some(10), Some(20));
+        assert_eq!(ints(20..30), get_plan_results(&mut node).unwrap());
+        assert_eq!(30, num_requests.get());
+    }
+}
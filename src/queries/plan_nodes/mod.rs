@@ -1,23 +1,39 @@
 //! This module contains all plan nodes.
 
+mod distinct;
 mod file_scan;
 mod hashed_group_aggregate;
+mod index_scan;
+mod limit_offset;
 mod project;
 #[cfg(test)]
 mod literal;
 mod nested_loop_join;
 mod rename;
+mod set_operation;
+mod simple_filter;
+mod sort;
+mod window;
+mod working_table;
 
+pub use self::distinct::{HashedDistinctNode, SortedDistinctNode};
 pub use self::file_scan::FileScanNode;
-pub use self::hashed_group_aggregate::HashedGroupAggregateNode;
+pub use self::hashed_group_aggregate::{HashedGroupAggregateNode, get_group_by_column_name};
+pub use self::index_scan::{IndexBound, IndexScanNode};
+pub use self::limit_offset::LimitOffsetNode;
 #[cfg(test)]
 pub use self::literal::LiteralNode;
 pub use self::nested_loop_join::NestedLoopJoinNode;
 pub use self::project::{ProjectNode, ProjectError};
 pub use self::rename::RenameNode;
+pub use self::set_operation::{HashedSetOperationNode, RecursiveUnionNode, UnionNode};
+pub use self::simple_filter::SimpleFilterNode;
+pub use self::sort::{DEFAULT_SORT_MEMORY, SortNode};
+pub use self::window::WindowNode;
+pub use self::working_table::{WorkingTable, WorkingTableScanNode};
 
 use ::Schema;
-use ::expressions::Expression;
+use ::expressions::{Environment, Expression};
 use ::queries::planning::{PlanResult, PlanError};
 use ::storage::Tuple;
 
@@ -40,6 +56,13 @@ pub trait PlanNode {
         // Do nothing by default.
     }
 
+    /// Set the environment used to resolve the column references that the node's own tuples can't,
+    /// e.g. references from a correlated subquery to the current row of the enclosing query. Nodes
+    /// with children pass it on to them.
+    fn set_parent_env(&mut self, _env: Environment) {
+        // Do nothing by default.
+    }
+
     /// Check whether the node has a predicate wrapping it. This is basically a static method for a
     /// class, defaulting to false.
     #[inline]
@@ -58,6 +58,8 @@ pub struct NestedLoopJoinNode<'a> {
     predicate: Option<Expression>,
     /// Information used to join nodes.
     join_data: JoinData,
+    /// The environment to resolve columns outside of the joined tuples with, if any.
+    parent_env: Option<Environment>,
 }
 
 impl<'a> ::std::ops::Deref for NestedLoopJoinNode<'a> {
@@ -98,6 +100,7 @@ impl<'a> NestedLoopJoinNode<'a> {
                     output_schema: None,
                     predicate: predicate,
                     join_data: Default::default(),
+                    parent_env: None,
                 }
             },
             _ => {
@@ -113,6 +116,7 @@ impl<'a> NestedLoopJoinNode<'a> {
                     output_schema: None,
                     predicate: predicate,
                     join_data: Default::default(),
+                    parent_env: None,
                 }
             }
         }
@@ -162,7 +166,7 @@ impl<'a> NestedLoopJoinNode<'a> {
             return Ok(true);
         }
         let predicate = self.predicate.clone().unwrap();
-        let mut env = Environment::new();
+        let mut env = Environment::with_parent(self.parent_env.as_ref());
 
         assert!(self.left_tuple.is_some());
         assert!(self.right_tuple.is_some());
@@ -303,14 +307,49 @@ impl<'a> PlanNode for NestedLoopJoinNode<'a> {
                     }
                 }
             },
+            JoinType::Semijoin | JoinType::Antijoin => {
+                // Semijoins produce the left tuples with at least one match, and antijoins produce
+                // the left tuples with no matches. Either way, the right side only has to be
+                // scanned until the first match.
+                let semijoin = self.join_type == JoinType::Semijoin;
+                self.current_tuple = None;
+                loop {
+                    self.left_tuple = try!(self.left.get_next_tuple()).map(|t| TupleLiteral::from_tuple(t));
+                    if self.left_tuple.is_none() {
+                        self.done = true;
+                        break;
+                    }
+
+                    // The right side is a subquery, which may be correlated with the left tuple.
+                    let mut env = Environment::with_parent(self.parent_env.as_ref());
+                    if let Some(ref mut tuple) = self.left_tuple {
+                        env.add_tuple_ref(self.left.get_schema(), tuple);
+                    }
+                    self.right.set_parent_env(env);
+                    self.right.initialize();
+                    let mut matched = false;
+                    loop {
+                        self.right_tuple = try!(self.right.get_next_tuple()).map(|t| TupleLiteral::from_tuple(t));
+                        if self.right_tuple.is_none() {
+                            break;
+                        }
+                        if try!(self.can_join_tuples()) {
+                            matched = true;
+                            break;
+                        }
+                    }
+                    self.right_tuple = None;
+
+                    if matched == semijoin {
+                        self.current_tuple = self.left_tuple.clone().map(|t| Box::new(t) as Box<Tuple>);
+                        break;
+                    }
+                }
+            }
             JoinType::RightOuter => {
                 // This shouldn't happen since we do a swap!
                 return Err(PlanError::Unimplemented);
             }
-            _ => {
-                // TODO: implement antijoin and semijoin.
-                return Err(PlanError::Unimplemented);
-            }
         }
 
         Ok(match self.current_tuple.as_mut() {
@@ -327,8 +366,12 @@ impl<'a> PlanNode for NestedLoopJoinNode<'a> {
 
         let mut schema = Schema::new();
 
+        // Semijoins and antijoins only produce the left tuples.
         try!(schema.add_columns(left_schema));
-        try!(schema.add_columns(right_schema));
+        match self.join_type {
+            JoinType::Semijoin | JoinType::Antijoin => {}
+            _ => try!(schema.add_columns(right_schema)),
+        }
 
         self.output_schema = Some(schema);
 
@@ -339,6 +382,15 @@ impl<'a> PlanNode for NestedLoopJoinNode<'a> {
         self.done = false;
         self.left_tuple = None;
         self.right_tuple = None;
+        // The join may be rescanned as the inner child of another join.
+        self.left.initialize();
+        self.right.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        self.left.set_parent_env(env.clone());
+        self.right.set_parent_env(env);
     }
 }
 
@@ -635,4 +687,41 @@ mod tests {
             ], result);
         }
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn test_semijoin_and_antijoin() {
+        let left_tuples = vec![
+            TupleLiteral::from_iter(vec![1i32.into(), 2i32.into()]),
+            TupleLiteral::from_iter(vec![3i32.into(), 4i32.into()]),
+            TupleLiteral::from_iter(vec![7i32.into(), 5i32.into()]),
+        ];
+        let right_tuples = vec![
+            TupleLiteral::from_iter(vec![3i32.into(), 6i32.into()]),
+            TupleLiteral::from_iter(vec![3i32.into(), 9i32.into()]),
+            TupleLiteral::from_iter(vec![7i32.into(), 8i32.into()]),
+        ];
+
+        for &(join_type, ref expected) in [(JoinType::Semijoin, vec![left_tuples[1].clone(), left_tuples[2].clone()]),
+                                           (JoinType::Antijoin, vec![left_tuples[0].clone()])].iter() {
+            let left_node = LiteralNode::from_iter(left_tuples.clone().into_iter(), LEFT_SCHEMA.clone()).unwrap();
+            let right_node = LiteralNode::from_iter(right_tuples.clone().into_iter(), RIGHT_SCHEMA.clone()).unwrap();
+            let (schema, result) = generate_node_results(Box::new(left_node), Box::new(right_node), join_type,
+                                                         Some(PREDICATE.clone()));
+
+            // Only the left tuples are produced, and each of them at most once.
+            assert_eq!(LEFT_SCHEMA.clone(), schema);
+            assert_eq!(*expected, result);
+        }
+
+        // Without a predicate, the result depends only on whether the right side is empty.
+        for &(join_type, ref right, ref expected) in [(JoinType::Semijoin, EMPTY.clone(), EMPTY.clone()),
+                                                      (JoinType::Antijoin, EMPTY.clone(), left_tuples.clone()),
+                                                      (JoinType::Semijoin, right_tuples.clone(), left_tuples.clone()),
+                                                      (JoinType::Antijoin, right_tuples.clone(), EMPTY.clone())].iter() {
+            let left_node = LiteralNode::from_iter(left_tuples.clone().into_iter(), LEFT_SCHEMA.clone()).unwrap();
+            let right_node = LiteralNode::from_iter(right.clone().into_iter(), RIGHT_SCHEMA.clone()).unwrap();
+            let (_, result) = generate_node_results(Box::new(left_node), Box::new(right_node), join_type, None);
+            assert_eq!(*expected, result);
+        }
+    }
+}
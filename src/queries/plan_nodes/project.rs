@@ -77,6 +77,7 @@ pub struct ProjectNode<'a> {
     input_schema: Schema,
     output_schema: Option<Schema>,
     planner: Option<&'a Planner>,
+    parent_env: Option<Environment>,
 }
 
 impl<'a> Default for ProjectNode<'a> {
@@ -88,6 +89,7 @@ impl<'a> Default for ProjectNode<'a> {
             input_schema: Schema::new(),
             output_schema: None,
             planner: None,
+            parent_env: None,
         }
     }
 }
@@ -137,7 +139,7 @@ impl<'a> ProjectNode<'a> {
                         let value = try!(tuple.get_column_value(*idx).map_err(|e| ProjectError::CouldNotReadColumnValue(column_name.clone(), e)));
                         result.add_value(value);
                     } else {
-                        let mut env = Environment::new();
+                        let mut env = Environment::with_parent(self.parent_env.as_ref());
                         env.add_tuple_ref(self.input_schema.clone(), tuple);
                         let value = try!(expression.evaluate(&mut Some(&mut env), &self.planner)
                             .map_err(|e| ProjectError::CouldNotResolve(expression.clone(), Box::new(e))));
@@ -183,6 +185,8 @@ impl<'a> ProjectNode<'a> {
                     let next = try!(child.get_next_tuple());
                     if next.is_none() {
                         self.current_tuple = None;
+                        // Put the child back so that the node can be rescanned.
+                        self.child = Some(child);
                         return Ok(());
                     }
                     TupleLiteral::from_tuple(next.unwrap())
@@ -256,12 +260,25 @@ impl<'a> PlanNode for ProjectNode<'a> {
                                                   Some(ref name) => name.clone(),
                                                   None => column_name.1.clone().unwrap(),
                                               })
-                    } else {
-                        if let Some(info) = ColumnInfo::from_select_value(select_value, &mut Some(&mut default_env)) {
-                            info
-                        } else {
+                    } else if let Some(info) = ColumnInfo::from_select_value(select_value, &mut Some(&mut default_env)) {
+                        info
+                    } else if let (&Expression::Subquery(ref clause), Some(planner)) = (expression, self.planner) {
+                        // The type of a subquery's value depends on the tables it selects from, so
+                        // the subquery has to be planned to find it.
+                        let schema = match planner.make_plan(*clause.clone()) {
+                            Ok(plan) => plan.get_schema(),
+                            Err(_) => return Err(ProjectError::CouldNotResolveType(expression.clone()).into()),
+                        };
+                        if schema.num_columns() != 1 {
                             return Err(ProjectError::CouldNotResolveType(expression.clone()).into());
                         }
+                        ColumnInfo::with_name(schema[0].column_type,
+                                              match *alias {
+                                                  Some(ref name) => name.clone(),
+                                                  None => format!("{}", expression),
+                                              })
+                    } else {
+                        return Err(ProjectError::CouldNotResolveType(expression.clone()).into());
                     };
                     result.add_column(col_info).map_err(Into::into)
                 }
@@ -290,4 +307,18 @@ impl<'a> PlanNode for ProjectNode<'a> {
         self.output_schema = Some(result);
         Ok(())
     }
+
+    fn initialize(&mut self) {
+        self.current_tuple = None;
+        if let Some(ref mut child) = self.child {
+            child.initialize();
+        }
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        if let Some(ref mut child) = self.child {
+            child.set_parent_env(env);
+        }
+    }
 }
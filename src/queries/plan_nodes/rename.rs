@@ -1,5 +1,6 @@
 //! This module provides the rename plan node.
 
+use ::expressions::Environment;
 use ::relations::{Schema};
 use ::queries::{PlanNode, PlanResult, PlanError};
 use ::storage::Tuple;
@@ -9,6 +10,7 @@ use ::storage::Tuple;
 pub struct RenameNode<'a> {
     child: Box<PlanNode + 'a>,
     table_name: String,
+    column_names: Option<Vec<String>>,
     input_schema: Schema,
     output_schema: Option<Schema>,
 }
@@ -24,11 +26,27 @@ impl<'a> RenameNode<'a> {
         RenameNode {
             child: child,
             table_name: table_name.into(),
+            column_names: None,
             input_schema: schema,
             // This will only be Some(...) if the node has been prepared!
             output_schema: None,
         }
     }
+
+    /// Instantiate a new rename node that renames the columns as well as the table.
+    ///
+    /// # Argument
+    /// * child - The child of the node.
+    /// * table_name - The new table name for the node.
+    /// * column_names - The new names of the columns, in order.
+    pub fn with_column_names<S: Into<String>>(child: Box<PlanNode + 'a>,
+                                              table_name: S,
+                                              column_names: Vec<String>)
+                                              -> RenameNode<'a> {
+        let mut node = RenameNode::new(child, table_name);
+        node.column_names = Some(column_names);
+        node
+    }
 }
 
 impl<'a> PlanNode for RenameNode<'a> {
@@ -47,7 +65,10 @@ impl<'a> PlanNode for RenameNode<'a> {
         }
 
         let mut new_schema = self.input_schema.clone();
-        try!(new_schema.set_table_name(self.table_name.as_ref()));
+        if let Some(ref names) = self.column_names {
+            try!(new_schema.set_column_names(names));
+        }
+        try!(new_schema.set_table_name(self.table_name.as_str()));
 
         self.output_schema = Some(new_schema);
 
@@ -58,4 +79,8 @@ impl<'a> PlanNode for RenameNode<'a> {
     fn initialize(&mut self) {
         self.child.initialize();
     }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.child.set_parent_env(env);
+    }
 }
\ No newline at end of file
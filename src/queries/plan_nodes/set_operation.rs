@@ -0,0 +1,491 @@
+//! This module provides the plan nodes for the set operations `UNION`, `INTERSECT` and `EXCEPT`.
+//!
+//! The [`UnionNode`] produces the tuples of its left child followed by those of its right child,
+//! which is `UNION ALL`; a plain `UNION` removes the duplicates from its results afterwards. The
+//! [`HashedSetOperationNode`] counts the tuples of its right child in a hash table, and then
+//! produces the tuples of its left child that are (for `INTERSECT`) or aren't (for `EXCEPT`) among
+//! them. The [`RecursiveUnionNode`] evaluates the `UNION` of a recursive query.
+//!
+//! [`UnionNode`]: struct.UnionNode.html
+//! [`HashedSetOperationNode`]: struct.HashedSetOperationNode.html
+//! [`RecursiveUnionNode`]: struct.RecursiveUnionNode.html
+
+use std::collections::{HashMap, HashSet};
+
+use ::Schema;
+use ::expressions::{Environment, Literal, SetOperator};
+use ::queries::plan_nodes::{PlanNode, WorkingTable};
+use ::queries::planning::{PlanError, PlanResult};
+use ::relations::ColumnType;
+use ::storage::{Tuple, TupleLiteral};
+
+/// Whether columns of the two types can be combined by a set operation.
+fn are_compatible_types(left: &ColumnType, right: &ColumnType) -> bool {
+    *left == ColumnType::Null || *right == ColumnType::Null || left == right ||
+    (left.is_numeric() && right.is_numeric()) || (left.is_string() && right.is_string())
+}
+
+/// Ranks the numeric types by the values they can represent, so that the values of both types can
+/// be converted to the type with the higher rank.
+fn get_numeric_rank(column_type: &ColumnType) -> u8 {
+    match *column_type {
+        ColumnType::TinyInt => 0,
+        ColumnType::SmallInt => 1,
+        ColumnType::Integer => 2,
+        ColumnType::BigInt => 3,
+        ColumnType::Numeric { .. } => 4,
+        ColumnType::Float => 5,
+        _ => 6,
+    }
+}
+
+/// Computes the schema of the results of a set operation from the schemas of its two queries. The
+/// queries must have the same number of columns, with compatible types at each position. The
+/// columns are named after those of the left query, and numeric columns take the wider of the two
+/// types.
+pub fn get_set_operation_schema(left: &Schema, right: &Schema) -> PlanResult<Schema> {
+    if left.num_columns() != right.num_columns() {
+        return Err(PlanError::SetOperandArity(left.num_columns(), right.num_columns()));
+    }
+
+    let mut schema = Schema::new();
+    for (left_info, right_info) in left.iter().zip(right.iter()) {
+        let (left_type, right_type) = (left_info.column_type, right_info.column_type);
+        if !are_compatible_types(&left_type, &right_type) {
+            return Err(PlanError::IncompatibleSetOperandTypes(left_type, right_type));
+        }
+
+        let mut info = left_info.clone();
+        if left_type == ColumnType::Null ||
+           (left_type.is_numeric() && get_numeric_rank(&right_type) > get_numeric_rank(&left_type)) {
+            info.column_type = right_type;
+        }
+        try!(schema.add_column(info));
+    }
+    Ok(schema)
+}
+
+/// Converts the numeric values of a tuple to the types of the columns of the schema, so that equal
+/// values from columns of different types are represented, and hashed, the same way.
+fn convert_tuple<T: Tuple + ?Sized>(tuple: &mut T, schema: &Schema) -> TupleLiteral {
+    let tuple = TupleLiteral::from_tuple(tuple);
+    TupleLiteral::from_iter(tuple.iter().zip(schema.iter()).map(|(value, info)| {
+        if !value.is_numeric() {
+            return value.clone();
+        }
+        let converted = match info.column_type {
+            ColumnType::TinyInt | ColumnType::SmallInt | ColumnType::Integer => value.as_int(),
+            ColumnType::BigInt => value.as_long(),
+            ColumnType::Numeric { .. } => value.as_decimal().map(Literal::Decimal),
+            ColumnType::Float => value.as_float(),
+            ColumnType::Double => value.as_double(),
+            _ => None,
+        };
+        converted.unwrap_or(value.clone())
+    }))
+}
+
+/// A plan node that produces all of the tuples of its left child, and then all of the tuples of
+/// its right child. This implements `UNION ALL`.
+pub struct UnionNode<'a> {
+    left: Box<PlanNode + 'a>,
+    right: Box<PlanNode + 'a>,
+    schema: Option<Schema>,
+    left_done: bool,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl<'a> UnionNode<'a> {
+    /// Instantiate a new union node.
+    ///
+    /// # Arguments
+    /// * left - The left child of the node.
+    /// * right - The right child of the node.
+    pub fn new(left: Box<PlanNode + 'a>, right: Box<PlanNode + 'a>) -> UnionNode<'a> {
+        UnionNode {
+            left: left,
+            right: right,
+            schema: None,
+            left_done: false,
+            current_tuple: None,
+        }
+    }
+}
+
+impl<'a> PlanNode for UnionNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.schema.clone().unwrap_or(Schema::new())
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        let schema = match self.schema {
+            Some(ref schema) => schema,
+            None => return Err(PlanError::NodeNotPrepared),
+        };
+
+        self.current_tuple = None;
+        if !self.left_done {
+            if let Some(tuple) = try!(self.left.get_next_tuple()) {
+                self.current_tuple = Some(convert_tuple(tuple, schema));
+            } else {
+                self.left_done = true;
+            }
+        }
+        if self.left_done {
+            if let Some(tuple) = try!(self.right.get_next_tuple()) {
+                self.current_tuple = Some(convert_tuple(tuple, schema));
+            }
+        }
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        try!(self.left.prepare());
+        try!(self.right.prepare());
+        self.schema = Some(try!(get_set_operation_schema(&self.left.get_schema(), &self.right.get_schema())));
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        self.left_done = false;
+        self.current_tuple = None;
+        self.left.initialize();
+        self.right.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.left.set_parent_env(env.clone());
+        self.right.set_parent_env(env);
+    }
+}
+
+/// A plan node that implements `INTERSECT` and `EXCEPT` by counting the tuples of its right child
+/// in a hash table, and then testing each tuple of its left child against the counts. The tuples
+/// are produced in the order of the left child.
+///
+/// Without `ALL`, each distinct tuple is produced at most once. With `ALL`, a tuple that appears
+/// `m` times in the left child and `n` times in the right child is produced `min(m, n)` times for
+/// `INTERSECT`, and `max(m - n, 0)` times for `EXCEPT`.
+pub struct HashedSetOperationNode<'a> {
+    left: Box<PlanNode + 'a>,
+    right: Box<PlanNode + 'a>,
+    operator: SetOperator,
+    all: bool,
+    schema: Option<Schema>,
+    counts: Option<HashMap<TupleLiteral, usize>>,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl<'a> HashedSetOperationNode<'a> {
+    /// Instantiate a new hash-based set operation node.
+    ///
+    /// # Arguments
+    /// * left - The left child of the node.
+    /// * right - The right child of the node.
+    /// * operator - The set operation, which must be `INTERSECT` or `EXCEPT`.
+    /// * all - Whether duplicate tuples are kept.
+    pub fn new(left: Box<PlanNode + 'a>,
+               right: Box<PlanNode + 'a>,
+               operator: SetOperator,
+               all: bool)
+               -> HashedSetOperationNode<'a> {
+        debug_assert!(operator != SetOperator::Union);
+        HashedSetOperationNode {
+            left: left,
+            right: right,
+            operator: operator,
+            all: all,
+            schema: None,
+            counts: None,
+            current_tuple: None,
+        }
+    }
+
+    fn count_right_tuples(&mut self) -> PlanResult<HashMap<TupleLiteral, usize>> {
+        let schema = self.schema.as_ref().unwrap();
+        let mut counts = HashMap::new();
+        while let Some(tuple) = try!(self.right.get_next_tuple()) {
+            *counts.entry(convert_tuple(tuple, schema)).or_insert(0) += 1;
+        }
+        Ok(counts)
+    }
+}
+
+impl<'a> PlanNode for HashedSetOperationNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.schema.clone().unwrap_or(Schema::new())
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        if self.schema.is_none() {
+            return Err(PlanError::NodeNotPrepared);
+        }
+
+        if self.counts.is_none() {
+            self.counts = Some(try!(self.count_right_tuples()));
+        }
+
+        self.current_tuple = None;
+        let schema = self.schema.as_ref().unwrap();
+        let counts = self.counts.as_mut().unwrap();
+        while let Some(tuple) = try!(self.left.get_next_tuple()) {
+            let tuple = convert_tuple(tuple, schema);
+            let count = counts.entry(tuple.clone()).or_insert(0);
+            let selected = match self.operator {
+                SetOperator::Intersect => {
+                    let selected = *count > 0;
+                    if selected {
+                        *count = if self.all { *count - 1 } else { 0 };
+                    }
+                    selected
+                }
+                SetOperator::Except => {
+                    let selected = *count == 0;
+                    if !selected && self.all {
+                        *count -= 1;
+                    } else if selected && !self.all {
+                        // Later duplicates of the tuple are skipped.
+                        *count = 1;
+                    }
+                    selected
+                }
+                SetOperator::Union => unreachable!(),
+            };
+
+            if selected {
+                self.current_tuple = Some(tuple);
+                break;
+            }
+        }
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        try!(self.left.prepare());
+        try!(self.right.prepare());
+        self.schema = Some(try!(get_set_operation_schema(&self.left.get_schema(), &self.right.get_schema())));
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        self.counts = None;
+        self.current_tuple = None;
+        self.left.initialize();
+        self.right.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.left.set_parent_env(env.clone());
+        self.right.set_parent_env(env);
+    }
+}
+
+/// A plan node that evaluates a recursive query of the form `anchor UNION [ALL] recursive`, where
+/// the recursive part reads the working table. The node produces the tuples of the anchor, then
+/// repeatedly evaluates the recursive part with the working table holding the tuples produced by
+/// the previous round, until a round produces no new tuples.
+///
+/// Without `ALL`, tuples that have already been produced are discarded, so that queries over
+/// cyclic data still finish.
+pub struct RecursiveUnionNode<'a> {
+    anchor: Box<PlanNode + 'a>,
+    recursive: Box<PlanNode + 'a>,
+    table: WorkingTable,
+    all: bool,
+    schema: Option<Schema>,
+    seen: HashSet<TupleLiteral>,
+    new_tuples: Vec<TupleLiteral>,
+    in_recursive: bool,
+    done: bool,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl<'a> RecursiveUnionNode<'a> {
+    /// Instantiate a new recursive union node.
+    ///
+    /// # Arguments
+    /// * anchor - The plan for the non-recursive part of the query.
+    /// * recursive - The plan for the recursive part of the query, which reads the working table.
+    /// * table - The working table.
+    /// * all - Whether duplicate tuples are kept.
+    pub fn new(anchor: Box<PlanNode + 'a>,
+               recursive: Box<PlanNode + 'a>,
+               table: WorkingTable,
+               all: bool)
+               -> RecursiveUnionNode<'a> {
+        RecursiveUnionNode {
+            anchor: anchor,
+            recursive: recursive,
+            table: table,
+            all: all,
+            schema: None,
+            seen: HashSet::new(),
+            new_tuples: vec![],
+            in_recursive: false,
+            done: false,
+            current_tuple: None,
+        }
+    }
+
+    fn next_child_tuple(&mut self) -> PlanResult<Option<TupleLiteral>> {
+        let schema = self.schema.as_ref().unwrap();
+        let child = if self.in_recursive { &mut self.recursive } else { &mut self.anchor };
+        Ok(try!(child.get_next_tuple()).map(|tuple| convert_tuple(tuple, schema)))
+    }
+}
+
+impl<'a> PlanNode for RecursiveUnionNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.schema.clone().unwrap_or(Schema::new())
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        if self.schema.is_none() {
+            return Err(PlanError::NodeNotPrepared);
+        }
+
+        self.current_tuple = None;
+        while !self.done {
+            match try!(self.next_child_tuple()) {
+                Some(tuple) => {
+                    if !self.all && !self.seen.insert(tuple.clone()) {
+                        continue;
+                    }
+                    self.new_tuples.push(tuple.clone());
+                    self.current_tuple = Some(tuple);
+                    break;
+                }
+                None => {
+                    if self.new_tuples.is_empty() {
+                        self.done = true;
+                    } else {
+                        // Start the next round on the tuples produced by this one.
+                        self.table.set_tuples(::std::mem::replace(&mut self.new_tuples, vec![]));
+                        self.recursive.initialize();
+                        self.in_recursive = true;
+                    }
+                }
+            }
+        }
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        try!(self.anchor.prepare());
+        try!(self.recursive.prepare());
+        self.schema = Some(try!(get_set_operation_schema(&self.anchor.get_schema(), &self.recursive.get_schema())));
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        self.seen.clear();
+        self.new_tuples.clear();
+        self.in_recursive = false;
+        self.done = false;
+        self.current_tuple = None;
+        self.table.set_tuples(vec![]);
+        self.anchor.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.anchor.set_parent_env(env.clone());
+        self.recursive.set_parent_env(env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::ColumnInfo;
+
+    fn make_child<'a>(values: &[i32]) -> Box<PlanNode + 'a> {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Integer, "A")]).unwrap();
+        let tuples: Vec<TupleLiteral> = values.iter()
+            .map(|&value| TupleLiteral::from_iter(vec![Literal::Int(value)]))
+            .collect();
+        Box::new(LiteralNode::from_iter(tuples.into_iter(), schema).unwrap())
+    }
+
+    fn make_tuples(values: &[i32]) -> Vec<TupleLiteral> {
+        values.iter().map(|&value| TupleLiteral::from_iter(vec![Literal::Int(value)])).collect()
+    }
+
+    const LEFT: &'static [i32] = &[1, 2, 2, 2, 3, 4, 4];
+    const RIGHT: &'static [i32] = &[2, 2, 4, 5, 5];
+
+    #[test]
+    fn test_union() {
+        let mut node = UnionNode::new(make_child(LEFT), make_child(RIGHT));
+        node.prepare().unwrap();
+
+        let expected = make_tuples(&[1, 2, 2, 2, 3, 4, 4, 2, 2, 4, 5, 5]);
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+    }
+
+    #[test]
+    fn test_hashed_set_operations() {
+        let cases: Vec<(SetOperator, bool, Vec<i32>)> = vec![
+            (SetOperator::Intersect, false, vec![2, 4]),
+            (SetOperator::Intersect, true, vec![2, 2, 4]),
+            (SetOperator::Except, false, vec![1, 3]),
+            (SetOperator::Except, true, vec![1, 2, 3, 4]),
+        ];
+
+        for (operator, all, values) in cases {
+            let mut node = HashedSetOperationNode::new(make_child(LEFT), make_child(RIGHT), operator, all);
+            node.prepare().unwrap();
+
+            let expected = make_tuples(&values);
+            assert_eq!(expected, get_plan_results(&mut node).unwrap());
+            assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        }
+    }
+
+    #[test]
+    fn test_set_operation_schema() {
+        let ints = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Integer, "A")]).unwrap();
+        let doubles = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Double, "B")]).unwrap();
+        let strings = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::VarChar { length: 10 }, "C")]).unwrap();
+        let nulls = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Null, "D")]).unwrap();
+        let pairs = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Integer, "A"),
+                                              ColumnInfo::with_name(ColumnType::Integer, "B")])
+            .unwrap();
+
+        let schema = get_set_operation_schema(&ints, &doubles).unwrap();
+        assert_eq!(vec![ColumnInfo::with_name(ColumnType::Double, "A")],
+                   schema.iter().cloned().collect::<Vec<ColumnInfo>>());
+        let schema = get_set_operation_schema(&nulls, &strings).unwrap();
+        assert_eq!(vec![ColumnInfo::with_name(ColumnType::VarChar { length: 10 }, "D")],
+                   schema.iter().cloned().collect::<Vec<ColumnInfo>>());
+        assert_eq!(Err(PlanError::IncompatibleSetOperandTypes(ColumnType::Integer, ColumnType::VarChar { length: 10 })),
+                   get_set_operation_schema(&ints, &strings));
+        assert_eq!(Err(PlanError::SetOperandArity(1, 2)), get_set_operation_schema(&ints, &pairs));
+    }
+
+    #[test]
+    fn test_mixed_numeric_types() {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::BigInt, "A")]).unwrap();
+        let longs = vec![TupleLiteral::from_iter(vec![Literal::Long(2)])];
+        let right = Box::new(LiteralNode::from_iter(longs.into_iter(), schema).unwrap());
+        let mut node = HashedSetOperationNode::new(make_child(LEFT), right, SetOperator::Intersect, false);
+        node.prepare().unwrap();
+
+        let expected = vec![TupleLiteral::from_iter(vec![Literal::Long(2)])];
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+    }
+}
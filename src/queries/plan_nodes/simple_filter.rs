@@ -0,0 +1,142 @@
+//! This module provides the simple filter plan node.
+
+use ::Schema;
+use ::expressions::{Environment, Expression, Literal};
+use ::queries::plan_nodes::PlanNode;
+use ::queries::planning::{PlanError, Planner, PlanResult};
+use ::storage::{Tuple, TupleLiteral};
+
+/// A select plan-node that filters the tuples of its child with a predicate. This is used for
+/// predicates that can't be applied by the child itself, e.g. because the child is a join or a
+/// projection.
+pub struct SimpleFilterNode<'a> {
+    child: Box<PlanNode + 'a>,
+    predicate: Expression,
+    current_tuple: Option<TupleLiteral>,
+    planner: Option<&'a Planner>,
+    parent_env: Option<Environment>,
+}
+
+impl<'a> SimpleFilterNode<'a> {
+    /// Instantiate a new simple filter node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * predicate - The predicate that the child's tuples must satisfy.
+    pub fn new(child: Box<PlanNode + 'a>, predicate: Expression) -> SimpleFilterNode<'a> {
+        SimpleFilterNode {
+            child: child,
+            predicate: predicate,
+            current_tuple: None,
+            planner: None,
+            parent_env: None,
+        }
+    }
+
+    /// Instantiate a new simple filter node whose predicate may contain subqueries, which are
+    /// planned with the given planner.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * predicate - The predicate that the child's tuples must satisfy.
+    /// * planner - The planner to plan subqueries with.
+    pub fn with_planner(child: Box<PlanNode + 'a>, predicate: Expression, planner: &'a Planner) -> SimpleFilterNode<'a> {
+        SimpleFilterNode {
+            planner: Some(planner),
+            ..SimpleFilterNode::new(child, predicate)
+        }
+    }
+
+    fn is_tuple_selected(&self, tuple: &mut TupleLiteral) -> PlanResult<bool> {
+        let mut env = Environment::with_parent(self.parent_env.as_ref());
+        env.add_tuple_ref(self.child.get_schema(), tuple);
+        match self.predicate.evaluate(&mut Some(&mut env), &self.planner) {
+            Ok(Literal::True) => Ok(true),
+            Ok(Literal::False) | Ok(Literal::Null) => Ok(false),
+            Ok(_) => Err(PlanError::InvalidPredicate),
+            Err(e) => Err(PlanError::CouldNotApplyPredicate(e)),
+        }
+    }
+}
+
+impl<'a> PlanNode for SimpleFilterNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.child.get_schema()
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        self.current_tuple = None;
+        loop {
+            let mut tuple = match try!(self.child.get_next_tuple()) {
+                Some(tuple) => TupleLiteral::from_tuple(tuple),
+                None => break,
+            };
+            if try!(self.is_tuple_selected(&mut tuple)) {
+                self.current_tuple = Some(tuple);
+                break;
+            }
+        }
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        self.child.prepare()
+    }
+
+    fn initialize(&mut self) {
+        self.current_tuple = None;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        self.child.set_parent_env(env);
+    }
+
+    #[inline]
+    fn has_predicate(&self) -> bool {
+        true
+    }
+
+    #[inline]
+    fn get_predicate(&self) -> Option<Expression> {
+        Some(self.predicate.clone())
+    }
+
+    fn set_predicate(&mut self, predicate: Expression) -> PlanResult<()> {
+        self.predicate = predicate;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::expressions::CompareType;
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::{ColumnInfo, ColumnType};
+
+    #[test]
+    fn test_filter() {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO")])
+            .unwrap();
+        let tuples: Vec<TupleLiteral> = (1..6).map(|i| TupleLiteral::from_iter(vec![Literal::Int(i)])).collect();
+        let child = LiteralNode::from_iter(tuples.into_iter(), schema).unwrap();
+        let predicate = Expression::Compare(Box::new(Expression::ColumnValue((None, Some("A".into())))),
+                                            CompareType::GreaterThan,
+                                            Box::new(Expression::Int(3)));
+        let mut node = SimpleFilterNode::new(Box::new(child), predicate);
+        node.prepare().unwrap();
+
+        let expected = vec![TupleLiteral::from_iter(vec![Literal::Int(4)]),
+                            TupleLiteral::from_iter(vec![Literal::Int(5)])];
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        // The child is rescanned when the node is initialized again.
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+    }
+}
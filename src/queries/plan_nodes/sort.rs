@@ -0,0 +1,480 @@
+//! This module provides the sort plan node.
+//!
+//! Tuples are sorted in memory as long as they fit in the node's memory budget. Larger inputs are
+//! sorted with an external merge sort: each time the budget is used up, the tuples collected so far
+//! are sorted and written to a run file, and the runs are merged once the input is exhausted. Run
+//! files are temporary data files created through the [`FileManager`], and hold one entry after
+//! another after the file's header page. Each entry is the values of the `ORDER BY` expressions
+//! followed by the values of the tuple, written with
+//! [`Literal::write_tagged`](../../expressions/literal/enum.Literal.html#method.write_tagged).
+//!
+//! The sort is stable, so tuples that compare as equal come out in the order the child produced
+//! them in. This makes the output order deterministic whenever the input order is.
+//!
+//! [`FileManager`]: ../../storage/file_manager/struct.FileManager.html
+
+use std::cmp::Ordering;
+use std::fs::File;
+use std::io::{self, BufReader, BufWriter, Seek, SeekFrom, Write};
+use std::sync::atomic::{self, AtomicUsize};
+
+use ::Schema;
+use ::expressions::{Environment, Literal, OrderByExpression};
+use ::queries::plan_nodes::PlanNode;
+use ::queries::planning::{PlanError, PlanResult};
+use ::storage::{DBFile, DBFileType, FileManager, Tuple, TupleLiteral, file_manager};
+use ::storage::dbfile::get_default_pagesize;
+
+/// The number of bytes of tuples a sort node keeps in memory by default before writing them out to
+/// a run file.
+pub const DEFAULT_SORT_MEMORY: usize = 4 * 1024 * 1024;
+
+/// The most runs that are merged at once. If there are more runs than this, groups of runs are
+/// merged into longer runs first, so that only this many run files are open at a time.
+const MAX_MERGE_RUNS: usize = 16;
+
+/// Used to give each run file a unique name.
+static NEXT_RUN_ID: AtomicUsize = AtomicUsize::new(0);
+
+/// A tuple being sorted, along with the values of the `ORDER BY` expressions for the tuple.
+struct SortEntry {
+    keys: Vec<Literal>,
+    tuple: TupleLiteral,
+}
+
+impl SortEntry {
+    fn write<W: Write>(&self, output: &mut W) -> io::Result<()> {
+        for value in self.keys.iter().chain(self.tuple.iter()) {
+            try!(value.write_tagged(output));
+        }
+        Ok(())
+    }
+
+    fn read<R: io::Read>(input: &mut R, num_keys: usize, num_columns: usize) -> io::Result<SortEntry> {
+        let mut keys = Vec::with_capacity(num_keys);
+        for _ in 0..num_keys {
+            keys.push(try!(Literal::read_tagged(input)));
+        }
+        let mut tuple = TupleLiteral::new();
+        for _ in 0..num_columns {
+            tuple.add_value(try!(Literal::read_tagged(input)));
+        }
+        Ok(SortEntry {
+            keys: keys,
+            tuple: tuple,
+        })
+    }
+}
+
+/// Compares the `ORDER BY` values of two entries.
+fn compare_keys(order_by: &[OrderByExpression], left: &SortEntry, right: &SortEntry) -> Ordering {
+    for (i, term) in order_by.iter().enumerate() {
+        match term.compare(&left.keys[i], &right.keys[i]) {
+            Ordering::Equal => {}
+            result => return result,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Evaluates the `ORDER BY` expressions on a tuple of the given schema.
+fn get_entry(order_by: &[OrderByExpression],
+             schema: &Schema,
+             tuple: &mut Tuple,
+             parent_env: Option<&Environment>)
+             -> PlanResult<SortEntry> {
+    let mut tuple = TupleLiteral::from_tuple(tuple);
+    let mut keys = Vec::with_capacity(order_by.len());
+    {
+        let mut env = Environment::with_parent(parent_env);
+        env.add_tuple_ref(schema.clone(), &mut tuple);
+        for term in order_by.iter() {
+            keys.push(try!(term.expression
+                .evaluate(&mut Some(&mut env), &None)
+                .map_err(PlanError::CouldNotEvaluateOrderBy)));
+        }
+    }
+    Ok(SortEntry {
+        keys: keys,
+        tuple: tuple,
+    })
+}
+
+/// A file holding a sorted run of entries.
+struct SortRun {
+    file_name: String,
+    num_entries: usize,
+}
+
+/// Reads the entries of a run back in order.
+struct RunReader {
+    input: BufReader<DBFile<File>>,
+    remaining: usize,
+    next: Option<SortEntry>,
+}
+
+impl RunReader {
+    fn new(file_manager: &FileManager, run: &SortRun) -> PlanResult<RunReader> {
+        let mut db_file = try!(file_manager.open_dbfile(&run.file_name));
+        let start = db_file.get_page_size() as u64;
+        try!(db_file.seek(SeekFrom::Start(start)).map_err(file_manager::Error::from));
+        Ok(RunReader {
+            input: BufReader::new(db_file),
+            remaining: run.num_entries,
+            next: None,
+        })
+    }
+
+    /// Reads the next entry of the run into `next`, leaving it empty at the end of the run.
+    fn advance(&mut self, num_keys: usize, num_columns: usize) -> PlanResult<()> {
+        self.next = if self.remaining > 0 {
+            self.remaining -= 1;
+            Some(try!(SortEntry::read(&mut self.input, num_keys, num_columns).map_err(file_manager::Error::from)))
+        } else {
+            None
+        };
+        Ok(())
+    }
+}
+
+/// A plan node that sorts the tuples of its child by the terms of an `ORDER BY` clause.
+pub struct SortNode<'a> {
+    child: Box<PlanNode + 'a>,
+    order_by: Vec<OrderByExpression>,
+    file_manager: FileManager,
+    max_memory: usize,
+    /// Whether the child's tuples have been read and sorted yet.
+    done_sorting: bool,
+    /// The sorted entries, when they all fit in memory. They are stored in reverse so that they
+    /// can be popped off in order.
+    sorted_entries: Vec<SortEntry>,
+    /// The run files, when the entries didn't fit in memory.
+    runs: Vec<SortRun>,
+    /// The readers of the runs being merged to produce the node's output.
+    readers: Vec<RunReader>,
+    current_tuple: Option<TupleLiteral>,
+    parent_env: Option<Environment>,
+}
+
+impl<'a> SortNode<'a> {
+    /// Instantiate a new sort node, which uses the default memory budget.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * order_by - The terms to sort the child's tuples by.
+    /// * file_manager - The file manager to create run files with.
+    pub fn new(child: Box<PlanNode + 'a>, order_by: Vec<OrderByExpression>, file_manager: &FileManager) -> SortNode<'a> {
+        SortNode::with_memory_budget(child, order_by, file_manager, DEFAULT_SORT_MEMORY)
+    }
+
+    /// Instantiate a new sort node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * order_by - The terms to sort the child's tuples by.
+    /// * file_manager - The file manager to create run files with.
+    /// * max_memory - The number of bytes of tuples to sort in memory. Inputs larger than this are
+    ///   sorted using run files.
+    pub fn with_memory_budget(child: Box<PlanNode + 'a>,
+                              order_by: Vec<OrderByExpression>,
+                              file_manager: &FileManager,
+                              max_memory: usize)
+                              -> SortNode<'a> {
+        SortNode {
+            child: child,
+            order_by: order_by,
+            file_manager: file_manager.clone(),
+            max_memory: max_memory,
+            done_sorting: false,
+            sorted_entries: vec![],
+            runs: vec![],
+            readers: vec![],
+            current_tuple: None,
+            parent_env: None,
+        }
+    }
+
+    /// Returns the number of run files the last sort used, which is zero if it fit in memory.
+    pub fn get_num_runs(&self) -> usize {
+        self.runs.len()
+    }
+
+    /// Creates a new, empty run file, returning its name and a writer positioned after its header.
+    fn create_run_file(&self) -> PlanResult<(String, BufWriter<DBFile<File>>)> {
+        let id = NEXT_RUN_ID.fetch_add(1, atomic::Ordering::SeqCst);
+        let file_name = format!("sort_run_{}_{}.tmp", ::std::process::id(), id);
+        debug!("Creating sort run file {}.", file_name);
+        let mut db_file = try!(self.file_manager.create_dbfile(&file_name, DBFileType::SortRunFile, get_default_pagesize()));
+        let start = db_file.get_page_size() as u64;
+        try!(db_file.seek(SeekFrom::Start(start)).map_err(file_manager::Error::from));
+        Ok((file_name, BufWriter::new(db_file)))
+    }
+
+    /// Sorts the entries collected so far and writes them to a new run file.
+    fn write_run(&mut self, mut entries: Vec<SortEntry>) -> PlanResult<()> {
+        {
+            let order_by = &self.order_by;
+            entries.sort_by(|a, b| compare_keys(order_by, a, b));
+        }
+        let (file_name, mut output) = try!(self.create_run_file());
+        for entry in entries.iter() {
+            try!(entry.write(&mut output).map_err(file_manager::Error::from));
+        }
+        try!(output.flush().map_err(file_manager::Error::from));
+        self.runs.push(SortRun {
+            file_name: file_name,
+            num_entries: entries.len(),
+        });
+        Ok(())
+    }
+
+    /// Opens readers on the given runs, each positioned on the run's first entry.
+    fn open_runs(&self, runs: &[SortRun]) -> PlanResult<Vec<RunReader>> {
+        let num_columns = self.child.get_schema().num_columns();
+        let mut readers = Vec::with_capacity(runs.len());
+        for run in runs {
+            let mut reader = try!(RunReader::new(&self.file_manager, run));
+            try!(reader.advance(self.order_by.len(), num_columns));
+            readers.push(reader);
+        }
+        Ok(readers)
+    }
+
+    /// Takes the smallest next entry of the readers, or `None` if they are all exhausted. Ties go
+    /// to the earliest run, which keeps the merge stable since earlier runs hold earlier tuples.
+    fn next_merged_entry(&self, readers: &mut [RunReader]) -> PlanResult<Option<SortEntry>> {
+        let mut smallest: Option<usize> = None;
+        for (i, reader) in readers.iter().enumerate() {
+            if let Some(ref entry) = reader.next {
+                smallest = match smallest {
+                    Some(j) if compare_keys(&self.order_by, entry, readers[j].next.as_ref().unwrap()) !=
+                               Ordering::Less => Some(j),
+                    _ => Some(i),
+                };
+            }
+        }
+        match smallest {
+            Some(i) => {
+                let entry = readers[i].next.take();
+                try!(readers[i].advance(self.order_by.len(), self.child.get_schema().num_columns()));
+                Ok(entry)
+            }
+            None => Ok(None),
+        }
+    }
+
+    /// Merges groups of runs into longer runs until few enough are left to merge them all at once.
+    fn merge_runs(&mut self) -> PlanResult<()> {
+        while self.runs.len() > MAX_MERGE_RUNS {
+            let runs: Vec<SortRun> = self.runs.drain(..).collect();
+            for group in runs.chunks(MAX_MERGE_RUNS) {
+                let mut readers = try!(self.open_runs(group));
+                let (file_name, mut output) = try!(self.create_run_file());
+                let mut num_entries = 0;
+                while let Some(entry) = try!(self.next_merged_entry(&mut readers)) {
+                    try!(entry.write(&mut output).map_err(file_manager::Error::from));
+                    num_entries += 1;
+                }
+                try!(output.flush().map_err(file_manager::Error::from));
+                self.runs.push(SortRun {
+                    file_name: file_name,
+                    num_entries: num_entries,
+                });
+                for run in group {
+                    try!(self.file_manager.remove_dbfile(&run.file_name));
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Reads all of the child's tuples and sorts them, using run files if they don't fit in memory.
+    fn sort(&mut self) -> PlanResult<()> {
+        let mut entries = Vec::new();
+        let mut memory_used = 0;
+        let mut buffer = Vec::new();
+        let schema = self.child.get_schema();
+        loop {
+            let entry = match try!(self.child.get_next_tuple()) {
+                Some(tuple) => try!(get_entry(&self.order_by, &schema, tuple, self.parent_env.as_ref())),
+                None => break,
+            };
+            buffer.clear();
+            try!(entry.write(&mut buffer).map_err(file_manager::Error::from));
+            memory_used += buffer.len();
+            entries.push(entry);
+
+            if memory_used > self.max_memory {
+                let full = ::std::mem::replace(&mut entries, Vec::new());
+                try!(self.write_run(full));
+                memory_used = 0;
+            }
+        }
+
+        if self.runs.is_empty() {
+            let order_by = &self.order_by;
+            entries.sort_by(|a, b| compare_keys(order_by, a, b));
+            entries.reverse();
+            self.sorted_entries = entries;
+        } else {
+            if !entries.is_empty() {
+                try!(self.write_run(entries));
+            }
+            debug!("Sorted {} tuples in {} runs.",
+                   self.runs.iter().map(|run| run.num_entries).sum::<usize>(),
+                   self.runs.len());
+            try!(self.merge_runs());
+            let readers = try!(self.open_runs(&self.runs));
+            self.readers = readers;
+        }
+        self.done_sorting = true;
+        Ok(())
+    }
+
+    /// Removes the run files of the last sort.
+    fn remove_runs(&mut self) {
+        self.readers.clear();
+        for run in self.runs.drain(..) {
+            if let Err(e) = self.file_manager.remove_dbfile(&run.file_name) {
+                warn!("Unable to remove sort run file {}: {}", run.file_name, e);
+            }
+        }
+    }
+}
+
+impl<'a> PlanNode for SortNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.child.get_schema()
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        if !self.done_sorting {
+            try!(self.sort());
+        }
+
+        let entry = if self.readers.is_empty() {
+            self.sorted_entries.pop()
+        } else {
+            let mut readers = ::std::mem::replace(&mut self.readers, Vec::new());
+            let entry = self.next_merged_entry(&mut readers);
+            self.readers = readers;
+            try!(entry)
+        };
+        self.current_tuple = entry.map(|entry| entry.tuple);
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        self.child.prepare()
+    }
+
+    fn initialize(&mut self) {
+        self.remove_runs();
+        self.done_sorting = false;
+        self.sorted_entries.clear();
+        self.current_tuple = None;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        self.child.set_parent_env(env);
+    }
+}
+
+impl<'a> Drop for SortNode<'a> {
+    fn drop(&mut self) {
+        self.remove_runs();
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::expressions::Expression;
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::{ColumnInfo, ColumnType};
+
+    fn make_child<'a>(values: &[(Option<i32>, &str)]) -> Box<PlanNode + 'a> {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+                                               ColumnInfo::with_table_name(ColumnType::VarChar { length: 20 },
+                                                                           "B",
+                                                                           "FOO")])
+            .unwrap();
+        let tuples: Vec<TupleLiteral> = values.iter()
+            .map(|&(a, b)| {
+                TupleLiteral::from_iter(vec![a.map(Literal::Int).unwrap_or(Literal::Null), Literal::String(b.into())])
+            })
+            .collect();
+        Box::new(LiteralNode::from_iter(tuples.into_iter(), schema).unwrap())
+    }
+
+    fn column(name: &str) -> Expression {
+        Expression::ColumnValue((None, Some(name.into())))
+    }
+
+    fn get_column(tuples: &[TupleLiteral], index: usize) -> Vec<Literal> {
+        tuples.iter().map(|tuple| tuple[index].clone()).collect()
+    }
+
+    #[test]
+    fn test_sort_in_memory() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let values = [(Some(3), "c"), (None, "n"), (Some(1), "a"), (Some(3), "b"), (Some(2), "d")];
+
+        let order_by = vec![OrderByExpression::new(column("A"), true, None)];
+        let mut node = SortNode::new(make_child(&values), order_by, &file_manager);
+        node.prepare().unwrap();
+        let results = get_plan_results(&mut node).unwrap();
+        assert_eq!(0, node.get_num_runs());
+        // Ties keep the child's order, and NULLs are last in ascending order by default.
+        assert_eq!(vec![Literal::String("a".into()), Literal::String("d".into()), Literal::String("c".into()),
+                        Literal::String("b".into()), Literal::String("n".into())],
+                   get_column(&results, 1));
+
+        let order_by = vec![OrderByExpression::new(column("A"), false, Some(false)),
+                            OrderByExpression::new(column("B"), true, None)];
+        let mut node = SortNode::new(make_child(&values), order_by, &file_manager);
+        node.prepare().unwrap();
+        let results = get_plan_results(&mut node).unwrap();
+        assert_eq!(vec![Literal::String("b".into()), Literal::String("c".into()), Literal::String("d".into()),
+                        Literal::String("a".into()), Literal::String("n".into())],
+                   get_column(&results, 1));
+    }
+
+    #[test]
+    fn test_external_sort() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let values: Vec<(Option<i32>, String)> = (0..500).map(|i| (Some((i * 37) % 100), format!("{}", i))).collect();
+        let borrowed: Vec<(Option<i32>, &str)> = values.iter().map(|&(a, ref b)| (a, b.as_str())).collect();
+
+        let order_by = vec![OrderByExpression::new(column("A"), true, None)];
+        let mut node = SortNode::with_memory_budget(make_child(&borrowed), order_by, &file_manager, 200);
+        node.prepare().unwrap();
+        let results = get_plan_results(&mut node).unwrap();
+        assert!(node.get_num_runs() > 1);
+        assert!(node.get_num_runs() <= MAX_MERGE_RUNS);
+
+        // The output is the same as a stable sort in memory.
+        let mut expected = values.clone();
+        expected.sort_by_key(|&(a, _)| a);
+        let expected: Vec<TupleLiteral> = expected.into_iter()
+            .map(|(a, b)| TupleLiteral::from_iter(vec![Literal::Int(a.unwrap()), Literal::String(b)]))
+            .collect();
+        assert_eq!(expected, results);
+
+        // Rescanning the node sorts again, and the run files are removed once the node is dropped.
+        assert_eq!(expected, get_plan_results(&mut node).unwrap());
+        drop(node);
+        assert_eq!(0, file_manager.get_file_paths().unwrap().len());
+    }
+}
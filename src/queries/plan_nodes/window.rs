@@ -0,0 +1,366 @@
+//! This module provides the window plan node, which computes window functions.
+
+use std::cmp::Ordering;
+use std::collections::HashMap;
+use std::ops::Range;
+
+use ::expressions::{Environment, Expression, Literal, OrderByExpression, WindowSpec};
+use ::functions::{AggregateFunction, Directory, WindowFunction, WindowRow};
+use ::queries::{PlanError, PlanNode, PlanResult};
+use ::relations::{ColumnInfo, Schema};
+use ::storage::{Tuple, TupleLiteral};
+use super::hashed_group_aggregate::get_aggregate_function;
+
+lazy_static! {
+    static ref DIRECTORY: Directory = Directory::new();
+}
+
+/// The function computed by a window function call. Aggregates are computed over the window frame
+/// of each row.
+enum CallFunction {
+    Window(Box<WindowFunction>),
+    Aggregate(Box<AggregateFunction>),
+}
+
+struct WindowCall {
+    expr: Expression,
+    function: CallFunction,
+    args: Vec<Expression>,
+    window: WindowSpec,
+}
+
+impl WindowCall {
+    fn new(expr: &Expression) -> PlanResult<WindowCall> {
+        let (name, distinct, args, window) = match *expr {
+            Expression::WindowFunction { ref name, distinct, ref args, ref window } => (name, distinct, args, window),
+            // This shouldn't happen...
+            _ => unreachable!(),
+        };
+        let func = try!(DIRECTORY.get(name.as_str()).map_err(|e| PlanError::CouldNotComputeWindowFunction(e.into())));
+
+        let function = if func.is_window() {
+            CallFunction::Window(func.get_as_window().unwrap())
+        } else if func.is_aggregate() {
+            let has_wildcard_arg = args.iter().any(|arg| *arg == Expression::ColumnValue((None, None)));
+            if has_wildcard_arg && name.as_str() != "COUNT" {
+                return Err(PlanError::WildCardInNonCountFunction(name.clone()));
+            }
+            if args.len() != 1 {
+                return Err(PlanError::Unimplemented);
+            }
+            if distinct {
+                let distinct_name = format!("{}#DISTINCT", name);
+                try!(DIRECTORY.get(distinct_name).map_err(|e| PlanError::CouldNotComputeWindowFunction(e.into())));
+            }
+            let func = get_aggregate_function(name, args.clone().into_iter(), distinct);
+            CallFunction::Aggregate(func.get_as_aggregate().unwrap())
+        } else {
+            return Err(PlanError::NotWindowFunction(name.clone()));
+        };
+
+        Ok(WindowCall {
+            expr: expr.clone(),
+            function: function,
+            args: args.clone(),
+            window: *window.clone(),
+        })
+    }
+
+    /// Evaluates the argument values of a row. `COUNT(*)` doesn't care what the value is.
+    fn evaluate_args(&self, env: &mut Environment) -> PlanResult<Vec<Literal>> {
+        if self.args.len() == 1 && self.args[0] == Expression::ColumnValue((None, None)) {
+            Ok(vec![Literal::Null])
+        } else {
+            evaluate_exprs(self.args.iter(), env)
+        }
+    }
+}
+
+fn evaluate_exprs<'a, I: Iterator<Item=&'a Expression>>(exprs: I, mut env: &mut Environment) -> PlanResult<Vec<Literal>> {
+    let mut values = Vec::new();
+    for expr in exprs {
+        values.push(try!(expr.evaluate(&mut Some(env), &None).map_err(PlanError::CouldNotComputeWindowFunction)));
+    }
+    Ok(values)
+}
+
+/// Compares the `ORDER BY` values of two rows of a partition.
+fn compare_keys(order_by: &[OrderByExpression], left: &[Literal], right: &[Literal]) -> Ordering {
+    for (i, term) in order_by.iter().enumerate() {
+        match term.compare(&left[i], &right[i]) {
+            Ordering::Equal => {}
+            result => return result,
+        }
+    }
+    Ordering::Equal
+}
+
+/// Splits the positions of a sorted partition into groups of peers, i.e. of rows that are equal
+/// according to the ordering.
+fn get_peer_groups(order_by: &[OrderByExpression], keys: &[&[Literal]]) -> Vec<Range<usize>> {
+    let mut groups = Vec::new();
+    let mut start = 0;
+    for pos in 1..(keys.len() + 1) {
+        if pos == keys.len() || compare_keys(order_by, keys[pos - 1], keys[pos]) != Ordering::Equal {
+            groups.push(start..pos);
+            start = pos;
+        }
+    }
+    groups
+}
+
+/// Computes a window function call for the rows of one partition, which are given in the
+/// partition's order.
+fn compute_partition(call: &mut WindowCall, args: &[Vec<Literal>], keys: &[&[Literal]]) -> PlanResult<Vec<Literal>> {
+    let mut results = Vec::with_capacity(args.len());
+
+    // The rows the aggregate currently holds the values of, so that it only needs to be computed
+    // from scratch when the frame stops growing, e.g. for a running total it never does.
+    let mut aggregated: Option<Range<usize>> = None;
+
+    for (peer_group, peers) in get_peer_groups(&call.window.order_by, keys).into_iter().enumerate() {
+        for index in peers.clone() {
+            // By default, the frame is every row up to the last of the row's peers, which is the
+            // whole partition if there is no ordering.
+            let frame = match call.window.frame {
+                Some(ref frame) => frame.get_rows(index, args.len()),
+                None => 0..peers.end,
+            };
+
+            let value = match call.function {
+                CallFunction::Window(ref func) => {
+                    let row = WindowRow {
+                        args: args,
+                        index: index,
+                        frame: frame,
+                        peers: peers.clone(),
+                        peer_group: peer_group,
+                    };
+                    try!(func.get_window_result(&row).map_err(|e| PlanError::CouldNotComputeWindowFunction(e.into())))
+                }
+                CallFunction::Aggregate(ref mut func) => {
+                    let start = match aggregated {
+                        Some(ref rows) if rows.start == frame.start && rows.end <= frame.end => rows.end,
+                        _ => {
+                            func.clear_result();
+                            frame.start
+                        }
+                    };
+                    for pos in start..frame.end {
+                        func.add_value(args[pos][0].clone());
+                    }
+                    aggregated = Some(frame);
+                    func.get_result()
+                }
+            };
+            results.push(value);
+        }
+    }
+    Ok(results)
+}
+
+/// Computes window functions. Unlike the grouping node, every input row is kept; the value of each
+/// window function for the row is added to the end of it. The rows are produced in the same order
+/// as the child produces them.
+pub struct WindowNode<'a> {
+    child: Box<PlanNode + 'a>,
+    input_schema: Schema,
+    output_schema: Option<Schema>,
+    calls: Vec<(String, WindowCall)>,
+    results: Option<Vec<TupleLiteral>>,
+    current_tuple: Option<TupleLiteral>,
+    parent_env: Option<Environment>,
+}
+
+impl<'a> WindowNode<'a> {
+    /// Instantiate a new window node.
+    ///
+    /// # Arguments
+    /// * child - The child of the node.
+    /// * window_calls - A list of window function calls along with their projection name.
+    pub fn new(child: Box<PlanNode + 'a>, window_calls: Vec<(String, Expression)>) -> PlanResult<WindowNode<'a>> {
+        let mut calls = Vec::with_capacity(window_calls.len());
+        for (name, expr) in window_calls {
+            calls.push((name, try!(WindowCall::new(&expr))));
+        }
+
+        let input_schema = child.get_schema();
+        Ok(WindowNode {
+            child: child,
+            input_schema: input_schema,
+            output_schema: None,
+            calls: calls,
+            results: None,
+            current_tuple: None,
+            parent_env: None,
+        })
+    }
+
+    /// Computes the values of a window function call for each of the rows.
+    fn compute_call(call: &mut WindowCall,
+                    rows: &[TupleLiteral],
+                    schema: &Schema,
+                    parent_env: Option<&Environment>)
+                    -> PlanResult<Vec<Literal>> {
+        // Split the rows into partitions, keeping the partitions and the rows in each of them in
+        // the order they were produced in.
+        let mut partitions: Vec<Vec<usize>> = Vec::new();
+        let mut partition_indexes: HashMap<TupleLiteral, usize> = HashMap::new();
+        let mut keys = Vec::with_capacity(rows.len());
+        let mut args = Vec::with_capacity(rows.len());
+        for (i, row) in rows.iter().enumerate() {
+            let mut env = Environment::with_parent(parent_env);
+            env.add_tuple(schema.clone(), row.clone());
+
+            let partition_key = TupleLiteral::from_iter(try!(evaluate_exprs(call.window.partition_by.iter(), &mut env)));
+            let next_index = partitions.len();
+            let index = *partition_indexes.entry(partition_key).or_insert(next_index);
+            if index == next_index {
+                partitions.push(Vec::new());
+            }
+            partitions[index].push(i);
+
+            keys.push(try!(evaluate_exprs(call.window.order_by.iter().map(|term| &term.expression), &mut env)));
+            args.push(try!(call.evaluate_args(&mut env)));
+        }
+
+        let mut results = vec![Literal::Null; rows.len()];
+        for mut partition in partitions {
+            // The sort is stable, so rows that are equal keep the order they were produced in.
+            {
+                let order_by = &call.window.order_by;
+                partition.sort_by(|&left, &right| compare_keys(order_by, &keys[left], &keys[right]));
+            }
+
+            let partition_args: Vec<Vec<Literal>> = partition.iter().map(|&i| args[i].clone()).collect();
+            let partition_keys: Vec<&[Literal]> = partition.iter().map(|&i| keys[i].as_slice()).collect();
+            let values = try!(compute_partition(call, &partition_args, &partition_keys));
+            for (&i, value) in partition.iter().zip(values.into_iter()) {
+                results[i] = value;
+            }
+        }
+        Ok(results)
+    }
+
+    fn compute_results(&mut self) -> PlanResult<()> {
+        let mut rows = Vec::new();
+        while let Some(tuple) = try!(self.child.get_next_tuple()) {
+            rows.push(TupleLiteral::from_tuple(tuple));
+        }
+
+        let mut results = rows.clone();
+        for &mut (_, ref mut call) in self.calls.iter_mut() {
+            let values = try!(WindowNode::compute_call(call, &rows, &self.input_schema, self.parent_env.as_ref()));
+            for (result, value) in results.iter_mut().zip(values.into_iter()) {
+                result.add_value(value);
+            }
+        }
+
+        // The results are taken from the end.
+        results.reverse();
+        self.results = Some(results);
+        Ok(())
+    }
+}
+
+impl<'a> PlanNode for WindowNode<'a> {
+    fn get_schema(&self) -> Schema {
+        self.output_schema.clone().unwrap_or(Schema::new())
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        if self.results.is_none() {
+            try!(self.compute_results());
+        }
+
+        self.current_tuple = self.results.as_mut().and_then(|results| results.pop());
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        let mut schema = self.input_schema.clone();
+        for &(ref name, ref call) in self.calls.iter() {
+            let col_type = try!(call.expr.get_column_type(&self.input_schema)
+                .map_err(PlanError::CouldNotComputeWindowFunction));
+            try!(schema.add_column(ColumnInfo::with_name(col_type, name.clone())));
+        }
+        self.output_schema = Some(schema);
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        // The child's tuples may be different this time, e.g. in a correlated subquery, so the
+        // window functions are computed again.
+        self.results = None;
+        self.current_tuple = None;
+        self.child.initialize();
+    }
+
+    fn set_parent_env(&mut self, env: Environment) {
+        self.parent_env = Some(env.clone());
+        self.child.set_parent_env(env);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::expressions::{FrameBound, WindowFrame};
+    use ::queries::get_plan_results;
+    use ::queries::plan_nodes::LiteralNode;
+    use ::relations::ColumnType;
+
+    fn column(name: &str) -> Expression {
+        Expression::ColumnValue((None, Some(name.into())))
+    }
+
+    fn call(name: &str, args: Vec<Expression>, window: WindowSpec) -> Expression {
+        Expression::WindowFunction {
+            name: name.into(),
+            distinct: false,
+            args: args,
+            window: Box::new(window),
+        }
+    }
+
+    #[test]
+    fn test_window_node() {
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+                                               ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO")])
+            .unwrap();
+        let values = [(1, 5), (2, 3), (1, 2), (2, 3), (1, 4)];
+        let tuples = values.iter().map(|&(a, b)| TupleLiteral::from_iter(vec![Literal::Int(a), Literal::Int(b)]));
+        let child = Box::new(LiteralNode::from_iter(tuples, schema).unwrap());
+
+        let by_b = WindowSpec::new(vec![column("A")], vec![OrderByExpression::new(column("B"), true, None)], None);
+        let last_two = WindowSpec::new(vec![column("A")],
+                                       vec![OrderByExpression::new(column("B"), true, None)],
+                                       Some(WindowFrame::new(FrameBound::Preceding(1), FrameBound::CurrentRow)));
+        let calls = vec![("#WIN1".into(), call("RANK", vec![], by_b.clone())),
+                         ("#WIN2".into(), call("SUM", vec![column("B")], last_two)),
+                         ("#WIN3".into(), call("COUNT", vec![column("B")], WindowSpec::new(vec![], vec![], None)))];
+        let mut node = WindowNode::new(child, calls).unwrap();
+        node.prepare().unwrap();
+        assert_eq!(5, node.get_schema().num_columns());
+
+        // The rows come out in the order they went in, with the values of the calls added.
+        let results = get_plan_results(&mut node).unwrap();
+        let expected: Vec<TupleLiteral> = [(1, 5, 3, 9, 5), (2, 3, 1, 3, 5), (1, 2, 1, 2, 5), (2, 3, 1, 6, 5), (1, 4, 2, 6, 5)]
+            .iter()
+            .map(|&(a, b, rank, sum, count)| TupleLiteral::from_iter(vec![Literal::Int(a), Literal::Int(b), Literal::Int(rank),
+                                                                          Literal::Int(sum), Literal::Int(count)]))
+            .collect();
+        assert_eq!(expected, results);
+
+        // Only window functions and aggregates can be called with an OVER clause.
+        let schema = Schema::with_columns(vec![ColumnInfo::with_name(ColumnType::Integer, "A")]).unwrap();
+        let child = Box::new(LiteralNode::from_iter(vec![].into_iter(), schema).unwrap());
+        match WindowNode::new(child, vec![("#WIN1".into(), call("ABS", vec![column("A")], by_b))]) {
+            Err(PlanError::NotWindowFunction(ref name)) if name == "ABS" => {}
+            _ => panic!("Expected ABS not to be a window function"),
+        }
+    }
+}
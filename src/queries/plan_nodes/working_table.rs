@@ -0,0 +1,152 @@
+//! This module provides the working table of a recursive query, and the plan node that reads it.
+//!
+//! A recursive query like `WITH RECURSIVE t AS (anchor UNION ALL recursive) ...` is evaluated by
+//! repeatedly evaluating its recursive part, where each evaluation reads the rows produced by the
+//! one before it from the [`WorkingTable`]. The [`WorkingTableScanNode`] is the plan node for the
+//! recursive part's references to the query.
+//!
+//! [`WorkingTable`]: struct.WorkingTable.html
+//! [`WorkingTableScanNode`]: struct.WorkingTableScanNode.html
+
+use std::sync::{Arc, RwLock};
+
+use ::Schema;
+use ::queries::plan_nodes::PlanNode;
+use ::queries::planning::{PlanError, PlanResult};
+use ::relations::SchemaError;
+use ::storage::{Tuple, TupleLiteral};
+
+#[derive(Debug)]
+struct WorkingTableData {
+    name: String,
+    column_names: Option<Vec<String>>,
+    schema: Option<Schema>,
+    tuples: Vec<TupleLiteral>,
+    referenced: bool,
+}
+
+/// The rows produced by the latest evaluation of the recursive part of a recursive query. The
+/// table is shared by the node evaluating the query and the nodes reading the table, so cloning a
+/// working table gives another handle to the same table.
+#[derive(Clone, Debug)]
+pub struct WorkingTable {
+    data: Arc<RwLock<WorkingTableData>>,
+}
+
+impl PartialEq for WorkingTable {
+    fn eq(&self, other: &WorkingTable) -> bool {
+        Arc::ptr_eq(&self.data, &other.data)
+    }
+}
+
+impl WorkingTable {
+    /// Creates a new, empty working table.
+    ///
+    /// # Arguments
+    /// * name - The name of the recursive query, which the table is referred to by.
+    /// * column_names - The names given to the query's columns, if any.
+    pub fn new(name: String, column_names: Option<Vec<String>>) -> WorkingTable {
+        WorkingTable {
+            data: Arc::new(RwLock::new(WorkingTableData {
+                name: name,
+                column_names: column_names,
+                schema: None,
+                tuples: vec![],
+                referenced: false,
+            })),
+        }
+    }
+
+    /// Retrieves the name of the recursive query.
+    pub fn get_name(&self) -> String {
+        self.data.read().unwrap().name.clone()
+    }
+
+    /// Retrieves the schema of the table, if it has been set.
+    pub fn get_schema(&self) -> Option<Schema> {
+        self.data.read().unwrap().schema.clone()
+    }
+
+    /// Sets the schema of the table from the schema of the non-recursive part of the query. The
+    /// columns are renamed to the names given to the query's columns, if any, and put on a table
+    /// named after the query.
+    pub fn set_schema(&self, mut schema: Schema) -> Result<(), SchemaError> {
+        let mut data = self.data.write().unwrap();
+        if let Some(ref names) = data.column_names {
+            try!(schema.set_column_names(names));
+        }
+        try!(schema.set_table_name(data.name.as_str()));
+        data.schema = Some(schema);
+        Ok(())
+    }
+
+    /// Records that the recursive part of the query refers to the table.
+    pub fn set_referenced(&self) {
+        self.data.write().unwrap().referenced = true;
+    }
+
+    /// Whether the recursive part of the query refers to the table, i.e. whether the query is
+    /// actually recursive.
+    pub fn is_referenced(&self) -> bool {
+        self.data.read().unwrap().referenced
+    }
+
+    /// Replaces the rows of the table.
+    pub fn set_tuples(&self, tuples: Vec<TupleLiteral>) {
+        self.data.write().unwrap().tuples = tuples;
+    }
+
+    /// Retrieves a copy of the row at the given index, if there is one.
+    pub fn get_tuple(&self, index: usize) -> Option<TupleLiteral> {
+        self.data.read().unwrap().tuples.get(index).cloned()
+    }
+}
+
+/// A plan node that produces the rows of a working table.
+pub struct WorkingTableScanNode {
+    table: WorkingTable,
+    index: usize,
+    current_tuple: Option<TupleLiteral>,
+}
+
+impl WorkingTableScanNode {
+    /// Instantiate a new working table scan node.
+    ///
+    /// # Arguments
+    /// * table - The working table to read.
+    pub fn new(table: WorkingTable) -> WorkingTableScanNode {
+        WorkingTableScanNode {
+            table: table,
+            index: 0,
+            current_tuple: None,
+        }
+    }
+}
+
+impl PlanNode for WorkingTableScanNode {
+    fn get_schema(&self) -> Schema {
+        self.table.get_schema().unwrap_or(Schema::new())
+    }
+
+    fn get_next_tuple(&mut self) -> PlanResult<Option<&mut Tuple>> {
+        self.current_tuple = self.table.get_tuple(self.index);
+        self.index += 1;
+
+        Ok(match self.current_tuple.as_mut() {
+            Some(tuple) => Some(tuple),
+            None => None,
+        })
+    }
+
+    fn prepare(&mut self) -> PlanResult<()> {
+        if self.table.get_schema().is_none() {
+            return Err(PlanError::NodeNotPrepared);
+        }
+        Ok(())
+    }
+
+    fn initialize(&mut self) {
+        self.index = 0;
+        self.current_tuple = None;
+    }
+}
@@ -0,0 +1,668 @@
+//! This module contains a cost-based query planner, which uses the statistics collected by
+//! `ANALYZE` to estimate the costs of plans, and chooses the order to join tables in.
+
+use std::collections::HashSet;
+
+use ::{ColumnName, Schema};
+use ::expressions::{Expression, ExpressionError, ExpressionProcessor, FromClause, FromClauseType,
+                    JoinConditionType, JoinType, SelectClause, SelectValue};
+use ::queries::{NestedLoopJoinNode, NodeResult, PlanError, PlanNode, Planner, PlanResult, ProjectNode,
+                RenameNode, SimpleFilterNode, SimplePlanner, DEFAULT_SORT_MEMORY, make_simple_select};
+use ::relations::ColumnStats;
+use ::storage::{FileManager, TableManager};
+
+use super::plan_cost::PlanCost;
+use super::selectivity::estimate_selectivity;
+use super::simple_planner::{finish_select_plan, make_set_operation_plan, make_subquery_joins,
+                            prepare_aggregates, prepare_windows, split_subquery_tests};
+
+/// The number of tuples assumed for a table that hasn't been analyzed.
+const DEFAULT_NUM_TUPLES: f32 = 1000.0;
+
+/// The tuple size assumed for a table that hasn't been analyzed.
+const DEFAULT_TUPLE_SIZE: f32 = 50.0;
+
+/// The number of data pages assumed for a table that hasn't been analyzed.
+const DEFAULT_NUM_PAGES: f32 = 20.0;
+
+/// The most leaves whose join orders are enumerated, since the number of subsets of the leaves
+/// grows exponentially. Larger joins are done in the order the `FROM` clause lists them.
+const MAX_JOIN_LEAVES: usize = 12;
+
+/// A leaf of the join tree being ordered. This is either a base table, or a join that can't be
+/// reordered with its surroundings, such as an outer join.
+struct JoinLeaf<'a> {
+    /// The part of the `FROM` clause the leaf comes from.
+    from_clause: FromClause,
+    /// The plan of a leaf that isn't a base table. It is built up front to find out its schema.
+    node: Option<Box<PlanNode + 'a>>,
+    /// The schema of the leaf's tuples.
+    schema: Schema,
+    /// The statistics of each column of the leaf's schema.
+    stats: Vec<ColumnStats>,
+    /// The estimated cost of the leaf's plan, including its conjuncts.
+    cost: PlanCost,
+}
+
+/// A term of the conjunction of the `WHERE` clause and the `ON` clauses of the joins.
+struct Conjunct {
+    /// The term itself.
+    expr: Expression,
+    /// The set of leaves whose columns the term refers to, as a bit-mask. If this is `None`, the
+    /// term is applied on top of the joins, e.g. because it contains a subquery.
+    leaves: Option<usize>,
+    /// The estimated selectivity of the term.
+    selectivity: f32,
+}
+
+/// Collects the columns an expression refers to, and whether it contains any subqueries.
+struct ColumnCollector {
+    columns: Vec<ColumnName>,
+    has_subquery: bool,
+}
+
+impl ExpressionProcessor for ColumnCollector {
+    fn enter(&mut self, node: &Expression) -> Result<(), ExpressionError> {
+        match *node {
+            Expression::ColumnValue(ref name) => self.columns.push(name.clone()),
+            Expression::Subquery(_) | Expression::Exists { .. } | Expression::InSubquery { .. } => {
+                self.has_subquery = true
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        Ok(node.clone())
+    }
+}
+
+/// Replaces references to a table's alias with references to the table itself, so that predicates
+/// on a renamed table can be applied by the scan of the table.
+struct AliasRemover {
+    alias: String,
+    table: String,
+}
+
+impl ExpressionProcessor for AliasRemover {
+    fn enter(&mut self, _node: &Expression) -> Result<(), ExpressionError> {
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        Ok(match *node {
+            Expression::ColumnValue((Some(ref table), ref column)) if *table == self.alias => {
+                Expression::ColumnValue((Some(self.table.clone()), column.clone()))
+            }
+            ref node => node.clone(),
+        })
+    }
+}
+
+/// Splits the leaves of the join tree out of a `FROM` clause. Inner joins and cross joins with `ON`
+/// clauses are flattened, and their `ON` clauses are added to the conjuncts, since their leaves
+/// can be joined in any order. Everything else becomes a leaf.
+fn collect_leaves(clause: &FromClause, leaves: &mut Vec<FromClause>, conjuncts: &mut Vec<Expression>) {
+    match **clause {
+        FromClauseType::JoinExpression { ref left,
+                                         ref right,
+                                         join_type: JoinType::Inner,
+                                         condition_type: JoinConditionType::OnExpr(ref expr) } |
+        FromClauseType::JoinExpression { ref left,
+                                         ref right,
+                                         join_type: JoinType::Cross,
+                                         condition_type: JoinConditionType::OnExpr(ref expr) } => {
+            collect_leaves(left, leaves, conjuncts);
+            collect_leaves(right, leaves, conjuncts);
+            add_conjuncts(expr, conjuncts);
+        }
+        _ => leaves.push(clause.clone()),
+    }
+}
+
+/// Adds the terms of a conjunction to the list of conjuncts.
+fn add_conjuncts(expr: &Expression, conjuncts: &mut Vec<Expression>) {
+    match *expr {
+        Expression::AND(ref exprs) => {
+            for expr in exprs.iter() {
+                add_conjuncts(expr, conjuncts);
+            }
+        }
+        Expression::True => {}
+        ref expr => conjuncts.push(expr.clone()),
+    }
+}
+
+/// Combines conjuncts back into a single predicate, if there are any.
+fn make_predicate(mut conjuncts: Vec<Expression>) -> Option<Expression> {
+    match conjuncts.len() {
+        0 => None,
+        1 => conjuncts.pop(),
+        _ => Some(Expression::AND(conjuncts)),
+    }
+}
+
+/// Returns the table names of a schema's columns, in order, if the columns of each table are next
+/// to each other and every column has a table name. This is needed to be able to put the columns
+/// of reordered leaves back in their original order.
+fn get_table_names(schema: &Schema) -> Option<Vec<String>> {
+    let mut result: Vec<String> = Vec::new();
+    for column in schema.iter() {
+        match column.table_name {
+            Some(ref name) => {
+                if result.last() == Some(name) {
+                    continue;
+                }
+                if result.contains(name) {
+                    return None;
+                }
+                result.push(name.clone());
+            }
+            None => return None,
+        }
+    }
+    Some(result)
+}
+
+/// Estimates the cost of a nested-loop join, which scans the right child once for each tuple of
+/// the left child.
+///
+/// # Arguments
+/// * left - The cost of the left child.
+/// * right - The cost of the right child.
+/// * selectivity - The selectivity of the join predicate.
+fn get_join_cost(left: &PlanCost, right: &PlanCost, selectivity: f32) -> PlanCost {
+    PlanCost::new(left.num_tuples * right.num_tuples * selectivity,
+                  left.tuple_size + right.tuple_size,
+                  left.cpu_cost + left.num_tuples * right.cpu_cost + left.num_tuples * right.num_tuples,
+                  left.num_block_ios + left.num_tuples * right.num_block_ios)
+}
+
+/// This planner estimates the costs of plans from the statistics of the tables involved, and uses
+/// them to choose the order of the joins in the `FROM` clause. Join orders are enumerated in the
+/// style of the System R optimizer: the cheapest left-deep plan for each set of leaves is found by
+/// dynamic programming, from the cheapest plans for its subsets.
+///
+/// Terms of the `WHERE` and `ON` clauses are applied as early as possible, i.e. in the scan of a
+/// table if they only refer to that table, or otherwise in the first join that has all of the
+/// tables they refer to.
+pub struct CostBasedPlanner<'a> {
+    file_manager: &'a FileManager,
+    table_manager: &'a TableManager,
+    /// Used to plan the leaves of the join tree that can't be reordered.
+    simple_planner: SimplePlanner<'a>,
+    sort_memory: usize,
+}
+
+impl<'a> CostBasedPlanner<'a> {
+    /// Instantiates a new CostBasedPlanner, whose sorts use the default memory budget.
+    pub fn new(file_manager: &'a FileManager, table_manager: &'a TableManager) -> CostBasedPlanner<'a> {
+        CostBasedPlanner::with_sort_memory(file_manager, table_manager, DEFAULT_SORT_MEMORY)
+    }
+
+    /// Instantiates a new CostBasedPlanner.
+    ///
+    /// # Arguments
+    /// * file_manager - A reference to the file manager.
+    /// * table_manager - A reference to the table manager.
+    /// * sort_memory - The number of bytes of tuples the sorts of its plans may keep in memory.
+    pub fn with_sort_memory(file_manager: &'a FileManager,
+                            table_manager: &'a TableManager,
+                            sort_memory: usize)
+                            -> CostBasedPlanner<'a> {
+        CostBasedPlanner {
+            file_manager: file_manager,
+            table_manager: table_manager,
+            simple_planner: SimplePlanner::with_sort_memory(file_manager, table_manager, sort_memory),
+            sort_memory: sort_memory,
+        }
+    }
+
+    /// Finds the schema, statistics and cost of a leaf of the join tree.
+    fn make_leaf(&self, clause: FromClause) -> PlanResult<JoinLeaf> {
+        match *clause.clone() {
+            FromClauseType::BaseTable { ref table, ref alias } => {
+                let table = try!(self.table_manager.get_table(self.file_manager, table.clone()));
+                let mut schema = table.get_schema();
+                if let Some(ref name) = *alias {
+                    try!(schema.set_table_name(name.as_str()));
+                }
+
+                let stats = table.get_stats();
+                let analyzed = stats.column_stats.iter().any(|c| c.num_unique_values.is_some());
+                let cost = if analyzed {
+                    PlanCost::new(stats.num_tuples as f32,
+                                  stats.avg_tuple_size,
+                                  stats.num_tuples as f32,
+                                  stats.num_data_pages as f32)
+                } else {
+                    PlanCost::new(DEFAULT_NUM_TUPLES, DEFAULT_TUPLE_SIZE, DEFAULT_NUM_TUPLES, DEFAULT_NUM_PAGES)
+                };
+                Ok(JoinLeaf {
+                    from_clause: clause,
+                    node: None,
+                    schema: schema,
+                    stats: stats.column_stats,
+                    cost: cost,
+                })
+            }
+            FromClauseType::JoinExpression { .. } | FromClauseType::WorkingTable { .. } => {
+                let mut node = try!(self.simple_planner.make_join_tree(clause.clone(), None));
+                try!(node.prepare());
+                let schema = node.get_schema();
+                let stats = vec![ColumnStats::new(); schema.num_columns()];
+                Ok(JoinLeaf {
+                    from_clause: clause,
+                    node: Some(node),
+                    schema: schema,
+                    stats: stats,
+                    cost: PlanCost::new(DEFAULT_NUM_TUPLES, DEFAULT_TUPLE_SIZE, DEFAULT_NUM_TUPLES, DEFAULT_NUM_PAGES),
+                })
+            }
+            FromClauseType::Subquery { ref subquery, ref alias, ref column_names } => {
+                let node = try!(self.make_plan(*subquery.clone()));
+                let mut node: Box<PlanNode> = match *column_names {
+                    Some(ref names) => Box::new(RenameNode::with_column_names(node, alias.as_str(), names.clone())),
+                    None => Box::new(RenameNode::new(node, alias.as_str())),
+                };
+                try!(node.prepare());
+                let schema = node.get_schema();
+                let stats = vec![ColumnStats::new(); schema.num_columns()];
+                Ok(JoinLeaf {
+                    from_clause: clause,
+                    node: Some(node),
+                    schema: schema,
+                    stats: stats,
+                    cost: PlanCost::new(DEFAULT_NUM_TUPLES, DEFAULT_TUPLE_SIZE, DEFAULT_NUM_TUPLES, DEFAULT_NUM_PAGES),
+                })
+            }
+        }
+    }
+
+    /// Builds the plan of a leaf of the join tree, applying the conjuncts that only refer to it.
+    fn make_leaf_plan<'b>(&'b self, leaf: &mut JoinLeaf<'b>, conjuncts: Vec<Expression>) -> NodeResult<'b> {
+        let predicate = make_predicate(conjuncts);
+        if let Some(node) = leaf.node.take() {
+            let mut node: Box<PlanNode + 'b> = match predicate {
+                Some(predicate) => Box::new(SimpleFilterNode::with_planner(node, predicate, self)),
+                None => node,
+            };
+            try!(node.prepare());
+            return Ok(node);
+        }
+
+        match *leaf.from_clause {
+            FromClauseType::BaseTable { ref table, alias: Some(ref alias) } => {
+                let mut remover = AliasRemover {
+                    alias: alias.clone(),
+                    table: table.clone(),
+                };
+                let predicate = match predicate {
+                    Some(mut expr) => Some(try!(expr.traverse(&mut remover).map_err(PlanError::CouldNotApplyPredicate))),
+                    None => None,
+                };
+                let node = try!(make_simple_select(self.file_manager, self.table_manager, table.clone(), predicate));
+                let mut node: Box<PlanNode + 'b> = Box::new(RenameNode::new(node, alias.as_str()));
+                try!(node.prepare());
+                Ok(node)
+            }
+            FromClauseType::BaseTable { ref table, alias: None } => {
+                make_simple_select(self.file_manager, self.table_manager, table.clone(), predicate)
+            }
+            FromClauseType::JoinExpression { .. } | FromClauseType::Subquery { .. } |
+            FromClauseType::WorkingTable { .. } => unreachable!(),
+        }
+    }
+
+    /// Finds the set of leaves a conjunct refers to, or `None` if it has to be applied on top of
+    /// the joins.
+    fn get_conjunct_leaves(expr: &Expression, leaves: &[JoinLeaf]) -> Option<usize> {
+        let mut collector = ColumnCollector {
+            columns: Vec::new(),
+            has_subquery: false,
+        };
+        if expr.clone().traverse(&mut collector).is_err() || collector.has_subquery {
+            return None;
+        }
+
+        let mut result = 0;
+        for name in collector.columns.iter() {
+            let matches: Vec<usize> = leaves.iter()
+                .enumerate()
+                .filter(|&(_, leaf)| !leaf.schema.find_columns(name).is_empty())
+                .map(|(i, _)| i)
+                .collect();
+            // Columns that are ambiguous, or that don't exist, are left for evaluation to report.
+            if matches.len() != 1 {
+                return None;
+            }
+            result |= 1 << matches[0];
+        }
+        if result == 0 { None } else { Some(result) }
+    }
+
+    /// Estimates the selectivity of a conjunct from the statistics of the leaves it refers to.
+    fn estimate_conjunct_selectivity(expr: &Expression, leaves: &[JoinLeaf], mask: usize) -> f32 {
+        let mut schema = Schema::new();
+        let mut stats = Vec::new();
+        for (i, leaf) in leaves.iter().enumerate() {
+            if mask & (1 << i) != 0 {
+                if schema.add_columns(leaf.schema.clone()).is_err() {
+                    return super::selectivity::DEFAULT_SELECTIVITY;
+                }
+                stats.extend(leaf.stats.iter().cloned());
+            }
+        }
+        estimate_selectivity(expr, &schema, &stats)
+    }
+
+    /// Returns whether a conjunct becomes applicable when a leaf is joined to a set of leaves,
+    /// i.e. it refers to both sides of the join and nothing else.
+    fn joins(conjunct: &Conjunct, left: usize, right: usize) -> bool {
+        match conjunct.leaves {
+            Some(mask) => mask & left != 0 && mask & right != 0 && mask & !(left | right) == 0,
+            None => false,
+        }
+    }
+
+    /// Finds the cheapest order to join the leaves in, by dynamic programming over the sets of
+    /// leaves. Each set's cheapest plan is the cheapest way of joining one of its leaves to the
+    /// cheapest plan of the rest of the set. Ties are broken in favor of the `FROM` clause's order.
+    fn choose_join_order(leaves: &[JoinLeaf], conjuncts: &[Conjunct]) -> (Vec<usize>, PlanCost) {
+        let num_leaves = leaves.len();
+        let all_leaves = (1 << num_leaves) - 1;
+        let mut best: Vec<Option<(Vec<usize>, PlanCost)>> = vec![None; all_leaves + 1];
+        for (i, leaf) in leaves.iter().enumerate() {
+            best[1 << i] = Some((vec![i], leaf.cost));
+        }
+
+        for set in 1..(all_leaves + 1) {
+            if (set as u32).count_ones() < 2 {
+                continue;
+            }
+            for i in (0..num_leaves).rev() {
+                let leaf = 1 << i;
+                if set & leaf == 0 {
+                    continue;
+                }
+                let rest = set ^ leaf;
+                let candidate = match best[rest] {
+                    Some((ref order, ref cost)) => {
+                        let selectivity = conjuncts.iter()
+                            .filter(|c| CostBasedPlanner::joins(c, rest, leaf))
+                            .fold(1.0, |result, c| result * c.selectivity);
+                        let mut order = order.clone();
+                        order.push(i);
+                        (order, get_join_cost(cost, &leaves[i].cost, selectivity))
+                    }
+                    None => continue,
+                };
+                let better = match best[set] {
+                    Some((_, ref cost)) => candidate.1.get_total_cost() < cost.get_total_cost(),
+                    None => true,
+                };
+                if better {
+                    best[set] = Some(candidate);
+                }
+            }
+        }
+        best[all_leaves].take().unwrap()
+    }
+
+    /// Estimates the cost of joining the leaves in the given order.
+    fn get_join_order_cost(leaves: &[JoinLeaf], conjuncts: &[Conjunct], order: &[usize]) -> PlanCost {
+        let mut cost = leaves[order[0]].cost;
+        let mut joined = 1 << order[0];
+        for &i in order[1..].iter() {
+            let leaf = 1 << i;
+            let selectivity = conjuncts.iter()
+                .filter(|c| CostBasedPlanner::joins(c, joined, leaf))
+                .fold(1.0, |result, c| result * c.selectivity);
+            cost = get_join_cost(&cost, &leaves[i].cost, selectivity);
+            joined |= leaf;
+        }
+        cost
+    }
+
+    /// Plans the `FROM` and `WHERE` clauses of a `SELECT` clause, and estimates the cost of the
+    /// plan.
+    fn make_join_plan<'b>(&'b self,
+                          from_clause: &FromClause,
+                          where_expr: Option<&Expression>)
+                          -> PlanResult<(Box<PlanNode + 'b>, PlanCost)> {
+        let mut leaf_clauses = Vec::new();
+        let mut exprs = Vec::new();
+        collect_leaves(from_clause, &mut leaf_clauses, &mut exprs);
+        if let Some(expr) = where_expr {
+            add_conjuncts(expr, &mut exprs);
+        }
+
+        let mut leaves = Vec::new();
+        for clause in leaf_clauses {
+            leaves.push(try!(self.make_leaf(clause)));
+        }
+        let conjuncts: Vec<Conjunct> = exprs.into_iter()
+            .map(|expr| {
+                let mask = CostBasedPlanner::get_conjunct_leaves(&expr, &leaves);
+                let selectivity = match mask {
+                    Some(mask) => CostBasedPlanner::estimate_conjunct_selectivity(&expr, &leaves, mask),
+                    None => 1.0,
+                };
+                Conjunct {
+                    expr: expr,
+                    leaves: mask,
+                    selectivity: selectivity,
+                }
+            })
+            .collect();
+        for (i, leaf) in leaves.iter_mut().enumerate() {
+            for conjunct in conjuncts.iter().filter(|c| c.leaves == Some(1 << i)) {
+                leaf.cost.num_tuples *= conjunct.selectivity;
+            }
+        }
+
+        // The leaves can only be reordered if their columns can be put back in order afterwards.
+        let table_names: Option<Vec<Vec<String>>> = leaves.iter().map(|leaf| get_table_names(&leaf.schema)).collect();
+        let can_reorder = match table_names {
+            Some(ref names) => {
+                let all: Vec<&String> = names.iter().flat_map(|names| names.iter()).collect();
+                let unique: HashSet<&String> = all.iter().cloned().collect();
+                all.len() == unique.len()
+            }
+            None => false,
+        };
+        let (order, cost) = if can_reorder && leaves.len() <= MAX_JOIN_LEAVES {
+            CostBasedPlanner::choose_join_order(&leaves, &conjuncts)
+        } else {
+            let order: Vec<usize> = (0..leaves.len()).collect();
+            let cost = CostBasedPlanner::get_join_order_cost(&leaves, &conjuncts, &order);
+            (order, cost)
+        };
+        debug!("Joining leaves in order {:?}, with estimated cost {}.", order, cost);
+
+        // Build the left-deep join tree, applying each conjunct at the first join it can be.
+        let mut cur_node: Option<Box<PlanNode + 'b>> = None;
+        let mut joined = 0;
+        for &i in order.iter() {
+            let leaf = 1 << i;
+            let local = conjuncts.iter().filter(|c| c.leaves == Some(leaf)).map(|c| c.expr.clone()).collect();
+            let node = try!(self.make_leaf_plan(&mut leaves[i], local));
+            cur_node = Some(match cur_node {
+                Some(left) => {
+                    let predicate = make_predicate(conjuncts.iter()
+                        .filter(|c| CostBasedPlanner::joins(c, joined, leaf))
+                        .map(|c| c.expr.clone())
+                        .collect());
+                    let mut join: Box<PlanNode + 'b> = Box::new(NestedLoopJoinNode::new(left, node, JoinType::Inner, predicate));
+                    try!(join.prepare());
+                    join
+                }
+                None => node,
+            });
+            joined |= leaf;
+        }
+        let mut cur_node = cur_node.unwrap();
+
+        if let Some(predicate) = make_predicate(conjuncts.iter()
+            .filter(|c| c.leaves.is_none())
+            .map(|c| c.expr.clone())
+            .collect()) {
+            cur_node = Box::new(SimpleFilterNode::with_planner(cur_node, predicate, self));
+            try!(cur_node.prepare());
+        }
+
+        if order.iter().enumerate().any(|(i, &leaf)| i != leaf) {
+            let values = table_names.unwrap()
+                .into_iter()
+                .flat_map(|names| names.into_iter())
+                .map(|name| SelectValue::WildcardColumn { table: Some(name) })
+                .collect();
+            cur_node = Box::new(ProjectNode::new(cur_node, values, self));
+            try!(cur_node.prepare());
+        }
+        Ok((cur_node, cost))
+    }
+}
+
+impl<'a> Planner for CostBasedPlanner<'a> {
+    fn make_plan(&self, mut clause: SelectClause) -> NodeResult {
+        if clause.set_operation.is_some() {
+            return make_set_operation_plan(clause, self, self.file_manager, self.sort_memory);
+        }
+
+        let node = match clause.from_clause.clone() {
+            Some(ref from_clause) => {
+                // Look for aggregate function calls, and transform expressions that include them so
+                // that we can compute them all in one grouping / aggregate plan node.
+                let extractor = try!(prepare_aggregates(&mut clause));
+                let windows = try!(prepare_windows(&mut clause));
+
+                // EXISTS and IN subquery tests are done with joins once the rest of the WHERE
+                // clause has been applied.
+                let (where_expr, subquery_tests) = split_subquery_tests(clause.where_expr.take());
+                clause.where_expr = where_expr;
+
+                let (cur_node, cost) = try!(self.make_join_plan(from_clause, clause.where_expr.as_ref()));
+                let cur_node = try!(make_subquery_joins(cur_node, subquery_tests, self));
+                try!(finish_select_plan(cur_node, &clause, &extractor, &windows, self, self.file_manager,
+                                        self.sort_memory, Some(&cost)))
+            }
+            None => {
+                let mut cur_node = Box::new(try!(ProjectNode::scalar(clause.values, self)));
+                try!(cur_node.prepare());
+                cur_node
+            }
+        };
+        Ok(node)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::Server;
+    use ::expressions::{CompareType, Literal};
+    use ::parser::select::select_clause;
+    use ::parser::statements;
+    use ::queries::get_plan_results;
+    use ::storage::TupleLiteral;
+
+    fn get_results(server: &Server, sql: &[u8]) -> Vec<TupleLiteral> {
+        get_results_with_sort_memory(server, sql, DEFAULT_SORT_MEMORY)
+    }
+
+    fn get_results_with_sort_memory(server: &Server, sql: &[u8], sort_memory: usize) -> Vec<TupleLiteral> {
+        let mut clause = select_clause(sql).unwrap().1;
+        clause.compute_schema(&server.file_manager, &server.table_manager).unwrap();
+        let planner = CostBasedPlanner::with_sort_memory(&server.file_manager, &server.table_manager, sort_memory);
+        let mut plan = planner.make_plan(clause).unwrap();
+        get_plan_results(&mut *plan).unwrap()
+    }
+
+    fn make_tuple(values: &[i32]) -> TupleLiteral {
+        TupleLiteral::from_iter(values.iter().map(|&value| Literal::Int(value)))
+    }
+
+    #[test]
+    fn test_join_order() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let mut sql = String::from("CREATE TABLE big (a integer, b integer); \
+                                    CREATE TABLE small (a integer, c integer); \
+                                    INSERT INTO small VALUES (1, 100); INSERT INTO small VALUES (2, 200); \
+                                    INSERT INTO small VALUES (3, 300); INSERT INTO small VALUES (60, 600);");
+        for i in 0..50 {
+            sql.push_str(&format!("INSERT INTO big VALUES ({}, {});", i, i * 10));
+        }
+        sql.push_str("ANALYZE big, small;");
+        for stmt in statements(sql.as_bytes()).unwrap().1 {
+            server.handle_command(stmt);
+        }
+
+        // The small table is scanned once on the outside, rather than 50 times on the inside.
+        {
+            let planner = CostBasedPlanner::new(&server.file_manager, &server.table_manager);
+            let leaves = vec![planner.make_leaf(FromClause::base_table("BIG".into(), None)).unwrap(),
+                              planner.make_leaf(FromClause::base_table("SMALL".into(), None)).unwrap()];
+            let expr = Expression::Compare(Box::new(Expression::ColumnValue((Some("BIG".into()), Some("A".into())))),
+                                           CompareType::Equals,
+                                           Box::new(Expression::ColumnValue((Some("SMALL".into()), Some("A".into())))));
+            let conjuncts = vec![Conjunct {
+                                     leaves: CostBasedPlanner::get_conjunct_leaves(&expr, &leaves),
+                                     selectivity: CostBasedPlanner::estimate_conjunct_selectivity(&expr, &leaves, 3),
+                                     expr: expr,
+                                 }];
+            assert_eq!(Some(3), conjuncts[0].leaves);
+            assert_eq!(1.0 / 50.0, conjuncts[0].selectivity);
+            assert_eq!(vec![1, 0], CostBasedPlanner::choose_join_order(&leaves, &conjuncts).0);
+        }
+
+        // The columns still come out in the order of the FROM clause, and the WHERE clause is
+        // applied.
+        let results = get_results(&server, b"SELECT * FROM big, small WHERE big.a = small.a AND small.c > 100");
+        assert_eq!(vec![make_tuple(&[2, 20, 2, 200]), make_tuple(&[3, 30, 3, 300])], results);
+
+        let results = get_results(&server, b"SELECT b.b, s.c FROM small AS s JOIN big AS b ON b.a = s.a WHERE b.a < 3");
+        assert_eq!(vec![make_tuple(&[10, 100]), make_tuple(&[20, 200])], results);
+
+        // Outer joins are kept together, and filtered afterwards.
+        let results = get_results(&server,
+                                  b"SELECT s.a, b.b FROM small AS s LEFT OUTER JOIN big AS b ON s.a = b.a, big AS c \
+                                    WHERE c.a = s.c / 100 AND b.b IS NULL");
+        let expected = vec![TupleLiteral::from_iter(vec![Literal::Int(60), Literal::Null])];
+        assert_eq!(expected, results);
+    }
+
+    #[test]
+    fn test_distinct_choice() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut server = Server::with_data_path(dir.path());
+
+        let mut sql = String::from("CREATE TABLE emp (name varchar(10), dept varchar(10));");
+        for (i, dept) in ["sales", "eng", "ops"].iter().cycle().take(30).enumerate() {
+            sql.push_str(&format!("INSERT INTO emp VALUES ('emp{}', '{}');", i, dept));
+        }
+        sql.push_str("ANALYZE emp;");
+        for stmt in statements(sql.as_bytes()).unwrap().1 {
+            server.handle_command(stmt);
+        }
+        let depts = |names: &[&str]| -> Vec<TupleLiteral> {
+            names.iter().map(|&name| TupleLiteral::from_iter(vec![name.into()])).collect()
+        };
+
+        // When the table fits in the sort memory, duplicates are removed by hashing, which keeps
+        // the first occurrence of each value in the order of the table.
+        let results = get_results(&server, b"SELECT DISTINCT dept FROM emp");
+        assert_eq!(depts(&["sales", "eng", "ops"]), results);
+
+        // Otherwise they are removed by sorting, and the ORDER BY clause is still applied.
+        let results = get_results_with_sort_memory(&server, b"SELECT DISTINCT dept FROM emp", 100);
+        assert_eq!(depts(&["eng", "ops", "sales"]), results);
+        let results = get_results_with_sort_memory(&server, b"SELECT DISTINCT dept FROM emp ORDER BY dept DESC", 100);
+        assert_eq!(depts(&["sales", "ops", "eng"]), results);
+    }
+}
@@ -1,16 +1,26 @@
 //! This package contains modules for representing query execution plans, as well as analyzing their
 //! costs.
 
+pub mod cost_based_planner;
+pub mod plan_cost;
+pub mod selectivity;
 pub mod simple_planner;
 
+pub use self::cost_based_planner::CostBasedPlanner;
+pub use self::plan_cost::PlanCost;
 pub use self::simple_planner::SimplePlanner;
 
-use ::relations::SchemaError;
-use ::expressions::{Expression, ExpressionError, SelectClause};
-use ::queries::{FileScanNode, NodeResult, PlanNode};
+use std::cmp::Ordering;
+
+use ::Schema;
+use ::relations::{ColumnType, SchemaError};
+use ::expressions::{CompareType, Expression, ExpressionError, Literal, SelectClause};
+use ::indexes::IndexInfo;
+use ::queries::{FileScanNode, IndexBound, IndexScanNode, NodeResult, PlanNode};
 use ::queries::plan_nodes::ProjectError;
 use ::storage::{FileManager, PinError, TableManager, TupleError, TupleLiteral, file_manager,
                 table_manager};
+use ::storage::table_manager::Table;
 
 /// An error that could occur during planning.
 #[derive(Clone, Debug, PartialEq)]
@@ -39,13 +49,30 @@ pub enum Error {
     AggregatesInWhereExpr(Vec<Expression>),
     /// An expression error occurred while processing aggregates.
     CouldNotProcessAggregates(ExpressionError),
-    /// GROUP BY expressions must be simple column references.
-    SimpleColumnReferenceGroupBy(Expression),
+    /// Aggregates are not allowed in GROUP BY expressions.
+    AggregatesInGroupBy(Expression),
+    /// A GROUP BY position doesn't refer to one of the select-values, or refers to a wildcard.
+    InvalidGroupByPosition(i32),
+    /// An ORDER BY position doesn't refer to one of the columns of the query's results.
+    InvalidOrderByPosition(i32),
     /// Only the COUNT function can take * as an argument.
     WildCardInNonCountFunction(String),
     /// A tuple was found in a plan that did not match the schema size. In the form of
     /// `(tuple size, schema size)`.
     WrongArity(usize, usize),
+    /// An ORDER BY expression could not be evaluated.
+    CouldNotEvaluateOrderBy(ExpressionError),
+    /// The queries of a set operation produce different numbers of columns. In the form of
+    /// `(left columns, right columns)`.
+    SetOperandArity(usize, usize),
+    /// The queries of a set operation produce columns of incompatible types at the same position.
+    IncompatibleSetOperandTypes(ColumnType, ColumnType),
+    /// Window functions are only allowed in the SELECT and ORDER BY clauses.
+    WindowFunctionsNotAllowed(Vec<Expression>),
+    /// The function called with an OVER clause is neither a window function nor an aggregate.
+    NotWindowFunction(String),
+    /// An expression error occurred while computing a window function.
+    CouldNotComputeWindowFunction(ExpressionError),
 }
 
 impl From<file_manager::Error> for Error {
@@ -96,9 +123,14 @@ impl ::std::fmt::Display for Error {
                 write!(f, "WHERE clause cannot contain aggregates. Found: {}", values.join(", "))
             },
             Error::CouldNotProcessAggregates(ref e) => write!(f, "Could not process aggregates: {}.", e),
-            Error::SimpleColumnReferenceGroupBy(ref expr) => {
-                write!(f, "NanoDB does not yet support GROUP BY expressions that are not simple \
-                column references; got {}", expr)
+            Error::AggregatesInGroupBy(ref expr) => {
+                write!(f, "GROUP BY expressions may not contain aggregates; got {}", expr)
+            },
+            Error::InvalidGroupByPosition(position) => {
+                write!(f, "GROUP BY position {} does not refer to a select-value expression.", position)
+            },
+            Error::InvalidOrderByPosition(position) => {
+                write!(f, "ORDER BY position {} does not refer to a column of the results.", position)
             },
             Error::WrongArity(tup_size, schema_size) => {
                 write!(f, "Tuple has different arity ({} columns) than target schema ({} columns).",
@@ -107,6 +139,24 @@ impl ::std::fmt::Display for Error {
             Error::WildCardInNonCountFunction(ref func_name) => {
                 write!(f, "Function {} does not allow wildcard arguments, only COUNT does.", func_name)
             }
+            Error::CouldNotEvaluateOrderBy(ref e) => write!(f, "Could not evaluate ORDER BY expression: {}.", e),
+            Error::SetOperandArity(left, right) => {
+                write!(f, "Queries combined by a set operation must have the same number of columns; got {} and {}.",
+                       left, right)
+            },
+            Error::IncompatibleSetOperandTypes(ref left, ref right) => {
+                write!(f, "Queries combined by a set operation have incompatible column types {} and {}.",
+                       left, right)
+            },
+            Error::WindowFunctionsNotAllowed(ref exprs) => {
+                let values: Vec<String> = exprs.iter().map(|e| format!("{}", e)).collect();
+                write!(f, "Window functions can only be used in the SELECT and ORDER BY clauses. Found: {}",
+                       values.join(", "))
+            },
+            Error::NotWindowFunction(ref name) => {
+                write!(f, "The function {} cannot be called with an OVER clause.", name)
+            },
+            Error::CouldNotComputeWindowFunction(ref e) => write!(f, "Could not compute window function: {}.", e),
         }
     }
 }
@@ -129,8 +179,131 @@ pub fn get_plan_results(plan: &mut PlanNode) -> PlanResult<Vec<TupleLiteral>> {
     Ok(tuples)
 }
 
+/// If the expression compares a column of the schema with a constant, non-`NULL` value, returns
+/// the index of the column, the comparison (as if the column were on the left), and the value.
+fn get_column_comparison(expr: &Expression, schema: &Schema) -> Option<(usize, CompareType, Literal)> {
+    let (column_name, compare_type, value) = match *expr {
+        Expression::Compare(ref left, compare_type, ref right) => {
+            match (&**left, &**right) {
+                (&Expression::ColumnValue(ref name), value) => (name, compare_type, value),
+                (value, &Expression::ColumnValue(ref name)) => {
+                    let flipped = match compare_type {
+                        CompareType::LessThan => CompareType::GreaterThan,
+                        CompareType::LessThanEqual => CompareType::GreaterThanEqual,
+                        CompareType::GreaterThan => CompareType::LessThan,
+                        CompareType::GreaterThanEqual => CompareType::LessThanEqual,
+                        other => other,
+                    };
+                    (name, flipped, value)
+                }
+                _ => return None,
+            }
+        }
+        _ => return None,
+    };
+
+    let columns = schema.find_columns(column_name);
+    if columns.len() != 1 {
+        return None;
+    }
+    // Only expressions that can be evaluated without any tuples are constants.
+    match value.evaluate(&mut None, &None) {
+        Ok(Literal::Null) | Err(_) => None,
+        Ok(literal) => Some((columns[0].0, compare_type, literal)),
+    }
+}
+
+/// Returns whichever of two lower bounds is tighter.
+fn tighter_lower_bound(current: Option<IndexBound>, bound: IndexBound) -> Option<IndexBound> {
+    Some(match current {
+        Some(current) => {
+            match bound.value.compare(&current.value) {
+                Ordering::Greater => bound,
+                Ordering::Equal if !bound.inclusive => bound,
+                _ => current,
+            }
+        }
+        None => bound,
+    })
+}
+
+/// Returns whichever of two upper bounds is tighter.
+fn tighter_upper_bound(current: Option<IndexBound>, bound: IndexBound) -> Option<IndexBound> {
+    Some(match current {
+        Some(current) => {
+            match bound.value.compare(&current.value) {
+                Ordering::Less => bound,
+                Ordering::Equal if !bound.inclusive => bound,
+                _ => current,
+            }
+        }
+        None => bound,
+    })
+}
+
+/// Looks through the conjuncts of a predicate for comparisons between the first column of one of
+/// the table's indexes and a constant, and picks the index that can narrow down the scan the
+/// most. An index with an equality comparison is preferred over one with a closed range, which in
+/// turn is preferred over one with an open range. Returns the chosen index along with the lower
+/// and upper bounds to scan it with, or `None` if no index can be used.
+///
+/// # Arguments
+/// * table - The table being selected from.
+/// * predicate - The predicate of the select.
+fn choose_index(table: &Table,
+                predicate: &Expression)
+                -> Option<(IndexInfo, Option<IndexBound>, Option<IndexBound>)> {
+    let schema = table.get_schema();
+    let conjuncts = match *predicate {
+        Expression::AND(ref exprs) => exprs.iter().collect(),
+        ref expr => vec![expr],
+    };
+    let comparisons: Vec<(usize, CompareType, Literal)> = conjuncts.into_iter()
+        .filter_map(|expr| get_column_comparison(expr, &schema))
+        .collect();
+
+    let mut result = None;
+    let mut best_score = 0;
+    for index in table.get_indexes() {
+        let mut lower = None;
+        let mut upper = None;
+        let mut has_equality = false;
+        for &(_, compare_type, ref value) in comparisons.iter().filter(|c| c.0 == index.column_indexes[0]) {
+            match compare_type {
+                CompareType::Equals => {
+                    has_equality = true;
+                    lower = tighter_lower_bound(lower, IndexBound::new(value.clone(), true));
+                    upper = tighter_upper_bound(upper, IndexBound::new(value.clone(), true));
+                }
+                CompareType::GreaterThan | CompareType::GreaterThanEqual => {
+                    let inclusive = compare_type == CompareType::GreaterThanEqual;
+                    lower = tighter_lower_bound(lower, IndexBound::new(value.clone(), inclusive));
+                }
+                CompareType::LessThan | CompareType::LessThanEqual => {
+                    let inclusive = compare_type == CompareType::LessThanEqual;
+                    upper = tighter_upper_bound(upper, IndexBound::new(value.clone(), inclusive));
+                }
+                CompareType::NotEquals => {}
+            }
+        }
+
+        let score = if has_equality {
+            3
+        } else {
+            lower.iter().count() + upper.iter().count()
+        };
+        if score > best_score {
+            best_score = score;
+            result = Some((index, lower, upper));
+        }
+    }
+    result
+}
+
 /// Returns a plan tree for executing a simple select against a single table, whose tuples can
-/// also be used for updating and deletion.
+/// also be used for updating and deletion. If the predicate compares the first column of one of
+/// the table's indexes with a constant, the index is used to find the tuples instead of scanning
+/// the whole table.
 ///
 /// # Arguments
 /// * file_manager - A reference to the file manager.
@@ -146,9 +319,19 @@ pub fn make_simple_select<'table, S: Into<String>>(file_manager: &FileManager,
 
     let table = try!(table_manager.get_table(file_manager, table_name));
 
-    let mut select_node = FileScanNode::new(table, predicate);
+    let index_choice = match predicate {
+        Some(ref expr) => choose_index(&table, expr),
+        None => None,
+    };
+    let mut select_node: Box<PlanNode> = match index_choice {
+        Some((index, lower, upper)) => {
+            debug!("Using index {} to select from table.", index.name);
+            Box::new(IndexScanNode::new(table, index, lower, upper, predicate))
+        }
+        None => Box::new(FileScanNode::new(table, predicate)),
+    };
     try!(select_node.prepare());
-    Ok(Box::new(select_node))
+    Ok(select_node)
 }
 
 /// This trait specifies the common entry-point for all query planner/optimizer implementations. The
@@ -159,3 +342,115 @@ pub trait Planner {
     /// Create a plan given a SELECT clause.
     fn make_plan(&self, clause: SelectClause) -> NodeResult;
 }
+
+/// The planners that can be used to plan `SELECT` statements.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum PlannerType {
+    /// The [`SimplePlanner`](simple_planner/struct.SimplePlanner.html), which joins tables in the
+    /// order the `FROM` clause lists them.
+    Simple,
+    /// The [`CostBasedPlanner`](cost_based_planner/struct.CostBasedPlanner.html), which uses table
+    /// statistics to choose the order to join tables in.
+    CostBased,
+}
+
+impl ::std::str::FromStr for PlannerType {
+    type Err = ();
+
+    /// Parses the name of a planner type, as used by `SET PLANNER`: `SIMPLE` or `COST_BASED`.
+    fn from_str(s: &str) -> Result<PlannerType, ()> {
+        match s.to_uppercase().as_str() {
+            "SIMPLE" => Ok(PlannerType::Simple),
+            "COST_BASED" => Ok(PlannerType::CostBased),
+            _ => Err(()),
+        }
+    }
+}
+
+impl PlannerType {
+    /// Instantiates a planner of this type.
+    ///
+    /// # Arguments
+    /// * file_manager - A reference to the file manager.
+    /// * table_manager - A reference to the table manager.
+    /// * sort_memory - The number of bytes of tuples the sorts of the planner's plans may keep in
+    ///   memory.
+    pub fn make_planner<'a>(&self,
+                            file_manager: &'a FileManager,
+                            table_manager: &'a TableManager,
+                            sort_memory: usize)
+                            -> Box<Planner + 'a> {
+        match *self {
+            PlannerType::Simple => {
+                Box::new(SimplePlanner::with_sort_memory(file_manager, table_manager, sort_memory))
+            }
+            PlannerType::CostBased => {
+                Box::new(CostBasedPlanner::with_sort_memory(file_manager, table_manager, sort_memory))
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::{ColumnInfo, ColumnType};
+
+    fn compare(column: &str, compare_type: CompareType, value: i32) -> Expression {
+        Expression::Compare(Box::new(Expression::ColumnValue((None, Some(column.into())))),
+                            compare_type,
+                            Box::new(Expression::Int(value)))
+    }
+
+    #[test]
+    fn test_choose_index() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let table_manager = TableManager::new();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "C", "FOO"),
+        ]).unwrap();
+        table_manager.create_table(&file_manager, "FOO", schema).unwrap();
+        table_manager.create_index(&file_manager, "FOO", "IDX_A", &["A".into()], false).unwrap();
+        table_manager.create_index(&file_manager, "FOO", "IDX_B", &["B".into()], false).unwrap();
+        let table = table_manager.get_table(&file_manager, "FOO").unwrap();
+
+        // Columns without an index, and comparisons that aren't with constants, can't be used.
+        assert_eq!(None, choose_index(&table, &compare("C", CompareType::Equals, 1)));
+        assert_eq!(None, choose_index(&table, &compare("A", CompareType::NotEquals, 1)));
+        let columns = Expression::Compare(Box::new(Expression::ColumnValue((None, Some("A".into())))),
+                                          CompareType::Equals,
+                                          Box::new(Expression::ColumnValue((None, Some("B".into())))));
+        assert_eq!(None, choose_index(&table, &columns));
+        assert_eq!(None, choose_index(&table, &Expression::OR(vec![compare("A", CompareType::Equals, 1),
+                                                                   compare("B", CompareType::Equals, 1)])));
+
+        // Constants on the left are flipped around, and the tightest bounds are used.
+        let predicate = Expression::AND(vec![
+            compare("A", CompareType::GreaterThan, 3),
+            Expression::Compare(Box::new(Expression::Int(5)),
+                                CompareType::LessThanEqual,
+                                Box::new(Expression::ColumnValue((Some("FOO".into()), Some("A".into()))))),
+            compare("A", CompareType::LessThan, 10),
+        ]);
+        let (index, lower, upper) = choose_index(&table, &predicate).unwrap();
+        assert_eq!("IDX_A", index.name);
+        assert_eq!(Some(IndexBound::new(Literal::Int(5), true)), lower);
+        assert_eq!(Some(IndexBound::new(Literal::Int(10), false)), upper);
+
+        // Equality lookups are preferred over ranges.
+        let predicate = Expression::AND(vec![
+            compare("A", CompareType::GreaterThan, 3),
+            compare("A", CompareType::LessThan, 10),
+            compare("B", CompareType::Equals, 7),
+        ]);
+        let (index, lower, upper) = choose_index(&table, &predicate).unwrap();
+        assert_eq!("IDX_B", index.name);
+        assert_eq!(lower, upper);
+        assert_eq!(Some(IndexBound::new(Literal::Int(7), true)), upper);
+    }
+}
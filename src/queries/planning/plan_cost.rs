@@ -0,0 +1,54 @@
+//! This module contains the cost estimates that planners use to compare plans with each other.
+
+/// How many tuples can be processed in the time it takes to read or write one block. This is used
+/// to combine the CPU and I/O costs of a plan into a single number.
+pub const BLOCK_IO_COST: f32 = 100.0;
+
+/// The estimated cost of a plan, along with the estimated size of its results.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct PlanCost {
+    /// The estimated number of tuples the plan produces.
+    pub num_tuples: f32,
+    /// The estimated average size of the tuples the plan produces, in bytes.
+    pub tuple_size: f32,
+    /// The estimated CPU cost of the plan, measured in the number of tuples processed.
+    pub cpu_cost: f32,
+    /// The estimated number of blocks the plan reads or writes.
+    pub num_block_ios: f32,
+}
+
+impl PlanCost {
+    /// Creates a new plan cost.
+    ///
+    /// # Arguments
+    /// * num_tuples - The estimated number of tuples the plan produces.
+    /// * tuple_size - The estimated average size of the tuples the plan produces.
+    /// * cpu_cost - The estimated CPU cost of the plan.
+    /// * num_block_ios - The estimated number of block reads and writes of the plan.
+    pub fn new(num_tuples: f32, tuple_size: f32, cpu_cost: f32, num_block_ios: f32) -> PlanCost {
+        PlanCost {
+            num_tuples: num_tuples,
+            tuple_size: tuple_size,
+            cpu_cost: cpu_cost,
+            num_block_ios: num_block_ios,
+        }
+    }
+
+    /// Returns the CPU and I/O costs of the plan combined into a single number, so that plans can
+    /// be compared with each other.
+    pub fn get_total_cost(&self) -> f32 {
+        self.cpu_cost + BLOCK_IO_COST * self.num_block_ios
+    }
+
+    /// Returns the estimated total size of the plan's results, in bytes.
+    pub fn get_results_size(&self) -> f32 {
+        self.num_tuples * self.tuple_size
+    }
+}
+
+impl ::std::fmt::Display for PlanCost {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        write!(f, "PlanCost[tuples={:.1}, tupSize={:.1}, cpuCost={:.1}, blockIOs={:.1}]",
+               self.num_tuples, self.tuple_size, self.cpu_cost, self.num_block_ios)
+    }
+}
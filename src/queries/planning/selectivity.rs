@@ -0,0 +1,200 @@
+//! This module estimates the selectivity of predicates, i.e. the fraction of tuples that satisfy
+//! them, from the statistics collected by `ANALYZE`.
+
+use ::Schema;
+use ::expressions::{CompareType, Expression, Literal};
+use ::relations::ColumnStats;
+
+use super::get_column_comparison;
+
+/// The selectivity assumed for predicates whose selectivity can't be estimated, e.g. because the
+/// columns involved haven't been analyzed.
+pub const DEFAULT_SELECTIVITY: f32 = 0.25;
+
+/// Returns the value of a literal as a double, if it is numeric.
+fn get_double(value: &Literal) -> Option<f64> {
+    match value.as_double() {
+        Some(Literal::Double(d)) => Some(d),
+        _ => None,
+    }
+}
+
+/// Returns the number of distinct values of a column, if it is known and not zero.
+fn get_num_unique_values(stats: &ColumnStats) -> Option<f32> {
+    match stats.num_unique_values {
+        Some(n) if n > 0 => Some(n as f32),
+        _ => None,
+    }
+}
+
+/// Returns the index of the column in the schema that the expression refers to, if it is a column
+/// reference that can be resolved without ambiguity.
+fn get_column_index(expr: &Expression, schema: &Schema) -> Option<usize> {
+    match *expr {
+        Expression::ColumnValue(ref name) => {
+            let columns = schema.find_columns(name);
+            if columns.len() == 1 {
+                Some(columns[0].0)
+            } else {
+                None
+            }
+        }
+        _ => None,
+    }
+}
+
+/// Estimates the selectivity of comparing a column with a constant. Equality uses the number of
+/// distinct values of the column, and ranges interpolate between the column's minimum and maximum
+/// values, which only works for numeric columns.
+fn estimate_comparison(compare_type: CompareType, value: &Literal, stats: &ColumnStats) -> f32 {
+    match compare_type {
+        CompareType::Equals => {
+            get_num_unique_values(stats).map(|n| 1.0 / n).unwrap_or(DEFAULT_SELECTIVITY)
+        }
+        CompareType::NotEquals => {
+            get_num_unique_values(stats).map(|n| 1.0 - 1.0 / n).unwrap_or(1.0 - DEFAULT_SELECTIVITY)
+        }
+        _ => {
+            let (min, max, value) = match (get_double(&stats.min_value),
+                                           get_double(&stats.max_value),
+                                           get_double(value)) {
+                (Some(min), Some(max), Some(value)) => (min, max, value),
+                _ => return DEFAULT_SELECTIVITY,
+            };
+            let fraction = if max <= min {
+                // Every value of the column is the same, so it either satisfies the comparison or
+                // it doesn't.
+                let satisfied = match compare_type {
+                    CompareType::LessThan => min < value,
+                    CompareType::LessThanEqual => min <= value,
+                    CompareType::GreaterThan => min > value,
+                    _ => min >= value,
+                };
+                if satisfied { 1.0 } else { 0.0 }
+            } else {
+                match compare_type {
+                    CompareType::LessThan | CompareType::LessThanEqual => (value - min) / (max - min),
+                    _ => (max - value) / (max - min),
+                }
+            };
+            fraction.max(0.0).min(1.0) as f32
+        }
+    }
+}
+
+/// Estimates the selectivity of a predicate, i.e. the fraction of the tuples of the schema that
+/// satisfy it. Conjunctions and disjunctions assume their terms are independent of each other, and
+/// anything that can't be estimated gets the [`DEFAULT_SELECTIVITY`](constant.DEFAULT_SELECTIVITY.html).
+///
+/// # Arguments
+/// * expr - The predicate to estimate the selectivity of.
+/// * schema - The schema of the tuples the predicate is applied to.
+/// * stats - The statistics of each column of the schema, in schema order.
+pub fn estimate_selectivity(expr: &Expression, schema: &Schema, stats: &[ColumnStats]) -> f32 {
+    match *expr {
+        Expression::True => 1.0,
+        Expression::False | Expression::Null => 0.0,
+        Expression::AND(ref exprs) => {
+            exprs.iter().fold(1.0, |result, expr| result * estimate_selectivity(expr, schema, stats))
+        }
+        Expression::OR(ref exprs) => {
+            1.0 - exprs.iter().fold(1.0, |result, expr| {
+                result * (1.0 - estimate_selectivity(expr, schema, stats))
+            })
+        }
+        Expression::NOT(ref inner) => 1.0 - estimate_selectivity(inner, schema, stats),
+        Expression::Compare(ref left, compare_type, ref right) => {
+            if let Some((index, compare_type, value)) = get_column_comparison(expr, schema) {
+                return match stats.get(index) {
+                    Some(column_stats) => estimate_comparison(compare_type, &value, column_stats),
+                    None => DEFAULT_SELECTIVITY,
+                };
+            }
+
+            // Equality between two columns selects one tuple per distinct value of the column with
+            // the most distinct values.
+            match (compare_type, get_column_index(left, schema), get_column_index(right, schema)) {
+                (CompareType::Equals, Some(left), Some(right)) => {
+                    let left = stats.get(left).and_then(get_num_unique_values);
+                    let right = stats.get(right).and_then(get_num_unique_values);
+                    match (left, right) {
+                        (Some(left), Some(right)) => 1.0 / left.max(right),
+                        (Some(n), None) | (None, Some(n)) => 1.0 / n,
+                        (None, None) => DEFAULT_SELECTIVITY,
+                    }
+                }
+                _ => DEFAULT_SELECTIVITY,
+            }
+        }
+        _ => DEFAULT_SELECTIVITY,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ::relations::{ColumnInfo, ColumnType};
+
+    fn compare(column: &str, compare_type: CompareType, value: Expression) -> Expression {
+        Expression::Compare(Box::new(Expression::ColumnValue((None, Some(column.into())))),
+                            compare_type,
+                            Box::new(value))
+    }
+
+    #[test]
+    fn test_estimate_selectivity() {
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "B", "FOO"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "C", "FOO"),
+        ]).unwrap();
+        let stats = vec![
+            ColumnStats {
+                num_unique_values: Some(10),
+                num_null_values: Some(0),
+                min_value: Literal::Int(0),
+                max_value: Literal::Int(100),
+            },
+            ColumnStats {
+                num_unique_values: Some(4),
+                num_null_values: Some(0),
+                min_value: Literal::Int(1),
+                max_value: Literal::Int(4),
+            },
+            ColumnStats::new(),
+        ];
+
+        let a_equals = compare("A", CompareType::Equals, Expression::Int(5));
+        assert_eq!(0.1, estimate_selectivity(&a_equals, &schema, &stats));
+        assert_eq!(0.9, estimate_selectivity(&compare("A", CompareType::NotEquals, Expression::Int(5)),
+                                             &schema, &stats));
+        assert_eq!(0.25, estimate_selectivity(&compare("A", CompareType::LessThan, Expression::Int(25)),
+                                              &schema, &stats));
+        assert_eq!(0.75, estimate_selectivity(&compare("A", CompareType::GreaterThan, Expression::Int(25)),
+                                              &schema, &stats));
+        assert_eq!(0.0, estimate_selectivity(&compare("A", CompareType::GreaterThan, Expression::Int(200)),
+                                             &schema, &stats));
+
+        // Constants on the left are flipped around.
+        let flipped = Expression::Compare(Box::new(Expression::Int(75)),
+                                          CompareType::LessThanEqual,
+                                          Box::new(Expression::ColumnValue((None, Some("A".into())))));
+        assert_eq!(0.25, estimate_selectivity(&flipped, &schema, &stats));
+
+        // Columns without statistics get the default selectivity.
+        let c_equals = compare("C", CompareType::Equals, Expression::Int(5));
+        assert_eq!(DEFAULT_SELECTIVITY, estimate_selectivity(&c_equals, &schema, &stats));
+
+        // Column equality uses the column with the most distinct values.
+        let columns = compare("A", CompareType::Equals, Expression::ColumnValue((None, Some("B".into()))));
+        assert_eq!(0.1, estimate_selectivity(&columns, &schema, &stats));
+
+        let b_equals = compare("B", CompareType::Equals, Expression::Int(2));
+        let and = Expression::AND(vec![a_equals.clone(), b_equals.clone()]);
+        assert!((estimate_selectivity(&and, &schema, &stats) - 0.025).abs() < 1e-6);
+        let or = Expression::OR(vec![a_equals.clone(), b_equals.clone()]);
+        assert!((estimate_selectivity(&or, &schema, &stats) - 0.325).abs() < 1e-6);
+        let not = Expression::NOT(Box::new(b_equals));
+        assert_eq!(0.75, estimate_selectivity(&not, &schema, &stats));
+    }
+}
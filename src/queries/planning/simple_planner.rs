@@ -1,12 +1,294 @@
 //! This module contains the classes and functions needed for a simple query planner.
 
-use ::expressions::{Expression, FromClause, FromClauseType, SelectClause, SelectValue};
-use ::queries::{AggregateFunctionExtractor, HashedGroupAggregateNode, NestedLoopJoinNode,
-                NodeResult, PlanError, PlanNode, Planner, PlanResult, ProjectNode,
-                make_simple_select, RenameNode};
+use ::{ColumnInfo, Schema};
+use ::expressions::{CompareType, Expression, ExpressionError, ExpressionProcessor, FromClause,
+                    FromClauseType, JoinType, OrderByExpression, SelectClause, SelectValue, SetOperator};
+use ::queries::{AggregateFunctionExtractor, HashedDistinctNode, HashedGroupAggregateNode,
+                HashedSetOperationNode, LimitOffsetNode, NestedLoopJoinNode, NodeResult, PlanCost, PlanError,
+                PlanNode, Planner, PlanResult, ProjectNode, make_simple_select, RecursiveUnionNode,
+                RenameNode, SimpleFilterNode, SortedDistinctNode, SortNode, DEFAULT_SORT_MEMORY, UnionNode,
+                WindowFunctionExtractor, WindowNode, WorkingTableScanNode, get_group_by_column_name};
 use ::storage::{FileManager, TableManager};
 
-fn prepare_aggregates(mut clause: &mut SelectClause) -> PlanResult<AggregateFunctionExtractor> {
+/// Looks for unqualified column references to any of a set of names, which are the aliases of the
+/// select-values of a `SELECT` clause.
+struct AliasFinder<'a> {
+    aliases: Vec<&'a str>,
+    found: bool,
+}
+
+impl<'a> ExpressionProcessor for AliasFinder<'a> {
+    fn enter(&mut self, node: &Expression) -> Result<(), ExpressionError> {
+        if let Expression::ColumnValue((None, Some(ref name))) = *node {
+            if self.aliases.contains(&name.as_str()) {
+                self.found = true;
+            }
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        Ok(node.clone())
+    }
+}
+
+/// Looks for subqueries in an expression.
+struct SubqueryFinder {
+    found: bool,
+}
+
+impl ExpressionProcessor for SubqueryFinder {
+    fn enter(&mut self, node: &Expression) -> Result<(), ExpressionError> {
+        match *node {
+            Expression::Subquery(_) | Expression::Exists { .. } | Expression::InSubquery { .. } => {
+                self.found = true;
+            }
+            _ => {}
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        Ok(node.clone())
+    }
+}
+
+/// Returns whether an expression contains any subqueries.
+fn has_subquery(expr: &Expression) -> bool {
+    let mut finder = SubqueryFinder { found: false };
+    // The finder itself never fails.
+    let _ = expr.clone().traverse(&mut finder);
+    finder.found
+}
+
+/// Replaces occurrences of computed `GROUP BY` expressions with references to the columns that the
+/// grouping node computes them into.
+struct GroupByReplacer<'a> {
+    exprs: &'a [Expression],
+}
+
+impl<'a> ExpressionProcessor for GroupByReplacer<'a> {
+    fn enter(&mut self, _node: &Expression) -> Result<(), ExpressionError> {
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        Ok(match *node {
+            Expression::ColumnValue(_) => node.clone(),
+            _ if self.exprs.contains(node) => {
+                Expression::ColumnValue((None, Some(get_group_by_column_name(node))))
+            }
+            _ => node.clone(),
+        })
+    }
+}
+
+/// Replaces positional references in the `GROUP BY` clause, e.g. `GROUP BY 1`, with the
+/// select-value expressions they refer to, and makes sure no `GROUP BY` expression contains an
+/// aggregate.
+fn prepare_group_by(clause: &mut SelectClause) -> PlanResult<()> {
+    if let Some(ref mut exprs) = clause.group_by_exprs {
+        for expr in exprs.iter_mut() {
+            if let Expression::Int(position) = *expr {
+                let value = if position >= 1 {
+                    clause.values.get(position as usize - 1)
+                } else {
+                    None
+                };
+                *expr = match value {
+                    Some(&SelectValue::Expression { ref expression, .. }) => expression.clone(),
+                    _ => return Err(PlanError::InvalidGroupByPosition(position)),
+                };
+            }
+
+            let mut extractor = AggregateFunctionExtractor::new();
+            try!(expr.clone().traverse(&mut extractor).map_err(PlanError::CouldNotProcessAggregates));
+            if extractor.found_aggregates() {
+                return Err(PlanError::AggregatesInGroupBy(expr.clone()));
+            }
+        }
+    }
+    Ok(())
+}
+
+/// Replaces positional references in the `ORDER BY` clause, e.g. `ORDER BY 1`, with the
+/// select-value expressions they refer to. When the select-values include a wildcard, the columns
+/// aren't known yet, so the positions are left for
+/// [`resolve_order_by_positions`](fn.resolve_order_by_positions.html) to resolve against the
+/// schema of the results.
+fn prepare_order_by(clause: &mut SelectClause) -> PlanResult<()> {
+    let has_wildcard = clause.values.iter().any(|value| match *value {
+        SelectValue::WildcardColumn { .. } => true,
+        _ => false,
+    });
+    if has_wildcard {
+        return Ok(());
+    }
+    for term in clause.order_by_exprs.iter_mut() {
+        if let Expression::Int(position) = term.expression {
+            let value = if position >= 1 {
+                clause.values.get(position as usize - 1)
+            } else {
+                None
+            };
+            term.expression = match value {
+                Some(&SelectValue::Expression { ref expression, .. }) => expression.clone(),
+                _ => return Err(PlanError::InvalidOrderByPosition(position)),
+            };
+        }
+    }
+    Ok(())
+}
+
+/// Checks whether any `ORDER BY` term is a position that is still to be resolved against the
+/// schema of the results.
+fn has_order_by_positions(clause: &SelectClause) -> bool {
+    clause.order_by_exprs.iter().any(|term| match term.expression {
+        Expression::Int(_) => true,
+        _ => false,
+    })
+}
+
+/// Replaces the positional references left in the `ORDER BY` clause with references to the
+/// columns of the given schema, which is the schema of the query's results.
+fn resolve_order_by_positions(order_by_exprs: &[OrderByExpression], schema: &Schema) -> PlanResult<Vec<OrderByExpression>> {
+    let mut result = Vec::new();
+    for term in order_by_exprs.iter() {
+        let mut term = term.clone();
+        if let Expression::Int(position) = term.expression {
+            let column = if position >= 1 && position as usize <= schema.num_columns() {
+                Some(&schema[position as usize - 1])
+            } else {
+                None
+            };
+            term.expression = match column {
+                Some(&ColumnInfo { name: Some(ref name), ref table_name, .. }) => {
+                    Expression::ColumnValue((table_name.clone(), Some(name.clone())))
+                }
+                _ => return Err(PlanError::InvalidOrderByPosition(position)),
+            };
+        }
+        result.push(term);
+    }
+    Ok(result)
+}
+
+/// Checks whether the `ORDER BY` clause can be applied before the projection, which is the case
+/// when every `ORDER BY` expression can be evaluated against the schema of the plan before the
+/// projection and none of them refer to the alias of a select-value. This lets queries sort on
+/// columns they don't select.
+fn can_sort_before_project(clause: &SelectClause, node: &PlanNode) -> PlanResult<bool> {
+    let mut finder = AliasFinder {
+        aliases: clause.values
+            .iter()
+            .filter_map(|value| match *value {
+                SelectValue::Expression { alias: Some(ref alias), .. } => Some(alias.as_str()),
+                _ => None,
+            })
+            .collect(),
+        found: false,
+    };
+    let schema = node.get_schema();
+    for term in clause.order_by_exprs.iter() {
+        try!(term.expression.clone().traverse(&mut finder).map_err(PlanError::CouldNotEvaluateOrderBy));
+        if finder.found || term.expression.get_column_type(&schema).is_err() {
+            return Ok(false);
+        }
+    }
+    Ok(true)
+}
+
+/// The name given to the column of an `IN` subquery when the test is done with a join, so that the
+/// join predicate can't confuse it with the columns of the outer query.
+const IN_SUBQUERY_COLUMN: &'static str = "#IN_VALUE";
+
+/// Splits the `EXISTS` and `IN` subquery tests that can be done with joins out of the top-level
+/// conjuncts of a `WHERE` clause. Returns the rest of the `WHERE` clause along with the tests.
+pub fn split_subquery_tests(where_expr: Option<Expression>) -> (Option<Expression>, Vec<Expression>) {
+    let conjuncts = match where_expr {
+        Some(Expression::AND(exprs)) => exprs,
+        Some(expr) => vec![expr],
+        None => return (None, vec![]),
+    };
+
+    let (tests, mut rest): (Vec<Expression>, Vec<Expression>) = conjuncts.into_iter().partition(|expr| {
+        match *expr {
+            Expression::Exists { .. } => true,
+            // The subquery's value must be a single expression so that it can be given a name.
+            Expression::InSubquery { ref subquery, .. } => {
+                match subquery.values.as_slice() {
+                    &[SelectValue::Expression { .. }] => true,
+                    _ => false,
+                }
+            }
+            _ => false,
+        }
+    });
+
+    let rest = match rest.len() {
+        0 => None,
+        1 => rest.pop(),
+        _ => Some(Expression::AND(rest)),
+    };
+    (rest, tests)
+}
+
+/// Applies `EXISTS` and `IN` subquery tests, as split out by
+/// [`split_subquery_tests`](fn.split_subquery_tests.html), to a plan by joining it with the plans
+/// of the subqueries. Positive tests become semijoins and negated tests become antijoins.
+///
+/// # Arguments
+/// * cur_node - The plan producing the rows to test.
+/// * tests - The subquery tests.
+/// * planner - The planner to plan the subqueries with.
+pub fn make_subquery_joins<'a>(mut cur_node: Box<PlanNode + 'a>,
+                               tests: Vec<Expression>,
+                               planner: &'a Planner)
+                               -> NodeResult<'a> {
+    for test in tests {
+        let (subquery, predicate, negated) = match test {
+            Expression::Exists { subquery, negated } => (*subquery, None, negated),
+            Expression::InSubquery { expr, mut subquery, negated } => {
+                if let SelectValue::Expression { ref mut alias, .. } = subquery.values[0] {
+                    *alias = Some(IN_SUBQUERY_COLUMN.into());
+                }
+                let value = Box::new(Expression::ColumnValue((None, Some(IN_SUBQUERY_COLUMN.into()))));
+                let equals = Expression::Compare(expr.clone(), CompareType::Equals, value.clone());
+
+                // A NULL on either side of the comparison makes it unknown. An unknown comparison
+                // doesn't count as a match for IN, but it does rule a row out for NOT IN, since
+                // the value might be in the subquery's results after all.
+                let predicate = if negated {
+                    Expression::OR(vec![Expression::IsNull(expr), Expression::IsNull(value), equals])
+                } else {
+                    Expression::AND(vec![Expression::NOT(Box::new(Expression::IsNull(expr))),
+                                         Expression::NOT(Box::new(Expression::IsNull(value))),
+                                         equals])
+                };
+                (*subquery, Some(predicate), negated)
+            }
+            _ => unreachable!(),
+        };
+
+        let join_type = if negated { JoinType::Antijoin } else { JoinType::Semijoin };
+        let subquery_node = try!(planner.make_plan(subquery));
+        cur_node = Box::new(NestedLoopJoinNode::new(cur_node, subquery_node, join_type, predicate));
+        try!(cur_node.prepare());
+    }
+    Ok(cur_node)
+}
+
+/// Looks for aggregate function calls in the SELECT, HAVING and ORDER BY clauses, replacing them
+/// with references to the columns the aggregates will be computed into. Fails if the WHERE clause
+/// contains aggregates.
+///
+/// The GROUP BY clause is prepared here too. Positional references in it are replaced with the
+/// select-values they refer to, and other occurrences of computed GROUP BY expressions are
+/// replaced with references to the grouped values.
+pub fn prepare_aggregates(mut clause: &mut SelectClause) -> PlanResult<AggregateFunctionExtractor> {
+    try!(prepare_group_by(clause));
+    try!(prepare_order_by(clause));
+
     // Analyze all expressions in the SELECT, WHERE and HAVING clauses for aggregate function calls.
     // (Obviously, if the WHERE clause contains aggregates then it's an error!)
     let mut extractor = AggregateFunctionExtractor::new();
@@ -30,7 +312,7 @@ fn prepare_aggregates(mut clause: &mut SelectClause) -> PlanResult<AggregateFunc
     // Make sure no conditions in the FROM clause contain aggregates...
     // TODO
 
-    // Now it's OK to find aggregates, so scan SELECT and HAVING clauses.
+    // Now it's OK to find aggregates, so scan SELECT, HAVING and ORDER BY clauses.
     for value in clause.values.iter_mut() {
         match *value {
             SelectValue::Expression { ref mut expression, .. } => {
@@ -41,7 +323,41 @@ fn prepare_aggregates(mut clause: &mut SelectClause) -> PlanResult<AggregateFunc
     }
 
     if let Some(ref mut having) = clause.having {
-        try!(having.traverse(&mut extractor).map_err(PlanError::CouldNotProcessAggregates));
+        *having = try!(having.traverse(&mut extractor).map_err(PlanError::CouldNotProcessAggregates));
+    }
+
+    for term in clause.order_by_exprs.iter_mut() {
+        term.expression = try!(term.expression
+            .traverse(&mut extractor)
+            .map_err(PlanError::CouldNotProcessAggregates));
+    }
+
+    // Now that the aggregates have been replaced, any remaining occurrences of computed GROUP BY
+    // expressions refer to the grouped values.
+    let group_by_exprs: Vec<Expression> = clause.group_by_exprs
+        .iter()
+        .flat_map(|exprs| exprs.iter())
+        .filter(|expr| match **expr {
+            Expression::ColumnValue(_) => false,
+            _ => true,
+        })
+        .cloned()
+        .collect();
+    if !group_by_exprs.is_empty() {
+        let mut replacer = GroupByReplacer { exprs: &group_by_exprs };
+        for value in clause.values.iter_mut() {
+            if let SelectValue::Expression { ref mut expression, .. } = *value {
+                *expression = try!(expression.traverse(&mut replacer).map_err(PlanError::CouldNotProcessAggregates));
+            }
+        }
+        if let Some(ref mut having) = clause.having {
+            *having = try!(having.traverse(&mut replacer).map_err(PlanError::CouldNotProcessAggregates));
+        }
+        for term in clause.order_by_exprs.iter_mut() {
+            term.expression = try!(term.expression
+                .traverse(&mut replacer)
+                .map_err(PlanError::CouldNotProcessAggregates));
+        }
     }
 
     if extractor.found_aggregates() {
@@ -64,40 +380,299 @@ fn prepare_aggregates(mut clause: &mut SelectClause) -> PlanResult<AggregateFunc
         if let Some(ref having) = clause.having {
             info!("Transformed HAVING clause: {}", having);
         }
+
+        for term in clause.order_by_exprs.iter() {
+            info!("Transformed ORDER BY term: {}", term);
+        }
+    }
+
+    Ok(extractor)
+}
+
+/// Looks for window function calls in the SELECT and ORDER BY clauses, replacing them with
+/// references to the columns the window functions will be computed into. Fails if the WHERE,
+/// GROUP BY or HAVING clauses contain window functions, since window functions are computed after
+/// those clauses are applied.
+///
+/// This is done after [`prepare_aggregates`](fn.prepare_aggregates.html), so that window functions
+/// can be computed over aggregates, e.g. `RANK() OVER (ORDER BY SUM(a))`.
+pub fn prepare_windows(clause: &mut SelectClause) -> PlanResult<WindowFunctionExtractor> {
+    let mut not_allowed = WindowFunctionExtractor::new();
+    {
+        let exprs = clause.where_expr
+            .iter()
+            .chain(clause.group_by_exprs.iter().flat_map(|exprs| exprs.iter()))
+            .chain(clause.having.iter());
+        for expr in exprs {
+            try!(expr.clone().traverse(&mut not_allowed).map_err(PlanError::CouldNotComputeWindowFunction));
+        }
+    }
+    if not_allowed.found_window_functions() {
+        let calls = not_allowed.get_window_calls().iter().map(|&(_, ref expr)| expr.clone()).collect();
+        return Err(PlanError::WindowFunctionsNotAllowed(calls));
+    }
+
+    let mut extractor = WindowFunctionExtractor::new();
+    for value in clause.values.iter_mut() {
+        if let SelectValue::Expression { ref mut expression, .. } = *value {
+            *expression = try!(expression.traverse(&mut extractor).map_err(PlanError::CouldNotComputeWindowFunction));
+        }
+    }
+    for term in clause.order_by_exprs.iter_mut() {
+        term.expression = try!(term.expression
+            .traverse(&mut extractor)
+            .map_err(PlanError::CouldNotComputeWindowFunction));
     }
 
+    for &(ref name, ref expr) in extractor.get_window_calls() {
+        info!("Found window function {} = {}", name, expr);
+    }
     Ok(extractor)
 }
 
+/// Adds the grouping and aggregation, the projection, the duplicate elimination, the ordering and
+/// the limit and offset of a `SELECT` clause on top of the plan for its `FROM` and `WHERE` clauses.
+///
+/// # Arguments
+/// * cur_node - The plan producing the rows of the `FROM` clause that satisfy the `WHERE` clause.
+/// * clause - The `SELECT` clause, after [`prepare_aggregates`](fn.prepare_aggregates.html) and
+///   [`prepare_windows`](fn.prepare_windows.html).
+/// * extractor - The aggregates found by `prepare_aggregates`.
+/// * windows - The window functions found by `prepare_windows`.
+/// * planner - The planner to evaluate subqueries in the projection with.
+/// * file_manager - The file manager for sorts to create their temporary files with.
+/// * sort_memory - The number of bytes of tuples sorts may keep in memory.
+/// * input_cost - The estimated cost of `cur_node`, if the planner estimates costs.
+pub fn finish_select_plan<'a>(mut cur_node: Box<PlanNode + 'a>,
+                              clause: &SelectClause,
+                              extractor: &AggregateFunctionExtractor,
+                              windows: &WindowFunctionExtractor,
+                              planner: &'a Planner,
+                              file_manager: &FileManager,
+                              sort_memory: usize,
+                              input_cost: Option<&PlanCost>)
+                              -> NodeResult<'a> {
+    // Removing duplicates by hashing keeps every distinct tuple in memory, so they are removed by
+    // sorting instead when the input is estimated to be larger than the sort memory. That sorts the
+    // results on all of their columns, so the ORDER BY clause has to be applied afterwards.
+    let sort_distinct = clause.distinct && match input_cost {
+        Some(cost) => cost.get_results_size() > sort_memory as f32,
+        None => false,
+    };
+
+    // Handle grouping and aggregation next, if there are any aggregate operations.
+    let has_group_by_exprs = if let Some(ref exprs) = clause.group_by_exprs {
+        !exprs.is_empty()
+    } else {
+        false
+    };
+    if extractor.found_aggregates() || has_group_by_exprs {
+        // Get the aggregates too (if present).
+        let aggregates = extractor.get_aggregate_calls();
+
+        // By default, use a hash-based grouping/aggregate node. Later we can replace
+        // with a sort-based grouping/aggregate node if it would be more efficient.
+        let node = try!(HashedGroupAggregateNode::new(cur_node,
+                                                 clause.group_by_exprs
+                                                     .clone()
+                                                     .unwrap_or(vec![]),
+                                                 aggregates));
+        cur_node = Box::new(node);
+        try!(cur_node.prepare());
+    }
+
+    // The HAVING predicate is applied to the groups, so it can refer to the grouping columns and
+    // the computed aggregates.
+    if let Some(ref having) = clause.having {
+        cur_node = Box::new(SimpleFilterNode::with_planner(cur_node, having.clone(), planner));
+        try!(cur_node.prepare());
+    }
+
+    // Window functions are computed over the rows that are left after grouping and the HAVING
+    // predicate, and the results can be sorted on.
+    if windows.found_window_functions() {
+        cur_node = Box::new(try!(WindowNode::new(cur_node, windows.get_window_calls().to_vec())));
+        try!(cur_node.prepare());
+    }
+
+    // Sort before the projection if we can, since the ORDER BY clause may refer to columns that
+    // aren't selected. Otherwise it refers to the select-values, so sort the projection's results.
+    let mut sorted = clause.order_by_exprs.is_empty();
+    if !sorted && !sort_distinct && !has_order_by_positions(clause) &&
+       try!(can_sort_before_project(clause, &*cur_node)) {
+        cur_node = Box::new(SortNode::with_memory_budget(cur_node, clause.order_by_exprs.clone(), file_manager,
+                                                         sort_memory));
+        try!(cur_node.prepare());
+        sorted = true;
+    }
+
+    // Here we handle any non-trivial projection. Essentially, the only time we don't
+    // project our results is when we select a wildcard that is not table specific by
+    // itself. So `SELECT foo.*` does not count, and neither does `SELECT COUNT(*)`.
+    // Only `SELECT *` counts as a trivial projection.
+    //
+    // If the projection is non-trivial and doesn't involve aggregates or window functions, then
+    // we just take the values the user provided. If there are aggregates or window functions,
+    // however, we replace the temporary name with the original expression, e.g. `COUNT(foo.a)`.
+    // Otherwise we proceed as before.
+    let has_computed_values = extractor.found_aggregates() || windows.found_window_functions();
+    if !clause.is_trivial_project() || has_computed_values {
+        let values = if !has_computed_values {
+            clause.values.clone()
+        } else {
+            let aggregates = extractor.get_aggregate_call_map();
+            let mut values: Vec<SelectValue> = Vec::new();
+            for value in clause.values.iter() {
+                match *value {
+                    SelectValue::Expression { ref expression, ref alias } => {
+                        match *alias {
+                            Some(_) => values.push(value.clone()),
+                            None => {
+                                if let Expression::ColumnValue(ref col_name) = *expression {
+                                    // shouldn't be able to get this far without actually having a column name
+                                    debug_assert!(col_name.1.is_some());
+
+                                    let name = col_name.1.clone().unwrap();
+                                    let expr = aggregates.get(&name).or_else(|| windows.get_window_call(&name));
+                                    if let Some(ref expr) = expr {
+                                        values.push(SelectValue::Expression {
+                                            expression: Expression::ColumnValue(col_name.clone()),
+                                            alias: Some(format!("{}", expr))
+                                        });
+                                    } else {
+                                        values.push(value.clone());
+                                    }
+                                } else {
+                                    values.push(value.clone());
+                                }
+                            }
+                        }
+                    }
+                    SelectValue::WildcardColumn { .. } => values.push(value.clone())
+                }
+            }
+            values
+        };
+        cur_node = Box::new(ProjectNode::new(cur_node, values, planner));
+        try!(cur_node.prepare());
+    }
+
+    // Hashing keeps the order of the tuples, in case they have already been sorted.
+    if sort_distinct {
+        cur_node = Box::new(SortedDistinctNode::with_memory_budget(cur_node, file_manager, sort_memory));
+        try!(cur_node.prepare());
+    } else if clause.distinct {
+        cur_node = Box::new(HashedDistinctNode::new(cur_node));
+        try!(cur_node.prepare());
+    }
+
+    if !sorted {
+        let order_by_exprs = try!(resolve_order_by_positions(&clause.order_by_exprs, &cur_node.get_schema()));
+        cur_node = Box::new(SortNode::with_memory_budget(cur_node, order_by_exprs, file_manager, sort_memory));
+        try!(cur_node.prepare());
+    }
+
+    // The limit and offset go on top of everything else, so that the plan stops being evaluated
+    // once enough rows have been produced.
+    if clause.limit.is_some() || clause.offset.is_some() {
+        cur_node = Box::new(LimitOffsetNode::new(cur_node, clause.limit, clause.offset));
+        try!(cur_node.prepare());
+    }
+
+    Ok(cur_node)
+}
+
+/// Builds the plan for a compound `SELECT` clause, which combines the results of its two queries
+/// with a set operation and then applies its own ordering, limit and offset to them.
+///
+/// # Arguments
+/// * clause - The compound `SELECT` clause.
+/// * planner - The planner to plan the two queries with.
+/// * file_manager - The file manager for sorts to create their temporary files with.
+/// * sort_memory - The number of bytes of tuples sorts may keep in memory.
+pub fn make_set_operation_plan<'a>(mut clause: SelectClause,
+                                   planner: &'a Planner,
+                                   file_manager: &FileManager,
+                                   sort_memory: usize)
+                                   -> NodeResult<'a> {
+    let operation = clause.set_operation.take().unwrap();
+    let left = try!(planner.make_plan(*operation.left));
+
+    // The recursive part of a recursive query reads the working table, which holds the results of
+    // the non-recursive part to begin with, so the working table's schema is needed to plan it.
+    if let Some(ref table) = operation.working_table {
+        try!(table.set_schema(left.get_schema()));
+    }
+    let right = try!(planner.make_plan(*operation.right));
+
+    let needs_distinct = operation.operator == SetOperator::Union && !operation.all &&
+                         operation.working_table.is_none();
+    let mut cur_node: Box<PlanNode> = match (operation.operator, operation.working_table) {
+        (SetOperator::Union, Some(table)) => Box::new(RecursiveUnionNode::new(left, right, table, operation.all)),
+        (SetOperator::Union, None) => Box::new(UnionNode::new(left, right)),
+        (operator, _) => Box::new(HashedSetOperationNode::new(left, right, operator, operation.all)),
+    };
+    try!(cur_node.prepare());
+
+    // INTERSECT and EXCEPT remove duplicates themselves, but a UNION needs to do it afterwards.
+    // A recursive UNION has to remove them as it goes, to find out when it's done.
+    if needs_distinct {
+        cur_node = Box::new(HashedDistinctNode::new(cur_node));
+        try!(cur_node.prepare());
+    }
+
+    let extractor = try!(prepare_aggregates(&mut clause));
+    let windows = try!(prepare_windows(&mut clause));
+    finish_select_plan(cur_node, &clause, &extractor, &windows, planner, file_manager, sort_memory, None)
+}
+
 /// This class generates execution plannodes for performing SQL queries. The primary responsibility
 /// is to generate plannodes for SQL `SELECT` statements, but `UPDATE` and `DELETE` expressions will
 /// also use this class to generate simple plannodes to identify the tuples to update or delete.
 pub struct SimplePlanner<'a> {
     file_manager: &'a FileManager,
     table_manager: &'a TableManager,
+    sort_memory: usize,
 }
 
 impl<'a> SimplePlanner<'a> {
-    /// Instantiates a new SimplePlanner.
+    /// Instantiates a new SimplePlanner, whose sorts use the default memory budget.
     pub fn new(file_manager: &'a FileManager, table_manager: &'a TableManager) -> SimplePlanner<'a> {
+        SimplePlanner::with_sort_memory(file_manager, table_manager, DEFAULT_SORT_MEMORY)
+    }
+
+    /// Instantiates a new SimplePlanner.
+    ///
+    /// # Arguments
+    /// * file_manager - A reference to the file manager.
+    /// * table_manager - A reference to the table manager.
+    /// * sort_memory - The number of bytes of tuples the sorts of its plans may keep in memory.
+    pub fn with_sort_memory(file_manager: &'a FileManager,
+                            table_manager: &'a TableManager,
+                            sort_memory: usize)
+                            -> SimplePlanner<'a> {
         SimplePlanner {
             file_manager: file_manager,
             table_manager: table_manager,
+            sort_memory: sort_memory,
         }
     }
 
-    fn make_join_tree(&self, clause: FromClause) -> NodeResult {
+    /// Builds the plan tree for the FROM clause. The WHERE predicate is only given for a base table
+    /// that isn't renamed, so that the select can use one of the table's indexes.
+    pub fn make_join_tree(&self, clause: FromClause, predicate: Option<&Expression>) -> NodeResult {
         match *clause {
             FromClauseType::BaseTable { ref table, ref alias } => {
-                let mut cur_node = try!(make_simple_select(self.file_manager, self.table_manager, table.clone(), None));
+                let predicate = if alias.is_none() { predicate.cloned() } else { None };
+                let mut cur_node = try!(make_simple_select(self.file_manager, self.table_manager, table.clone(), predicate));
                 if let Some(ref name) = *alias {
-                    cur_node = Box::new(RenameNode::new(cur_node, name.as_ref()));
+                    cur_node = Box::new(RenameNode::new(cur_node, name.as_str()));
                 }
                 Ok(cur_node)
             }
             FromClauseType::JoinExpression { ref left, ref right, ref join_type, .. } => {
-                let left_child = try!(self.make_join_tree(*left.clone()));
-                let right_child = try!(self.make_join_tree(*right.clone()));
+                let left_child = try!(self.make_join_tree(*left.clone(), None));
+                let right_child = try!(self.make_join_tree(*right.clone(), None));
 
                 let mut cur_node: Box<PlanNode> = Box::new(NestedLoopJoinNode::new(left_child,
                                                                                    right_child,
@@ -112,98 +687,67 @@ impl<'a> SimplePlanner<'a> {
 
                 Ok(cur_node)
             }
+            FromClauseType::Subquery { ref subquery, ref alias, ref column_names } => {
+                // The subquery's results are renamed so that they can be referred to by its alias.
+                let node = try!(self.make_plan(*subquery.clone()));
+                let mut cur_node: Box<PlanNode> = match *column_names {
+                    Some(ref names) => Box::new(RenameNode::with_column_names(node, alias.as_str(), names.clone())),
+                    None => Box::new(RenameNode::new(node, alias.as_str())),
+                };
+                try!(cur_node.prepare());
+                Ok(cur_node)
+            }
+            FromClauseType::WorkingTable { ref table, ref alias } => {
+                let mut cur_node: Box<PlanNode> = Box::new(WorkingTableScanNode::new(table.clone()));
+                try!(cur_node.prepare());
+                if let Some(ref name) = *alias {
+                    cur_node = Box::new(RenameNode::new(cur_node, name.as_str()));
+                    try!(cur_node.prepare());
+                }
+                Ok(cur_node)
+            }
         }
     }
 }
 
 impl<'a> Planner for SimplePlanner<'a> {
     fn make_plan(&self, mut clause: SelectClause) -> NodeResult {
+        if clause.set_operation.is_some() {
+            return make_set_operation_plan(clause, self, self.file_manager, self.sort_memory);
+        }
+
         let node = match clause.from_clause.clone() {
             Some(ref from_clause) => {
-                let mut cur_node = try!(self.make_join_tree(from_clause.clone()));
+                // EXISTS and IN subquery tests are done with joins instead of with the predicate.
+                let (where_expr, subquery_tests) = split_subquery_tests(clause.where_expr.take());
+                clause.where_expr = where_expr;
+
+                // Subqueries can only be evaluated with a planner, which scans don't have, so a
+                // WHERE clause with subqueries is applied with a filter node instead.
+                let scan_predicate = match clause.where_expr {
+                    Some(ref expr) if !has_subquery(expr) => Some(expr),
+                    _ => None,
+                };
+                let mut cur_node = try!(self.make_join_tree(from_clause.clone(), scan_predicate));
                 try!(cur_node.prepare());
 
                 // Look for aggregate function calls, and transform expressions that include them so
                 // that we can compute them all in one grouping / aggregate plan node.
                 let extractor = try!(prepare_aggregates(&mut clause));
+                let windows = try!(prepare_windows(&mut clause));
 
-                if cur_node.has_predicate() {
-                    if let Some(ref expr) = clause.where_expr {
+                if let Some(ref expr) = clause.where_expr {
+                    if cur_node.has_predicate() && !has_subquery(expr) {
                         try!(cur_node.as_mut().set_predicate(expr.clone()));
-                    }
-                }
-
-                // Handle grouping and aggregation next, if there are any aggregate operations.
-                let has_group_by_exprs = if let Some(ref exprs) = clause.group_by_exprs {
-                    !exprs.is_empty()
-                } else {
-                    false
-                };
-                if extractor.found_aggregates() || has_group_by_exprs {
-                    // Get the aggregates too (if present).
-                    let aggregates = extractor.get_aggregate_calls();
-
-                    // By default, use a hash-based grouping/aggregate node. Later we can replace
-                    // with a sort-based grouping/aggregate node if it would be more efficient.
-                    let node = try!(HashedGroupAggregateNode::new(cur_node,
-                                                             clause.group_by_exprs
-                                                                 .clone()
-                                                                 .unwrap_or(vec![]),
-                                                             aggregates));
-                    cur_node = Box::new(node);
-                    try!(cur_node.prepare());
-                }
-
-                // Here we handle any non-trivial projection. Essentially, the only time we don't
-                // project our results is when we select a wildcard that is not table specific by
-                // itself. So `SELECT foo.*` does not count, and neither does `SELECT COUNT(*)`.
-                // Only `SELECT *` counts as a trivial projection.
-                //
-                // If the projection is non-trivial and doesn't involve aggregates, then we just
-                // take the values the user provided. If there are aggregates, however, we replace
-                // the temporary aggregate name with the original expression, e.g. `COUNT(foo.a)`.
-                // Otherwise we proceed as before.
-                if !clause.is_trivial_project() || extractor.found_aggregates() {
-                    let values = if !extractor.found_aggregates() {
-                        clause.values.clone()
                     } else {
-                        let aggregates = extractor.get_aggregate_call_map();
-                        let mut values: Vec<SelectValue> = Vec::new();
-                        for value in clause.values.iter() {
-                            match *value {
-                                SelectValue::Expression { ref expression, ref alias } => {
-                                    match *alias {
-                                        Some(_) => values.push(value.clone()),
-                                        None => {
-                                            if let Expression::ColumnValue(ref col_name) = *expression {
-                                                // shouldn't be able to get this far without actually having a column name
-                                                debug_assert!(col_name.1.is_some());
-
-                                                let name = col_name.1.clone().unwrap();
-                                                if let Some(ref expr) = aggregates.get(&name) {
-                                                    values.push(SelectValue::Expression {
-                                                        expression: Expression::ColumnValue(col_name.clone()),
-                                                        alias: Some(format!("{}", expr))
-                                                    });
-                                                } else {
-                                                    values.push(value.clone());
-                                                }
-                                            } else {
-                                                values.push(value.clone());
-                                            }
-                                        }
-                                    }
-                                }
-                                SelectValue::WildcardColumn { .. } => values.push(value.clone())
-                            }
-                        }
-                        values
-                    };
-                    cur_node = Box::new(ProjectNode::new(cur_node, values, self));
-                    try!(cur_node.prepare());
+                        cur_node = Box::new(SimpleFilterNode::with_planner(cur_node, expr.clone(), self));
+                        try!(cur_node.prepare());
+                    }
                 }
 
-                cur_node
+                let cur_node = try!(make_subquery_joins(cur_node, subquery_tests, self));
+                try!(finish_select_plan(cur_node, &clause, &extractor, &windows, self, self.file_manager,
+                                        self.sort_memory, None))
             }
             None => {
                 let mut cur_node = Box::new(try!(ProjectNode::scalar(clause.values, self)));
@@ -0,0 +1,107 @@
+use ::expressions::{Expression, ExpressionError, ExpressionProcessor};
+
+/// This expression-processor implementation looks for window function calls within an expression,
+/// extracts them and gives them a name, then replaces the calls with column-lookups using the
+/// generated names, in the same way as the
+/// [`AggregateFunctionExtractor`](struct.AggregateFunctionExtractor.html) does for aggregates.
+pub struct WindowFunctionExtractor {
+    window_calls: Vec<(String, Expression)>,
+    current_call: Option<Expression>,
+}
+
+impl WindowFunctionExtractor {
+    /// Create a new window function extractor.
+    pub fn new() -> WindowFunctionExtractor {
+        WindowFunctionExtractor {
+            window_calls: Vec::new(),
+            current_call: None,
+        }
+    }
+
+    /// Whether window function calls were found or not.
+    #[inline]
+    pub fn found_window_functions(&self) -> bool { !self.window_calls.is_empty() }
+
+    /// Returns the window function calls along with the names they were given, in the order they
+    /// were found.
+    #[inline]
+    pub fn get_window_calls(&self) -> &[(String, Expression)] {
+        &self.window_calls
+    }
+
+    /// Returns the window function call given the name, if there is one.
+    pub fn get_window_call(&self, name: &str) -> Option<&Expression> {
+        self.window_calls.iter().find(|&&(ref call_name, _)| call_name == name).map(|&(_, ref expr)| expr)
+    }
+}
+
+impl ExpressionProcessor for WindowFunctionExtractor {
+    fn enter(&mut self, node: &Expression) -> Result<(), ExpressionError> {
+        if let Expression::WindowFunction { .. } = *node {
+            if let Some(ref call) = self.current_call {
+                return Err(ExpressionError::NestedWindowFunction {
+                    parent: call.clone(),
+                    nested: node.clone(),
+                });
+            }
+            self.current_call = Some(node.clone());
+        }
+        Ok(())
+    }
+
+    fn leave(&mut self, node: &Expression) -> Result<Expression, ExpressionError> {
+        if let Expression::WindowFunction { .. } = *node {
+            self.current_call = None;
+
+            // The function will be computed by a window node, so replace the call with a
+            // placeholder column name.
+            let name = format!("#WIN{}", self.window_calls.len() + 1);
+            self.window_calls.push((name.clone(), node.clone()));
+            return Ok(Expression::ColumnValue((None, Some(name))));
+        }
+        Ok(node.clone())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    use ::expressions::{Expression, ExpressionError, WindowSpec};
+
+    #[test]
+    fn test_processor() {
+        let mut extractor = WindowFunctionExtractor::new();
+
+        let window = Box::new(WindowSpec::new(vec![Expression::ColumnValue((None, Some("A".into())))],
+                                              vec![],
+                                              None));
+        let rank = Expression::WindowFunction {
+            name: "RANK".into(),
+            distinct: false,
+            args: vec![],
+            window: window.clone(),
+        };
+        let mut expr1 = Expression::Arithmetic(Box::new(rank.clone()),
+                                               ::expressions::ArithmeticType::Plus,
+                                               Box::new(Expression::Int(1)));
+        let mut expr2 = Expression::WindowFunction {
+            name: "SUM".into(),
+            distinct: false,
+            args: vec![rank.clone()],
+            window: window.clone(),
+        };
+
+        assert_eq!(Ok(Expression::Arithmetic(Box::new(Expression::ColumnValue((None, Some("#WIN1".into())))),
+                                             ::expressions::ArithmeticType::Plus,
+                                             Box::new(Expression::Int(1)))),
+                   expr1.traverse(&mut extractor));
+        assert!(extractor.found_window_functions());
+        assert_eq!(Some(&rank), extractor.get_window_call("#WIN1"));
+
+        assert_eq!(Err(ExpressionError::NestedWindowFunction {
+            parent: expr2.clone(),
+            nested: rank.clone(),
+        }), expr2.traverse(&mut extractor));
+    }
+}
@@ -2,7 +2,8 @@
 
 use std::fmt;
 
-use ::expressions::{Environment, Literal, SelectValue};
+use ::expressions::{Date, Decimal, Environment, Interval, Literal, SelectValue, Time, Timestamp};
+use ::expressions::temporal;
 use ::relations::Schema;
 
 /// A shorthand type for storing a column name in (table_name, column_name) form.
@@ -79,6 +80,8 @@ pub enum ColumnType {
     /// A date/time value with higher precision than
     /// [ColumnType::DateTime](enum.ColumnType.html#variant.DateTime).
     Timestamp,
+    /// An interval of time, in months, days and microseconds.
+    Interval,
     /// A file-pointer value. This is not exposed in SQL, but is used
     /// internally.
     FilePointer,
@@ -103,6 +106,7 @@ impl From<ColumnType> for u8 {
             ColumnType::Time => 32,
             ColumnType::DateTime => 33,
             ColumnType::Timestamp => 34,
+            ColumnType::Interval => 35,
             ColumnType::FilePointer => 41,
         }
     }
@@ -126,6 +130,7 @@ impl From<u8> for ColumnType {
             32 => ColumnType::Time,
             33 => ColumnType::DateTime,
             34 => ColumnType::Timestamp,
+            35 => ColumnType::Interval,
             41 => ColumnType::FilePointer,
             0 | _ => ColumnType::Null,
         }
@@ -149,17 +154,25 @@ impl ColumnType {
     /// # Arguments
     /// * expr - The expression to check.
     pub fn can_store_literal(&self, value: Literal) -> bool {
+        if let ColumnType::Numeric { scale, precision } = *self {
+            // Any number can be stored in a NUMERIC column, as long as it has few enough digits
+            // once it is rounded to the column's scale.
+            return match value.as_decimal() {
+                Some(d) => d.fits(precision, scale),
+                None => value == Literal::Null,
+            };
+        }
         match value {
             Literal::Long(_) => {
                 match *self {
-                    ColumnType::BigInt => true,
+                    ColumnType::BigInt | ColumnType::Float | ColumnType::Double => true,
                     _ => false,
                 }
             }
             Literal::Int(_) => {
                 match *self {
-                    ColumnType::Integer | ColumnType::TinyInt | ColumnType::SmallInt | ColumnType::Date |
-                    ColumnType::Time | ColumnType::Timestamp => true,
+                    ColumnType::Integer | ColumnType::TinyInt | ColumnType::SmallInt | ColumnType::BigInt |
+                    ColumnType::Float | ColumnType::Double => true,
                     _ => false,
                 }
             }
@@ -175,14 +188,41 @@ impl ColumnType {
                     _ => false,
                 }
             }
+            Literal::Decimal(_) => {
+                match *self {
+                    ColumnType::Double | ColumnType::Float => true,
+                    _ => false,
+                }
+            }
             Literal::String(s) => {
                 match *self {
                     ColumnType::Char { length } |
                     ColumnType::VarChar { length } => s.len() as u16 <= length,
                     ColumnType::Blob | ColumnType::Text => true,
+                    // Strings are parsed when they are stored in date and time columns.
+                    ColumnType::Date => temporal::as_date(&Literal::String(s)).is_some(),
+                    ColumnType::Time => temporal::as_time(&Literal::String(s)).is_some(),
+                    ColumnType::DateTime |
+                    ColumnType::Timestamp => temporal::as_timestamp(&Literal::String(s)).is_some(),
+                    ColumnType::Interval => temporal::as_interval(&Literal::String(s)).is_some(),
                     _ => false,
                 }
             }
+            Literal::Bytes(_) => *self == ColumnType::Blob,
+            Literal::Date(_) => {
+                match *self {
+                    ColumnType::Date | ColumnType::DateTime | ColumnType::Timestamp => true,
+                    _ => false,
+                }
+            }
+            Literal::Timestamp(_) => {
+                match *self {
+                    ColumnType::DateTime | ColumnType::Timestamp => true,
+                    _ => false,
+                }
+            }
+            Literal::Time(_) => *self == ColumnType::Time,
+            Literal::Interval(_) => *self == ColumnType::Interval,
             Literal::True | Literal::False => {
                 match *self {
                     ColumnType::TinyInt => true,
@@ -201,14 +241,30 @@ impl ColumnType {
             ColumnType::BigInt => Literal::Long(0),
             ColumnType::Float => Literal::Float(0.0),
             ColumnType::Double => Literal::Double(0.0),
+            ColumnType::Numeric { scale, .. } => Literal::Decimal(Decimal::new(0, scale)),
             ColumnType::Char { .. } |
             ColumnType::VarChar { .. } |
             ColumnType::Text => Literal::String("".into()),
+            ColumnType::Blob => Literal::Bytes(vec![]),
+            ColumnType::Date => Literal::Date(Date::from_days(0).unwrap()),
+            ColumnType::Time => Literal::Time(Time::from_micros(0)),
+            ColumnType::DateTime |
+            ColumnType::Timestamp => Literal::Timestamp(Timestamp::from_micros(0).unwrap()),
+            ColumnType::Interval => Literal::Interval(Interval::new(0, 0, 0)),
             // TODO
             _ => Literal::Null,
         }
     }
 
+    /// Whether the column type is a date, time, timestamp or interval.
+    pub fn is_temporal(&self) -> bool {
+        match *self {
+            ColumnType::Date | ColumnType::Time | ColumnType::DateTime | ColumnType::Timestamp |
+            ColumnType::Interval => true,
+            _ => false,
+        }
+    }
+
     /// Whether the column type is numeric.
     pub fn is_numeric(&self) -> bool {
         match *self {
@@ -217,6 +273,23 @@ impl ColumnType {
             _ => false,
         }
     }
+
+    /// Whether the column type is a character-sequence.
+    pub fn is_string(&self) -> bool {
+        match *self {
+            ColumnType::Char { .. } | ColumnType::VarChar { .. } | ColumnType::Text => true,
+            _ => false,
+        }
+    }
+
+    /// Whether values of the column type have a variable size, so that large values can be moved
+    /// out into overflow pages.
+    pub fn is_variable_length(&self) -> bool {
+        match *self {
+            ColumnType::VarChar { .. } | ColumnType::Text | ColumnType::Blob => true,
+            _ => false,
+        }
+    }
 }
 
 #[derive(Debug, Clone, PartialEq, Eq, Hash)]
@@ -4,7 +4,9 @@
 
 mod column;
 mod schema;
+mod table_stats;
 
 pub use self::column::{ColumnName, ColumnType, ColumnInfo, EMPTY_CHAR, EMPTY_NUMERIC, EMPTY_VARCHAR,
                        column_name_to_string};
-pub use self::schema::{Schema, Error as SchemaError, NameError};
\ No newline at end of file
+pub use self::schema::{Schema, Error as SchemaError, NameError};
+pub use self::table_stats::{ColumnStats, ColumnStatsCollector, TableStats, MAX_STRING_STATS_LENGTH};
\ No newline at end of file
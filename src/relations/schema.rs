@@ -58,6 +58,9 @@ pub enum Error {
     AmbiguousColumnsAfterTableRename(String, Vec<String>),
     /// A select value could not be resolved.
     CouldNotResolveSelectValue(SelectValue),
+    /// The wrong number of names was given for the columns of the schema. In the form of
+    /// `(names given, number of columns)`.
+    WrongNumberOfColumnNames(usize, usize),
 }
 
 impl ::std::fmt::Display for Error {
@@ -80,6 +83,9 @@ impl ::std::fmt::Display for Error {
             Error::CouldNotResolveSelectValue(ref value) => {
                 write!(f, "The select value {} could not be resolved.", value)
             }
+            Error::WrongNumberOfColumnNames(names, columns) => {
+                write!(f, "{} column names were given for {} columns.", names, columns)
+            }
         }
     }
 }
@@ -137,9 +143,15 @@ impl Schema {
 
     /// Creates a new schema by reading a header page.
     pub fn from_header_page(page: &mut DBPage) -> Result<Schema, Error> {
+        Schema::from_header_page_at(page, OFFSET_SCHEMA_START)
+    }
+
+    /// Creates a new schema by reading a header page, where the schema starts at the given offset.
+    /// This is for file formats that store extra metadata before the schema.
+    pub fn from_header_page_at(page: &mut DBPage, start: usize) -> Result<Schema, Error> {
         let mut result = Schema::new();
 
-        try!(page.seek(SeekFrom::Start(OFFSET_SCHEMA_START as u64)));
+        try!(page.seek(SeekFrom::Start(start as u64)));
 
         let num_tables = try!(page.read_u8());
         let mut table_names: Vec<String> = Vec::new();
@@ -166,8 +178,9 @@ impl Schema {
                 let length: u16 = try!(page.read_u16::<BigEndian>());
                 ColumnType::VarChar { length: length }
             } else if type_id == u8::from(EMPTY_NUMERIC) {
-                // TODO: When NUMERICs are written properly, read them properly here too.
-                EMPTY_NUMERIC.clone()
+                let precision = try!(page.read_u8());
+                let scale = try!(page.read_u8());
+                ColumnType::Numeric { scale: scale as u16, precision: precision as u16 }
             } else {
                 type_id.into()
             };
@@ -181,7 +194,7 @@ impl Schema {
                 return Err(Error::EmptyColumnName(i as usize));
             }
 
-            try!(result.add_column(ColumnInfo::with_table_name(col_type, col_name.as_ref(), table_name.as_ref())));
+            try!(result.add_column(ColumnInfo::with_table_name(col_type, col_name.as_str(), table_name.as_str())));
         }
 
         Ok(result)
@@ -375,6 +388,8 @@ impl Schema {
                 let key = Some(table_name.clone());
                 if let Some(table_cols) = self.cols_hashed_by_table.get(&key) {
                     found.extend(table_cols.values().map(|idx| (*idx, self.column_infos[*idx].clone())));
+                    // Keep the columns in schema order, rather than hash order.
+                    found.sort_by_key(|&(idx, _)| idx);
                 }
             }
             (None, Some(ref column_name)) => {
@@ -407,10 +422,22 @@ impl Schema {
     ///
     /// # Errors
     /// This function can fail if anything goes wrong trying to write to the given output.
-    pub fn write<W: WriteNanoDBExt + Seek>(&self, mut output: &mut W) -> Result<(), io::Error> {
+    pub fn write<W: WriteNanoDBExt + Seek>(&self, output: &mut W) -> Result<(), io::Error> {
+        self.write_at(output, OFFSET_SCHEMA_START)
+    }
+
+    /// Write the schema to some output, starting at the given offset.
+    ///
+    /// # Arguments
+    /// * output - The output to write the schema to.
+    /// * start - The offset at which the schema starts.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to write to the given output.
+    pub fn write_at<W: WriteNanoDBExt + Seek>(&self, mut output: &mut W, start: usize) -> Result<(), io::Error> {
         info! ("Writing table schema: {}", self);
 
-        try!(output.seek(SeekFrom::Start(OFFSET_SCHEMA_START as u64)));
+        try!(output.seek(SeekFrom::Start(start as u64)));
 
         let mut table_mapping: HashMap<Option<String>, usize> = Default::default();
         let mut cur_table: usize = 0;
@@ -440,7 +467,10 @@ impl Schema {
                 ColumnType::VarChar { length } => {
                     try!(output.write_u16::<BigEndian>(length as u16));
                 }
-                // TODO: Handle NUMERIC here.
+                ColumnType::Numeric { scale, precision } => {
+                    try!(output.write_u8(precision as u8));
+                    try!(output.write_u8(scale as u8));
+                }
                 _ => {}
             }
 
@@ -513,6 +543,25 @@ impl Schema {
 
         Ok(())
     }
+
+    /// This method renames the columns of this schema, in order, to the specified names. The table
+    /// names of the columns are kept. This method will return an error if the number of names
+    /// doesn't match the number of columns, or if the result would have duplicate column names.
+    pub fn set_column_names<S: AsRef<str>>(&mut self, names: &[S]) -> Result<(), Error> {
+        if names.len() != self.column_infos.len() {
+            return Err(Error::WrongNumberOfColumnNames(names.len(), self.column_infos.len()));
+        }
+
+        let mut result = Schema::new();
+        for (info, name) in self.column_infos.iter().zip(names.iter()) {
+            let mut new_info = info.clone();
+            new_info.name = Some(name.as_ref().into());
+            try!(result.add_column(new_info));
+        }
+
+        *self = result;
+        Ok(())
+    }
 }
 
 impl ::std::fmt::Display for Schema {
@@ -683,4 +732,22 @@ mod tests {
         schema4.set_table_name("ABC"));
         assert_eq!(vec![a_foo.clone(), a_bar.clone()], schema4.column_infos);
     }
+
+    #[test]
+    fn test_set_column_names() {
+        let a_foo = ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO");
+        let b_bar = ColumnInfo::with_table_name(ColumnType::Float, "B", "BAR");
+        let c_foo = ColumnInfo::with_table_name(ColumnType::Integer, "C", "FOO");
+        let d_bar = ColumnInfo::with_table_name(ColumnType::Float, "D", "BAR");
+
+        let mut schema = Schema::with_columns(vec![a_foo.clone(), b_bar.clone()]).unwrap();
+
+        assert_eq!(Ok(()), schema.set_column_names(&["C", "D"]));
+        assert_eq!(vec![c_foo.clone(), d_bar.clone()], schema.column_infos);
+        assert!(schema.has_column("C"));
+        assert!(!schema.has_column("A"));
+
+        assert_eq!(Err(Error::WrongNumberOfColumnNames(1, 2)), schema.set_column_names(&["E"]));
+        assert_eq!(vec![c_foo.clone(), d_bar.clone()], schema.column_infos);
+    }
 }
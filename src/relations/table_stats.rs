@@ -0,0 +1,257 @@
+//! This module contains the statistics that `ANALYZE` collects about a table and its columns, which
+//! the query planner uses to estimate the cost of plans.
+//!
+//! The statistics are stored in the table's header page, after the rest of the table's metadata.
+//! They are laid out like this:
+//!
+//! * The number of data pages, the number of tuples (both unsigned ints) and the average tuple
+//!   size (a float).
+//! * The number of columns with statistics (an unsigned byte), followed by the statistics of each
+//!   column: the number of distinct values and of `NULL` values (unsigned ints, which are
+//!   `0xFFFFFFFF` when unknown) and the minimum and maximum values.
+//!
+//! The minimum and maximum values are stored as a type byte followed by the value, so that they can
+//! be read without the column's type. Strings longer than
+//! [`MAX_STRING_STATS_LENGTH`](constant.MAX_STRING_STATS_LENGTH.html) aren't kept, so that the
+//! statistics of a table always fit in its header page.
+
+use std::collections::HashSet;
+use std::io;
+
+use byteorder::BigEndian;
+
+use ::expressions::Literal;
+use ::storage::{ReadNanoDBExt, WriteNanoDBExt};
+
+/// The longest string value that is kept as a column's minimum or maximum value.
+pub const MAX_STRING_STATS_LENGTH: usize = 64;
+
+/// The value stored in place of a count that is unknown.
+const UNKNOWN_COUNT: u32 = 0xFFFFFFFF;
+
+/// The statistics of a single column of a table.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ColumnStats {
+    /// The number of distinct non-`NULL` values in the column, if known.
+    pub num_unique_values: Option<u32>,
+    /// The number of `NULL` values in the column, if known.
+    pub num_null_values: Option<u32>,
+    /// The smallest non-`NULL` value in the column, or `Literal::Null` if unknown.
+    pub min_value: Literal,
+    /// The largest non-`NULL` value in the column, or `Literal::Null` if unknown.
+    pub max_value: Literal,
+}
+
+impl ColumnStats {
+    /// Creates the statistics of a column that hasn't been analyzed, so that nothing is known.
+    pub fn new() -> ColumnStats {
+        ColumnStats {
+            num_unique_values: None,
+            num_null_values: None,
+            min_value: Literal::Null,
+            max_value: Literal::Null,
+        }
+    }
+
+    /// Write the column statistics to some output.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to write to the given output.
+    pub fn write<W: WriteNanoDBExt>(&self, output: &mut W) -> Result<(), io::Error> {
+        try!(output.write_u32::<BigEndian>(self.num_unique_values.unwrap_or(UNKNOWN_COUNT)));
+        try!(output.write_u32::<BigEndian>(self.num_null_values.unwrap_or(UNKNOWN_COUNT)));
+        try!(self.min_value.write_tagged(output));
+        self.max_value.write_tagged(output)
+    }
+
+    /// Reads column statistics from some input.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to read from the given input.
+    pub fn read<R: ReadNanoDBExt>(input: &mut R) -> Result<ColumnStats, io::Error> {
+        let read_count = |count: u32| if count == UNKNOWN_COUNT { None } else { Some(count) };
+        let num_unique_values = read_count(try!(input.read_u32::<BigEndian>()));
+        let num_null_values = read_count(try!(input.read_u32::<BigEndian>()));
+        Ok(ColumnStats {
+            num_unique_values: num_unique_values,
+            num_null_values: num_null_values,
+            min_value: try!(Literal::read_tagged(input)),
+            max_value: try!(Literal::read_tagged(input)),
+        })
+    }
+}
+
+impl Default for ColumnStats {
+    fn default() -> Self {
+        ColumnStats::new()
+    }
+}
+
+/// The statistics of a table, as collected by `ANALYZE`.
+#[derive(Debug, Clone, PartialEq)]
+pub struct TableStats {
+    /// The number of pages of the table's file that hold tuples.
+    pub num_data_pages: u32,
+    /// The number of tuples in the table.
+    pub num_tuples: u32,
+    /// The average number of bytes a tuple takes up in its page.
+    pub avg_tuple_size: f32,
+    /// The statistics of each column of the table, in schema order.
+    pub column_stats: Vec<ColumnStats>,
+}
+
+impl TableStats {
+    /// Creates the statistics of a table that hasn't been analyzed.
+    ///
+    /// # Arguments
+    /// * num_columns - The number of columns in the table.
+    pub fn new(num_columns: usize) -> TableStats {
+        TableStats {
+            num_data_pages: 0,
+            num_tuples: 0,
+            avg_tuple_size: 0.0,
+            column_stats: vec![ColumnStats::new(); num_columns],
+        }
+    }
+
+    /// Write the table statistics to some output.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to write to the given output.
+    pub fn write<W: WriteNanoDBExt>(&self, output: &mut W) -> Result<(), io::Error> {
+        try!(output.write_u32::<BigEndian>(self.num_data_pages));
+        try!(output.write_u32::<BigEndian>(self.num_tuples));
+        try!(output.write_f32::<BigEndian>(self.avg_tuple_size));
+        try!(output.write_u8(self.column_stats.len() as u8));
+        for stats in self.column_stats.iter() {
+            try!(stats.write(output));
+        }
+        Ok(())
+    }
+
+    /// Reads table statistics from some input. Files written before statistics were stored have
+    /// zeroes here, which read as an empty table with unknown column statistics.
+    ///
+    /// # Arguments
+    /// * input - The input to read from.
+    /// * num_columns - The number of columns in the table.
+    ///
+    /// # Errors
+    /// This function can fail if anything goes wrong trying to read from the given input.
+    pub fn read<R: ReadNanoDBExt>(input: &mut R, num_columns: usize) -> Result<TableStats, io::Error> {
+        let mut result = TableStats::new(0);
+        result.num_data_pages = try!(input.read_u32::<BigEndian>());
+        result.num_tuples = try!(input.read_u32::<BigEndian>());
+        result.avg_tuple_size = try!(input.read_f32::<BigEndian>());
+        let num_stats = try!(input.read_u8()) as usize;
+        for _ in 0..num_stats {
+            result.column_stats.push(try!(ColumnStats::read(input)));
+        }
+        result.column_stats.resize(num_columns, ColumnStats::new());
+        Ok(result)
+    }
+}
+
+/// Collects the statistics of a single column as the values of the column are scanned.
+#[derive(Debug)]
+pub struct ColumnStatsCollector {
+    unique_values: HashSet<Literal>,
+    num_null_values: u32,
+    min_value: Literal,
+    max_value: Literal,
+}
+
+impl ColumnStatsCollector {
+    /// Creates a collector that hasn't seen any values.
+    pub fn new() -> ColumnStatsCollector {
+        ColumnStatsCollector {
+            unique_values: HashSet::new(),
+            num_null_values: 0,
+            min_value: Literal::Null,
+            max_value: Literal::Null,
+        }
+    }
+
+    /// Records a value of the column.
+    ///
+    /// # Arguments
+    /// * value - The value to record.
+    pub fn add_value(&mut self, value: Literal) {
+        if value == Literal::Null {
+            self.num_null_values += 1;
+            return;
+        }
+
+        if self.min_value == Literal::Null || value.compare(&self.min_value) == ::std::cmp::Ordering::Less {
+            self.min_value = value.clone();
+        }
+        if self.max_value == Literal::Null || value.compare(&self.max_value) == ::std::cmp::Ordering::Greater {
+            self.max_value = value.clone();
+        }
+        self.unique_values.insert(value);
+    }
+
+    /// Returns the statistics of the values recorded so far.
+    pub fn get_column_stats(&self) -> ColumnStats {
+        let keep = |value: &Literal| match *value {
+            Literal::String(ref s) if s.len() > MAX_STRING_STATS_LENGTH => Literal::Null,
+            Literal::Bytes(ref b) if b.len() > MAX_STRING_STATS_LENGTH => Literal::Null,
+            Literal::FilePointer { .. } => Literal::Null,
+            ref value => value.clone(),
+        };
+        ColumnStats {
+            num_unique_values: Some(self.unique_values.len() as u32),
+            num_null_values: Some(self.num_null_values),
+            min_value: keep(&self.min_value),
+            max_value: keep(&self.max_value),
+        }
+    }
+}
+
+impl Default for ColumnStatsCollector {
+    fn default() -> Self {
+        ColumnStatsCollector::new()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::io::Cursor;
+
+    use super::*;
+
+    #[test]
+    fn test_collect_and_write() {
+        let mut collector = ColumnStatsCollector::new();
+        for value in vec![Literal::Int(3), Literal::Null, Literal::Int(1), Literal::Int(3), Literal::Int(7)] {
+            collector.add_value(value);
+        }
+        let column_stats = collector.get_column_stats();
+        assert_eq!(Some(3), column_stats.num_unique_values);
+        assert_eq!(Some(1), column_stats.num_null_values);
+        assert_eq!(Literal::Int(1), column_stats.min_value);
+        assert_eq!(Literal::Int(7), column_stats.max_value);
+
+        let mut long_strings = ColumnStatsCollector::new();
+        long_strings.add_value(Literal::String("a".into()));
+        long_strings.add_value(Literal::String("z".repeat(MAX_STRING_STATS_LENGTH + 1)));
+        let string_stats = long_strings.get_column_stats();
+        assert_eq!(Literal::String("a".into()), string_stats.min_value);
+        assert_eq!(Literal::Null, string_stats.max_value);
+
+        let stats = TableStats {
+            num_data_pages: 2,
+            num_tuples: 5,
+            avg_tuple_size: 9.5,
+            column_stats: vec![column_stats, string_stats, ColumnStats::new()],
+        };
+        let mut buffer = Cursor::new(Vec::new());
+        stats.write(&mut buffer).unwrap();
+        buffer.set_position(0);
+        assert_eq!(stats, TableStats::read(&mut buffer, 3).unwrap());
+
+        // Zeroes read as a table that hasn't been analyzed.
+        let mut zeroes = Cursor::new(vec![0u8; 16]);
+        assert_eq!(TableStats::new(2), TableStats::read(&mut zeroes, 2).unwrap());
+    }
+}
@@ -3,8 +3,10 @@
 use std::fs;
 use std::path::{Path, PathBuf};
 
-use ::commands::Command;
-use ::storage::{FileManager, TableManager};
+use ::commands::{Command, CreateCommand, DropCommand, ExecutionError, TransactionCommand};
+use ::queries::{DEFAULT_SORT_MEMORY, PlannerType};
+use ::storage::{FileManager, TableManager, storage_manager};
+use ::transactions::transaction_manager;
 
 /// This class provides the entry-point operations for managing the database server, and executing
 /// commands against it. While it is certainly possible to implement these operations outside of
@@ -15,6 +17,11 @@ pub struct Server {
     pub file_manager: FileManager,
     /// The server's table manager instance.
     pub table_manager: TableManager,
+    /// The planner used to plan `SELECT` statements. This can be changed with `SET PLANNER`.
+    pub planner_type: PlannerType,
+    /// The number of bytes of tuples each sort may keep in memory before it writes them out to
+    /// run files. This can be changed with `SET SORT_MEMORY`.
+    pub sort_memory: usize,
 }
 
 impl Server {
@@ -25,24 +32,89 @@ impl Server {
     }
 
     /// Instantiates a new server instance with the data files stored at the provided path.
+    ///
+    /// If the server previously stopped in the middle of a command, the data files are recovered
+    /// from the write-ahead log before the server starts.
     pub fn with_data_path<P: AsRef<Path>>(path: P) -> Server {
         let path = path.as_ref();
         if !path.exists() {
             fs::create_dir(&path).unwrap();
         }
         let file_manager = FileManager::with_directory(&path).unwrap();
+        if let Err(e) = transaction_manager::start_up(&path) {
+            panic!("Unable to recover the data files in {}: {}", path.display(), e);
+        }
         Server {
             file_manager: file_manager,
             table_manager: TableManager::new(),
+            planner_type: PlannerType::Simple,
+            sort_memory: DEFAULT_SORT_MEMORY,
         }
     }
 
     /// Executes a provided command.
     ///
-    /// If an error occurs in the command, it is printed to the console.
+    /// Outside of a transaction, each command runs in its own transaction: if an error occurs in
+    /// the command, it is printed to the console and the command's changes are rolled back;
+    /// otherwise the transaction is committed. Inside a transaction started with `BEGIN`, a failed
+    /// command only rolls back its own changes, and the transaction stays in progress. The pages
+    /// the command dirtied stay in the buffer manager; committed changes are durable through the
+    /// write-ahead log, and the pages are written back when they are evicted, at a checkpoint, or
+    /// when the server shuts down.
     pub fn handle_command(&mut self, mut command: Box<Command>) {
-        if let Err(e) = command.execute(self, &mut ::std::io::stdout()) {
+        let data_dir = self.file_manager.get_base_dir().to_path_buf();
+        let result = if command.as_any().is::<TransactionCommand>() {
+            match command.execute(self, &mut ::std::io::stdout()) {
+                Ok(_) => Ok(()),
+                Err(e) => {
+                    println!("{}", e);
+                    Ok(())
+                }
+            }
+        } else if transaction_manager::is_in_transaction(&data_dir) {
+            if is_ddl_command(&*command) {
+                println!("{}", ExecutionError::DDLInTransaction);
+                Ok(())
+            } else {
+                transaction_manager::get_savepoint(&data_dir).and_then(|savepoint| {
+                    match command.execute(self, &mut ::std::io::stdout()) {
+                        Ok(_) => Ok(()),
+                        Err(e) => {
+                            println!("{}", e);
+                            transaction_manager::rollback_to_savepoint(&data_dir, savepoint)
+                        }
+                    }
+                })
+            }
+        } else {
+            transaction_manager::begin_transaction(&data_dir).and_then(|_| {
+                match command.execute(self, &mut ::std::io::stdout()) {
+                    Ok(_) => transaction_manager::commit_transaction(&data_dir),
+                    Err(e) => {
+                        println!("{}", e);
+                        transaction_manager::rollback_transaction(&data_dir)
+                    }
+                }
+            })
+        };
+        if let Err(e) = result {
             println!("{}", e);
         }
     }
 }
+
+impl Drop for Server {
+    fn drop(&mut self) {
+        // Shutting down writes every cached page back to disk, so that committed changes aren't
+        // left only in the write-ahead log.
+        if let Err(e) = storage_manager::flush_all_pages() {
+            println!("{}", e);
+        }
+    }
+}
+
+/// Checks whether a command creates or drops a table or index. Since creating and deleting files
+/// isn't logged, these commands can't be run inside a transaction.
+fn is_ddl_command(command: &Command) -> bool {
+    command.as_any().is::<CreateCommand>() || command.as_any().is::<DropCommand>()
+}
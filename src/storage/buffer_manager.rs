@@ -0,0 +1,495 @@
+//! This module contains the Buffer Manager, which caches the pages of database files in memory so
+//! that repeatedly accessing the same page does not go back to the disk every time.
+//!
+//! Pages are cached by the path of the file they belong to and their page number. The cached data is
+//! shared with the pages handed out, rather than copied for each of them. Each cached page carries
+//! a pin-count; a page with a positive pin-count is in use and will never be evicted.
+//! When the cache grows past its configured size, unpinned pages are evicted in the order chosen by
+//! a [`ReplacementPolicy`](trait.ReplacementPolicy.html), and dirty pages are written back to their
+//! file as they are evicted.
+
+use std::collections::HashMap;
+use std::fs::OpenOptions;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use ::storage::{DBFile, DBFileType, file_manager};
+use ::transactions::LogSequenceNumber;
+use ::transactions::transaction_manager;
+
+/// The default size of the page cache, in bytes.
+pub const DEFAULT_PAGECACHE_SIZE: usize = 1024 * 1024;
+
+/// A key uniquely identifying a page of a database file: the path of the file and the page number.
+pub type PageKey = (PathBuf, u32);
+
+/// A replacement policy decides which page should be evicted from the buffer manager when space
+/// needs to be freed up. The buffer manager notifies the policy whenever pages are added, accessed
+/// or removed, and asks the policy for a victim when the cache is full.
+pub trait ReplacementPolicy: ::std::fmt::Debug + Send {
+    /// Records that a page has been added to the cache.
+    fn page_added(&mut self, key: &PageKey);
+
+    /// Records that a page already in the cache has been accessed.
+    fn page_accessed(&mut self, key: &PageKey);
+
+    /// Records that a page has been removed from the cache.
+    fn page_removed(&mut self, key: &PageKey);
+
+    /// Chooses a page to evict, or `None` if no page can be evicted.
+    ///
+    /// # Arguments
+    /// * can_evict - Returns whether a given page is allowed to be evicted (i.e. it is unpinned).
+    fn choose_victim(&mut self, can_evict: &Fn(&PageKey) -> bool) -> Option<PageKey>;
+}
+
+/// A least-recently-used replacement policy. The page that was accessed the longest time ago is
+/// evicted first.
+#[derive(Debug, Default)]
+pub struct LRUPolicy {
+    last_access: HashMap<PageKey, u64>,
+    clock: u64,
+}
+
+impl LRUPolicy {
+    /// Instantiates a new LRU policy.
+    pub fn new() -> LRUPolicy {
+        Default::default()
+    }
+
+    fn touch(&mut self, key: &PageKey) {
+        self.clock += 1;
+        self.last_access.insert(key.clone(), self.clock);
+    }
+}
+
+impl ReplacementPolicy for LRUPolicy {
+    fn page_added(&mut self, key: &PageKey) {
+        self.touch(key);
+    }
+
+    fn page_accessed(&mut self, key: &PageKey) {
+        self.touch(key);
+    }
+
+    fn page_removed(&mut self, key: &PageKey) {
+        self.last_access.remove(key);
+    }
+
+    fn choose_victim(&mut self, can_evict: &Fn(&PageKey) -> bool) -> Option<PageKey> {
+        self.last_access
+            .iter()
+            .filter(|&(key, _)| can_evict(key))
+            .min_by_key(|&(_, time)| *time)
+            .map(|(key, _)| key.clone())
+    }
+}
+
+/// A CLOCK (second-chance) replacement policy. Pages are arranged in a ring with a reference bit
+/// each; the clock hand sweeps the ring, clearing reference bits, and evicts the first unpinned
+/// page whose bit is already clear.
+#[derive(Debug, Default)]
+pub struct ClockPolicy {
+    ring: Vec<PageKey>,
+    referenced: HashMap<PageKey, bool>,
+    hand: usize,
+}
+
+impl ClockPolicy {
+    /// Instantiates a new CLOCK policy.
+    pub fn new() -> ClockPolicy {
+        Default::default()
+    }
+}
+
+impl ReplacementPolicy for ClockPolicy {
+    fn page_added(&mut self, key: &PageKey) {
+        if self.referenced.insert(key.clone(), true).is_none() {
+            self.ring.push(key.clone());
+        }
+    }
+
+    fn page_accessed(&mut self, key: &PageKey) {
+        if let Some(bit) = self.referenced.get_mut(key) {
+            *bit = true;
+        }
+    }
+
+    fn page_removed(&mut self, key: &PageKey) {
+        if self.referenced.remove(key).is_none() {
+            return;
+        }
+        if let Some(index) = self.ring.iter().position(|k| k == key) {
+            self.ring.remove(index);
+            if index < self.hand {
+                self.hand -= 1;
+            }
+            if self.hand >= self.ring.len() {
+                self.hand = 0;
+            }
+        }
+    }
+
+    fn choose_victim(&mut self, can_evict: &Fn(&PageKey) -> bool) -> Option<PageKey> {
+        // Two full sweeps are enough: the first clears every reference bit, so the second is
+        // guaranteed to find a victim if any unpinned page exists.
+        for _ in 0..(2 * self.ring.len()) {
+            let key = self.ring[self.hand].clone();
+            self.hand = (self.hand + 1) % self.ring.len();
+            if !can_evict(&key) {
+                continue;
+            }
+            let bit = self.referenced.get_mut(&key).unwrap();
+            if *bit {
+                *bit = false;
+            } else {
+                return Some(key);
+            }
+        }
+        None
+    }
+}
+
+#[derive(Debug)]
+struct BufferedPage {
+    data: Arc<Vec<u8>>,
+    pin_count: u32,
+    dirty: bool,
+}
+
+/// The buffer manager caches pages of database files, up to a maximum number of bytes.
+#[derive(Debug)]
+pub struct BufferManager {
+    max_cache_size: usize,
+    cache_size: usize,
+    pages: HashMap<PageKey, BufferedPage>,
+    policy: Box<ReplacementPolicy>,
+    hits: u64,
+    misses: u64,
+}
+
+impl Default for BufferManager {
+    fn default() -> BufferManager {
+        BufferManager::new(DEFAULT_PAGECACHE_SIZE, Box::new(LRUPolicy::new()))
+    }
+}
+
+/// Writes the given page data back to the file at the provided path. The write-ahead log is forced
+/// up to the page's LSN first, so that the page's changes can always be undone.
+fn write_back(path: &Path, page_no: u32, data: &[u8]) -> Result<(), file_manager::Error> {
+    try!(transaction_manager::force_wal(path, LogSequenceNumber::read_from_page(data))
+        .map_err(|e| file_manager::Error::WALError(e.to_string())));
+    let file = try!(OpenOptions::new().write(true).open(path));
+    let mut dbfile = try!(DBFile::with_path(DBFileType::Unknown, data.len() as u32, file, path));
+    file_manager::save_page(&mut dbfile, page_no, data)
+}
+
+impl BufferManager {
+    /// Instantiates a new buffer manager.
+    ///
+    /// # Arguments
+    /// * max_cache_size - The maximum number of bytes of page data to keep in memory.
+    /// * policy - The policy used to decide which pages to evict.
+    pub fn new(max_cache_size: usize, policy: Box<ReplacementPolicy>) -> BufferManager {
+        BufferManager {
+            max_cache_size: max_cache_size,
+            cache_size: 0,
+            pages: HashMap::new(),
+            policy: policy,
+            hits: 0,
+            misses: 0,
+        }
+    }
+
+    /// Returns the maximum number of bytes of page data the buffer manager will hold.
+    pub fn get_max_cache_size(&self) -> usize {
+        self.max_cache_size
+    }
+
+    /// Returns the number of bytes of page data currently held.
+    pub fn get_cache_size(&self) -> usize {
+        self.cache_size
+    }
+
+    /// Returns the number of page requests that were served from the cache.
+    pub fn get_hits(&self) -> u64 {
+        self.hits
+    }
+
+    /// Returns the number of page requests that were not in the cache.
+    pub fn get_misses(&self) -> u64 {
+        self.misses
+    }
+
+    /// Checks whether a page is currently cached.
+    pub fn contains_page(&self, key: &PageKey) -> bool {
+        self.pages.contains_key(key)
+    }
+
+    /// Returns the pin-count of a cached page, or `None` if the page is not cached.
+    pub fn get_pin_count(&self, key: &PageKey) -> Option<u32> {
+        self.pages.get(key).map(|page| page.pin_count)
+    }
+
+    /// Retrieves a cached page's data, or `None` if the page is not cached. The data is shared with
+    /// the cache, not copied.
+    pub fn get_page(&mut self, key: &PageKey) -> Option<Arc<Vec<u8>>> {
+        match self.pages.get(key) {
+            Some(page) => {
+                self.hits += 1;
+                self.policy.page_accessed(key);
+                Some(page.data.clone())
+            }
+            None => {
+                self.misses += 1;
+                None
+            }
+        }
+    }
+
+    /// Retrieves a cached page's data, or `None` if the page is not cached. Unlike
+    /// [`get_page`](#method.get_page), this doesn't count as an access to the page.
+    pub fn peek_page(&self, key: &PageKey) -> Option<Arc<Vec<u8>>> {
+        self.pages.get(key).map(|page| page.data.clone())
+    }
+
+    /// Adds a clean page to the cache, evicting other pages if there is not enough space. If the
+    /// page is already cached, its data is left untouched.
+    ///
+    /// # Errors
+    /// This will fail if a dirty page could not be written back while being evicted.
+    pub fn add_page(&mut self, key: PageKey, data: Arc<Vec<u8>>) -> Result<(), file_manager::Error> {
+        if self.pages.contains_key(&key) {
+            return Ok(());
+        }
+        try!(self.ensure_space_available(data.len()));
+        self.cache_size += data.len();
+        self.policy.page_added(&key);
+        self.pages.insert(key, BufferedPage {
+            data: data,
+            pin_count: 0,
+            dirty: false,
+        });
+        Ok(())
+    }
+
+    /// Replaces the data of a page and marks it dirty, so that it is written back to disk when it is
+    /// evicted or flushed. The page is added to the cache if it is not already there.
+    ///
+    /// # Errors
+    /// This will fail if a dirty page could not be written back while being evicted.
+    pub fn write_page(&mut self, key: PageKey, data: Arc<Vec<u8>>) -> Result<(), file_manager::Error> {
+        if !self.pages.contains_key(&key) {
+            try!(self.add_page(key.clone(), data.clone()));
+        }
+        let page = self.pages.get_mut(&key).unwrap();
+        page.data = data;
+        page.dirty = true;
+        self.policy.page_accessed(&key);
+        Ok(())
+    }
+
+    /// Increments the pin-count of a cached page. Does nothing if the page is not cached.
+    pub fn pin_page(&mut self, key: &PageKey) {
+        if let Some(page) = self.pages.get_mut(key) {
+            page.pin_count += 1;
+        }
+    }
+
+    /// Decrements the pin-count of a cached page. Does nothing if the page is not cached.
+    pub fn unpin_page(&mut self, key: &PageKey) {
+        if let Some(page) = self.pages.get_mut(key) {
+            if page.pin_count > 0 {
+                page.pin_count -= 1;
+            }
+        }
+    }
+
+    /// Evicts unpinned pages until there is enough room for a new page of the given size. If every
+    /// page is pinned, the cache is allowed to grow past its maximum size.
+    fn ensure_space_available(&mut self, size: usize) -> Result<(), file_manager::Error> {
+        while self.cache_size + size > self.max_cache_size {
+            let victim = {
+                let pages = &self.pages;
+                self.policy.choose_victim(&|key: &PageKey| {
+                    pages.get(key).map(|page| page.pin_count == 0) == Some(true)
+                })
+            };
+            match victim {
+                Some(key) => try!(self.evict_page(&key)),
+                None => {
+                    warn!("All {} cached pages are pinned; exceeding the page cache size of {} bytes.",
+                          self.pages.len(),
+                          self.max_cache_size);
+                    break;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn evict_page(&mut self, key: &PageKey) -> Result<(), file_manager::Error> {
+        debug!("Evicting page {} of file {} from the buffer manager.", key.1, key.0.display());
+        if let Some(page) = self.pages.remove(key) {
+            self.policy.page_removed(key);
+            self.cache_size -= page.data.len();
+            if page.dirty {
+                if key.0.exists() {
+                    try!(write_back(key.0.as_path(), key.1, &page.data));
+                } else {
+                    warn!("Discarding dirty page {} of missing file {}.", key.1, key.0.display());
+                }
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every dirty page of the given file back to disk. The pages stay in the cache.
+    ///
+    /// # Errors
+    /// This will fail if any page could not be written.
+    pub fn flush_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), file_manager::Error> {
+        let path = path.as_ref();
+        for (key, page) in self.pages.iter_mut() {
+            if page.dirty && key.0 == path {
+                try!(write_back(path, key.1, &page.data));
+                page.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Writes every dirty page in the cache back to disk. The pages stay in the cache.
+    ///
+    /// # Errors
+    /// This will fail if any page could not be written.
+    pub fn flush_all(&mut self) -> Result<(), file_manager::Error> {
+        for (key, page) in self.pages.iter_mut() {
+            if page.dirty {
+                if key.0.exists() {
+                    try!(write_back(key.0.as_path(), key.1, &page.data));
+                }
+                page.dirty = false;
+            }
+        }
+        Ok(())
+    }
+
+    /// Removes every page of the given file from the cache *without* writing dirty pages back. This
+    /// is used when the file itself is being deleted.
+    pub fn remove_file<P: AsRef<Path>>(&mut self, path: P) {
+        let path = path.as_ref();
+        let keys: Vec<PageKey> = self.pages.keys().filter(|key| key.0 == path).cloned().collect();
+        for key in keys {
+            if let Some(page) = self.pages.remove(&key) {
+                self.cache_size -= page.data.len();
+            }
+            self.policy.page_removed(&key);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::storage::{DBFileType, FileManager, file_manager};
+
+    fn key(page_no: u32) -> PageKey {
+        (PathBuf::from("foo.tbl"), page_no)
+    }
+
+    #[test]
+    fn test_lru_eviction() {
+        let mut bm = BufferManager::new(3 * 512, Box::new(LRUPolicy::new()));
+        for page_no in 0..3 {
+            bm.add_page(key(page_no), Arc::new(vec![0; 512])).unwrap();
+        }
+        assert_eq!(3 * 512, bm.get_cache_size());
+
+        // Touch page 0, so that page 1 is now the least recently used.
+        assert!(bm.get_page(&key(0)).is_some());
+        bm.add_page(key(3), Arc::new(vec![0; 512])).unwrap();
+
+        assert!(bm.contains_page(&key(0)));
+        assert!(!bm.contains_page(&key(1)));
+        assert!(bm.contains_page(&key(2)));
+        assert!(bm.contains_page(&key(3)));
+        assert_eq!(3 * 512, bm.get_cache_size());
+        assert_eq!(1, bm.get_hits());
+    }
+
+    #[test]
+    fn test_clock_eviction() {
+        let mut bm = BufferManager::new(3 * 512, Box::new(ClockPolicy::new()));
+        for page_no in 0..3 {
+            bm.add_page(key(page_no), Arc::new(vec![0; 512])).unwrap();
+        }
+
+        // The first sweep clears every reference bit, so page 0 is the victim.
+        bm.add_page(key(3), Arc::new(vec![0; 512])).unwrap();
+        assert!(!bm.contains_page(&key(0)));
+
+        // Page 1 gets a second chance after being accessed; page 2 is evicted instead.
+        assert!(bm.get_page(&key(1)).is_some());
+        bm.add_page(key(4), Arc::new(vec![0; 512])).unwrap();
+        assert!(bm.contains_page(&key(1)));
+        assert!(!bm.contains_page(&key(2)));
+    }
+
+    #[test]
+    fn test_pinned_pages_not_evicted() {
+        let mut bm = BufferManager::new(2 * 512, Box::new(LRUPolicy::new()));
+        bm.add_page(key(0), Arc::new(vec![0; 512])).unwrap();
+        bm.add_page(key(1), Arc::new(vec![0; 512])).unwrap();
+        bm.pin_page(&key(0));
+        bm.pin_page(&key(1));
+
+        // Nothing can be evicted, so the cache grows past its limit.
+        bm.add_page(key(2), Arc::new(vec![0; 512])).unwrap();
+        assert!(bm.contains_page(&key(0)));
+        assert!(bm.contains_page(&key(1)));
+        assert_eq!(3 * 512, bm.get_cache_size());
+
+        // Once page 0 is unpinned, it is the only candidate.
+        bm.unpin_page(&key(0));
+        assert_eq!(Some(0), bm.get_pin_count(&key(0)));
+        bm.add_page(key(3), Arc::new(vec![0; 512])).unwrap();
+        assert!(!bm.contains_page(&key(0)));
+        assert!(bm.contains_page(&key(1)));
+    }
+
+    #[test]
+    fn test_dirty_page_written_on_eviction() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let path = dbfile.file_info.path.clone().unwrap();
+
+        let mut bm = BufferManager::new(512, Box::new(LRUPolicy::new()));
+        let mut data = vec![0; 512];
+        data[0] = DBFileType::HeapTupleFile as u8;
+        data[1] = 9;
+        data[100] = 42;
+        bm.write_page((path.clone(), 0), Arc::new(data)).unwrap();
+
+        // The page has not hit the disk yet.
+        let mut buffer = vec![0; 512];
+        file_manager::load_page(&mut dbfile, 0, &mut buffer, false).unwrap();
+        assert_eq!(0, buffer[100]);
+
+        // Evicting the dirty page writes it back.
+        bm.add_page((path.clone(), 1), Arc::new(vec![0; 512])).unwrap();
+        assert!(!bm.contains_page(&(path.clone(), 0)));
+        file_manager::load_page(&mut dbfile, 0, &mut buffer, false).unwrap();
+        assert_eq!(42, buffer[100]);
+
+        // Removing a file's pages discards them without writing them.
+        bm.write_page((path.clone(), 0), Arc::new(vec![7; 512])).unwrap();
+        bm.remove_file(&path);
+        assert_eq!(0, bm.get_cache_size());
+        file_manager::load_page(&mut dbfile, 0, &mut buffer, false).unwrap();
+        assert_eq!(42, buffer[100]);
+    }
+}
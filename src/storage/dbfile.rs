@@ -111,6 +111,8 @@ pub enum DBFileType {
     TxnStateFile = 3,
     /// Represents a write-ahead log file used for transaction processing and recovery.
     WriteAheadLogFile = 4,
+    /// Represents a temporary file holding a sorted run of tuples, used by external sorts.
+    SortRunFile = 5,
     /// Represents an unknown file type (usually due to corrupted data).
     Unknown,
 }
@@ -122,6 +124,7 @@ impl From<u8> for DBFileType {
             2 => DBFileType::BTreeTupleFile,
             3 => DBFileType::TxnStateFile,
             4 => DBFileType::WriteAheadLogFile,
+            5 => DBFileType::SortRunFile,
             _ => DBFileType::Unknown,
         }
     }
@@ -134,6 +137,7 @@ impl ::std::fmt::Display for DBFileType {
             DBFileType::BTreeTupleFile => write!(f, "BTREE"),
             DBFileType::TxnStateFile => write!(f, "TXN_STATE"),
             DBFileType::WriteAheadLogFile => write!(f, "WAL"),
+            DBFileType::SortRunFile => write!(f, "SORT_RUN"),
             DBFileType::Unknown => write!(f, "UNKNOWN"),
         }
     }
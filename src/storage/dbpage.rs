@@ -3,13 +3,19 @@
 use std::error::Error as ErrorTrait;
 use std::io::{self, ErrorKind, SeekFrom};
 use std::io::prelude::*;
+use std::path::PathBuf;
+use std::sync::Arc;
 
 use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
 
 use ::{ColumnType, Schema};
 use ::expressions::Literal;
+use ::expressions::temporal::{self, MICROS_PER_SECOND};
 use ::storage::{DBFileInfo, PinError, Pinnable, Tuple, TupleError, WriteNanoDBExt};
-use ::storage::page_tuple::get_null_flags_size;
+use ::storage::overflow_page::OVERFLOW_VALUE;
+use ::storage::page_tuple::{get_null_flags_size, get_numeric_storage_size};
+use ::storage::storage_manager::buffer_manager;
+use ::transactions::{LogSequenceNumber, PAGE_LSN_SIZE};
 
 /// The offset in the data page where the number of slots in the slot table is stored.
 const OFFSET_NUM_SLOTS: u16 = 0;
@@ -34,6 +40,8 @@ pub enum Error {
     /// The tuple provided does not have the same arity as the schema provided. In the form of
     /// (tuple size, schema size).
     WrongArity(usize, usize),
+    /// The slot holds no tuple.
+    EmptySlot(u16),
 }
 
 impl ::std::fmt::Display for Error {
@@ -57,6 +65,7 @@ impl ::std::fmt::Display for Error {
                 write!(f, "Tuple has different arity ({} columns) than target schema ({} columns).",
                        tup_size, schema_size)
             }
+            Error::EmptySlot(slot) => write!(f, "Slot {} does not hold a tuple.", slot),
         }
     }
 }
@@ -86,7 +95,7 @@ pub fn get_slot_offset(slot: u16) -> u16 {
 ///
 /// # Design
 /// It is very important that the page is marked dirty *before* any changes are made, because this
-/// is the point when the old version of the page data is copied before changes are made.
+/// is the point when the old version of the page data is kept before changes are made.
 /// Additionally, the page's data must not be manipulated separately from the methods provided by
 /// this class, or else the old version of the page won't be recorded properly.
 pub struct DBPage {
@@ -94,13 +103,13 @@ pub struct DBPage {
     pub page_no: u32,
     pin_count: u32,
     dirty: bool,
-    /// The data contained in the page.
-    ///
-    /// TODO: Ideally, this should be a `&[u8]`.
-    pub page_data: Vec<u8>,
-    old_page_data: Option<Vec<u8>>,
+    /// The data contained in the page. A page loaded from the buffer manager shares the cached data
+    /// until the page is first changed, at which point the page gets its own copy of the data.
+    pub page_data: Arc<Vec<u8>>,
+    old_page_data: Option<Arc<Vec<u8>>>,
     is_disk_backed: bool,
     cur_page_position: u64,
+    file_path: Option<PathBuf>,
 }
 
 impl DBPage {
@@ -112,22 +121,31 @@ impl DBPage {
     /// * page_no - The page number.
     ///
     /// # Error
-    /// An error can occur if the buffer manager was unable to allocate the space to store the page.
-    /// Currently the buffer manager does not exist, so this should never return an error.
+    /// Currently this should never return an error. Pages are registered with the buffer manager
+    /// by [`load_dbpage`](../storage_manager/fn.load_dbpage.html), not here.
     pub fn new(db_file_info: &DBFileInfo, page_no: u32) -> Result<DBPage, Error> {
+        DBPage::with_data(db_file_info, page_no, Arc::new(vec![0; db_file_info.page_size as usize]))
+    }
+
+    /// Instantiate a new `DBPage` instance holding the provided data, e.g. the data of a page cached
+    /// by the buffer manager. The data is shared rather than copied.
+    ///
+    /// # Arguments
+    /// * db_file_info - The `DBFile` metadata.
+    /// * page_no - The page number.
+    /// * page_data - The data of the page.
+    pub fn with_data(db_file_info: &DBFileInfo, page_no: u32, page_data: Arc<Vec<u8>>) -> Result<DBPage, Error> {
         let is_disk_backed = db_file_info.path.as_ref().map(|pb| pb.exists()) == Some(true);
         let page = DBPage {
             page_no: page_no,
             pin_count: 0,
             dirty: false,
-            // Right now we just allocate a new buffer no matter what.
-            // TODO: Use BufferManager to get a page.
-            page_data: vec![0; db_file_info.page_size as usize],
+            page_data: page_data,
             old_page_data: None,
             is_disk_backed: is_disk_backed,
             cur_page_position: 0,
+            file_path: db_file_info.path.clone(),
         };
-        // TODO: Use buffer manager
         Ok(page)
     }
 
@@ -136,6 +154,32 @@ impl DBPage {
         self.is_disk_backed
     }
 
+    /// Returns the path of the file the page belongs to, if it has one.
+    pub fn get_file_path(&self) -> Option<&PathBuf> {
+        self.file_path.as_ref()
+    }
+
+    /// Returns the LSN of the last logged change to the page, which is stored at the end of the
+    /// page's data.
+    pub fn get_page_lsn(&self) -> LogSequenceNumber {
+        LogSequenceNumber::read_from_page(&self.page_data)
+    }
+
+    /// Stores the LSN of the last logged change to the page at the end of the page's data.
+    ///
+    /// # Arguments
+    /// * lsn - The LSN of the change.
+    pub fn set_page_lsn(&mut self, lsn: LogSequenceNumber) {
+        lsn.write_to_page(&mut Arc::make_mut(&mut self.page_data)[..]);
+    }
+
+    /// Returns the offset just past the last byte of the page that tuple data can be stored in,
+    /// since the page's LSN is stored at the very end of the page.
+    #[inline]
+    fn get_data_end(&self) -> u16 {
+        (self.page_data.len() - PAGE_LSN_SIZE) as u16
+    }
+
     /// Sets the dirty flag to true or false, indicating whether the page's data has or has not been
     /// changed in memory.
     ///
@@ -211,7 +255,7 @@ impl DBPage {
             return Err(());
         }
         self.set_dirty(true);
-        &self.page_data[position..(position + length)].copy_from_slice(buffer);
+        Arc::make_mut(&mut self.page_data)[position..(position + length)].copy_from_slice(buffer);
         Ok(length)
     }
 
@@ -233,9 +277,21 @@ impl DBPage {
     /// used by the Buffer Manager when a page is removed from the cache so that no other database
     /// code will continue to try to use the page.
     ///
-    /// Since the buffer manager does not currently exist, this function does nothing.
+    /// Any pins the page still holds are released in the buffer manager.
     pub fn invalidate(&mut self) {
-        // TODO: Do stuff with buffer manager here.
+        if self.pin_count > 0 {
+            self.release_buffer_pin();
+        }
+        self.pin_count = 0;
+        self.dirty = false;
+        self.old_page_data = None;
+    }
+
+    /// Tells the buffer manager that this page object no longer holds a pin on its cached page.
+    fn release_buffer_pin(&self) {
+        if let Some(ref path) = self.file_path {
+            buffer_manager().unpin_page(&(path.clone(), self.page_no));
+        }
     }
 
     /// This helper function returns the amount of free space in a tuple data page. It simply uses
@@ -277,7 +333,7 @@ impl DBPage {
     fn get_tuple_data_start(&mut self) -> Result<u16, Error> {
         let num_slots = try!(self.get_num_slots());
         // If there are no tuples in this page, "data start" is the top of the page data.
-        let mut data_start = self.page_data.len() as u16;
+        let mut data_start = self.get_data_end();
 
         if num_slots > 0 {
             let mut slot = num_slots - 1;
@@ -345,9 +401,9 @@ impl DBPage {
         debug!("Current number of slots on page: {}", num_slots);
 
         // This variable tracks where the new tuple should END. It starts
-        // as the page-size, and gets moved down past each valid tuple in
-        // the page, until we find an available slot in the page.
-        let mut new_tuple_end = self.page_data.len() as u16;
+        // as the end of the page data, and gets moved down past each valid
+        // tuple in the page, until we find an available slot in the page.
+        let mut new_tuple_end = self.get_data_end();
 
         let mut slot = 0;
         while slot < num_slots {
@@ -411,16 +467,22 @@ impl DBPage {
         self.set_dirty(true);
 
         let src_data = self.page_data[src_pos..(src_pos + length)].to_vec();
-        &self.page_data[dest_pos..(dest_pos + length)].copy_from_slice(&src_data);
+        Arc::make_mut(&mut self.page_data)[dest_pos..(dest_pos + length)].copy_from_slice(&src_data);
     }
 
     fn set_data_range(&mut self, position: usize, length: usize, value: u8) {
         self.set_dirty(true);
-        for i in 0..length {
-            self.page_data[position + i] = value;
+        for byte in Arc::make_mut(&mut self.page_data)[position..(position + length)].iter_mut() {
+            *byte = value;
         }
     }
 
+    /// Sets every byte of the page to zero, e.g. before a page is rewritten from scratch.
+    pub fn clear(&mut self) {
+        let length = self.page_data.len();
+        self.set_data_range(0, length, 0);
+    }
+
     fn insert_tuple_data_range(&mut self, offset: u16, len: u16) -> Result<(), Error> {
         let tuple_data_start = try!(self.get_tuple_data_start());
 
@@ -461,6 +523,129 @@ impl DBPage {
         Ok(())
     }
 
+    fn delete_tuple_data_range(&mut self, offset: u16, len: u16) -> Result<(), Error> {
+        let tuple_data_start = try!(self.get_tuple_data_start());
+
+        if offset < tuple_data_start {
+            return Err(Error::OffsetNotInTuplePortion(offset, tuple_data_start));
+        }
+
+        // If off == tupDataStart then there's no need to move anything.
+        if offset > tuple_data_start {
+            // Move the data in the range [tupDataStart, off) to [tupDataStart + len, off + len),
+            // overwriting the range [off, off + len) that is being removed.
+            self.move_data_range(tuple_data_start as usize,
+                                 (tuple_data_start + len) as usize,
+                                 (offset - tuple_data_start) as usize);
+        }
+
+        // Zero out the space that was just freed up.
+        self.set_data_range(tuple_data_start as usize, len as usize, 0);
+
+        // Update affected slots; this includes all slots below the specified offset. The update is
+        // easy; slot values just move up by len bytes.
+        let num_slots = try!(self.get_num_slots());
+        for slot in 0..num_slots {
+            let slot_value = try!(self.get_slot_value(slot));
+            if slot_value != EMPTY_SLOT && slot_value < offset {
+                // Update this slot's offset.
+                try!(self.set_slot_value(slot, slot_value + len));
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Returns the length of the tuple stored in the specified slot. Since tuples are stored in
+    /// slot order from the end of the page, the tuple ends where the tuple of the previous
+    /// non-empty slot starts.
+    ///
+    /// # Arguments
+    /// * slot - the slot of the tuple.
+    ///
+    /// # Errors
+    /// Returns an `InvalidSlot` error if the slot is out of range, or an `EmptySlot` error if it
+    /// holds no tuple.
+    pub fn get_tuple_length(&mut self, slot: u16) -> Result<u16, Error> {
+        let tuple_start = try!(self.get_slot_value(slot));
+        if tuple_start == EMPTY_SLOT {
+            return Err(Error::EmptySlot(slot));
+        }
+
+        let mut tuple_end = self.get_data_end();
+        let mut prev_slot = slot;
+        while prev_slot > 0 {
+            prev_slot -= 1;
+            let prev_tuple_start = try!(self.get_slot_value(prev_slot));
+            if prev_tuple_start != EMPTY_SLOT {
+                tuple_end = prev_tuple_start;
+                break;
+            }
+        }
+
+        Ok(tuple_end - tuple_start)
+    }
+
+    /// Deletes the tuple in the specified slot, reclaiming the space its data used in the page and
+    /// marking the slot as `EMPTY_SLOT`. Any empty slots at the end of the slot list are removed
+    /// from the page as well.
+    ///
+    /// # Arguments
+    /// * slot - the slot of the tuple to delete.
+    ///
+    /// # Errors
+    /// Returns an `InvalidSlot` error if the slot is out of range, or an `EmptySlot` error if it
+    /// holds no tuple.
+    pub fn delete_tuple(&mut self, slot: u16) -> Result<(), Error> {
+        let tuple_start = try!(self.get_slot_value(slot));
+        if tuple_start == EMPTY_SLOT {
+            return Err(Error::EmptySlot(slot));
+        }
+        let tuple_length = try!(self.get_tuple_length(slot));
+        debug!("Deleting {}-byte tuple at slot {} (offset {}).", tuple_length, slot, tuple_start);
+
+        try!(self.delete_tuple_data_range(tuple_start, tuple_length));
+        try!(self.set_slot_value(slot, EMPTY_SLOT));
+
+        // Trim off any empty slots at the end of the slot list.
+        let mut num_slots = try!(self.get_num_slots());
+        while num_slots > 0 && try!(self.get_slot_value(num_slots - 1)) == EMPTY_SLOT {
+            num_slots -= 1;
+        }
+        self.set_num_slots(num_slots)
+    }
+
+    /// Grows or shrinks the tuple in the specified slot to a new length, moving the data of the
+    /// tuples stored before it in the page as necessary. Returns the new offset of the tuple, whose
+    /// data must then be rewritten. (Shrinking keeps the end of the tuple's data, and growing adds
+    /// zeroed space to the front of it.)
+    ///
+    /// # Arguments
+    /// * slot - the slot of the tuple to resize.
+    /// * new_len - the new length of the tuple's data.
+    ///
+    /// # Errors
+    /// Returns a `NotEnoughFreeSpace` error if the page doesn't have room for the tuple to grow,
+    /// or an `EmptySlot` error if the slot holds no tuple.
+    pub fn resize_tuple(&mut self, slot: u16, new_len: u16) -> Result<u16, Error> {
+        let tuple_start = try!(self.get_slot_value(slot));
+        let old_len = try!(self.get_tuple_length(slot));
+
+        let new_start = if new_len > old_len {
+            // Make room in front of the tuple; this moves the tuples before it down the page.
+            try!(self.insert_tuple_data_range(tuple_start, new_len - old_len));
+            tuple_start - (new_len - old_len)
+        } else if new_len < old_len {
+            try!(self.delete_tuple_data_range(tuple_start, old_len - new_len));
+            tuple_start + (old_len - new_len)
+        } else {
+            tuple_start
+        };
+
+        try!(self.set_slot_value(slot, new_start));
+        Ok(new_start)
+    }
+
     /// This is a helper function to set or clear the value of a column's `NULL` flag.
     ///
     /// # Arguments
@@ -489,7 +674,7 @@ impl DBPage {
         self.write_u8(null_flag).map_err(Into::into)
     }
 
-    fn write_non_null_value(&mut self, offset: u16, col_type: ColumnType, value: Literal) -> Result<u16, Error> {
+    fn write_non_null_value(&mut self, offset: u16, col_type: ColumnType, value: Literal) -> Result<u16, TupleError> {
         let offset = offset as u64;
         try!(self.seek(SeekFrom::Start(offset as u64)));
 
@@ -544,17 +729,85 @@ impl DBPage {
                 try!(self.write_f64::<BigEndian>(value));
                 Ok(8)
             }
+            ColumnType::Numeric { scale, precision } => {
+                // The value is rounded to the column's scale, and only its unscaled digits are
+                // stored. A value that doesn't fit the column's precision would be truncated by
+                // the narrowing writes below, so it is rejected instead.
+                let unscaled = match value.as_decimal() {
+                    Some(ref d) if d.fits(precision, scale) => d.rescale(scale).unwrap().get_unscaled(),
+                    _ => return Err(TupleError::ValueOutOfRange(value, col_type)),
+                };
+                let size = get_numeric_storage_size(precision);
+                match size {
+                    1 => try!(self.write_i8(unscaled as i8)),
+                    2 => try!(self.write_i16::<BigEndian>(unscaled as i16)),
+                    4 => try!(self.write_i32::<BigEndian>(unscaled as i32)),
+                    _ => try!(self.write_i64::<BigEndian>(unscaled)),
+                }
+                Ok(size)
+            }
+            ColumnType::Date => {
+                let date = temporal::as_date(&value).unwrap();
+                try!(self.write_i32::<BigEndian>(date.get_days()));
+                Ok(4)
+            }
+            ColumnType::Time => {
+                // TIME values only keep whole seconds.
+                let time = temporal::as_time(&value).unwrap();
+                try!(self.write_i32::<BigEndian>((time.get_micros() / MICROS_PER_SECOND) as i32));
+                Ok(4)
+            }
+            ColumnType::DateTime => {
+                // DATETIME values only keep whole seconds.
+                let timestamp = temporal::as_timestamp(&value).unwrap().truncate_to_seconds();
+                try!(self.write_i64::<BigEndian>(timestamp.get_micros() / MICROS_PER_SECOND));
+                Ok(8)
+            }
+            ColumnType::Timestamp => {
+                let timestamp = temporal::as_timestamp(&value).unwrap();
+                try!(self.write_i64::<BigEndian>(timestamp.get_micros()));
+                Ok(8)
+            }
+            ColumnType::Interval => {
+                let interval = temporal::as_interval(&value).unwrap();
+                try!(self.write_i32::<BigEndian>(interval.months));
+                try!(self.write_i32::<BigEndian>(interval.days));
+                try!(self.write_i64::<BigEndian>(interval.micros));
+                Ok(16)
+            }
             ColumnType::Char { length } => {
                 let value = value.as_string().unwrap();
                 try!(self.write_fixed_size_string(value, length));
                 Ok(length)
 
             }
-            ColumnType::VarChar { length: _ } => {
-                let value = value.as_string().unwrap();
-                let str_len = value.len();
-                try!(self.write_varchar65535(value));
-                Ok(2 + str_len as u16)
+            ColumnType::VarChar { length: _ } | ColumnType::Text | ColumnType::Blob => {
+                // Values that were moved out into overflow pages are stored as a marker followed
+                // by a pointer to the first overflow page; other values are stored as a two byte
+                // length followed by the value's bytes, without any character decoding.
+                match value {
+                    Literal::FilePointer { page_no, offset } => {
+                        try!(self.write_u16::<BigEndian>(OVERFLOW_VALUE));
+                        try!(self.write_u16::<BigEndian>(page_no));
+                        try!(self.write_u16::<BigEndian>(offset));
+                        Ok(6)
+                    }
+                    _ => {
+                        let bytes = value.as_bytes().unwrap();
+                        try!(self.write_u16::<BigEndian>(bytes.len() as u16));
+                        try!(self.write_all(bytes));
+                        Ok(2 + bytes.len() as u16)
+                    }
+                }
+            }
+            ColumnType::FilePointer => {
+                let (page_no, offset) = match value {
+                    Literal::FilePointer { page_no, offset } => (page_no, offset),
+                    _ => (0, 0),
+                };
+                try!(self.write_u16::<BigEndian>(page_no));
+                try!(self.write_u16::<BigEndian>(offset));
+                Ok(4)
             }
             _ => unimplemented!(),
         }
@@ -652,8 +905,14 @@ impl Seek for DBPage {
 
 impl Pinnable for DBPage {
     fn pin(&mut self) {
+        // The buffer manager counts how many page objects hold pins on a page, not the individual
+        // pins, so it only needs to hear about the first one.
+        if self.pin_count == 0 {
+            if let Some(ref path) = self.file_path {
+                buffer_manager().pin_page(&(path.clone(), self.page_no));
+            }
+        }
         self.pin_count += 1;
-        // TODO: Record page pinned in buffer manager
     }
 
     fn unpin(&mut self) -> Result<(), PinError> {
@@ -663,7 +922,9 @@ impl Pinnable for DBPage {
 
         self.pin_count -= 1;
 
-        // TODO: Record page unpinned in buffer manager
+        if self.pin_count == 0 {
+            self.release_buffer_pin();
+        }
 
         Ok(())
     }
@@ -673,12 +934,24 @@ impl Pinnable for DBPage {
     }
 }
 
+impl Drop for DBPage {
+    fn drop(&mut self) {
+        // Page objects are frequently dropped while still pinned (e.g. when a tuple goes out of
+        // scope), so make sure the cached page can be evicted again.
+        if self.pin_count > 0 {
+            self.release_buffer_pin();
+        }
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use std::io::Cursor;
 
     use super::*;
-    use ::storage::{DBFile, DBFileType, PinError, Pinnable};
+    use ::ColumnInfo;
+    use ::expressions::Decimal;
+    use ::storage::{DBFile, DBFileType, PinError, Pinnable, TupleLiteral};
 
     #[test]
     fn test_pinning() {
@@ -696,4 +969,90 @@ mod tests {
         assert_eq!(Ok(()), page.unpin());
         assert_eq!(Err(PinError::PinCountNotPositive(0)), page.unpin());
     }
+
+    #[test]
+    fn test_delete_tuple() {
+        let contents = vec![0; 512];
+        let dbfile = DBFile::new(DBFileType::HeapTupleFile, 512, Cursor::new(contents)).unwrap();
+
+        let mut page = DBPage::new(&dbfile, 1).unwrap();
+        page.init_new_page().unwrap();
+        for (i, len) in vec![10u16, 20, 30].into_iter().enumerate() {
+            let slot = page.alloc_new_tuple(len).unwrap();
+            let offset = page.get_slot_value(slot).unwrap();
+            page.set_data_range(offset as usize, len as usize, i as u8 + 1);
+        }
+        let free_space = page.get_free_space().unwrap();
+
+        // Deleting a tuple in the middle moves the following tuples up.
+        page.delete_tuple(1).unwrap();
+        assert_eq!(3, page.get_num_slots().unwrap());
+        assert_eq!(EMPTY_SLOT, page.get_slot_value(1).unwrap());
+        assert_eq!(free_space + 20, page.get_free_space().unwrap());
+        assert_eq!(Err(Error::EmptySlot(1)), page.delete_tuple(1));
+        let offset = page.get_slot_value(2).unwrap() as usize;
+        assert_eq!(466, offset);
+        assert!(page.page_data[offset..(offset + 30)].iter().all(|b| *b == 3));
+        assert_eq!(30, page.get_tuple_length(2).unwrap());
+
+        // Deleting the last tuple also removes the empty slots at the end of the slot list.
+        page.delete_tuple(2).unwrap();
+        assert_eq!(1, page.get_num_slots().unwrap());
+        assert_eq!(10, page.get_tuple_length(0).unwrap());
+        assert_eq!(Err(Error::InvalidSlot(1, 2)), page.get_slot_value(2));
+
+        // The freed space is reused by new tuples.
+        assert_eq!(1, page.alloc_new_tuple(50).unwrap());
+        assert_eq!(446, page.get_slot_value(1).unwrap());
+    }
+
+    #[test]
+    fn test_resize_tuple() {
+        let contents = vec![0; 512];
+        let dbfile = DBFile::new(DBFileType::HeapTupleFile, 512, Cursor::new(contents)).unwrap();
+
+        let mut page = DBPage::new(&dbfile, 1).unwrap();
+        page.init_new_page().unwrap();
+        for (i, len) in vec![10u16, 20, 30].into_iter().enumerate() {
+            let slot = page.alloc_new_tuple(len).unwrap();
+            let offset = page.get_slot_value(slot).unwrap();
+            page.set_data_range(offset as usize, len as usize, i as u8 + 1);
+        }
+
+        // Growing a tuple moves the tuples stored after it in the slot list down the page.
+        assert_eq!(471, page.resize_tuple(1, 25).unwrap());
+        assert_eq!(25, page.get_tuple_length(1).unwrap());
+        assert_eq!(441, page.get_slot_value(2).unwrap());
+        assert!(page.page_data[441..471].iter().all(|b| *b == 3));
+        assert!(page.page_data[471..476].iter().all(|b| *b == 0));
+        assert!(page.page_data[476..496].iter().all(|b| *b == 2));
+
+        // Shrinking a tuple moves them back up.
+        assert_eq!(486, page.resize_tuple(1, 10).unwrap());
+        assert_eq!(456, page.get_slot_value(2).unwrap());
+        assert!(page.page_data[456..486].iter().all(|b| *b == 3));
+        assert_eq!(10, page.get_tuple_length(0).unwrap());
+
+        let free_space = page.get_free_space().unwrap();
+        assert_eq!(Err(Error::NotEnoughFreeSpace(free_space + 1, free_space)),
+                   page.resize_tuple(0, 10 + free_space + 1));
+    }
+
+    #[test]
+    fn test_store_numeric_out_of_range() {
+        let contents = vec![0; 512];
+        let dbfile = DBFile::new(DBFileType::HeapTupleFile, 512, Cursor::new(contents)).unwrap();
+        let column_type = ColumnType::Numeric { scale: 2, precision: 4 };
+        let schema = Schema::with_columns(vec![ColumnInfo::with_name(column_type, "A")]).unwrap();
+
+        let mut page = DBPage::new(&dbfile, 1).unwrap();
+        page.init_new_page().unwrap();
+        let slot = page.alloc_new_tuple(3).unwrap();
+        let offset = page.get_slot_value(slot).unwrap();
+
+        // 123.45 has too many digits to be written in the two bytes a NUMERIC(4, 2) value takes.
+        let value = Literal::Decimal(Decimal::new(12345, 2));
+        assert_eq!(Err(TupleError::ValueOutOfRange(value.clone(), column_type)),
+                   page.store_new_tuple(offset, schema, TupleLiteral::from_iter(vec![value])));
+    }
 }
@@ -8,7 +8,7 @@ use std::path::{Path, PathBuf};
 use nom::{IResult, be_u8};
 
 use ::relations::SchemaError;
-use ::storage::{dbpage, PinError};
+use ::storage::{dbpage, PinError, storage_manager};
 use ::storage::dbfile::{self, DBFile, DBFileType, encode_pagesize};
 
 named!(parse_header (&[u8]) -> (u8, Result<u32, dbfile::Error>), do_parse!(
@@ -109,6 +109,8 @@ pub enum Error {
     NotFullyRead,
     /// An error occurred while saving a page.
     PageSaveError,
+    /// A change to a page could not be written to the write-ahead log.
+    WALError(String),
 }
 
 impl ::std::fmt::Display for Error {
@@ -139,6 +141,7 @@ impl ::std::fmt::Display for Error {
             Error::DBPageError(ref e) => write!(f, "{}", e),
             Error::SchemaError(ref e) => write!(f, "{}", e),
             Error::PinError(ref e) => write!(f, "{}", e),
+            Error::WALError(ref e) => write!(f, "Could not write to the write-ahead log: {}", e),
         }
     }
 }
@@ -299,6 +302,11 @@ impl FileManager {
         })
     }
 
+    /// Returns the base directory the data files are stored in.
+    pub fn get_base_dir(&self) -> &Path {
+        self.base_dir.as_path()
+    }
+
     /// Return a list of file paths of files in the base directory.
     ///
     /// # Errors
@@ -340,7 +348,10 @@ impl FileManager {
             return Err(Error::DBFileDoesNotExist(filename.as_ref().to_string_lossy().into()));
         }
 
-        fs::remove_file(self.base_dir.clone().join(filename)).map_err(Into::into)
+        let full_path = self.base_dir.clone().join(filename);
+        // Any cached pages of the file are now meaningless, so make sure they are never written.
+        storage_manager::buffer_manager().remove_file(&full_path);
+        fs::remove_file(full_path).map_err(Into::into)
     }
 
     /// This method creates a new database file in the directory used by the
@@ -452,6 +463,7 @@ impl FileManager {
                     2 => DBFileType::BTreeTupleFile,
                     3 => DBFileType::TxnStateFile,
                     4 => DBFileType::WriteAheadLogFile,
+                    5 => DBFileType::SortRunFile,
                     _ => {
                         return Err(Error::InvalidDBFileType(type_id));
                     }
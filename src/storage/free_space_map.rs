@@ -0,0 +1,283 @@
+//! This module contains the free-space map of heap tuple files, which records roughly how much free
+//! space each page of the file has, so that inserts can find a page with room for a new tuple
+//! without reading every page of the file.
+//!
+//! The map is stored in a chain of FSM pages in the heap file itself, the first of which is
+//! recorded in the file's header page. Each FSM page is laid out like this:
+//!
+//! * __Bytes 0-1:__ the [`FSM_PAGE`](constant.FSM_PAGE.html) marker, stored where data pages store
+//!   their number of slots, so that scans can tell the two kinds of page apart.
+//! * __Bytes 2-3:__ the number of the next FSM page in the chain, or 0 if this is the last page.
+//! * __Bytes 4-:__ one entry byte per page of the file, up to the page's LSN at the end of the
+//!   page. The first FSM page covers pages 0, 1, 2, ..., the second one continues from where the
+//!   first one stops, and so on.
+//!
+//! Each entry records the page's free space in units of 1/256th of the page size, rounded down, so
+//! a page always has at least as much room as its entry promises. Pages that don't hold tuples
+//! (the header page, overflow pages and the FSM pages themselves) have an entry of 0.
+
+use std::fs::File;
+use std::io::SeekFrom;
+use std::io::prelude::*;
+use std::u16;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::storage::{DBFile, DBPage, Pinnable, TupleError};
+use ::storage::storage_manager::{load_dbpage, save_dbpage};
+use ::transactions::PAGE_LSN_SIZE;
+
+/// This value is stored in place of the number of slots of a page to mark it as an FSM page.
+pub const FSM_PAGE: u16 = 0xFFFE;
+
+/// The offset in an FSM page where the number of the next FSM page in the chain is stored.
+const OFFSET_NEXT_PAGE: u64 = 2;
+
+/// The offset in an FSM page where the entries start.
+const OFFSET_ENTRIES: usize = 4;
+
+/// Checks whether the page is a page of the free-space map.
+///
+/// # Arguments
+/// * page - The page to check.
+pub fn is_fsm_page(page: &mut DBPage) -> Result<bool, TupleError> {
+    try!(page.seek(SeekFrom::Start(0)));
+    Ok(try!(page.read_u16::<BigEndian>()) == FSM_PAGE)
+}
+
+/// Returns the number of pages in the file, including pages that have been created but not yet
+/// written back to disk, since creating a page extends the file.
+///
+/// # Arguments
+/// * db_file - The file to count the pages of.
+pub fn get_num_pages(db_file: &DBFile<File>) -> Result<u32, TupleError> {
+    let length = try!(db_file.get_contents().metadata()).len();
+    Ok((length / db_file.get_page_size() as u64) as u32)
+}
+
+/// Returns the number of entries a single FSM page holds.
+#[inline]
+fn get_capacity(page_size: u32) -> u32 {
+    page_size - (OFFSET_ENTRIES + PAGE_LSN_SIZE) as u32
+}
+
+/// Returns the entry recording the given amount of free space.
+#[inline]
+fn get_category(page_size: u32, free_space: u16) -> u8 {
+    ::std::cmp::min(255, free_space as u32 / (page_size / 256)) as u8
+}
+
+/// The free-space map of a heap tuple file.
+#[derive(Debug, PartialEq)]
+pub struct FreeSpaceMap {
+    first_page: u16,
+}
+
+impl FreeSpaceMap {
+    /// Creates a handle to the free-space map starting at the given page.
+    ///
+    /// # Arguments
+    /// * first_page - The first FSM page of the file, or 0 if the file has no free-space map yet.
+    pub fn new(first_page: u16) -> FreeSpaceMap {
+        FreeSpaceMap { first_page: first_page }
+    }
+
+    /// Returns the first FSM page of the file, or 0 if the file has no free-space map yet.
+    pub fn get_first_page(&self) -> u16 {
+        self.first_page
+    }
+
+    /// Returns the pages of the map, in order. The chain stops at the first page that isn't an FSM
+    /// page, e.g. because a rollback removed it, in which case the rest of the map is rebuilt as
+    /// pages are updated.
+    fn get_pages(&self, db_file: &mut DBFile<File>) -> Result<Vec<u16>, TupleError> {
+        let mut result = Vec::new();
+        let mut page_no = self.first_page;
+        while page_no != 0 {
+            let mut page = try!(load_dbpage(db_file, page_no as u32, false));
+            if !try!(is_fsm_page(&mut page)) {
+                try!(page.unpin());
+                break;
+            }
+            try!(page.seek(SeekFrom::Start(OFFSET_NEXT_PAGE)));
+            let next_page = try!(page.read_u16::<BigEndian>());
+            try!(page.unpin());
+            result.push(page_no);
+            page_no = next_page;
+        }
+        Ok(result)
+    }
+
+    /// Adds a new FSM page to the end of the file and of the chain.
+    fn add_page(&mut self, db_file: &mut DBFile<File>, pages: &mut Vec<u16>) -> Result<(), TupleError> {
+        let page_no = try!(get_num_pages(db_file));
+        // The chain and the header page store FSM page numbers in two bytes.
+        if page_no > u16::MAX as u32 {
+            return Err(TupleError::FileTooBig(page_no));
+        }
+        debug!("Adding FSM page {}.", page_no);
+
+        let mut page = try!(load_dbpage(db_file, page_no, true));
+        try!(page.seek(SeekFrom::Start(0)));
+        try!(page.write_u16::<BigEndian>(FSM_PAGE));
+        try!(page.write_u16::<BigEndian>(0));
+        let num_entries = get_capacity(db_file.get_page_size()) as usize;
+        try!(page.write_all(&vec![0u8; num_entries]));
+        try!(save_dbpage(db_file, &mut page));
+        try!(page.unpin());
+
+        match pages.last() {
+            Some(&last_page) => {
+                let mut last = try!(load_dbpage(db_file, last_page as u32, false));
+                try!(last.seek(SeekFrom::Start(OFFSET_NEXT_PAGE)));
+                try!(last.write_u16::<BigEndian>(page_no as u16));
+                try!(save_dbpage(db_file, &mut last));
+                try!(last.unpin());
+            }
+            None => self.first_page = page_no as u16,
+        }
+        pages.push(page_no as u16);
+        Ok(())
+    }
+
+    /// Records the amount of free space a page of the file has, adding pages to the map if it
+    /// doesn't cover the page yet.
+    ///
+    /// # Arguments
+    /// * db_file - The heap file the map belongs to.
+    /// * page_no - The page whose free space changed.
+    /// * free_space - The number of bytes of free space in the page.
+    pub fn set_free_space(&mut self, db_file: &mut DBFile<File>, page_no: u32, free_space: u16) -> Result<(), TupleError> {
+        let page_size = db_file.get_page_size();
+        let capacity = get_capacity(page_size);
+        let mut pages = try!(self.get_pages(db_file));
+        while pages.len() as u32 <= page_no / capacity {
+            try!(self.add_page(db_file, &mut pages));
+        }
+
+        let fsm_page_no = pages[(page_no / capacity) as usize];
+        let position = OFFSET_ENTRIES as u64 + (page_no % capacity) as u64;
+        let category = get_category(page_size, free_space);
+        trace!("Page {} has free space category {}.", page_no, category);
+
+        let mut page = try!(load_dbpage(db_file, fsm_page_no as u32, false));
+        try!(page.seek(SeekFrom::Start(position)));
+        if try!(page.read_u8()) != category {
+            try!(page.seek(SeekFrom::Start(position)));
+            try!(page.write_u8(category));
+            try!(save_dbpage(db_file, &mut page));
+        }
+        try!(page.unpin());
+        Ok(())
+    }
+
+    /// Returns the pages whose entries are at least the given category, in page order.
+    fn find_pages(&self, db_file: &mut DBFile<File>, category: u8, limit: usize) -> Result<Vec<u32>, TupleError> {
+        let capacity = get_capacity(db_file.get_page_size());
+        let mut result = Vec::new();
+        for (i, fsm_page_no) in try!(self.get_pages(db_file)).into_iter().enumerate() {
+            let mut page = try!(load_dbpage(db_file, fsm_page_no as u32, false));
+            {
+                let entries = &page.page_data[OFFSET_ENTRIES..(OFFSET_ENTRIES + capacity as usize)];
+                for (j, &entry) in entries.iter().enumerate() {
+                    if entry >= category && entry > 0 {
+                        result.push(i as u32 * capacity + j as u32);
+                        if result.len() >= limit {
+                            break;
+                        }
+                    }
+                }
+            }
+            try!(page.unpin());
+            if result.len() >= limit {
+                break;
+            }
+        }
+        Ok(result)
+    }
+
+    /// Returns the first page that the map says has at least the given amount of free space, or
+    /// `None` if there is no such page. The page's actual free space should be checked, since the
+    /// map may be out of date (e.g. after a rollback).
+    ///
+    /// # Arguments
+    /// * db_file - The heap file the map belongs to.
+    /// * needed - The number of bytes of free space needed.
+    pub fn find_page(&self, db_file: &mut DBFile<File>, needed: u16) -> Result<Option<u32>, TupleError> {
+        // Round the category up, since entries are rounded down.
+        let page_size = db_file.get_page_size();
+        let unit = page_size / 256;
+        let category = ::std::cmp::min(255, (needed as u32 + unit - 1) / unit) as u8;
+        Ok(try!(self.find_pages(db_file, category, 1)).pop())
+    }
+
+    /// Returns up to the given number of pages that the map says are empty data pages. Since
+    /// entries are rounded down, the pages should be checked to actually be empty.
+    ///
+    /// # Arguments
+    /// * db_file - The heap file the map belongs to.
+    /// * empty_space - The free space of an empty data page.
+    /// * limit - The maximum number of pages to return.
+    pub fn find_empty_pages(&self, db_file: &mut DBFile<File>, empty_space: u16, limit: usize) -> Result<Vec<u32>, TupleError> {
+        let category = get_category(db_file.get_page_size(), empty_space);
+        self.find_pages(db_file, category, limit)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::storage::{DBFileType, FileManager};
+
+    #[test]
+    fn test_free_space_map() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        assert_eq!(1, get_num_pages(&dbfile).unwrap());
+
+        let mut fsm = FreeSpaceMap::new(0);
+        assert_eq!(None, fsm.find_page(&mut dbfile, 10).unwrap());
+
+        // The first entry is written to a new FSM page at the end of the file.
+        {
+            let mut page = load_dbpage(&mut dbfile, 1, true).unwrap();
+            page.init_new_page().unwrap();
+            save_dbpage(&mut dbfile, &mut page).unwrap();
+        }
+        fsm.set_free_space(&mut dbfile, 1, 100).unwrap();
+        assert_eq!(2, fsm.get_first_page());
+        assert_eq!(3, get_num_pages(&dbfile).unwrap());
+        let mut page = load_dbpage(&mut dbfile, 2, false).unwrap();
+        assert!(is_fsm_page(&mut page).unwrap());
+
+        fsm.set_free_space(&mut dbfile, 5, 300).unwrap();
+        assert_eq!(Some(1), fsm.find_page(&mut dbfile, 100).unwrap());
+        assert_eq!(Some(5), fsm.find_page(&mut dbfile, 101).unwrap());
+        assert_eq!(None, fsm.find_page(&mut dbfile, 301).unwrap());
+        assert_eq!(vec![5], fsm.find_empty_pages(&mut dbfile, 300, 5).unwrap());
+
+        // Pages past the end of the first FSM page get a second one.
+        fsm.set_free_space(&mut dbfile, 600, 400).unwrap();
+        assert_eq!(Some(600), fsm.find_page(&mut dbfile, 301).unwrap());
+        assert_eq!(2, fsm.get_first_page());
+        assert_eq!(vec![2, 3], fsm.get_pages(&mut dbfile).unwrap());
+    }
+
+    #[test]
+    fn test_fsm_page_numbers_too_big() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+
+        // A new FSM page would go at the end of the file, where its number doesn't fit in the chain.
+        let num_pages = u16::MAX as u32 + 1;
+        dbfile.get_contents().set_len(num_pages as u64 * 512).unwrap();
+        let mut fsm = FreeSpaceMap::new(0);
+        assert_eq!(Err(TupleError::FileTooBig(num_pages)), fsm.set_free_space(&mut dbfile, 1, 100));
+        assert_eq!(0, fsm.get_first_page());
+        assert_eq!(num_pages, get_num_pages(&dbfile).unwrap());
+    }
+}
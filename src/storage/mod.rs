@@ -23,16 +23,20 @@
 //! - Certain operations on tuple files can't be provided on the [`TupleFile`]() implementation
 //! itself, so they are provided by the [`TupleFileManager`]() interface.
 
+pub mod buffer_manager;
 pub mod dbfile;
 pub mod dbpage;
 pub mod header_page;
 pub mod file_manager;
+pub mod free_space_map;
+pub mod overflow_page;
 pub mod page_tuple;
 pub mod table_manager;
 pub mod tuple_files;
 pub mod tuple_literal;
 pub mod storage_manager;
 
+pub use self::buffer_manager::BufferManager;
 pub use self::dbfile::{DBFile, DBFileInfo, DBFileType};
 pub use self::dbpage::DBPage;
 pub use self::file_manager::FileManager;
@@ -235,6 +239,22 @@ pub enum TupleError {
     InvalidColumnIndex(usize, usize),
     /// The tuple size is too large for the page. Format is (tuple_size, page_size).
     TupleTooBig(u16, u32),
+    /// A page of a tuple file has a type that doesn't make sense where it was found. Format is
+    /// (page_no, page_type).
+    InvalidPageType(u32, u8),
+    /// A value is too long to be stored in a tuple. Format is the length of the value in bytes.
+    ValueTooLong(usize),
+    /// A value doesn't fit in its column, e.g. a `NUMERIC` value with too many digits. Format is
+    /// (value, column_type).
+    ValueOutOfRange(Literal, ColumnType),
+    /// A page in a chain of overflow pages is not an overflow page. Format is the page number.
+    InvalidOverflowPage(u32),
+    /// A file has more pages than can be referenced by a page number. Format is the page number
+    /// that couldn't be used.
+    FileTooBig(u32),
+    /// The tuple is too large to be stored in a B<sup>+</sup> tree tuple file, which doesn't move
+    /// values out into overflow pages. Format is (tuple_size, max_tuple_size).
+    TupleTooBigForBTree(u16, u16),
 }
 
 impl From<io::Error> for TupleError {
@@ -279,6 +299,25 @@ impl ::std::fmt::Display for TupleError {
             TupleError::TupleTooBig(tuple_size, page_size) => {
                 write!(f, "Tuple size {} is larger than page size {}.", tuple_size, page_size)
             }
+            TupleError::InvalidPageType(page_no, page_type) => {
+                write!(f, "Page {} has unexpected page type {}.", page_no, page_type)
+            }
+            TupleError::ValueTooLong(length) => {
+                write!(f, "A value of {} bytes is too long to be stored in a tuple.", length)
+            }
+            TupleError::ValueOutOfRange(ref value, col_type) => {
+                write!(f, "The value {} is out of range for a column of type {}.", value, col_type)
+            }
+            TupleError::InvalidOverflowPage(page_no) => {
+                write!(f, "Page {} is not an overflow page.", page_no)
+            }
+            TupleError::FileTooBig(page_no) => {
+                write!(f, "The file is too big, page {} can't be referenced.", page_no)
+            }
+            TupleError::TupleTooBigForBTree(tuple_size, max_tuple_size) => {
+                write!(f, "Tuple size {} is larger than the largest tuple a B+ tree file can store ({} bytes), \
+                           since B+ tree files don't support overflow pages.", tuple_size, max_tuple_size)
+            }
         }
     }
 }
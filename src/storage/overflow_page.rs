@@ -0,0 +1,248 @@
+//! This module contains utilities for overflow pages, which store `VARCHAR`, `TEXT` and `BLOB`
+//! values that are too large to be kept in their tuple.
+//!
+//! A large value is split across a chain of overflow pages in the tuple's own heap file, and the
+//! tuple stores a [`OVERFLOW_VALUE`](constant.OVERFLOW_VALUE.html) marker followed by a
+//! file-pointer to the first page of the chain. Each overflow page is laid out like this:
+//!
+//! * __Bytes 0-1:__ the [`OVERFLOW_PAGE`](constant.OVERFLOW_PAGE.html) marker, stored where data
+//!   pages store their number of slots, so that scans can tell the two kinds of page apart.
+//! * __Bytes 2-3:__ the number of the next page in the chain, or 0 if this is the last page.
+//! * __Bytes 4-5:__ the number of bytes of the value stored in this page.
+//! * __Bytes 6-:__ the bytes of the value, up to the page's LSN at the end of the page.
+//!
+//! When a value is freed, its overflow pages are turned back into empty data pages, so they can
+//! be reused for new tuples or new overflow chains. Choosing the pages for a new chain is left to
+//! the heap file, which knows where its empty pages are.
+
+use std::fs::{File, OpenOptions};
+use std::io::SeekFrom;
+use std::io::prelude::*;
+use std::u16;
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::expressions::Literal;
+use ::storage::{DBFile, DBFileType, DBPage, Pinnable, TupleError, file_manager};
+use ::storage::storage_manager::{load_dbpage, save_dbpage};
+use ::transactions::PAGE_LSN_SIZE;
+
+/// This value is stored in place of the number of slots of a page to mark it as an overflow page.
+pub const OVERFLOW_PAGE: u16 = 0xFFFF;
+
+/// This value is stored in place of the length of a `VARCHAR`, `TEXT` or `BLOB` value to mark that
+/// the value is stored in overflow pages.
+pub const OVERFLOW_VALUE: u16 = 0xFFFF;
+
+/// The offset in an overflow page where the number of the next page in the chain is stored.
+const OFFSET_NEXT_PAGE: u64 = 2;
+
+/// The offset in an overflow page where the number of bytes stored in the page is stored.
+const OFFSET_DATA_LENGTH: u64 = 4;
+
+/// The offset in an overflow page where the value's bytes start.
+const OFFSET_DATA: u16 = 6;
+
+/// Returns the length above which `TEXT` and `BLOB` values are moved out of their tuple into
+/// overflow pages. This is an eighth of the page size, so that several tuples with large values
+/// can still share a data page. Other variable-size values are only moved out when their tuple
+/// wouldn't fit in a page otherwise.
+///
+/// # Arguments
+/// * page_size - The page size of the tuple file.
+pub fn get_max_inline_size(page_size: u32) -> usize {
+    page_size as usize / 8
+}
+
+/// Returns the number of bytes of a value that a single overflow page can hold.
+#[inline]
+fn get_page_capacity(page_size: u32) -> usize {
+    page_size as usize - OFFSET_DATA as usize - PAGE_LSN_SIZE
+}
+
+/// Checks whether the page is an overflow page rather than a data page.
+///
+/// # Arguments
+/// * page - The page to check.
+pub fn is_overflow_page(page: &mut DBPage) -> Result<bool, TupleError> {
+    try!(page.seek(SeekFrom::Start(0)));
+    Ok(try!(page.read_u16::<BigEndian>()) == OVERFLOW_PAGE)
+}
+
+/// Returns the number of overflow pages needed to store a value of the given length.
+///
+/// # Arguments
+/// * page_size - The page size of the tuple file.
+/// * length - The length of the value in bytes.
+pub fn get_num_pages_needed(page_size: u32, length: usize) -> usize {
+    let capacity = get_page_capacity(page_size);
+    ::std::cmp::max(1, (length + capacity - 1) / capacity)
+}
+
+/// Stores a value in a new chain of overflow pages in the file, returning the file-pointer to store
+/// in the tuple in place of the value.
+///
+/// # Arguments
+/// * db_file - The heap file the value's tuple is stored in.
+/// * data - The bytes of the value.
+/// * page_nos - The pages to store the value in, which must be empty data pages or past the end
+///   of the file. There must be as many as
+///   [`get_num_pages_needed`](fn.get_num_pages_needed.html) returns.
+///
+/// # Errors
+/// Returns `FileTooBig` without writing anything if a page number doesn't fit in the two bytes
+/// that the chain and the file-pointer store it in.
+pub fn write_value(db_file: &mut DBFile<File>, data: &[u8], page_nos: &[u32]) -> Result<Literal, TupleError> {
+    if let Some(&page_no) = page_nos.iter().find(|&&page_no| page_no > u16::MAX as u32) {
+        return Err(TupleError::FileTooBig(page_no));
+    }
+    let capacity = get_page_capacity(db_file.get_page_size());
+    debug!("Storing {}-byte value in overflow pages {:?}.", data.len(), page_nos);
+
+    for (i, &page_no) in page_nos.iter().enumerate() {
+        let chunk = &data[::std::cmp::min(i * capacity, data.len())..
+                          ::std::cmp::min((i + 1) * capacity, data.len())];
+        let next_page = page_nos.get(i + 1).map(|&n| n as u16).unwrap_or(0);
+
+        let mut page = try!(load_dbpage(db_file, page_no, true));
+        try!(page.seek(SeekFrom::Start(0)));
+        try!(page.write_u16::<BigEndian>(OVERFLOW_PAGE));
+        try!(page.write_u16::<BigEndian>(next_page));
+        try!(page.write_u16::<BigEndian>(chunk.len() as u16));
+        try!(page.write_all(chunk));
+        try!(save_dbpage(db_file, &mut page));
+        try!(page.unpin());
+    }
+
+    Ok(Literal::FilePointer {
+        page_no: page_nos[0] as u16,
+        offset: OFFSET_DATA,
+    })
+}
+
+/// Reads a value back out of the chain of overflow pages starting at the given page.
+///
+/// # Arguments
+/// * db_file - The heap file the value's tuple is stored in.
+/// * first_page - The first page of the overflow chain.
+pub fn read_value(db_file: &mut DBFile<File>, first_page: u16) -> Result<Vec<u8>, TupleError> {
+    let mut result = Vec::new();
+    let mut page_no = first_page;
+    while page_no != 0 {
+        let mut page = try!(load_dbpage(db_file, page_no as u32, false));
+        if !try!(is_overflow_page(&mut page)) {
+            try!(page.unpin());
+            return Err(TupleError::InvalidOverflowPage(page_no as u32));
+        }
+        try!(page.seek(SeekFrom::Start(OFFSET_NEXT_PAGE)));
+        let next_page = try!(page.read_u16::<BigEndian>());
+        try!(page.seek(SeekFrom::Start(OFFSET_DATA_LENGTH)));
+        let length = try!(page.read_u16::<BigEndian>()) as usize;
+        let start = OFFSET_DATA as usize;
+        result.extend_from_slice(&page.page_data[start..(start + length)]);
+        try!(page.unpin());
+        page_no = next_page;
+    }
+    Ok(result)
+}
+
+/// Reads a value back out of a chain of overflow pages in the same file as the given page. This
+/// is used when reading tuples, which only have access to their own page.
+///
+/// # Arguments
+/// * page - A page of the file the overflow chain is stored in.
+/// * first_page - The first page of the overflow chain.
+pub fn read_value_from_page_file(page: &DBPage, first_page: u16) -> Result<Vec<u8>, TupleError> {
+    let path = match page.get_file_path() {
+        Some(path) => path.clone(),
+        None => return Err(TupleError::InvalidOverflowPage(first_page as u32)),
+    };
+    let file = try!(OpenOptions::new().read(true).write(true).open(&path));
+    let page_size = page.page_data.len() as u32;
+    let mut db_file = try!(DBFile::with_path(DBFileType::HeapTupleFile, page_size, file, path)
+        .map_err(file_manager::Error::from));
+    read_value(&mut db_file, first_page)
+}
+
+/// Frees the chain of overflow pages starting at the given page, turning each page back into an
+/// empty data page. Returns the pages that were freed.
+///
+/// # Arguments
+/// * db_file - The heap file the value's tuple is stored in.
+/// * first_page - The first page of the overflow chain.
+pub fn free_value(db_file: &mut DBFile<File>, first_page: u16) -> Result<Vec<u32>, TupleError> {
+    let mut result = Vec::new();
+    let mut page_no = first_page;
+    while page_no != 0 {
+        let mut page = try!(load_dbpage(db_file, page_no as u32, false));
+        if !try!(is_overflow_page(&mut page)) {
+            try!(page.unpin());
+            return Err(TupleError::InvalidOverflowPage(page_no as u32));
+        }
+        try!(page.seek(SeekFrom::Start(OFFSET_NEXT_PAGE)));
+        let next_page = try!(page.read_u16::<BigEndian>());
+        debug!("Freeing overflow page {}.", page_no);
+
+        let data_end = page.page_data.len() - PAGE_LSN_SIZE;
+        try!(page.write_at_position(0, &vec![0u8; data_end])
+            .map_err(|_| TupleError::IOError("Unable to clear the overflow page.".into())));
+        try!(page.init_new_page());
+        try!(save_dbpage(db_file, &mut page));
+        try!(page.unpin());
+        result.push(page_no as u32);
+        page_no = next_page;
+    }
+    Ok(result)
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::storage::FileManager;
+
+    #[test]
+    fn test_overflow_chain() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+
+        let data: Vec<u8> = (0..2000).map(|i| (i % 251) as u8).collect();
+        assert_eq!(4, get_num_pages_needed(512, data.len()));
+        let pointer = write_value(&mut dbfile, &data, &[1, 2, 3, 4]).unwrap();
+        assert_eq!(Literal::FilePointer { page_no: 1, offset: 6 }, pointer);
+        assert_eq!(data, read_value(&mut dbfile, 1).unwrap());
+
+        let page = load_dbpage(&mut dbfile, 1, false).unwrap();
+        assert_eq!(data, read_value_from_page_file(&page, 1).unwrap());
+
+        // Freed pages become empty data pages.
+        assert_eq!(vec![1, 2, 3, 4], free_value(&mut dbfile, 1).unwrap());
+        let mut page = load_dbpage(&mut dbfile, 2, false).unwrap();
+        assert!(!is_overflow_page(&mut page).unwrap());
+        assert_eq!(0, page.get_num_slots().unwrap());
+        assert_eq!(Err(TupleError::InvalidOverflowPage(1)), read_value(&mut dbfile, 1));
+
+        let pointer = write_value(&mut dbfile, b"hello", &[3]).unwrap();
+        assert_eq!(Literal::FilePointer { page_no: 3, offset: 6 }, pointer);
+        assert_eq!(b"hello".to_vec(), read_value(&mut dbfile, 3).unwrap());
+    }
+
+    #[test]
+    fn test_page_numbers_too_big() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+
+        // Nothing is written if any page of the chain can't be referenced.
+        let data = vec![7u8; 1000];
+        let too_big = u16::MAX as u32 + 1;
+        assert_eq!(Err(TupleError::FileTooBig(too_big)), write_value(&mut dbfile, &data, &[1, too_big]));
+        assert_eq!(1, ::storage::free_space_map::get_num_pages(&dbfile).unwrap());
+
+        let pointer = write_value(&mut dbfile, &data, &[1, u16::MAX as u32]).unwrap();
+        assert_eq!(Literal::FilePointer { page_no: 1, offset: 6 }, pointer);
+        assert_eq!(data, read_value(&mut dbfile, 1).unwrap());
+    }
+}
@@ -1,12 +1,14 @@
 //! A module which stores utilities for a basic page tuple.
 
-use std::io::{Seek, SeekFrom};
+use std::io::{Read, Seek, SeekFrom};
 
 use byteorder::{BigEndian, ReadBytesExt};
 
 use ::{ColumnType, Schema};
-use ::expressions::Literal;
-use ::storage::{DBPage, PinError, Pinnable, ReadNanoDBExt, Tuple, TupleError};
+use ::expressions::{Date, Decimal, Interval, Literal, Time, Timestamp};
+use ::expressions::temporal::MICROS_PER_SECOND;
+use ::storage::{DBPage, PinError, Pinnable, ReadNanoDBExt, Tuple, TupleError, TupleLiteral};
+use ::storage::overflow_page::{self, OVERFLOW_VALUE};
 
 /// This value is used in [`valueOffsets`](#) when a column value is set to `NULL`.
 pub static NULL_OFFSET: u16 = 0;
@@ -21,10 +23,20 @@ pub fn get_tuple_storage_size<T: Tuple>(schema: Schema, tuple: &mut T) -> Result
     for col_info in schema {
         let value = try!(tuple.get_column_value(col_idx));
         if value != Literal::Null {
-            let data_length = match col_info.column_type {
-                ColumnType::VarChar { length: _ } => value.as_string().unwrap().len(),
+            let data_length = match value {
+                // A reference to the overflow pages holding a variable-size value.
+                Literal::FilePointer { .. } if col_info.column_type.is_variable_length() => 4,
+                _ if col_info.column_type.is_variable_length() => value.as_bytes().unwrap().len(),
                 _ => 0,
             };
+            if data_length >= OVERFLOW_VALUE as usize {
+                return Err(TupleError::ValueTooLong(data_length));
+            }
+            if let ColumnType::Numeric { scale, precision } = col_info.column_type {
+                if !value.as_decimal().map_or(false, |d| d.fits(precision, scale)) {
+                    return Err(TupleError::ValueOutOfRange(value, col_info.column_type));
+                }
+            }
             storage_size += try!(get_storage_size(col_info.column_type, data_length as u16));
         }
 
@@ -61,12 +73,23 @@ fn get_storage_size(col_type: ColumnType, data_length: u16) -> Result<u16, Tuple
         ColumnType::SmallInt => Ok(2),
         ColumnType::BigInt | ColumnType::Double => Ok(8),
         ColumnType::TinyInt => Ok(1),
+        ColumnType::Numeric { precision, .. } => Ok(get_numeric_storage_size(precision)),
+        // Dates are stored as a four-byte day number, times as a four-byte number of seconds since
+        // midnight, DATETIME values as an eight-byte number of seconds and TIMESTAMP values as an
+        // eight-byte number of microseconds since 1970-01-01.
+        ColumnType::Date | ColumnType::Time => Ok(4),
+        ColumnType::DateTime | ColumnType::Timestamp => Ok(8),
+        // Intervals are a four-byte number of months, a four-byte number of days and an eight-byte
+        // number of microseconds.
+        ColumnType::Interval => Ok(16),
         // CHAR values are of a fixed size, but the size is specified in
         // the length field and there is no other storage required.
         ColumnType::Char { length } => Ok(length),
-        // VARCHAR values are of a variable size, but there is always a
-        // two byte length specified at the start of the value.
-        ColumnType::VarChar { length: _ } => Ok(2 + data_length),
+        // VARCHAR, TEXT and BLOB values are of a variable size, but there is always a two byte
+        // length specified at the start of the value, unless the value is stored in overflow
+        // pages, in which case the length is replaced by a marker and followed by a four-byte
+        // file-pointer to the first overflow page.
+        ColumnType::VarChar { length: _ } | ColumnType::Text | ColumnType::Blob => Ok(2 + data_length),
         // File-pointers are comprised of a two-byte page number and a
         // two-byte offset in the page.
         ColumnType::FilePointer => Ok(4),
@@ -75,6 +98,202 @@ fn get_storage_size(col_type: ColumnType, data_length: u16) -> Result<u16, Tuple
     }
 }
 
+/// Returns the storage size of a `NUMERIC` value with the given precision. The value is stored as
+/// its unscaled integer, in the smallest integer type that can hold that many digits.
+///
+/// # Arguments
+/// * precision - The total number of digits of the column.
+pub fn get_numeric_storage_size(precision: u16) -> u16 {
+    if precision <= 2 {
+        1
+    } else if precision <= 4 {
+        2
+    } else if precision <= 9 {
+        4
+    } else {
+        8
+    }
+}
+
+/// Returns the storage size of the (non-`NULL`) column value stored at the given offset of a page.
+/// For variable-size columns, the size is read from the page itself.
+///
+/// # Arguments
+/// * page - The page the value is stored in.
+/// * col_type - The column's data type.
+/// * offset - The offset of the value in the page.
+pub fn get_column_value_size(page: &mut DBPage, col_type: ColumnType, offset: u16) -> Result<u16, TupleError> {
+    let data_length = if col_type.is_variable_length() {
+        // The storage size depends on the size of the data value being stored. In this case,
+        // read out the data length.
+        try!(page.seek(SeekFrom::Start(offset as u64)));
+        match try!(page.read_u16::<BigEndian>()) {
+            OVERFLOW_VALUE => 4,
+            length => length,
+        }
+    } else {
+        0
+    };
+    get_storage_size(col_type, data_length)
+}
+
+/// Reads the (non-`NULL`) column value stored at the given offset of a page.
+///
+/// # Arguments
+/// * page - The page the value is stored in.
+/// * offset - The offset of the value in the page.
+/// * col_type - The column's data type.
+pub fn read_column_value(page: &mut DBPage, offset: u16, col_type: ColumnType) -> Result<Literal, TupleError> {
+    try!(page.seek(SeekFrom::Start(offset as u64)));
+    match col_type {
+        ColumnType::TinyInt => {
+            let value = try!(page.read_i8());
+            Ok(Literal::Int(value as i32))
+        }
+        ColumnType::SmallInt => {
+            let value = try!(page.read_i16::<BigEndian>());
+            Ok(Literal::Int(value as i32))
+        }
+        ColumnType::Integer => {
+            let value = try!(page.read_i32::<BigEndian>());
+            Ok(Literal::Int(value))
+        }
+        ColumnType::BigInt => {
+            let value = try!(page.read_i64::<BigEndian>());
+            Ok(Literal::Long(value))
+        }
+        ColumnType::Float => {
+            let value = try!(page.read_f32::<BigEndian>());
+            Ok(Literal::Float(value))
+        }
+        ColumnType::Double => {
+            let value = try!(page.read_f64::<BigEndian>());
+            Ok(Literal::Double(value))
+        }
+        ColumnType::Numeric { scale, precision } => {
+            let unscaled = match get_numeric_storage_size(precision) {
+                1 => try!(page.read_i8()) as i64,
+                2 => try!(page.read_i16::<BigEndian>()) as i64,
+                4 => try!(page.read_i32::<BigEndian>()) as i64,
+                _ => try!(page.read_i64::<BigEndian>()),
+            };
+            Ok(Literal::Decimal(Decimal::new(unscaled, scale)))
+        }
+        ColumnType::Date => {
+            let days = try!(page.read_i32::<BigEndian>());
+            Ok(Date::from_days(days as i64).map(Literal::Date).unwrap_or(Literal::Null))
+        }
+        ColumnType::Time => {
+            let seconds = try!(page.read_i32::<BigEndian>());
+            Ok(Literal::Time(Time::from_micros(seconds as i64 * MICROS_PER_SECOND)))
+        }
+        ColumnType::DateTime => {
+            let seconds = try!(page.read_i64::<BigEndian>());
+            Ok(Timestamp::from_micros(seconds * MICROS_PER_SECOND).map(Literal::Timestamp).unwrap_or(Literal::Null))
+        }
+        ColumnType::Timestamp => {
+            let micros = try!(page.read_i64::<BigEndian>());
+            Ok(Timestamp::from_micros(micros).map(Literal::Timestamp).unwrap_or(Literal::Null))
+        }
+        ColumnType::Interval => {
+            let months = try!(page.read_i32::<BigEndian>());
+            let days = try!(page.read_i32::<BigEndian>());
+            let micros = try!(page.read_i64::<BigEndian>());
+            Ok(Literal::Interval(Interval::new(months, days, micros)))
+        }
+        ColumnType::Char { length } => {
+            let value = try!(page.read_fixed_size_string(length));
+            Ok(Literal::String(value))
+        }
+        ColumnType::VarChar { length: _ } | ColumnType::Text | ColumnType::Blob => {
+            let length = try!(page.read_u16::<BigEndian>());
+            let bytes = if length == OVERFLOW_VALUE {
+                // The value is stored in a chain of overflow pages, so reassemble it.
+                let first_page = try!(page.read_u16::<BigEndian>());
+                try!(overflow_page::read_value_from_page_file(page, first_page))
+            } else {
+                let mut buf = vec![0u8; length as usize];
+                try!(page.read_exact(&mut buf));
+                buf
+            };
+            // BLOB values are raw bytes, so only character values are decoded.
+            if col_type == ColumnType::Blob {
+                return Ok(Literal::Bytes(bytes));
+            }
+            match String::from_utf8(bytes) {
+                Ok(value) => Ok(Literal::String(value)),
+                Err(_) => Err(TupleError::IOError("The stored value is not valid UTF-8.".into())),
+            }
+        }
+        ColumnType::FilePointer => {
+            let page_no = try!(page.read_u16::<BigEndian>());
+            let offset = try!(page.read_u16::<BigEndian>());
+            Ok(Literal::FilePointer { page_no: page_no, offset: offset })
+        }
+        _ => Err(TupleError::UnsupportedColumnType(col_type)),
+    }
+}
+
+/// Reads the tuple stored at the given offset of a page into a tuple literal, returning the tuple
+/// along with the number of bytes it occupies in the page.
+///
+/// # Arguments
+/// * page - The page the tuple is stored in.
+/// * offset - The offset of the start of the tuple (i.e. its `NULL`-flags) in the page.
+/// * schema - The schema of the stored tuple.
+pub fn read_tuple(page: &mut DBPage, offset: u16, schema: &Schema) -> Result<(TupleLiteral, u16), TupleError> {
+    let num_columns = schema.num_columns();
+    let mut null_flags = vec![0u8; get_null_flags_size(num_columns) as usize];
+    try!(page.seek(SeekFrom::Start(offset as u64)));
+    try!(page.read_exact(&mut null_flags));
+
+    let mut result = TupleLiteral::new();
+    let mut value_offset = offset + null_flags.len() as u16;
+    for i in 0..num_columns {
+        if (null_flags[i / 8] >> (i % 8)) & 0x01 != 0 {
+            result.add_value(Literal::Null);
+        } else {
+            let col_type = schema[i].column_type;
+            result.add_value(try!(read_column_value(page, value_offset, col_type)));
+            value_offset += try!(get_column_value_size(page, col_type, value_offset));
+        }
+    }
+
+    Ok((result, value_offset - offset))
+}
+
+/// Returns the first overflow page of each variable-size value of the tuple stored at the given
+/// offset of a page that has been moved out into overflow pages.
+///
+/// # Arguments
+/// * page - The page the tuple is stored in.
+/// * offset - The offset of the start of the tuple (i.e. its `NULL`-flags) in the page.
+/// * schema - The schema of the stored tuple.
+pub fn read_overflow_pages(page: &mut DBPage, offset: u16, schema: &Schema) -> Result<Vec<u16>, TupleError> {
+    let num_columns = schema.num_columns();
+    let mut null_flags = vec![0u8; get_null_flags_size(num_columns) as usize];
+    try!(page.seek(SeekFrom::Start(offset as u64)));
+    try!(page.read_exact(&mut null_flags));
+
+    let mut result = Vec::new();
+    let mut value_offset = offset + null_flags.len() as u16;
+    for i in 0..num_columns {
+        if (null_flags[i / 8] >> (i % 8)) & 0x01 != 0 {
+            continue;
+        }
+        let col_type = schema[i].column_type;
+        if col_type.is_variable_length() {
+            try!(page.seek(SeekFrom::Start(value_offset as u64)));
+            if try!(page.read_u16::<BigEndian>()) == OVERFLOW_VALUE {
+                result.push(try!(page.read_u16::<BigEndian>()));
+            }
+        }
+        value_offset += try!(get_column_value_size(page, col_type, value_offset));
+    }
+
+    Ok(result)
+}
+
 /// This class is a partial implementation of the {@link Tuple} interface that handles reading and
 /// writing tuple data against a {@link DBPage} object. This can be used to read and write tuples in
 /// a table file, keys in an index file, etc. It could also be used to store and manage tuples in
@@ -131,6 +350,11 @@ impl PageTuple {
         Ok(result)
     }
 
+    /// Returns the offset in the page where the tuple starts.
+    pub fn get_page_offset(&self) -> u16 {
+        self.page_offset
+    }
+
     fn check_column_index(&self, col_index: usize) -> Result<(), TupleError> {
         let num_columns = self.schema.num_columns();
         if col_index < num_columns {
@@ -140,19 +364,6 @@ impl PageTuple {
         }
     }
 
-    fn get_column_value_size(&mut self, col_type: ColumnType, offset: u16) -> Result<u16, TupleError> {
-        let data_length = match col_type {
-            ColumnType::VarChar { length: _ } => {
-                // The storage size depends on the size of the data value being stored. In this
-                // case, read out the data length.
-                try!(self.db_page.seek(SeekFrom::Start(offset as u64)));
-                try!(self.db_page.read_u16::<BigEndian>())
-            }
-            _ => 0,
-        };
-        get_storage_size(col_type, data_length)
-    }
-
     /// Returns the offset where the tuple's data actually starts. This is past the bytes used to
     /// store NULL-flags.
     fn get_data_start_offset(&self) -> u16 {
@@ -195,7 +406,7 @@ impl PageTuple {
                 self.value_offsets[i] = value_offset;
 
                 let col_type = self.schema[i].column_type;
-                value_offset += try!(self.get_column_value_size(col_type, value_offset)) as u16;
+                value_offset += try!(get_column_value_size(&mut self.db_page, col_type, value_offset)) as u16;
             }
         }
 
@@ -246,50 +457,8 @@ impl Tuple for PageTuple {
         }
 
         let offset = self.value_offsets[col_index];
-        try!(self.db_page.seek(SeekFrom::Start(offset as u64)));
-
         let col_type: ColumnType = self.schema[col_index].column_type;
-
-        match col_type {
-            ColumnType::TinyInt => {
-                let value = try!(self.db_page.read_i8());
-                Ok(Literal::Int(value as i32))
-            }
-            ColumnType::SmallInt => {
-                let value = try!(self.db_page.read_i16::<BigEndian>());
-                Ok(Literal::Int(value as i32))
-            }
-            ColumnType::Integer => {
-                let value = try!(self.db_page.read_i32::<BigEndian>());
-                Ok(Literal::Int(value))
-            }
-            ColumnType::BigInt => {
-                let value = try!(self.db_page.read_i64::<BigEndian>());
-                Ok(Literal::Long(value))
-            }
-            ColumnType::Float => {
-                let value = try!(self.db_page.read_f32::<BigEndian>());
-                Ok(Literal::Float(value))
-            }
-            ColumnType::Double => {
-                let value = try!(self.db_page.read_f64::<BigEndian>());
-                Ok(Literal::Double(value))
-            }
-            ColumnType::Char { length } => {
-                let value = try!(self.db_page.read_fixed_size_string(length));
-                Ok(Literal::String(value))
-            }
-            ColumnType::VarChar { length: _ } => {
-                let value = try!(self.db_page.read_varchar65535());
-                Ok(Literal::String(value))
-            }
-            ColumnType::FilePointer => {
-                let page_no = try!(self.db_page.read_u16::<BigEndian>());
-                let offset = try!(self.db_page.read_u16::<BigEndian>());
-                Ok(Literal::FilePointer { page_no: page_no, offset: offset })
-            }
-            _ => Err(TupleError::UnsupportedColumnType(col_type)),
-        }
+        read_column_value(&mut self.db_page, offset, col_type)
     }
 
     fn get_external_reference(&self) -> Option<Literal> {
@@ -1,8 +1,57 @@
 //! This module contains utilities for general database file storage handling.
+//!
+//! All page accesses go through a single, global [`BufferManager`](../buffer_manager/struct.BufferManager.html)
+//! so that pages are cached across tuple files and commands. The buffer manager can be replaced
+//! (e.g. to change the cache size or replacement policy) with
+//! [`set_buffer_manager`](fn.set_buffer_manager.html).
+//!
+//! While a transaction is in progress, every page saved with [`save_dbpage`](fn.save_dbpage.html)
+//! has its changes recorded in the write-ahead log by the
+//! [`transaction_manager`](../../transactions/transaction_manager/index.html).
 
 use std::fs::File;
+use std::sync::{Arc, Mutex, MutexGuard};
 
 use ::storage::{DBFile, DBPage, Pinnable, file_manager};
+use ::storage::buffer_manager::{BufferManager, PageKey};
+use ::transactions::transaction_manager;
+
+lazy_static! {
+    static ref BUFFER_MANAGER: Mutex<BufferManager> = Mutex::new(BufferManager::default());
+}
+
+/// Returns a handle to the global buffer manager.
+pub fn buffer_manager() -> MutexGuard<'static, BufferManager> {
+    match BUFFER_MANAGER.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Replaces the global buffer manager, e.g. to use a different cache size or replacement policy.
+/// All dirty pages in the old buffer manager are written back first.
+///
+/// # Errors
+/// This will fail if the dirty pages of the old buffer manager could not be written.
+pub fn set_buffer_manager(new_manager: BufferManager) -> Result<(), file_manager::Error> {
+    let mut manager = buffer_manager();
+    try!(manager.flush_all());
+    *manager = new_manager;
+    Ok(())
+}
+
+/// Writes every dirty page held by the buffer manager back to disk.
+pub fn flush_all_pages() -> Result<(), file_manager::Error> {
+    buffer_manager().flush_all()
+}
+
+/// Writes every dirty page of the given database file back to disk.
+pub fn flush_dbfile(dbfile: &mut DBFile<File>) -> Result<(), file_manager::Error> {
+    match dbfile.file_info.path.clone() {
+        Some(path) => buffer_manager().flush_file(path),
+        None => Ok(()),
+    }
+}
 
 /// This method returns a database page to use, retrieving it from the buffer manager if it is
 /// already loaded, or reading it from the specified data file if it is not already loaded. If the
@@ -10,6 +59,10 @@ use ::storage::{DBFile, DBPage, Pinnable, file_manager};
 /// cause other database pages to be evicted from the buffer manager, and written back to disk if
 /// the evicted pages are dirty.
 ///
+/// The returned page is pinned, and shares its data with the buffer manager until it is changed;
+/// the changes are only seen by other pages once the page is saved with
+/// [`save_dbpage`](fn.save_dbpage.html).
+///
 /// The `create` flag controls whether an error is propagated, if the requested page is past the
 /// current end of the data file. (Note that if a new page is created, the file's size will not
 /// reflect the new page until it is actually written to the file.)
@@ -19,10 +72,24 @@ use ::storage::{DBFile, DBPage, Pinnable, file_manager};
 /// * page_no - The number of the page to load.
 /// * create - A flag specifying whether the page should be created if it doesn't already exist.
 pub fn load_dbpage(dbfile: &mut DBFile<File>, page_no: u32, create: bool) -> Result<DBPage, file_manager::Error> {
-    // TODO: Use BufferManager
+    let key: Option<PageKey> = dbfile.file_info.path.clone().map(|path| (path, page_no));
+
+    if let Some(ref key) = key {
+        // The buffer manager must be released before pinning, since pinning goes through it.
+        let cached = buffer_manager().get_page(key);
+        if let Some(data) = cached {
+            let mut page = try!(DBPage::with_data(&dbfile.file_info, page_no, data));
+            page.pin();
+            return Ok(page);
+        }
+    }
+
     let mut page = try!(DBPage::new(&dbfile.file_info, page_no));
-    match file_manager::load_page(dbfile, page_no, &mut page.page_data, create) {
+    match file_manager::load_page(dbfile, page_no, &mut Arc::make_mut(&mut page.page_data)[..], create) {
         Ok(()) => {
+            if let Some(key) = key {
+                try!(buffer_manager().add_page(key, page.page_data.clone()));
+            }
             page.pin();
             Ok(page)
         }
@@ -32,3 +99,89 @@ pub fn load_dbpage(dbfile: &mut DBFile<File>, page_no: u32, create: bool) -> Res
         }
     }
 }
+
+/// Saves a database page through the buffer manager, and then clears the page's dirty flag. The
+/// data is written to the file when the page is evicted or flushed.
+///
+/// If a transaction is in progress, the changes from the previously saved version of the page are
+/// logged first, and the page's LSN is updated.
+///
+/// # Arguments
+/// * dbfile - The database file the page belongs to.
+/// * page - The page to save.
+pub fn save_dbpage(dbfile: &mut DBFile<File>, page: &mut DBPage) -> Result<(), file_manager::Error> {
+    match dbfile.file_info.path.clone() {
+        Some(path) => {
+            if transaction_manager::is_logging(&path) {
+                let key = (path.clone(), page.page_no);
+                let cached = buffer_manager().peek_page(&key);
+                let before = match cached {
+                    Some(data) => data,
+                    None => {
+                        let mut data = vec![0; page.page_data.len()];
+                        try!(file_manager::load_page(dbfile, page.page_no, &mut data, true));
+                        Arc::new(data)
+                    }
+                };
+                let lsn = try!(transaction_manager::log_page_update(&path, page.page_no, &before, &page.page_data)
+                    .map_err(|e| file_manager::Error::WALError(e.to_string())));
+                if let Some(lsn) = lsn {
+                    page.set_page_lsn(lsn);
+                }
+            }
+            try!(buffer_manager().write_page((path, page.page_no), page.page_data.clone()))
+        }
+        None => try!(file_manager::save_page(dbfile, page.page_no, &page.page_data)),
+    }
+    page.set_dirty(false);
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::storage::{DBFileType, FileManager};
+
+    #[test]
+    fn test_save_and_load_dbpage() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+
+        {
+            let mut page = load_dbpage(&mut dbfile, 1, true).unwrap();
+            page.write_at_position(10, &[1, 2, 3]).unwrap();
+            save_dbpage(&mut dbfile, &mut page).unwrap();
+        }
+
+        // The saved page is served from the cache before it has been written to disk.
+        let page = load_dbpage(&mut dbfile, 1, false).unwrap();
+        assert_eq!(&[1, 2, 3], &page.page_data[10..13]);
+
+        flush_dbfile(&mut dbfile).unwrap();
+        let mut buffer = vec![0; 512];
+        file_manager::load_page(&mut dbfile, 1, &mut buffer, false).unwrap();
+        assert_eq!(&[1, 2, 3], &buffer[10..13]);
+    }
+
+    #[test]
+    fn test_cached_pages_are_shared() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut dbfile = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+
+        let first = load_dbpage(&mut dbfile, 1, true).unwrap();
+        let mut second = load_dbpage(&mut dbfile, 1, false).unwrap();
+        assert!(Arc::ptr_eq(&first.page_data, &second.page_data));
+
+        // A changed page gets its own copy of the data, which replaces the cached data when saved.
+        second.write_at_position(10, &[1, 2, 3]).unwrap();
+        assert!(!Arc::ptr_eq(&first.page_data, &second.page_data));
+        assert_eq!(&[0, 0, 0], &first.page_data[10..13]);
+        save_dbpage(&mut dbfile, &mut second).unwrap();
+        let third = load_dbpage(&mut dbfile, 1, false).unwrap();
+        assert!(Arc::ptr_eq(&second.page_data, &third.page_data));
+    }
+}
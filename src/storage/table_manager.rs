@@ -5,34 +5,132 @@ use std::collections::HashMap;
 use std::rc::Rc;
 
 use ::Schema;
-use ::storage::{DBFileType, FileManager, file_manager, Tuple, TupleError};
-use ::storage::tuple_files::{HeapFilePageTuple, HeapTupleFile};
+use ::expressions::Literal;
+use ::indexes::{IndexInfo, get_index_file_name};
+use ::relations::TableStats;
+use ::storage::{DBFileType, FileManager, PinError, Pinnable, file_manager, Tuple, TupleError, TupleLiteral};
+use ::storage::tuple_files::{BTreeFilePageTuple, BTreeTupleFile, FilePageTuple, HeapTupleFile, TupleFile};
 use ::storage::dbfile::get_default_pagesize;
 
-/// This class represents a single table in the database, including the table's name, and the tuple
-/// file that holds the table's data.
+/// This class represents a single table in the database, including the table's name, the tuple
+/// file that holds the table's data, and the files of the indexes on the table.
 #[derive(Debug, PartialEq, Clone)]
 pub struct Table {
     /// The name of the table.
     pub name: Option<String>,
-    tuple_file: Rc<RefCell<HeapTupleFile>>,
+    tuple_file: Rc<RefCell<TupleFile>>,
+    indexes: Rc<RefCell<Vec<(IndexInfo, BTreeTupleFile)>>>,
 }
 
 impl Table {
     /// Retrieve the schema from the tuple file.
     pub fn get_schema(&self) -> Schema {
-        self.tuple_file.borrow().schema.clone()
+        self.tuple_file.borrow().get_schema().clone()
     }
 
-    /// Wrapper around the tuple file's `add_tuple` method.
-    pub fn add_tuple<'a, T: Tuple + 'a>(&self, tuple: T) -> Result<Box<Tuple + 'a>, TupleError> {
+    /// Returns the descriptions of the indexes on the table.
+    pub fn get_indexes(&self) -> Vec<IndexInfo> {
+        self.indexes.borrow().iter().map(|&(ref info, _)| info.clone()).collect()
+    }
+
+    /// Returns the statistics of the table, as collected by the last `ANALYZE` of the table. A
+    /// table that has never been analyzed has no tuples and unknown column statistics.
+    pub fn get_stats(&self) -> TableStats {
+        self.tuple_file.borrow().get_stats().clone()
+    }
+
+    /// Scans the table to collect its statistics, storing them in the table's header page.
+    pub fn analyze(&self) -> Result<TableStats, Error> {
+        self.tuple_file.borrow_mut().analyze().map_err(Into::into)
+    }
+
+    /// Wrapper around the tuple file's `add_tuple` method, which also adds the tuple to each of
+    /// the table's indexes.
+    ///
+    /// # Errors
+    /// Besides errors from storing the tuple, this fails without adding the tuple if it would
+    /// violate a unique index.
+    pub fn add_tuple<'a, T: Tuple + 'a>(&self, mut tuple: T) -> Result<Box<Tuple + 'a>, Error> {
+        let mut indexes = self.indexes.borrow_mut();
+        if indexes.is_empty() {
+            let mut borrowed = self.tuple_file.borrow_mut();
+            return borrowed.add_tuple(tuple).map_err(Into::into);
+        }
+
+        let values = TupleLiteral::from_tuple(&mut tuple);
+        for &mut (ref info, ref mut index_file) in indexes.iter_mut() {
+            if info.unique {
+                try!(check_unique(info, index_file, &values));
+            }
+        }
+
+        let added = try!(self.tuple_file.borrow_mut().add_tuple(values.clone()));
+        let pointer = added.get_external_reference().unwrap_or(Literal::Null);
+        for &mut (ref info, ref mut index_file) in indexes.iter_mut() {
+            let mut entry = try!(index_file.add_tuple(info.get_entry(&values, pointer.clone())));
+            try!(entry.unpin());
+        }
+        Ok(added)
+    }
+
+    /// Deletes a tuple from the table, along with its entries in each of the table's indexes.
+    /// Returns whether the tuple was found.
+    ///
+    /// # Arguments
+    /// * pointer - The file pointer to the tuple, as returned by its `get_external_reference`
+    ///   method.
+    /// * values - The values of the tuple.
+    pub fn delete_tuple(&self, pointer: &Literal, values: &TupleLiteral) -> Result<bool, Error> {
+        let mut indexes = self.indexes.borrow_mut();
+        for &mut (ref info, ref mut index_file) in indexes.iter_mut() {
+            try!(index_file.delete_tuple(&info.get_entry(values, pointer.clone())));
+        }
         let mut borrowed = self.tuple_file.borrow_mut();
-        let result = borrowed.add_tuple(tuple);
-        result
+        borrowed.delete_tuple(pointer, values).map_err(Into::into)
+    }
+
+    /// Replaces the values of a tuple of the table, updating the table's indexes to match. Returns
+    /// whether the tuple was found.
+    ///
+    /// # Arguments
+    /// * pointer - The file pointer to the tuple, as returned by its `get_external_reference`
+    ///   method.
+    /// * old_values - The current values of the tuple.
+    /// * new_values - The new values of the tuple.
+    ///
+    /// # Errors
+    /// Besides errors from storing the tuple, this fails without changing the tuple if the new
+    /// values would violate a unique index.
+    pub fn update_tuple(&self,
+                        pointer: &Literal,
+                        old_values: &TupleLiteral,
+                        new_values: &TupleLiteral)
+                        -> Result<bool, Error> {
+        let mut indexes = self.indexes.borrow_mut();
+        for &mut (ref info, ref mut index_file) in indexes.iter_mut() {
+            if info.unique && info.get_key(old_values) != info.get_key(new_values) {
+                try!(check_unique(info, index_file, new_values));
+            }
+        }
+
+        let new_pointer = match try!(self.tuple_file.borrow_mut().update_tuple(pointer, old_values, new_values)) {
+            Some(new_pointer) => new_pointer,
+            None => return Ok(false),
+        };
+        for &mut (ref info, ref mut index_file) in indexes.iter_mut() {
+            let old_entry = info.get_entry(old_values, pointer.clone());
+            let new_entry = info.get_entry(new_values, new_pointer.clone());
+            if old_entry != new_entry {
+                try!(index_file.delete_tuple(&old_entry));
+                let mut added = try!(index_file.add_tuple(new_entry));
+                try!(added.unpin());
+            }
+        }
+        Ok(true)
     }
 
     /// Wrapper around the tuple file's `get_first_tuple` method.
-    pub fn get_first_tuple(&self) -> Result<Option<HeapFilePageTuple>, TupleError> {
+    pub fn get_first_tuple(&self) -> Result<Option<FilePageTuple>, TupleError> {
         let mut borrowed = self.tuple_file.borrow_mut();
         let result = borrowed.get_first_tuple();
         result
@@ -41,12 +139,80 @@ impl Table {
 
     /// Wrapper around the tuple file's `get_next_tuple` method.
     pub fn get_next_tuple(&self,
-                          cur_tuple: &HeapFilePageTuple)
-                          -> Result<Option<HeapFilePageTuple>, TupleError> {
+                          cur_tuple: &FilePageTuple)
+                          -> Result<Option<FilePageTuple>, TupleError> {
         let mut borrowed = self.tuple_file.borrow_mut();
         let result = borrowed.get_next_tuple(cur_tuple);
         result
     }
+
+    /// Wrapper around the tuple file's `get_tuple` method, which looks up a tuple by the file
+    /// pointer stored in an index entry.
+    pub fn get_tuple(&self, pointer: &Literal) -> Result<Option<FilePageTuple>, TupleError> {
+        let mut borrowed = self.tuple_file.borrow_mut();
+        let result = borrowed.get_tuple(pointer);
+        result
+    }
+
+    /// Returns the first entry of the named index whose key is at least the given key (or strictly
+    /// greater than it, if `inclusive` is false), or `None` if there is no such entry. The key may
+    /// hold values for only the leading indexed columns.
+    ///
+    /// # Arguments
+    /// * index_name - The name of the index.
+    /// * key - The key to search for.
+    /// * inclusive - Whether entries equal to the key are included.
+    pub fn find_index_entry(&self,
+                            index_name: &str,
+                            key: &TupleLiteral,
+                            inclusive: bool)
+                            -> Result<Option<BTreeFilePageTuple>, Error> {
+        let mut indexes = self.indexes.borrow_mut();
+        match indexes.iter_mut().find(|&&mut (ref info, _)| info.name == index_name) {
+            Some(&mut (_, ref mut index_file)) => {
+                let result = if inclusive {
+                    index_file.find_first_tuple_at_least(key)
+                } else {
+                    index_file.find_first_tuple_greater_than(key)
+                };
+                result.map_err(Into::into)
+            }
+            None => Err(Error::IndexDoesNotExist(index_name.into())),
+        }
+    }
+
+    /// Returns the entry of the named index that follows the given entry, or `None` if there are
+    /// no more entries.
+    ///
+    /// # Arguments
+    /// * index_name - The name of the index.
+    /// * cur_entry - The current entry of the index.
+    pub fn get_next_index_entry(&self,
+                                index_name: &str,
+                                cur_entry: &BTreeFilePageTuple)
+                                -> Result<Option<BTreeFilePageTuple>, Error> {
+        let mut indexes = self.indexes.borrow_mut();
+        match indexes.iter_mut().find(|&&mut (ref info, _)| info.name == index_name) {
+            Some(&mut (_, ref mut index_file)) => index_file.get_next_tuple(cur_entry).map_err(Into::into),
+            None => Err(Error::IndexDoesNotExist(index_name.into())),
+        }
+    }
+}
+
+/// Checks that adding a tuple with the given values would not violate a unique index. Since `NULL`
+/// is never equal to anything, keys containing a `NULL` never conflict.
+fn check_unique(info: &IndexInfo, index_file: &mut BTreeTupleFile, values: &TupleLiteral) -> Result<(), Error> {
+    let key = info.get_key(values);
+    if key.iter().any(|value| *value == Literal::Null) {
+        return Ok(());
+    }
+    match try!(index_file.find_first_tuple_equal(&key)) {
+        Some(mut existing) => {
+            try!(existing.unpin());
+            Err(Error::UniqueViolation(info.name.clone()))
+        }
+        None => Ok(()),
+    }
 }
 
 /// Given the name of a table, return the file name which will correspond to the table in the data
@@ -64,12 +230,34 @@ pub fn get_table_file_name<S: Into<String>>(table_name: S) -> String {
 pub enum Error {
     /// A file manager error occurred while using a table utility method.
     FileManagerError(file_manager::Error),
+    /// An error occurred while reading or writing a tuple of the table or one of its indexes.
+    TupleError(TupleError),
+    /// The named column does not exist in the table.
+    ColumnDoesNotExist(String),
+    /// An index with the given name already exists on the table.
+    IndexExists(String),
+    /// No index with the given name exists on the table.
+    IndexDoesNotExist(String),
+    /// The named table is stored in a format that does not support indexes.
+    IndexesUnsupported(String),
+    /// Adding the tuple would violate the named unique index.
+    UniqueViolation(String),
 }
 
 impl ::std::fmt::Display for Error {
     fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
         match *self {
             Error::FileManagerError(ref e) => write!(f, "{}", e),
+            Error::TupleError(ref e) => write!(f, "{}", e),
+            Error::ColumnDoesNotExist(ref name) => write!(f, "The column {} does not exist.", name),
+            Error::IndexExists(ref name) => write!(f, "The index {} already exists.", name),
+            Error::IndexDoesNotExist(ref name) => write!(f, "The index {} does not exist.", name),
+            Error::IndexesUnsupported(ref name) => {
+                write!(f, "The table {} is not a heap table, so it can't be indexed.", name)
+            }
+            Error::UniqueViolation(ref name) => {
+                write!(f, "The tuple would violate the unique index {}.", name)
+            }
         }
     }
 }
@@ -80,6 +268,18 @@ impl From<file_manager::Error> for Error {
     }
 }
 
+impl From<TupleError> for Error {
+    fn from(error: TupleError) -> Error {
+        Error::TupleError(error)
+    }
+}
+
+impl From<PinError> for Error {
+    fn from(error: PinError) -> Error {
+        Error::TupleError(error.into())
+    }
+}
+
 /// This class provides utilities for tables that can have indexes and constraints on them.
 pub struct TableManager {
     open_tables: RefCell<HashMap<String, Table>>,
@@ -95,13 +295,27 @@ impl TableManager {
         let name = name.into();
 
         if !self.open_tables.borrow().contains_key(&name) {
-            match file_manager.open_dbfile(get_table_file_name(name.as_ref())) {
+            match file_manager.open_dbfile(get_table_file_name(name.as_str())) {
                 Ok(db_file) => {
-                    match HeapTupleFile::open(db_file) {
+                    let tuple_file = match db_file.file_info.file_type {
+                        DBFileType::BTreeTupleFile => BTreeTupleFile::open(db_file).map(TupleFile::BTree),
+                        _ => HeapTupleFile::open(db_file).map(TupleFile::Heap),
+                    };
+                    match tuple_file {
                         Ok(tuple_file) => {
+                            let mut indexes = Vec::new();
+                            if let TupleFile::Heap(ref heap_file) = tuple_file {
+                                for info in heap_file.indexes.iter() {
+                                    let index_file_name = get_index_file_name(name.as_str(), info.name.as_str());
+                                    let index_db_file = try!(file_manager.open_dbfile(index_file_name));
+                                    indexes.push((info.clone(), try!(BTreeTupleFile::open(index_db_file))));
+                                }
+                            }
+
                             let table = Table {
                                 name: name.clone().into(),
                                 tuple_file: Rc::new(RefCell::new(tuple_file)),
+                                indexes: Rc::new(RefCell::new(indexes)),
                             };
 
                             self.open_tables.borrow_mut().insert(name.clone(), table);
@@ -124,7 +338,7 @@ impl TableManager {
     pub fn get_table<S: Into<String>>(&self, file_manager: &FileManager, name: S) -> Result<Table, Error> {
         let name = name.into();
 
-        try!(self.insert_if_needed(file_manager, name.as_ref()));
+        try!(self.insert_if_needed(file_manager, name.as_str()));
 
         Ok(self.open_tables.borrow().get(&name).unwrap().clone())
     }
@@ -148,18 +362,40 @@ impl TableManager {
                                          table_name: S,
                                          schema: Schema)
                                          -> Result<(), Error> {
+        self.create_table_with_key(file_manager, table_name, schema, 0)
+    }
+
+    /// Creates a new table file like [`create_table`](#method.create_table), but if
+    /// `num_key_columns` is positive the table is stored in a B<sup>+</sup> tree tuple file,
+    /// ordered by that many leading columns of the schema.
+    pub fn create_table_with_key<S: Into<String>>(&self,
+                                                  file_manager: &FileManager,
+                                                  table_name: S,
+                                                  schema: Schema,
+                                                  num_key_columns: usize)
+                                                  -> Result<(), Error> {
         let table_name = table_name.into();
         let page_size = get_default_pagesize();
 
         let table_filename = get_table_file_name(table_name.clone());
+        let file_type = if num_key_columns > 0 {
+            DBFileType::BTreeTupleFile
+        } else {
+            DBFileType::HeapTupleFile
+        };
 
-        match file_manager.create_dbfile(table_filename, DBFileType::HeapTupleFile, page_size) {
+        match file_manager.create_dbfile(table_filename, file_type, page_size) {
             Ok(db_file) => {
-                let tuple_file = try!(HeapTupleFile::new(db_file, schema));
+                let tuple_file = if num_key_columns > 0 {
+                    TupleFile::BTree(try!(BTreeTupleFile::new(db_file, schema, num_key_columns)))
+                } else {
+                    TupleFile::Heap(try!(HeapTupleFile::new(db_file, schema)))
+                };
 
                 let table = Table {
                     name: table_name.clone().into(),
                     tuple_file: Rc::new(RefCell::new(tuple_file)),
+                    indexes: Rc::new(RefCell::new(Vec::new())),
                 };
 
                 self.open_tables.borrow_mut().insert(table_name, table);
@@ -169,6 +405,141 @@ impl TableManager {
             Err(e) => Err(e.into()),
         }
     }
+
+    /// Creates a new index on a table, adding an entry for every tuple already in the table. The
+    /// index is recorded in the table's header page, so it is opened again with the table.
+    ///
+    /// # Arguments
+    /// * file_manager - The file manager to create the index file with.
+    /// * table_name - The name of the table to index.
+    /// * index_name - The name of the new index.
+    /// * column_names - The names of the columns to index, in order.
+    /// * unique - Whether the index should only allow one tuple for each set of values.
+    ///
+    /// # Errors
+    /// This fails if the table is not a heap table, if the index already exists, if any of the
+    /// columns don't exist, or if `unique` is set but the table already has duplicate values.
+    pub fn create_index<S1: Into<String>, S2: Into<String>>(&self,
+                                                            file_manager: &FileManager,
+                                                            table_name: S1,
+                                                            index_name: S2,
+                                                            column_names: &[String],
+                                                            unique: bool)
+                                                            -> Result<(), Error> {
+        let table_name = table_name.into();
+        let index_name = index_name.into();
+        let table = try!(self.get_table(file_manager, table_name.as_str()));
+        let schema = table.get_schema();
+
+        if table.get_indexes().iter().any(|info| info.name == index_name) {
+            return Err(Error::IndexExists(index_name));
+        }
+        let mut column_indexes = Vec::new();
+        for column_name in column_names.iter() {
+            match schema.iter().position(|col| col.name.as_ref() == Some(column_name)) {
+                Some(index) => column_indexes.push(index),
+                None => return Err(Error::ColumnDoesNotExist(column_name.clone())),
+            }
+        }
+        let info = IndexInfo::new(index_name.as_str(), unique, column_indexes);
+
+        let index_file_name = get_index_file_name(table_name.as_str(), index_name.as_str());
+        let index_schema = try!(info.get_schema(&schema).map_err(file_manager::Error::from));
+        {
+            let mut tuple_file = table.tuple_file.borrow_mut();
+            match *tuple_file {
+                TupleFile::Heap(_) => {}
+                _ => return Err(Error::IndexesUnsupported(table_name)),
+            }
+
+            let db_file = try!(file_manager.create_dbfile(index_file_name.as_str(),
+                                                          DBFileType::BTreeTupleFile,
+                                                          get_default_pagesize()));
+            let mut index_file = try!(BTreeTupleFile::new(db_file, index_schema, info.get_num_key_columns()));
+
+            debug!("Adding the existing tuples of {} to index {}.", table_name, index_name);
+            let populated = populate_index(&info, &mut index_file, &mut tuple_file);
+            if let Err(e) = populated {
+                try!(file_manager.remove_dbfile(index_file_name.as_str()));
+                return Err(e);
+            }
+
+            if let TupleFile::Heap(ref mut heap_file) = *tuple_file {
+                heap_file.indexes.push(info.clone());
+                try!(heap_file.save_metadata());
+            }
+            table.indexes.borrow_mut().push((info, index_file));
+        }
+
+        Ok(())
+    }
+
+    /// Removes an index from a table, deleting its file and its entry in the table's header page.
+    ///
+    /// # Arguments
+    /// * file_manager - The file manager to delete the index file with.
+    /// * table_name - The name of the indexed table.
+    /// * index_name - The name of the index to remove.
+    pub fn drop_index<S1: Into<String>, S2: Into<String>>(&self,
+                                                          file_manager: &FileManager,
+                                                          table_name: S1,
+                                                          index_name: S2)
+                                                          -> Result<(), Error> {
+        let table_name = table_name.into();
+        let index_name = index_name.into();
+        let table = try!(self.get_table(file_manager, table_name.as_str()));
+
+        let position = table.indexes.borrow().iter().position(|&(ref info, _)| info.name == index_name);
+        match position {
+            Some(position) => {
+                table.indexes.borrow_mut().remove(position);
+            }
+            None => return Err(Error::IndexDoesNotExist(index_name)),
+        }
+
+        if let TupleFile::Heap(ref mut heap_file) = *table.tuple_file.borrow_mut() {
+            heap_file.indexes.retain(|info| info.name != index_name);
+            try!(heap_file.save_metadata());
+        }
+
+        try!(file_manager.remove_dbfile(get_index_file_name(table_name, index_name)));
+        Ok(())
+    }
+
+    /// Deletes a table's file and the files of all of its indexes, and forgets about the table.
+    ///
+    /// # Arguments
+    /// * file_manager - The file manager to delete the files with.
+    /// * table_name - The name of the table to delete.
+    pub fn drop_table<S: Into<String>>(&self, file_manager: &FileManager, table_name: S) -> Result<(), Error> {
+        let table_name = table_name.into();
+        let table = try!(self.get_table(file_manager, table_name.as_str()));
+        self.open_tables.borrow_mut().remove(&table_name);
+
+        for info in table.get_indexes() {
+            try!(file_manager.remove_dbfile(get_index_file_name(table_name.as_str(), info.name)));
+        }
+        try!(file_manager.remove_dbfile(get_table_file_name(table_name)));
+        Ok(())
+    }
+}
+
+/// Adds an entry to an index for every tuple of a table.
+fn populate_index(info: &IndexInfo, index_file: &mut BTreeTupleFile, tuple_file: &mut TupleFile) -> Result<(), Error> {
+    let mut cur_tuple = try!(tuple_file.get_first_tuple());
+    while let Some(mut tuple) = cur_tuple {
+        let values = TupleLiteral::from_tuple(&mut tuple);
+        if info.unique {
+            try!(check_unique(info, index_file, &values));
+        }
+        let pointer = tuple.get_external_reference().unwrap_or(Literal::Null);
+        let mut entry = try!(index_file.add_tuple(info.get_entry(&values, pointer)));
+        try!(entry.unpin());
+
+        try!(tuple.unpin());
+        cur_tuple = try!(tuple_file.get_next_tuple(&tuple));
+    }
+    Ok(())
 }
 
 #[cfg(test)]
@@ -177,7 +548,8 @@ mod tests {
 
     use super::*;
     use ::{ColumnInfo, ColumnType, Schema};
-    use ::storage::FileManager;
+    use ::expressions::Literal;
+    use ::storage::{FileManager, TupleLiteral};
 
     #[test]
     fn test_create_table() {
@@ -198,4 +570,131 @@ mod tests {
 
         assert_eq!(table.get_schema(), schema);
     }
+
+    #[test]
+    fn test_create_table_with_key() {
+        let dir = TempDir::new("test_dbfiles").expect("Unable to create test_dbfiles directory!");
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "foo"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "B", "foo"),
+        ])
+            .unwrap();
+
+        {
+            let table_manager = TableManager::new();
+            table_manager.create_table_with_key(&file_manager, "foo", schema.clone(), 1).unwrap();
+            let table = table_manager.get_table(&file_manager, "foo").unwrap();
+            for i in vec![3, 1, 2] {
+                table.add_tuple(TupleLiteral::from_iter(vec![Literal::Int(i), Literal::Int(-i)])).unwrap();
+            }
+        }
+
+        // A new table manager opens the file again, as a B+ tree tuple file.
+        let table_manager = TableManager::new();
+        let table = table_manager.get_table(&file_manager, "foo").unwrap();
+        assert_eq!(table.get_schema(), schema);
+
+        let mut values = Vec::new();
+        let mut cur = table.get_first_tuple().unwrap();
+        while let Some(mut tuple) = cur {
+            values.push(tuple.get_column_value(0).unwrap());
+            cur = table.get_next_tuple(&tuple).unwrap();
+        }
+        assert_eq!(vec![Literal::Int(1), Literal::Int(2), Literal::Int(3)], values);
+    }
+
+    #[test]
+    fn test_text_values_in_overflow_pages() {
+        let dir = TempDir::new("test_dbfiles").expect("Unable to create test_dbfiles directory!");
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let table_manager = TableManager::new();
+
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::Integer, "A"),
+            ColumnInfo::with_name(ColumnType::Text, "B"),
+        ])
+            .unwrap();
+        table_manager.create_table(&file_manager, "foo", schema).unwrap();
+        let table = table_manager.get_table(&file_manager, "foo").unwrap();
+
+        // The large value is longer than a page, while the small one stays in its tuple.
+        let large: String = (0..20000).map(|i| (b'a' + (i % 26) as u8) as char).collect();
+        let small = "short note".to_string();
+        for (i, value) in vec![large.clone(), small.clone()].into_iter().enumerate() {
+            let mut tuple = table.add_tuple(TupleLiteral::from_iter(vec![Literal::Int(i as i32),
+                                                                           Literal::String(value)]))
+                .unwrap();
+            tuple.unpin().unwrap();
+        }
+
+        let mut rows = Vec::new();
+        let mut cur = table.get_first_tuple().unwrap();
+        while let Some(mut tuple) = cur {
+            rows.push((tuple.get_external_reference().unwrap(), TupleLiteral::from_tuple(&mut tuple)));
+            cur = table.get_next_tuple(&tuple).unwrap();
+        }
+        assert_eq!(2, rows.len());
+        assert_eq!(Literal::String(large.clone()), rows[0].1[1]);
+        assert_eq!(Literal::String(small.clone()), rows[1].1[1]);
+
+        // Updating the small value to a large one moves it out of the tuple as well.
+        let updated = TupleLiteral::from_iter(vec![Literal::Int(1), Literal::String(large.clone())]);
+        assert!(table.update_tuple(&rows[1].0, &rows[1].1, &updated).unwrap());
+        let mut tuple = table.get_tuple(&rows[1].0).unwrap().unwrap();
+        assert_eq!(Literal::String(large.clone()), tuple.get_column_value(1).unwrap());
+
+        // Deleting the tuples frees their overflow pages, which are reused for the next value.
+        assert!(table.delete_tuple(&rows[0].0, &rows[0].1).unwrap());
+        assert!(table.delete_tuple(&rows[1].0, &updated).unwrap());
+        assert_eq!(None, table.get_first_tuple().unwrap().map(|_| ()));
+        let mut tuple = table.add_tuple(TupleLiteral::from_iter(vec![Literal::Int(2), Literal::String(large.clone())]))
+            .unwrap();
+        assert_eq!(Literal::String(large), tuple.get_column_value(1).unwrap());
+        // Page 1 holds the tuples, page 2 the free-space map and pages 3-5 the value, so the tuple
+        // goes back to the now empty data page 1 instead of a new page.
+        match tuple.get_external_reference() {
+            Some(Literal::FilePointer { page_no, .. }) => assert_eq!(1, page_no),
+            other => panic!("Unexpected external reference {:?}", other),
+        }
+    }
+
+    #[test]
+    fn test_varchar_and_blob_values_in_overflow_pages() {
+        let dir = TempDir::new("test_dbfiles").expect("Unable to create test_dbfiles directory!");
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let table_manager = TableManager::new();
+
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::VarChar { length: 10000 }, "A"),
+            ColumnInfo::with_name(ColumnType::VarChar { length: 10000 }, "B"),
+            ColumnInfo::with_name(ColumnType::Blob, "C"),
+        ])
+            .unwrap();
+        table_manager.create_table(&file_manager, "foo", schema).unwrap();
+        let table = table_manager.get_table(&file_manager, "foo").unwrap();
+
+        // Neither VARCHAR value fits in a page alongside the other, so the larger one is moved
+        // out, while BLOB bytes are stored as they are, even if they aren't valid UTF-8.
+        let long = "x".repeat(9000);
+        let shorter = "y".repeat(3000);
+        let bytes: Vec<u8> = (0..20000).map(|i| (i % 256) as u8).collect();
+        let values = TupleLiteral::from_iter(vec![Literal::String(shorter.clone()), Literal::String(long.clone()),
+                                                  Literal::Bytes(bytes.clone())]);
+        let mut tuple = table.add_tuple(values.clone()).unwrap();
+        tuple.unpin().unwrap();
+
+        let mut tuple = table.get_first_tuple().unwrap().unwrap();
+        assert_eq!(values, TupleLiteral::from_tuple(&mut tuple));
+        let pointer = tuple.get_external_reference().unwrap();
+        tuple.unpin().unwrap();
+
+        // Updated values are moved out of the tuple the same way.
+        let updated = TupleLiteral::from_iter(vec![Literal::String(long.clone()), Literal::String(long.clone()),
+                                                   Literal::Bytes(vec![0xFF, 0xFE, 0x00])]);
+        assert!(table.update_tuple(&pointer, &values, &updated).unwrap());
+        let mut tuple = table.get_tuple(&pointer).unwrap().unwrap();
+        assert_eq!(updated, TupleLiteral::from_tuple(&mut tuple));
+    }
 }
@@ -0,0 +1,1250 @@
+//! This module contains utilities and classes for tuple files with a backing structure based on a
+//! B<sup>+</sup> tree, which keeps the tuples ordered by a prefix of the schema's columns (the
+//! *key*).
+//!
+//! Page 0 is the header page. Besides the file type and page size, it stores the page number of
+//! the root of the tree, the head of the list of free pages, the number of key columns, the schema
+//! and then the statistics collected by `ANALYZE`. Every other page starts with a byte giving its type:
+//!
+//! * **Leaf pages** store the page number of the next leaf and the number of entries, followed by
+//! the tuples themselves, packed together in key order.
+//! * **Inner pages** store the number of child pointers, followed by the pointers interleaved with
+//! the keys that separate them: `p0, k0, p1, k1, ..., pn`. Every tuple under `p(i)` has a key
+//! that is no greater than `k(i)`, and every tuple under `p(i + 1)` has a key no less than it.
+//! * **Free pages** store the page number of the next free page.
+//!
+//! The last bytes of every page hold the page's LSN, so nodes never use them.
+//!
+//! Page numbers are stored as unsigned shorts, so that tuples can be referenced with file
+//! pointers.
+//!
+//! Unlike heap files, B<sup>+</sup> tree files have no overflow pages, so every value of a tuple is
+//! stored in its node. A tuple can take up at most about a third of a page (see
+//! `get_max_tuple_size`), and adding a larger tuple fails with `TupleTooBigForBTree`.
+
+use std::cmp::{self, Ordering};
+use std::fs::File;
+use std::u16;
+use std::io::{Seek, SeekFrom};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::Schema;
+use ::expressions::Literal;
+use ::relations::{ColumnStatsCollector, TableStats};
+use ::storage::{DBFile, DBPage, PinError, Pinnable, Tuple, TupleError, TupleLiteral};
+use ::storage::file_manager;
+use ::storage::page_tuple::{PageTuple, get_tuple_storage_size, read_tuple};
+use ::storage::storage_manager::{load_dbpage, save_dbpage};
+use ::transactions::PAGE_LSN_SIZE;
+
+/// The offset in the header page where the page number of the root of the tree is stored. This
+/// value is an unsigned short, and is 0 when the tree is empty.
+pub const OFFSET_ROOT_PAGE: usize = 6;
+
+/// The offset in the header page where the page number of the first free page is stored. This
+/// value is an unsigned short, and is 0 when there are no free pages.
+pub const OFFSET_FIRST_EMPTY_PAGE: usize = 8;
+
+/// The offset in the header page where the number of key columns is stored. This value is an
+/// unsigned byte.
+pub const OFFSET_NUM_KEY_COLUMNS: usize = 10;
+
+/// The offset in the header page where the table schema starts.
+pub const OFFSET_SCHEMA_START: usize = 11;
+
+/// The page type of a page on the free list.
+const PAGE_TYPE_FREE: u8 = 0;
+/// The page type of an inner page of the tree.
+const PAGE_TYPE_INNER: u8 = 1;
+/// The page type of a leaf page of the tree.
+const PAGE_TYPE_LEAF: u8 = 2;
+
+/// The offset in a free page where the next free page number is stored.
+const OFFSET_NEXT_FREE_PAGE: usize = 1;
+/// The offset in a leaf page where the next leaf page number is stored.
+const OFFSET_NEXT_LEAF_PAGE: usize = 1;
+/// The offset in a leaf page where the number of entries is stored.
+const OFFSET_NUM_ENTRIES: usize = 3;
+/// The offset in a leaf page where the entries start.
+const OFFSET_LEAF_ENTRIES: usize = 5;
+/// The offset in an inner page where the number of pointers is stored.
+const OFFSET_NUM_POINTERS: usize = 1;
+/// The offset in an inner page where the pointers and keys start.
+const OFFSET_INNER_POINTERS: usize = 3;
+
+/// An in-memory copy of a leaf page.
+#[derive(Debug)]
+struct LeafNode {
+    page_no: u16,
+    next_leaf: u16,
+    entries: Vec<TupleLiteral>,
+}
+
+/// An in-memory copy of an inner page.
+#[derive(Debug)]
+struct InnerNode {
+    page_no: u16,
+    pointers: Vec<u16>,
+    keys: Vec<TupleLiteral>,
+}
+
+#[derive(Debug)]
+enum Node {
+    Leaf(LeafNode),
+    Inner(InnerNode),
+}
+
+/// A page tuple stored in a B<sup>+</sup> tree leaf, so it has an associated entry index.
+pub struct BTreeFilePageTuple {
+    page_tuple: PageTuple,
+    /// The index of the entry in the leaf page that holds the tuple.
+    pub entry: u16,
+}
+
+impl ::std::ops::Deref for BTreeFilePageTuple {
+    type Target = PageTuple;
+
+    fn deref(&self) -> &Self::Target {
+        &self.page_tuple
+    }
+}
+
+impl ::std::hash::Hash for BTreeFilePageTuple {
+    fn hash<H: ::std::hash::Hasher>(&self, state: &mut H) {
+        self.page_tuple.db_page.page_no.hash(state);
+        self.entry.hash(state);
+    }
+}
+
+impl Pinnable for BTreeFilePageTuple {
+    fn pin(&mut self) {
+        self.page_tuple.pin()
+    }
+
+    fn unpin(&mut self) -> Result<(), PinError> {
+        self.page_tuple.unpin()
+    }
+
+    fn get_pin_count(&self) -> u32 {
+        self.page_tuple.get_pin_count()
+    }
+}
+
+impl Tuple for BTreeFilePageTuple {
+    fn is_disk_backed(&self) -> bool {
+        self.page_tuple.is_disk_backed()
+    }
+
+    fn is_null_value(&self, col_index: usize) -> Result<bool, TupleError> {
+        self.page_tuple.is_null_value(col_index)
+    }
+
+    fn get_column_count(&self) -> usize {
+        self.page_tuple.get_column_count()
+    }
+
+    fn get_column_value(&mut self, col_index: usize) -> Result<Literal, TupleError> {
+        self.page_tuple.get_column_value(col_index)
+    }
+
+    fn get_external_reference(&self) -> Option<Literal> {
+        Some(Literal::FilePointer {
+            page_no: self.page_tuple.db_page.page_no as u16,
+            offset: self.page_tuple.get_page_offset(),
+        })
+    }
+}
+
+/// This class implements tuple file processing for B<sup>+</sup> tree files, which store tuples in
+/// the order of their key columns.
+#[derive(Debug, PartialEq)]
+pub struct BTreeTupleFile {
+    db_file: DBFile<File>,
+    /// The schema of tuples in this tuple file.
+    pub schema: Schema,
+    /// The number of leading columns of the schema that make up the key the tuples are ordered by.
+    pub num_key_columns: usize,
+    key_schema: Schema,
+    /// The statistics collected by the last `ANALYZE` of the file, which are stored in the header
+    /// page after the schema.
+    pub stats: TableStats,
+}
+
+impl BTreeTupleFile {
+    /// Instantiates a new B<sup>+</sup> tree tuple file with a given `DBFile`, schema and number of
+    /// key columns. *Note: this may fail.*
+    ///
+    /// # Arguments
+    /// * db_file - The backing `DBFile`.
+    /// * schema - The schema that the file is based on.
+    /// * num_key_columns - The number of leading columns of the schema to order the tuples by.
+    pub fn new(db_file: DBFile<File>,
+               schema: Schema,
+               num_key_columns: usize)
+               -> Result<BTreeTupleFile, file_manager::Error> {
+        let num_key_columns = cmp::min(num_key_columns, schema.num_columns());
+        let key_schema = try!(Schema::with_columns(schema.iter().take(num_key_columns).cloned()));
+        let stats = TableStats::new(schema.num_columns());
+        let mut result = BTreeTupleFile {
+            db_file: db_file,
+            schema: schema,
+            num_key_columns: num_key_columns,
+            key_schema: key_schema,
+            stats: stats,
+        };
+        try!(result.save_metadata());
+        Ok(result)
+    }
+
+    /// Creates a new B<sup>+</sup> tree tuple file by reading a given `DBFile` and parsing the
+    /// number of key columns and the schema.
+    ///
+    /// # Arguments
+    /// * db_file - The backing `DBFile`.
+    pub fn open(mut db_file: DBFile<File>) -> Result<BTreeTupleFile, file_manager::Error> {
+        let mut header_page = try!(load_dbpage(&mut db_file, 0, false));
+
+        try!(header_page.seek(SeekFrom::Start(OFFSET_NUM_KEY_COLUMNS as u64)));
+        let num_key_columns = try!(header_page.read_u8()) as usize;
+        let schema = try!(Schema::from_header_page_at(&mut header_page, OFFSET_SCHEMA_START));
+        let key_schema = try!(Schema::with_columns(schema.iter().take(num_key_columns).cloned()));
+        let stats = try!(TableStats::read(&mut header_page, schema.num_columns()));
+
+        Ok(BTreeTupleFile {
+            db_file: db_file,
+            schema: schema,
+            num_key_columns: num_key_columns,
+            key_schema: key_schema,
+            stats: stats,
+        })
+    }
+
+    /// Writes the metadata of the tuple file to disk.
+    ///
+    /// This handles writing of the number of key columns, the schema and the statistics to the
+    /// header page, which is saved through the buffer manager. The root and free-list pointers are maintained
+    /// separately as the tree changes.
+    pub fn save_metadata(&mut self) -> Result<(), file_manager::Error> {
+        let mut header_page = try!(load_dbpage(&mut self.db_file, 0, false));
+
+        try!(header_page.seek(SeekFrom::Start(OFFSET_NUM_KEY_COLUMNS as u64)));
+        try!(header_page.write_u8(self.num_key_columns as u8));
+        try!(self.schema.write_at(&mut header_page, OFFSET_SCHEMA_START));
+        try!(self.stats.write(&mut header_page));
+
+        try!(save_dbpage(&mut self.db_file, &mut header_page));
+
+        try!(header_page.unpin());
+
+        Ok(())
+    }
+
+    /// Returns the largest tuple that can be stored in the file. This guarantees that a page which
+    /// overflows by one tuple can always be split into two pages that fit, and that an inner page
+    /// can hold at least three keys.
+    fn get_max_tuple_size(&self) -> u16 {
+        ((self.get_usable_size() - OFFSET_LEAF_ENTRIES as u32 - 6) / 3) as u16
+    }
+
+    /// Returns the number of bytes of a page that a node can use.
+    fn get_usable_size(&self) -> u32 {
+        self.db_file.get_page_size() - PAGE_LSN_SIZE as u32
+    }
+
+    fn read_header_value(&mut self, offset: usize) -> Result<u16, TupleError> {
+        let mut header_page = try!(load_dbpage(&mut self.db_file, 0, false));
+        try!(header_page.seek(SeekFrom::Start(offset as u64)));
+        let value = try!(header_page.read_u16::<BigEndian>());
+        try!(header_page.unpin());
+        Ok(value)
+    }
+
+    fn write_header_value(&mut self, offset: usize, value: u16) -> Result<(), TupleError> {
+        let mut header_page = try!(load_dbpage(&mut self.db_file, 0, false));
+        try!(header_page.seek(SeekFrom::Start(offset as u64)));
+        try!(header_page.write_u16::<BigEndian>(value));
+        try!(save_dbpage(&mut self.db_file, &mut header_page));
+        try!(header_page.unpin());
+        Ok(())
+    }
+
+    /// Returns the page number of the root of the tree, or 0 if the tree is empty.
+    fn get_root_page(&mut self) -> Result<u16, TupleError> {
+        self.read_header_value(OFFSET_ROOT_PAGE)
+    }
+
+    fn set_root_page(&mut self, page_no: u16) -> Result<(), TupleError> {
+        debug!("Setting root of B+ tree to page {}.", page_no);
+        self.write_header_value(OFFSET_ROOT_PAGE, page_no)
+    }
+
+    /// Returns a page number that can be used for a new node, taking it from the free list if
+    /// possible and extending the file otherwise.
+    fn allocate_page(&mut self) -> Result<u16, TupleError> {
+        let first_free = try!(self.read_header_value(OFFSET_FIRST_EMPTY_PAGE));
+        if first_free != 0 {
+            let mut page = try!(load_dbpage(&mut self.db_file, first_free as u32, false));
+            try!(page.seek(SeekFrom::Start(OFFSET_NEXT_FREE_PAGE as u64)));
+            let next_free = try!(page.read_u16::<BigEndian>());
+            try!(page.unpin());
+            try!(self.write_header_value(OFFSET_FIRST_EMPTY_PAGE, next_free));
+            debug!("Reusing free page {} for B+ tree node.", first_free);
+            return Ok(first_free);
+        }
+
+        let file_length = try!(self.db_file.get_contents().metadata()).len();
+        let page_no = (file_length / self.db_file.get_page_size() as u64) as u32;
+        // Page numbers are stored in two bytes, so a page past that can't be referenced.
+        if page_no > u16::MAX as u32 {
+            return Err(TupleError::FileTooBig(page_no));
+        }
+        debug!("Creating new page {} for B+ tree node.", page_no);
+        // Loading the page with the create flag extends the file, so the page can't be handed out
+        // twice.
+        let mut page = try!(load_dbpage(&mut self.db_file, page_no, true));
+        try!(page.unpin());
+        Ok(page_no as u16)
+    }
+
+    /// Adds a page that is no longer used by the tree to the free list.
+    fn free_page(&mut self, page_no: u16) -> Result<(), TupleError> {
+        debug!("Freeing B+ tree page {}.", page_no);
+        let first_free = try!(self.read_header_value(OFFSET_FIRST_EMPTY_PAGE));
+
+        let mut page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        page.clear();
+        try!(page.seek(SeekFrom::Start(0)));
+        try!(page.write_u8(PAGE_TYPE_FREE));
+        try!(page.write_u16::<BigEndian>(first_free));
+        try!(save_dbpage(&mut self.db_file, &mut page));
+        try!(page.unpin());
+
+        self.write_header_value(OFFSET_FIRST_EMPTY_PAGE, page_no)
+    }
+
+    fn load_node(&mut self, page_no: u16) -> Result<Node, TupleError> {
+        let mut page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        try!(page.seek(SeekFrom::Start(0)));
+        let node = match try!(page.read_u8()) {
+            PAGE_TYPE_LEAF => {
+                try!(page.seek(SeekFrom::Start(OFFSET_NEXT_LEAF_PAGE as u64)));
+                let next_leaf = try!(page.read_u16::<BigEndian>());
+                let num_entries = try!(page.read_u16::<BigEndian>());
+                let mut entries = Vec::with_capacity(num_entries as usize);
+                let mut offset = OFFSET_LEAF_ENTRIES as u16;
+                for _ in 0..num_entries {
+                    let (tuple, size) = try!(read_tuple(&mut page, offset, &self.schema));
+                    entries.push(tuple);
+                    offset += size;
+                }
+                Node::Leaf(LeafNode {
+                    page_no: page_no,
+                    next_leaf: next_leaf,
+                    entries: entries,
+                })
+            }
+            PAGE_TYPE_INNER => {
+                try!(page.seek(SeekFrom::Start(OFFSET_NUM_POINTERS as u64)));
+                let num_pointers = try!(page.read_u16::<BigEndian>());
+                let mut pointers = Vec::with_capacity(num_pointers as usize);
+                let mut keys = Vec::with_capacity(num_pointers as usize);
+                let mut offset = OFFSET_INNER_POINTERS as u16;
+                for i in 0..num_pointers {
+                    try!(page.seek(SeekFrom::Start(offset as u64)));
+                    pointers.push(try!(page.read_u16::<BigEndian>()));
+                    offset += 2;
+                    if i + 1 < num_pointers {
+                        let (key, size) = try!(read_tuple(&mut page, offset, &self.key_schema));
+                        keys.push(key);
+                        offset += size;
+                    }
+                }
+                Node::Inner(InnerNode {
+                    page_no: page_no,
+                    pointers: pointers,
+                    keys: keys,
+                })
+            }
+            page_type => return Err(TupleError::InvalidPageType(page_no as u32, page_type)),
+        };
+        try!(page.unpin());
+        Ok(node)
+    }
+
+    fn load_leaf(&mut self, page_no: u16) -> Result<LeafNode, TupleError> {
+        match try!(self.load_node(page_no)) {
+            Node::Leaf(leaf) => Ok(leaf),
+            Node::Inner(_) => Err(TupleError::InvalidPageType(page_no as u32, PAGE_TYPE_INNER)),
+        }
+    }
+
+    fn load_inner(&mut self, page_no: u16) -> Result<InnerNode, TupleError> {
+        match try!(self.load_node(page_no)) {
+            Node::Inner(inner) => Ok(inner),
+            Node::Leaf(_) => Err(TupleError::InvalidPageType(page_no as u32, PAGE_TYPE_LEAF)),
+        }
+    }
+
+    fn write_leaf(&mut self, leaf: &LeafNode) -> Result<(), TupleError> {
+        let mut page = try!(load_dbpage(&mut self.db_file, leaf.page_no as u32, true));
+        page.clear();
+        try!(page.seek(SeekFrom::Start(0)));
+        try!(page.write_u8(PAGE_TYPE_LEAF));
+        try!(page.write_u16::<BigEndian>(leaf.next_leaf));
+        try!(page.write_u16::<BigEndian>(leaf.entries.len() as u16));
+
+        let mut offset = OFFSET_LEAF_ENTRIES as u16;
+        for entry in leaf.entries.iter() {
+            let size = try!(get_tuple_storage_size(self.schema.clone(), &mut entry.clone()));
+            try!(page.store_new_tuple(offset, self.schema.clone(), entry.clone()));
+            offset += size;
+        }
+
+        try!(save_dbpage(&mut self.db_file, &mut page));
+        try!(page.unpin());
+        Ok(())
+    }
+
+    fn write_inner(&mut self, inner: &InnerNode) -> Result<(), TupleError> {
+        let mut page = try!(load_dbpage(&mut self.db_file, inner.page_no as u32, true));
+        page.clear();
+        try!(page.seek(SeekFrom::Start(0)));
+        try!(page.write_u8(PAGE_TYPE_INNER));
+        try!(page.seek(SeekFrom::Start(OFFSET_NUM_POINTERS as u64)));
+        try!(page.write_u16::<BigEndian>(inner.pointers.len() as u16));
+
+        let mut offset = OFFSET_INNER_POINTERS as u16;
+        for (i, pointer) in inner.pointers.iter().enumerate() {
+            try!(page.seek(SeekFrom::Start(offset as u64)));
+            try!(page.write_u16::<BigEndian>(*pointer));
+            offset += 2;
+            if let Some(key) = inner.keys.get(i) {
+                let size = try!(get_tuple_storage_size(self.key_schema.clone(), &mut key.clone()));
+                try!(page.store_new_tuple(offset, self.key_schema.clone(), key.clone()));
+                offset += size;
+            }
+        }
+
+        try!(save_dbpage(&mut self.db_file, &mut page));
+        try!(page.unpin());
+        Ok(())
+    }
+
+    /// Returns the sizes of the given tuples when stored with the given schema.
+    fn get_sizes(schema: &Schema, tuples: &[TupleLiteral]) -> Result<Vec<u16>, TupleError> {
+        let mut sizes = Vec::with_capacity(tuples.len());
+        for tuple in tuples.iter() {
+            sizes.push(try!(get_tuple_storage_size(schema.clone(), &mut tuple.clone())));
+        }
+        Ok(sizes)
+    }
+
+    fn get_leaf_size(&self, entries: &[TupleLiteral]) -> Result<u32, TupleError> {
+        let sizes = try!(BTreeTupleFile::get_sizes(&self.schema, entries));
+        Ok(OFFSET_LEAF_ENTRIES as u32 + sizes.iter().fold(0, |acc, size| acc + *size as u32))
+    }
+
+    fn get_inner_size(&self, pointers: &[u16], keys: &[TupleLiteral]) -> Result<u32, TupleError> {
+        let sizes = try!(BTreeTupleFile::get_sizes(&self.key_schema, keys));
+        Ok(OFFSET_INNER_POINTERS as u32 + 2 * pointers.len() as u32 +
+           sizes.iter().fold(0, |acc, size| acc + *size as u32))
+    }
+
+    /// Returns the index at which to split the given values so that the two halves take up
+    /// roughly the same number of bytes, keeping at least `min_each` values on either side.
+    fn get_split_index(sizes: &[u16], min_each: usize) -> usize {
+        let total = sizes.iter().fold(0, |acc, size| acc + *size as u32);
+        let mut so_far = 0;
+        let mut index = 0;
+        while index < sizes.len() && so_far + (sizes[index] as u32) / 2 < total / 2 {
+            so_far += sizes[index] as u32;
+            index += 1;
+        }
+        cmp::max(min_each, cmp::min(index, sizes.len() - min_each))
+    }
+
+    /// Returns the key of the given tuple, i.e. its first `num_key_columns` values.
+    fn get_key(&self, tuple: &TupleLiteral) -> TupleLiteral {
+        TupleLiteral::from_iter(tuple.iter().take(self.num_key_columns).cloned())
+    }
+
+    /// Compares two tuples by their keys. If either tuple has fewer values than the key, only that
+    /// many values are compared, so a partial key acts as a prefix search.
+    fn compare_keys(&self, tuple1: &TupleLiteral, tuple2: &TupleLiteral) -> Ordering {
+        let num_values = cmp::min(self.num_key_columns, cmp::min(tuple1.len(), tuple2.len()));
+        for i in 0..num_values {
+            match tuple1[i].compare(&tuple2[i]) {
+                Ordering::Equal => continue,
+                ordering => return ordering,
+            }
+        }
+        Ordering::Equal
+    }
+
+    /// Returns the index of the child to follow in an inner node when searching for the given key.
+    /// If `inclusive` is true, this is the last child whose tuples may equal the key; otherwise it
+    /// is the first such child.
+    fn get_child_index(&self, inner: &InnerNode, key: &TupleLiteral, inclusive: bool) -> usize {
+        inner.keys
+            .iter()
+            .take_while(|separator| match self.compare_keys(separator, key) {
+                Ordering::Less => true,
+                Ordering::Equal => inclusive,
+                Ordering::Greater => false,
+            })
+            .count()
+    }
+
+    /// Descends from the root to the leaf for the given key, returning the path of inner nodes as
+    /// (page number, child index) pairs along with the leaf.
+    fn find_leaf(&mut self,
+                 root: u16,
+                 key: &TupleLiteral,
+                 inclusive: bool)
+                 -> Result<(Vec<(u16, usize)>, LeafNode), TupleError> {
+        let mut path = Vec::new();
+        let mut page_no = root;
+        loop {
+            match try!(self.load_node(page_no)) {
+                Node::Leaf(leaf) => return Ok((path, leaf)),
+                Node::Inner(inner) => {
+                    let index = self.get_child_index(&inner, key, inclusive);
+                    path.push((page_no, index));
+                    page_no = inner.pointers[index];
+                }
+            }
+        }
+    }
+
+    /// Returns the tuple stored at the given entry of the given leaf page.
+    fn get_tuple_at(&mut self, page_no: u16, entry: u16) -> Result<BTreeFilePageTuple, TupleError> {
+        let mut page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        let mut offset = OFFSET_LEAF_ENTRIES as u16;
+        for _ in 0..entry {
+            let (_, size) = try!(read_tuple(&mut page, offset, &self.schema));
+            offset += size;
+        }
+
+        let mut tuple = try!(PageTuple::new(page, offset, self.schema.clone()));
+        tuple.pin();
+        Ok(BTreeFilePageTuple {
+            page_tuple: tuple,
+            entry: entry,
+        })
+    }
+
+    /// Returns the first tuple at or after the given entry of the given leaf, following the chain
+    /// of leaves, that satisfies the given condition.
+    fn scan_leaves<F>(&mut self,
+                      mut leaf: LeafNode,
+                      mut entry: usize,
+                      condition: F)
+                      -> Result<Option<BTreeFilePageTuple>, TupleError>
+        where F: Fn(&BTreeTupleFile, &TupleLiteral) -> bool
+    {
+        loop {
+            while entry < leaf.entries.len() {
+                if condition(self, &leaf.entries[entry]) {
+                    return self.get_tuple_at(leaf.page_no, entry as u16).map(Some);
+                }
+                entry += 1;
+            }
+            if leaf.next_leaf == 0 {
+                return Ok(None);
+            }
+            leaf = try!(self.load_leaf(leaf.next_leaf));
+            entry = 0;
+        }
+    }
+
+    /// Adds the specified tuple into the table file, returning a new object corresponding to the
+    /// actual tuple added to the table. The tuple is placed after any tuples with an equal key.
+    ///
+    /// # Arguments
+    /// * tuple - a tuple object containing the values to add to the table
+    pub fn add_tuple<'a, T: Tuple + 'a>(&mut self, mut tuple: T) -> Result<Box<Tuple + 'a>, TupleError> {
+        let tuple = TupleLiteral::from_tuple(&mut tuple);
+        let tuple_size = try!(get_tuple_storage_size(self.schema.clone(), &mut tuple.clone()));
+        debug!("Adding new tuple of size {} bytes.", tuple_size);
+
+        if tuple_size > self.get_max_tuple_size() {
+            return Err(TupleError::TupleTooBigForBTree(tuple_size, self.get_max_tuple_size()));
+        }
+
+        let root = try!(self.get_root_page());
+        if root == 0 {
+            let page_no = try!(self.allocate_page());
+            try!(self.write_leaf(&LeafNode {
+                page_no: page_no,
+                next_leaf: 0,
+                entries: vec![tuple],
+            }));
+            try!(self.set_root_page(page_no));
+            return Ok(Box::new(try!(self.get_tuple_at(page_no, 0))));
+        }
+
+        let (path, mut leaf) = try!(self.find_leaf(root, &tuple, true));
+        let position = leaf.entries
+            .iter()
+            .take_while(|entry| self.compare_keys(entry, &tuple) != Ordering::Greater)
+            .count();
+        leaf.entries.insert(position, tuple);
+
+        if try!(self.get_leaf_size(&leaf.entries)) <= self.get_usable_size() {
+            try!(self.write_leaf(&leaf));
+            return Ok(Box::new(try!(self.get_tuple_at(leaf.page_no, position as u16))));
+        }
+
+        // The leaf overflowed, so split it in two and add the new leaf to the parent.
+        let sizes = try!(BTreeTupleFile::get_sizes(&self.schema, &leaf.entries));
+        let split_index = BTreeTupleFile::get_split_index(&sizes, 1);
+        let right = LeafNode {
+            page_no: try!(self.allocate_page()),
+            next_leaf: leaf.next_leaf,
+            entries: leaf.entries.split_off(split_index),
+        };
+        leaf.next_leaf = right.page_no;
+        debug!("Splitting leaf page {} into pages {} and {}.", leaf.page_no, leaf.page_no, right.page_no);
+
+        try!(self.write_leaf(&leaf));
+        try!(self.write_leaf(&right));
+        let separator = self.get_key(&right.entries[0]);
+        try!(self.insert_into_parent(path, leaf.page_no, separator, right.page_no));
+
+        let result = if position < split_index {
+            try!(self.get_tuple_at(leaf.page_no, position as u16))
+        } else {
+            try!(self.get_tuple_at(right.page_no, (position - split_index) as u16))
+        };
+        Ok(Box::new(result))
+    }
+
+    /// Adds a separator key and the page to its right to the parent of a node that was split,
+    /// splitting the parent in turn if it overflows.
+    fn insert_into_parent(&mut self,
+                          mut path: Vec<(u16, usize)>,
+                          left: u16,
+                          separator: TupleLiteral,
+                          right: u16)
+                          -> Result<(), TupleError> {
+        let (parent_no, index) = match path.pop() {
+            Some(entry) => entry,
+            None => {
+                // The root was split, so the tree grows by one level.
+                let root = InnerNode {
+                    page_no: try!(self.allocate_page()),
+                    pointers: vec![left, right],
+                    keys: vec![separator],
+                };
+                try!(self.write_inner(&root));
+                return self.set_root_page(root.page_no);
+            }
+        };
+
+        let mut parent = try!(self.load_inner(parent_no));
+        parent.keys.insert(index, separator);
+        parent.pointers.insert(index + 1, right);
+        self.write_or_split_inner(path, parent)
+    }
+
+    /// Writes an inner node whose keys have changed, splitting it and adding the new node to its
+    /// parent if it no longer fits in a page. `path` is the path to the node's parent.
+    fn write_or_split_inner(&mut self, path: Vec<(u16, usize)>, mut node: InnerNode) -> Result<(), TupleError> {
+        if try!(self.get_inner_size(&node.pointers, &node.keys)) <= self.get_usable_size() {
+            return self.write_inner(&node);
+        }
+
+        // The middle key moves up into the parent, rather than being copied like in a leaf, so the
+        // right node needs at least two keys to be left with one.
+        let sizes = try!(BTreeTupleFile::get_sizes(&self.key_schema, &node.keys));
+        let split_index = cmp::min(BTreeTupleFile::get_split_index(&sizes, 1), sizes.len() - 2);
+        let mut right_keys = node.keys.split_off(split_index);
+        let up_key = right_keys.remove(0);
+        let new_node = InnerNode {
+            page_no: try!(self.allocate_page()),
+            pointers: node.pointers.split_off(split_index + 1),
+            keys: right_keys,
+        };
+        debug!("Splitting inner page {} into pages {} and {}.", node.page_no, node.page_no, new_node.page_no);
+
+        try!(self.write_inner(&node));
+        try!(self.write_inner(&new_node));
+        self.insert_into_parent(path, node.page_no, up_key, new_node.page_no)
+    }
+
+    /// Returns the first tuple in this table file, i.e. the one with the smallest key, or `None` if
+    /// there are no tuples in the file.
+    pub fn get_first_tuple(&mut self) -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        let mut page_no = try!(self.get_root_page());
+        if page_no == 0 {
+            return Ok(None);
+        }
+
+        loop {
+            match try!(self.load_node(page_no)) {
+                Node::Inner(inner) => page_no = inner.pointers[0],
+                Node::Leaf(leaf) => return self.scan_leaves(leaf, 0, |_, _| true),
+            }
+        }
+    }
+
+    /// Walks the leaves of the tree to collect the statistics of the tuples in the file, then
+    /// stores them in the header page. Only leaf pages are counted as data pages.
+    pub fn analyze(&mut self) -> Result<TableStats, TupleError> {
+        let num_columns = self.schema.num_columns();
+        let mut collectors: Vec<ColumnStatsCollector> = (0..num_columns).map(|_| ColumnStatsCollector::new()).collect();
+        let mut num_data_pages = 0;
+        let mut num_tuples = 0;
+        let mut total_size = 0u64;
+
+        let mut page_no = try!(self.get_root_page());
+        while page_no != 0 {
+            match try!(self.load_node(page_no)) {
+                Node::Inner(inner) => page_no = inner.pointers[0],
+                Node::Leaf(leaf) => {
+                    num_data_pages += 1;
+                    for size in try!(BTreeTupleFile::get_sizes(&self.schema, &leaf.entries)) {
+                        total_size += size as u64;
+                    }
+                    for entry in leaf.entries.iter() {
+                        for (i, value) in entry.iter().enumerate() {
+                            collectors[i].add_value(value.clone());
+                        }
+                        num_tuples += 1;
+                    }
+                    page_no = leaf.next_leaf;
+                }
+            }
+        }
+
+        self.stats = TableStats {
+            num_data_pages: num_data_pages,
+            num_tuples: num_tuples,
+            avg_tuple_size: if num_tuples > 0 { total_size as f32 / num_tuples as f32 } else { 0.0 },
+            column_stats: collectors.iter().map(|c| c.get_column_stats()).collect(),
+        };
+        try!(self.save_metadata());
+        Ok(self.stats.clone())
+    }
+
+    /// Returns the tuple that follows the specified tuple in key order, or `None` if there are no
+    /// more tuples in the file.
+    pub fn get_next_tuple(&mut self,
+                          cur_tuple: &BTreeFilePageTuple)
+                          -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        let prev_page_no = cur_tuple.db_page.page_no;
+        let next_entry = cur_tuple.entry + 1;
+
+        let mut db_page: DBPage = try!(load_dbpage(&mut self.db_file, prev_page_no, false));
+        try!(db_page.seek(SeekFrom::Start(OFFSET_NUM_ENTRIES as u64)));
+        let num_entries = try!(db_page.read_u16::<BigEndian>());
+
+        if next_entry < num_entries {
+            // The next tuple directly follows the current one in the page.
+            let (_, size) = try!(read_tuple(&mut db_page, cur_tuple.get_page_offset(), &self.schema));
+            let mut tuple = try!(PageTuple::new(db_page, cur_tuple.get_page_offset() + size,
+                                                self.schema.clone()));
+            tuple.pin();
+            return Ok(Some(BTreeFilePageTuple {
+                page_tuple: tuple,
+                entry: next_entry,
+            }));
+        }
+
+        try!(db_page.seek(SeekFrom::Start(OFFSET_NEXT_LEAF_PAGE as u64)));
+        let next_leaf = try!(db_page.read_u16::<BigEndian>());
+        try!(db_page.unpin());
+
+        if next_leaf == 0 {
+            return Ok(None);
+        }
+        let leaf = try!(self.load_leaf(next_leaf));
+        self.scan_leaves(leaf, 0, |_, _| true)
+    }
+
+    /// Returns the tuple that the given file pointer refers to, as returned by the tuple's
+    /// `get_external_reference` method, or `None` if there is no longer a tuple at that position.
+    /// Note that tuples move when pages are split or merged, so pointers are only valid until the
+    /// file is next modified.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple.
+    pub fn get_tuple(&mut self, pointer: &Literal) -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        let (page_no, tuple_offset) = match *pointer {
+            Literal::FilePointer { page_no, offset } => (page_no, offset),
+            _ => return Ok(None),
+        };
+        if page_no == 0 {
+            return Ok(None);
+        }
+
+        let mut db_page: DBPage = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        try!(db_page.seek(SeekFrom::Start(0)));
+        let page_type = try!(db_page.read_u8());
+        try!(db_page.seek(SeekFrom::Start(OFFSET_NUM_ENTRIES as u64)));
+        let num_entries = try!(db_page.read_u16::<BigEndian>());
+
+        let mut offset = OFFSET_LEAF_ENTRIES as u16;
+        for entry in 0..num_entries {
+            if page_type != PAGE_TYPE_LEAF || offset > tuple_offset {
+                break;
+            }
+            if offset == tuple_offset {
+                let mut tuple = try!(PageTuple::new(db_page, offset, self.schema.clone()));
+                tuple.pin();
+                return Ok(Some(BTreeFilePageTuple {
+                    page_tuple: tuple,
+                    entry: entry,
+                }));
+            }
+            let (_, size) = try!(read_tuple(&mut db_page, offset, &self.schema));
+            offset += size;
+        }
+
+        try!(db_page.unpin());
+        Ok(None)
+    }
+
+    /// Returns the first tuple whose key is greater than or equal to the given key, or `None` if
+    /// there is no such tuple. The key may have fewer values than there are key columns, in which
+    /// case only those values are compared.
+    ///
+    /// # Arguments
+    /// * key - The key to search for.
+    pub fn find_first_tuple_at_least(&mut self,
+                                     key: &TupleLiteral)
+                                     -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        self.find_first_tuple(key, false)
+    }
+
+    /// Returns the first tuple whose key is strictly greater than the given key, or `None` if
+    /// there is no such tuple. The key may have fewer values than there are key columns, in which
+    /// case only those values are compared.
+    ///
+    /// # Arguments
+    /// * key - The key to search for.
+    pub fn find_first_tuple_greater_than(&mut self,
+                                         key: &TupleLiteral)
+                                         -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        self.find_first_tuple(key, true)
+    }
+
+    /// Returns the first tuple whose key is equal to the given key, or `None` if there is no such
+    /// tuple. The following tuples with an equal key can be found with
+    /// [`get_next_tuple`](#method.get_next_tuple).
+    ///
+    /// # Arguments
+    /// * key - The key to search for.
+    pub fn find_first_tuple_equal(&mut self,
+                                  key: &TupleLiteral)
+                                  -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        let mut result = try!(self.find_first_tuple_at_least(key));
+        let is_equal = match result {
+            Some(ref mut tuple) => {
+                let found = TupleLiteral::from_tuple(tuple);
+                self.compare_keys(&found, key) == Ordering::Equal
+            }
+            None => false,
+        };
+        if is_equal {
+            Ok(result)
+        } else {
+            if let Some(mut tuple) = result {
+                try!(tuple.unpin());
+            }
+            Ok(None)
+        }
+    }
+
+    fn find_first_tuple(&mut self,
+                        key: &TupleLiteral,
+                        strictly_greater: bool)
+                        -> Result<Option<BTreeFilePageTuple>, TupleError> {
+        let root = try!(self.get_root_page());
+        if root == 0 {
+            return Ok(None);
+        }
+
+        let (_, leaf) = try!(self.find_leaf(root, key, strictly_greater));
+        self.scan_leaves(leaf, 0, |file, entry| {
+            match file.compare_keys(entry, key) {
+                Ordering::Greater => true,
+                Ordering::Equal => !strictly_greater,
+                Ordering::Less => false,
+            }
+        })
+    }
+
+    /// Finds the path to the leaf containing a tuple equal to the given tuple. Since tuples with
+    /// equal keys may span several leaves, every child that may hold the key is searched.
+    fn find_tuple_path(&mut self,
+                       page_no: u16,
+                       tuple: &TupleLiteral,
+                       path: &mut Vec<(u16, usize)>)
+                       -> Result<Option<(LeafNode, usize)>, TupleError> {
+        match try!(self.load_node(page_no)) {
+            Node::Leaf(leaf) => {
+                Ok(leaf.entries.iter().position(|entry| entry == tuple).map(|index| (leaf, index)))
+            }
+            Node::Inner(inner) => {
+                let first = self.get_child_index(&inner, tuple, false);
+                let last = self.get_child_index(&inner, tuple, true);
+                for index in first..(last + 1) {
+                    path.push((page_no, index));
+                    if let Some(found) = try!(self.find_tuple_path(inner.pointers[index], tuple, path)) {
+                        return Ok(Some(found));
+                    }
+                    path.pop();
+                }
+                Ok(None)
+            }
+        }
+    }
+
+    /// Deletes a tuple equal to the specified tuple from the file, merging or rebalancing pages
+    /// that become less than half full. Returns whether a tuple was found and deleted.
+    ///
+    /// # Arguments
+    /// * tuple - The values of the tuple to delete.
+    pub fn delete_tuple(&mut self, tuple: &TupleLiteral) -> Result<bool, TupleError> {
+        let root = try!(self.get_root_page());
+        if root == 0 {
+            return Ok(false);
+        }
+
+        let mut path = Vec::new();
+        let (mut leaf, index) = match try!(self.find_tuple_path(root, tuple, &mut path)) {
+            Some(found) => found,
+            None => return Ok(false),
+        };
+        debug!("Deleting entry {} of leaf page {}.", index, leaf.page_no);
+        leaf.entries.remove(index);
+
+        if path.is_empty() {
+            // The leaf is the root, so it can hold any number of tuples.
+            if leaf.entries.is_empty() {
+                try!(self.set_root_page(0));
+                try!(self.free_page(leaf.page_no));
+            } else {
+                try!(self.write_leaf(&leaf));
+            }
+            return Ok(true);
+        }
+
+        if try!(self.get_leaf_size(&leaf.entries)) >= self.get_usable_size() / 2 {
+            try!(self.write_leaf(&leaf));
+            return Ok(true);
+        }
+
+        let (parent_no, child_index) = path.pop().unwrap();
+        let mut parent = try!(self.load_inner(parent_no));
+
+        // Pair the leaf up with a sibling, preferring the one on its left.
+        let separator_index = if child_index > 0 { child_index - 1 } else { child_index };
+        let (mut left, mut right) = if child_index > 0 {
+            (try!(self.load_leaf(parent.pointers[separator_index])), leaf)
+        } else {
+            let sibling = try!(self.load_leaf(parent.pointers[separator_index + 1]));
+            (leaf, sibling)
+        };
+
+        let mut entries = left.entries.split_off(0);
+        entries.extend(right.entries.drain(..));
+
+        if try!(self.get_leaf_size(&entries)) <= self.get_usable_size() {
+            debug!("Merging leaf pages {} and {}.", left.page_no, right.page_no);
+            left.entries = entries;
+            left.next_leaf = right.next_leaf;
+            try!(self.write_leaf(&left));
+            try!(self.free_page(right.page_no));
+
+            parent.keys.remove(separator_index);
+            parent.pointers.remove(separator_index + 1);
+            try!(self.rebalance_inner(path, parent));
+        } else {
+            debug!("Redistributing tuples between leaf pages {} and {}.", left.page_no, right.page_no);
+            let sizes = try!(BTreeTupleFile::get_sizes(&self.schema, &entries));
+            let split_index = BTreeTupleFile::get_split_index(&sizes, 1);
+            right.entries = entries.split_off(split_index);
+            left.entries = entries;
+            parent.keys[separator_index] = self.get_key(&right.entries[0]);
+
+            // The new separator may be longer than the old one, so the parent is written first, in
+            // case it has to be split and there's no room in the file for another page.
+            try!(self.write_or_split_inner(path, parent));
+            try!(self.write_leaf(&left));
+            try!(self.write_leaf(&right));
+        }
+
+        Ok(true)
+    }
+
+    /// Writes an inner node that lost a key, merging it with or borrowing keys from a sibling if it
+    /// has become less than half full. If the root is left with a single child, that child becomes
+    /// the new root.
+    fn rebalance_inner(&mut self, mut path: Vec<(u16, usize)>, node: InnerNode) -> Result<(), TupleError> {
+        let (parent_no, child_index) = match path.pop() {
+            Some(entry) => entry,
+            None => {
+                if node.keys.is_empty() {
+                    try!(self.set_root_page(node.pointers[0]));
+                    return self.free_page(node.page_no);
+                }
+                return self.write_inner(&node);
+            }
+        };
+
+        if try!(self.get_inner_size(&node.pointers, &node.keys)) >= self.get_usable_size() / 2 {
+            return self.write_inner(&node);
+        }
+
+        let mut parent = try!(self.load_inner(parent_no));
+        let separator_index = if child_index > 0 { child_index - 1 } else { child_index };
+        let (mut left, mut right) = if child_index > 0 {
+            (try!(self.load_inner(parent.pointers[separator_index])), node)
+        } else {
+            let sibling = try!(self.load_inner(parent.pointers[separator_index + 1]));
+            (node, sibling)
+        };
+
+        // The separator from the parent comes down between the two nodes' keys.
+        let mut keys = left.keys.split_off(0);
+        keys.push(parent.keys[separator_index].clone());
+        keys.extend(right.keys.drain(..));
+        let mut pointers = left.pointers.split_off(0);
+        pointers.extend(right.pointers.drain(..));
+
+        if try!(self.get_inner_size(&pointers, &keys)) <= self.get_usable_size() {
+            debug!("Merging inner pages {} and {}.", left.page_no, right.page_no);
+            left.keys = keys;
+            left.pointers = pointers;
+            try!(self.write_inner(&left));
+            try!(self.free_page(right.page_no));
+
+            parent.keys.remove(separator_index);
+            parent.pointers.remove(separator_index + 1);
+            self.rebalance_inner(path, parent)
+        } else {
+            debug!("Redistributing keys between inner pages {} and {}.", left.page_no, right.page_no);
+            let sizes = try!(BTreeTupleFile::get_sizes(&self.key_schema, &keys));
+            let split_index = cmp::min(BTreeTupleFile::get_split_index(&sizes, 1), sizes.len() - 2);
+            let mut right_keys = keys.split_off(split_index);
+            parent.keys[separator_index] = right_keys.remove(0);
+            right.keys = right_keys;
+            right.pointers = pointers.split_off(split_index + 1);
+            left.keys = keys;
+            left.pointers = pointers;
+
+            // As with leaves, the parent may have to be split to hold its new separator.
+            try!(self.write_or_split_inner(path, parent));
+            try!(self.write_inner(&left));
+            self.write_inner(&right)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::{ColumnInfo, ColumnType};
+    use ::storage::{DBFileType, FileManager};
+
+    fn make_file(dir: &TempDir, name: &str) -> BTreeTupleFile {
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile(name, DBFileType::BTreeTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "T"),
+            ColumnInfo::with_table_name(ColumnType::VarChar { length: 20 }, "B", "T"),
+        ]).unwrap();
+        BTreeTupleFile::new(db_file, schema, 1).unwrap()
+    }
+
+    fn make_tuple(i: i32) -> TupleLiteral {
+        TupleLiteral::from_iter(vec![Literal::Int(i), Literal::String(format!("value {}", i))])
+    }
+
+    fn collect_keys(file: &mut BTreeTupleFile) -> Vec<i32> {
+        let mut result = Vec::new();
+        let mut cur = file.get_first_tuple().unwrap();
+        while let Some(mut tuple) = cur {
+            match tuple.get_column_value(0).unwrap() {
+                Literal::Int(i) => result.push(i),
+                other => panic!("Unexpected value {}", other),
+            }
+            cur = file.get_next_tuple(&tuple).unwrap();
+        }
+        result
+    }
+
+    // Produces the numbers 0..n in a scrambled order.
+    fn scrambled(n: i32) -> Vec<i32> {
+        (0..n).map(|i| (i * 37) % n).collect()
+    }
+
+    #[test]
+    fn test_ordered_iteration() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "ordered.tbl");
+
+        assert!(file.get_first_tuple().unwrap().is_none());
+
+        for i in vec![5, 3, 9, 1, 7] {
+            let mut added = file.add_tuple(make_tuple(i)).unwrap();
+            assert_eq!(Literal::Int(i), added.get_column_value(0).unwrap());
+        }
+
+        assert_eq!(vec![1, 3, 5, 7, 9], collect_keys(&mut file));
+    }
+
+    #[test]
+    fn test_splits_and_reopen() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "splits.tbl");
+
+        // Enough tuples to split inner pages as well as leaves.
+        for i in scrambled(3000) {
+            file.add_tuple(make_tuple(i)).unwrap();
+        }
+        assert_eq!((0..3000).collect::<Vec<_>>(), collect_keys(&mut file));
+
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let mut reopened = BTreeTupleFile::open(file_manager.open_dbfile("splits.tbl").unwrap()).unwrap();
+        assert_eq!(file.schema, reopened.schema);
+        assert_eq!(1, reopened.num_key_columns);
+        assert_eq!((0..3000).collect::<Vec<_>>(), collect_keys(&mut reopened));
+    }
+
+    #[test]
+    fn test_seek() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "seek.tbl");
+
+        for i in scrambled(300) {
+            // Only even keys, with every key twice.
+            file.add_tuple(make_tuple(2 * i)).unwrap();
+            file.add_tuple(make_tuple(2 * i)).unwrap();
+        }
+
+        let key = TupleLiteral::from_iter(vec![Literal::Int(101)]);
+        let mut found = file.find_first_tuple_at_least(&key).unwrap().unwrap();
+        assert_eq!(Literal::Int(102), found.get_column_value(0).unwrap());
+        assert!(file.find_first_tuple_equal(&key).unwrap().is_none());
+
+        let key = TupleLiteral::from_iter(vec![Literal::Int(250)]);
+        let mut found = file.find_first_tuple_equal(&key).unwrap().unwrap();
+        assert_eq!(Literal::Int(250), found.get_column_value(0).unwrap());
+        let mut next = file.get_next_tuple(&found).unwrap().unwrap();
+        assert_eq!(Literal::Int(250), next.get_column_value(0).unwrap());
+        let mut after = file.get_next_tuple(&next).unwrap().unwrap();
+        assert_eq!(Literal::Int(252), after.get_column_value(0).unwrap());
+
+        let mut greater = file.find_first_tuple_greater_than(&key).unwrap().unwrap();
+        assert_eq!(Literal::Int(252), greater.get_column_value(0).unwrap());
+
+        let key = TupleLiteral::from_iter(vec![Literal::Int(598)]);
+        assert!(file.find_first_tuple_greater_than(&key).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_delete_and_merge() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "delete.tbl");
+
+        for i in scrambled(3000) {
+            file.add_tuple(make_tuple(i)).unwrap();
+        }
+
+        assert!(!file.delete_tuple(&make_tuple(3000)).unwrap());
+        for i in scrambled(3000).into_iter().filter(|i| i % 30 != 0) {
+            assert!(file.delete_tuple(&make_tuple(i)).unwrap());
+        }
+        assert_eq!((0..100).map(|i| i * 30).collect::<Vec<_>>(), collect_keys(&mut file));
+
+        // Freed pages are reused rather than growing the file.
+        let length = file.db_file.get_contents().metadata().unwrap().len();
+        for i in 0..100 {
+            file.add_tuple(make_tuple(i * 30 + 1)).unwrap();
+        }
+        assert_eq!(length, file.db_file.get_contents().metadata().unwrap().len());
+
+        for i in 0..100 {
+            assert!(file.delete_tuple(&make_tuple(i * 30)).unwrap());
+            assert!(file.delete_tuple(&make_tuple(i * 30 + 1)).unwrap());
+        }
+        assert!(collect_keys(&mut file).is_empty());
+        assert_eq!(0, file.get_root_page().unwrap());
+    }
+
+    #[test]
+    fn test_file_too_big() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "big.tbl");
+
+        // The last page number that fits in two bytes can still be used, but not the one after.
+        file.db_file.get_contents().set_len(u16::MAX as u64 * 512).unwrap();
+        assert_eq!(Ok(u16::MAX), file.allocate_page());
+        assert_eq!(Err(TupleError::FileTooBig(u16::MAX as u32 + 1)), file.allocate_page());
+    }
+
+    #[test]
+    fn test_tuple_too_big() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut file = make_file(&dir, "big.tbl");
+
+        // Values aren't moved out into overflow pages, so a tuple larger than a third of a page
+        // can't be stored.
+        let max_tuple_size = file.get_max_tuple_size();
+        let tuple = TupleLiteral::from_iter(vec![Literal::Int(1), Literal::String("x".repeat(200))]);
+        match file.add_tuple(tuple) {
+            Err(TupleError::TupleTooBigForBTree(207, max)) => assert_eq!(max_tuple_size, max),
+            Err(e) => panic!("Unexpected error {}", e),
+            Ok(_) => panic!("The tuple shouldn't fit"),
+        }
+        assert_eq!(Vec::<i32>::new(), collect_keys(&mut file));
+    }
+
+    #[test]
+    fn test_delete_with_long_keys() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("long.tbl", DBFileType::BTreeTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_table_name(ColumnType::VarChar { length: 150 }, "K", "T"),
+            ColumnInfo::with_table_name(ColumnType::Integer, "A", "T"),
+        ]).unwrap();
+        let mut file = BTreeTupleFile::new(db_file, schema, 1).unwrap();
+
+        // Short and long keys alternate, so redistributing tuples between leaves can replace a
+        // short separator in a full parent with a much longer one.
+        let make = |i: i32| {
+            let key = if i % 2 == 0 { format!("{:04}", i) } else { format!("{:04}{}", i, "x".repeat(120)) };
+            TupleLiteral::from_iter(vec![Literal::String(key), Literal::Int(i)])
+        };
+        for i in scrambled(400) {
+            file.add_tuple(make(i)).unwrap();
+        }
+        let mut remaining: Vec<i32> = (0..400).collect();
+        for i in scrambled(400).into_iter().filter(|i| i % 3 != 0) {
+            assert!(file.delete_tuple(&make(i)).unwrap());
+            remaining.retain(|&j| j != i);
+        }
+
+        let mut values = Vec::new();
+        let mut cur = file.get_first_tuple().unwrap();
+        while let Some(mut tuple) = cur {
+            match tuple.get_column_value(1).unwrap() {
+                Literal::Int(i) => values.push(i),
+                other => panic!("Unexpected value {}", other),
+            }
+            cur = file.get_next_tuple(&tuple).unwrap();
+        }
+        assert_eq!(remaining, values);
+        for &i in remaining.iter() {
+            let key = TupleLiteral::from_iter(vec![make(i)[0].clone()]);
+            assert!(file.find_first_tuple_equal(&key).unwrap().is_some());
+        }
+    }
+}
@@ -2,14 +2,22 @@
 //! heap.
 
 use std::fs::File;
+use std::u16;
 
-use ::Schema;
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::{ColumnType, Schema};
 use ::expressions::Literal;
-use ::storage::{DBFile, DBPage, PinError, Pinnable, Tuple, TupleError};
+use ::indexes::{IndexInfo, read_indexes, write_indexes};
+use ::relations::{ColumnStatsCollector, TableStats};
+use ::storage::{DBFile, DBPage, PinError, Pinnable, Tuple, TupleError, TupleLiteral};
 use ::storage::dbpage::{EMPTY_SLOT, get_slot_offset};
-use ::storage::file_manager;
-use ::storage::page_tuple::{PageTuple, get_tuple_storage_size};
-use ::storage::storage_manager::load_dbpage;
+use ::storage::{file_manager, overflow_page};
+use ::storage::free_space_map::{FreeSpaceMap, get_num_pages, is_fsm_page};
+use ::storage::overflow_page::is_overflow_page;
+use ::storage::page_tuple::{PageTuple, get_tuple_storage_size, read_overflow_pages, read_tuple};
+use ::storage::storage_manager::{load_dbpage, save_dbpage};
+use ::transactions::PAGE_LSN_SIZE;
 
 /// A page tuple stored in a heap file, so it has an associated slot.
 pub struct HeapFilePageTuple {
@@ -111,12 +119,26 @@ impl<'a> Tuple for &'a mut HeapFilePageTuple {
     }
 }
 
+/// Returns whether the page holds tuples, rather than being an overflow page or a page of the
+/// free-space map.
+fn is_data_page(page: &mut DBPage) -> Result<bool, TupleError> {
+    Ok(!try!(is_overflow_page(page)) && !try!(is_fsm_page(page)))
+}
+
 /// This class implements tuple file processing for heap files.
 #[derive(Debug, PartialEq)]
 pub struct HeapTupleFile {
     db_file: DBFile<File>,
     /// The schema of tuples in this tuple file.
     pub schema: Schema,
+    /// The indexes on this tuple file, which are stored in the header page after the schema.
+    pub indexes: Vec<IndexInfo>,
+    /// The map of free space in the file's pages, whose first page is stored in the header page
+    /// after the indexes.
+    free_space_map: FreeSpaceMap,
+    /// The statistics collected by the last `ANALYZE` of the file, which are stored in the header
+    /// page after the first page of the free-space map.
+    pub stats: TableStats,
 }
 
 impl HeapTupleFile {
@@ -126,9 +148,13 @@ impl HeapTupleFile {
     /// * db_file - The backing `DBFile`.
     /// * schema - The schema that the file is based on.
     pub fn new(db_file: DBFile<File>, schema: Schema) -> Result<HeapTupleFile, file_manager::Error> {
+        let num_columns = schema.num_columns();
         let mut result = HeapTupleFile {
             db_file: db_file,
             schema: schema,
+            indexes: vec![],
+            free_space_map: FreeSpaceMap::new(0),
+            stats: TableStats::new(num_columns),
         };
         try!(result.save_metadata());
         Ok(result)
@@ -142,25 +168,79 @@ impl HeapTupleFile {
         let mut header_page = try!(load_dbpage(&mut db_file, 0, false));
 
         let schema = try!(Schema::from_header_page(&mut header_page));
+        // The index descriptions directly follow the schema, and are followed by the first page
+        // of the free-space map and the table's statistics.
+        let indexes = try!(read_indexes(&mut header_page));
+        let fsm_first_page = try!(header_page.read_u16::<BigEndian>());
+        let stats = try!(TableStats::read(&mut header_page, schema.num_columns()));
 
-        Ok(HeapTupleFile {
+        let mut result = HeapTupleFile {
             db_file: db_file,
             schema: schema,
-        })
+            indexes: indexes,
+            free_space_map: FreeSpaceMap::new(fsm_first_page),
+            stats: stats,
+        };
+        if fsm_first_page == 0 {
+            // Files written before the free-space map existed get one built from their pages.
+            try!(result.build_free_space_map().map_err(|e| match e {
+                TupleError::FileManagerError(e) => e,
+                e => file_manager::Error::IOError(e.to_string()),
+            }));
+        }
+        Ok(result)
+    }
+
+    /// Builds the free-space map of a file that doesn't have one yet, by reading every page.
+    fn build_free_space_map(&mut self) -> Result<(), TupleError> {
+        let mut page_no = 1;
+        while page_no < try!(get_num_pages(&self.db_file)) {
+            let mut page = try!(load_dbpage(&mut self.db_file, page_no, false));
+            let free_space = if try!(is_data_page(&mut page)) {
+                try!(page.get_free_space())
+            } else {
+                0
+            };
+            try!(page.unpin());
+            try!(self.set_free_space(page_no, free_space));
+            page_no += 1;
+        }
+        Ok(())
+    }
+
+    /// Records the free space of a page in the free-space map, saving the header page if the map
+    /// had to be started.
+    fn set_free_space(&mut self, page_no: u32, free_space: u16) -> Result<(), TupleError> {
+        let first_page = self.free_space_map.get_first_page();
+        try!(self.free_space_map.set_free_space(&mut self.db_file, page_no, free_space));
+        if self.free_space_map.get_first_page() != first_page {
+            try!(self.save_metadata());
+        }
+        Ok(())
+    }
+
+    /// Records the current free space of a data page in the free-space map.
+    fn update_free_space(&mut self, page: &mut DBPage) -> Result<(), TupleError> {
+        let free_space = try!(page.get_free_space());
+        self.set_free_space(page.page_no, free_space)
     }
 
     /// Writes the metadata of the tuple file to disk.
     ///
-    /// This handles writing of the schema and (TODO) stats on the table to the disk, and (TODO)
-    /// handling all buffer management via the buffer manager.
+    /// This handles writing of the schema, the table's indexes, the first page of the free-space
+    /// map and the table's statistics to the header page, which is saved through the buffer
+    /// manager.
     pub fn save_metadata(&mut self) -> Result<(), file_manager::Error> {
         let mut header_page = try!(load_dbpage(&mut self.db_file, 0, false));
 
         header_page.pin();
 
         try!(self.schema.write(&mut header_page));
+        try!(write_indexes(&mut header_page, &self.indexes));
+        try!(header_page.write_u16::<BigEndian>(self.free_space_map.get_first_page()));
+        try!(self.stats.write(&mut header_page));
 
-        try!(file_manager::save_page(&mut self.db_file, 0, &header_page.page_data));
+        try!(save_dbpage(&mut self.db_file, &mut header_page));
 
         try!(header_page.unpin());
 
@@ -173,45 +253,41 @@ impl HeapTupleFile {
     /// # Arguments
     /// * tuple - a tuple object containing the values to add to the table
     pub fn add_tuple<'a, T: Tuple + 'a>(&mut self, mut tuple: T) -> Result<Box<Tuple + 'a>, TupleError> {
-        let tuple_size = try!(get_tuple_storage_size(self.schema.clone(), &mut tuple));
+        let values = TupleLiteral::from_tuple(&mut tuple);
+        let (large_columns, tuple_size) = try!(self.get_large_columns(&values));
         debug!("Adding new tuple of size {} bytes.", tuple_size);
 
-        if (tuple_size + 2) as u32 > self.db_file.get_page_size() {
-            return Err(TupleError::TupleTooBig(tuple_size, self.db_file.get_page_size()));
-        }
-
-        let mut page_no = 1;
+        // Ask the free-space map for a page with room for the tuple and its slot. The map can be
+        // out of date (e.g. after a rollback), so each page it suggests is checked, and its entry
+        // corrected if it doesn't actually have room.
+        let needed = tuple_size + 2;
         let mut db_page: Option<DBPage> = None;
-        loop {
-            let cur_page = load_dbpage(&mut self.db_file, page_no, false);
-            if cur_page.is_err() {
-                // Couldn't load the current page, because it doesn't exist.
-                // Break out of the loop.
-                debug!("Reached end of data file without finding space for new tuple.");
-                break;
-            }
-
-            // We can unwrap here because we would have broken if cur_page were not Ok.
-            let mut cur_page = cur_page.unwrap();
-
-            let free_space = try!(cur_page.get_free_space());
+        while let Some(page_no) = try!(self.free_space_map.find_page(&mut self.db_file, needed)) {
+            let mut cur_page = try!(load_dbpage(&mut self.db_file, page_no, false));
+            let free_space = if try!(is_data_page(&mut cur_page)) {
+                try!(cur_page.get_free_space())
+            } else {
+                0
+            };
             trace!("Page {} has {} bytes of free space.", page_no, free_space);
 
-            if free_space >= tuple_size + 2 {
+            if free_space >= needed {
                 debug!("Found space for new tuple in page {}.", page_no);
                 db_page = Some(cur_page);
                 break;
             }
 
-            // If we reached this point then the page doesn't have enough space, so go on to the
-            // next data page.
             try!(cur_page.unpin());
-            page_no += 1;
+            try!(self.set_free_space(page_no, free_space));
         }
 
         if db_page.is_none() {
-            // Try to create a new page at the end of the file. In this circumstance, page_no is
-            // *just past* the last page in the data file.
+            // Create a new page at the end of the file. Tuples are referenced by file-pointers,
+            // which store page numbers in two bytes, so this is checked before anything is written.
+            let page_no = try!(get_num_pages(&self.db_file));
+            if page_no > u16::MAX as u32 {
+                return Err(TupleError::FileTooBig(page_no));
+            }
             debug!("Creating new page {} to store new tuple.", page_no);
             let mut cur_page = try!(load_dbpage(&mut self.db_file, page_no, true));
             try!(cur_page.init_new_page());
@@ -221,14 +297,27 @@ impl HeapTupleFile {
         // At this point there is some DBPage here.
         let mut db_page = db_page.unwrap();
 
+        // The large values are stored once the tuple's page is known, so that the page isn't
+        // handed out for their overflow pages too.
+        if !large_columns.is_empty() {
+            try!(self.set_free_space(db_page.page_no, 0));
+        }
+        let tuple = match self.store_large_values(values, &large_columns) {
+            Ok(tuple) => tuple,
+            Err(e) => {
+                try!(self.update_free_space(&mut db_page));
+                return Err(e);
+            }
+        };
+
         let slot = try!(db_page.alloc_new_tuple(tuple_size));
         let tuple_offset = try!(db_page.get_slot_value(slot));
 
-        debug!("New tuple will reside on page {}, slot {}.", page_no, slot);
+        debug!("New tuple will reside on page {}, slot {}.", db_page.page_no, slot);
 
         try!(db_page.store_new_tuple(tuple_offset, self.schema.clone(), tuple));
-        try!(file_manager::save_page(&mut self.db_file, page_no, &db_page.page_data));
-        db_page.set_dirty(false);
+        try!(save_dbpage(&mut self.db_file, &mut db_page));
+        try!(self.update_free_space(&mut db_page));
         let mut page_tuple = try!(PageTuple::new(db_page, tuple_offset, self.schema.clone()));
         page_tuple.pin();
 
@@ -236,7 +325,296 @@ impl HeapTupleFile {
         // multiple references to the page because tuples cannot overlap.
         //        try!(db_page.unpin());
 
-        Ok(Box::new(page_tuple))
+        Ok(Box::new(HeapFilePageTuple {
+            page_tuple: page_tuple,
+            slot: slot,
+        }))
+    }
+
+    /// Returns the indexes of the variable-size columns whose values must be moved out into
+    /// overflow pages, along with the size of the tuple once they have been. `TEXT` and `BLOB`
+    /// values larger than the maximum inline size are always moved out; after that, the largest
+    /// remaining variable-size values (including `VARCHAR` values) are moved out until the tuple
+    /// fits in a page.
+    ///
+    /// # Errors
+    /// Returns `TupleTooBig` if the tuple doesn't fit in a page even with all of its variable-size
+    /// values moved out.
+    fn get_large_columns(&self, values: &TupleLiteral) -> Result<(Vec<usize>, u16), TupleError> {
+        let page_size = self.db_file.get_page_size();
+        let max_inline_size = overflow_page::get_max_inline_size(page_size);
+
+        let mut candidates: Vec<(usize, usize)> = Vec::new();
+        for (col_idx, value) in values.iter().enumerate() {
+            let column_type = self.schema[col_idx].column_type;
+            if column_type.is_variable_length() {
+                if let Some(bytes) = value.as_bytes() {
+                    candidates.push((bytes.len(), col_idx));
+                }
+            }
+        }
+        // Largest values first, so that as few values as possible are moved out.
+        candidates.sort_by(|a, b| b.cmp(a));
+
+        let (mut result, mut remaining): (Vec<usize>, Vec<usize>) = (Vec::new(), Vec::new());
+        for &(size, col_idx) in candidates.iter() {
+            match self.schema[col_idx].column_type {
+                ColumnType::Text | ColumnType::Blob if size > max_inline_size => result.push(col_idx),
+                _ => remaining.push(col_idx),
+            }
+        }
+        let mut remaining = remaining.into_iter();
+
+        loop {
+            // Large values only take up a file-pointer in the tuple, so size the tuple with a
+            // placeholder pointer before any overflow pages are written.
+            let mut sized_values = values.clone();
+            for &col_idx in result.iter() {
+                sized_values.set_value(col_idx, Literal::FilePointer { page_no: 0, offset: 0 });
+            }
+            // A value that is too long to be stored inline at all must be moved out as well.
+            let error = match get_tuple_storage_size(self.schema.clone(), &mut sized_values) {
+                Ok(tuple_size) => {
+                    if (tuple_size as usize + 2 + PAGE_LSN_SIZE) as u32 <= page_size {
+                        return Ok((result, tuple_size));
+                    }
+                    TupleError::TupleTooBig(tuple_size, page_size)
+                }
+                Err(TupleError::ValueTooLong(length)) => TupleError::ValueTooLong(length),
+                Err(e) => return Err(e),
+            };
+            match remaining.next() {
+                Some(col_idx) => result.push(col_idx),
+                None => return Err(error),
+            }
+        }
+    }
+
+    /// Writes the values of the given columns into overflow pages, returning the tuple values with
+    /// those values replaced by file-pointers to their overflow pages.
+    fn store_large_values(&mut self, mut values: TupleLiteral, columns: &[usize]) -> Result<TupleLiteral, TupleError> {
+        for &col_idx in columns {
+            let data = values[col_idx].as_bytes().unwrap_or_default().to_vec();
+            let num_pages = overflow_page::get_num_pages_needed(self.db_file.get_page_size(), data.len());
+            let page_nos = try!(self.find_empty_pages(num_pages));
+            let pointer = try!(overflow_page::write_value(&mut self.db_file, &data, &page_nos));
+            for page_no in page_nos {
+                try!(self.set_free_space(page_no, 0));
+            }
+            values.set_value(col_idx, pointer);
+        }
+        Ok(values)
+    }
+
+    /// Returns the given number of pages to store an overflow chain in. Empty data pages are
+    /// reused first, and the remaining pages are added to the end of the file.
+    fn find_empty_pages(&mut self, count: usize) -> Result<Vec<u32>, TupleError> {
+        let empty_space = (self.db_file.get_page_size() as usize - 2 - PAGE_LSN_SIZE) as u16;
+        let candidates = try!(self.free_space_map.find_empty_pages(&mut self.db_file, empty_space, count));
+
+        let mut result = Vec::new();
+        for page_no in candidates {
+            let mut page = try!(load_dbpage(&mut self.db_file, page_no, false));
+            if try!(is_data_page(&mut page)) && try!(page.get_num_slots()) == 0 {
+                result.push(page_no);
+            }
+            try!(page.unpin());
+        }
+
+        let mut page_no = try!(get_num_pages(&self.db_file));
+        while result.len() < count {
+            // Overflow chains store page numbers in two bytes.
+            if page_no > u16::MAX as u32 {
+                return Err(TupleError::FileTooBig(page_no));
+            }
+            result.push(page_no);
+            page_no += 1;
+        }
+        Ok(result)
+    }
+
+    /// Frees the chains of overflow pages starting at the given pages.
+    fn free_overflow_pages(&mut self, first_pages: Vec<u16>) -> Result<(), TupleError> {
+        let empty_space = (self.db_file.get_page_size() as usize - 2 - PAGE_LSN_SIZE) as u16;
+        for first_page in first_pages {
+            for page_no in try!(overflow_page::free_value(&mut self.db_file, first_page)) {
+                try!(self.set_free_space(page_no, empty_space));
+            }
+        }
+        Ok(())
+    }
+
+    /// Scans every page of the file to collect the statistics of the table, then stores them in
+    /// the header page. Only pages holding at least one tuple are counted as data pages, so empty
+    /// pages (e.g. freed overflow pages, or pages whose tuples were all deleted), overflow pages
+    /// and pages of the free-space map aren't counted.
+    pub fn analyze(&mut self) -> Result<TableStats, TupleError> {
+        let num_columns = self.schema.num_columns();
+        let mut collectors: Vec<ColumnStatsCollector> = (0..num_columns).map(|_| ColumnStatsCollector::new()).collect();
+        let mut num_data_pages = 0;
+        let mut num_tuples = 0;
+        let mut total_size = 0u64;
+
+        let num_pages = try!(get_num_pages(&self.db_file));
+        for page_no in 1..num_pages {
+            let mut db_page = try!(load_dbpage(&mut self.db_file, page_no, false));
+            if !try!(is_data_page(&mut db_page)) {
+                try!(db_page.unpin());
+                continue;
+            }
+
+            let mut has_tuples = false;
+            for slot in 0..try!(db_page.get_num_slots()) {
+                let offset = try!(db_page.get_slot_value(slot));
+                if offset == EMPTY_SLOT {
+                    continue;
+                }
+                let (values, _) = try!(read_tuple(&mut db_page, offset, &self.schema));
+                for (i, value) in values.iter().enumerate() {
+                    collectors[i].add_value(value.clone());
+                }
+                total_size += try!(db_page.get_tuple_length(slot)) as u64;
+                num_tuples += 1;
+                has_tuples = true;
+            }
+            if has_tuples {
+                num_data_pages += 1;
+            }
+            try!(db_page.unpin());
+        }
+
+        self.stats = TableStats {
+            num_data_pages: num_data_pages,
+            num_tuples: num_tuples,
+            avg_tuple_size: if num_tuples > 0 { total_size as f32 / num_tuples as f32 } else { 0.0 },
+            column_stats: collectors.iter().map(|c| c.get_column_stats()).collect(),
+        };
+        try!(self.save_metadata());
+        Ok(self.stats.clone())
+    }
+
+    /// Returns the tuple that the given file pointer refers to, as returned by the tuple's
+    /// `get_external_reference` method, or `None` if the tuple has been deleted.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple's slot.
+    pub fn get_tuple(&mut self, pointer: &Literal) -> Result<Option<HeapFilePageTuple>, TupleError> {
+        let (page_no, slot_offset) = match *pointer {
+            Literal::FilePointer { page_no, offset } => (page_no, offset),
+            _ => return Ok(None),
+        };
+        let slot = slot_offset / 2 - 1;
+
+        let mut db_page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        let num_slots = try!(db_page.get_num_slots());
+        let offset = if slot < num_slots {
+            try!(db_page.get_slot_value(slot))
+        } else {
+            EMPTY_SLOT
+        };
+        if offset == EMPTY_SLOT {
+            try!(db_page.unpin());
+            return Ok(None);
+        }
+
+        let mut tuple = try!(PageTuple::new(db_page, offset, self.schema.clone()));
+        tuple.pin();
+        Ok(Some(HeapFilePageTuple {
+            page_tuple: tuple,
+            slot: slot,
+        }))
+    }
+
+    /// Deletes the tuple that the given file pointer refers to, reclaiming its space in the page.
+    /// Returns whether there was a tuple to delete. The slots of the other tuples are not changed,
+    /// so pointers to them remain valid.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple's slot.
+    pub fn delete_tuple(&mut self, pointer: &Literal) -> Result<bool, TupleError> {
+        let (page_no, slot_offset) = match *pointer {
+            Literal::FilePointer { page_no, offset } => (page_no, offset),
+            _ => return Ok(false),
+        };
+        let slot = slot_offset / 2 - 1;
+
+        let mut db_page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        let num_slots = try!(db_page.get_num_slots());
+        if slot >= num_slots || try!(db_page.get_slot_value(slot)) == EMPTY_SLOT {
+            try!(db_page.unpin());
+            return Ok(false);
+        }
+
+        let offset = try!(db_page.get_slot_value(slot));
+        let overflow_pages = try!(read_overflow_pages(&mut db_page, offset, &self.schema));
+
+        debug!("Deleting tuple at page {}, slot {}.", page_no, slot);
+        try!(db_page.delete_tuple(slot));
+        try!(save_dbpage(&mut self.db_file, &mut db_page));
+        try!(self.update_free_space(&mut db_page));
+        try!(db_page.unpin());
+
+        try!(self.free_overflow_pages(overflow_pages));
+        Ok(true)
+    }
+
+    /// Replaces the values of the tuple that the given file pointer refers to. The tuple is
+    /// rewritten in place if its size doesn't change; otherwise it is grown or shrunk within its
+    /// page, or moved to another page if its page doesn't have room for it. Returns the file
+    /// pointer to the updated tuple, or `None` if there was no tuple to update.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple's slot.
+    /// * values - The new values of the tuple.
+    pub fn update_tuple(&mut self, pointer: &Literal, values: &TupleLiteral) -> Result<Option<Literal>, TupleError> {
+        let (page_no, slot_offset) = match *pointer {
+            Literal::FilePointer { page_no, offset } => (page_no, offset),
+            _ => return Ok(None),
+        };
+        let slot = slot_offset / 2 - 1;
+
+        let mut db_page = try!(load_dbpage(&mut self.db_file, page_no as u32, false));
+        let num_slots = try!(db_page.get_num_slots());
+        if slot >= num_slots || try!(db_page.get_slot_value(slot)) == EMPTY_SLOT {
+            try!(db_page.unpin());
+            return Ok(None);
+        }
+
+        // The old values' overflow pages are freed once the tuple has been rewritten, and the new
+        // large values are given overflow pages of their own.
+        let offset = try!(db_page.get_slot_value(slot));
+        let overflow_pages = try!(read_overflow_pages(&mut db_page, offset, &self.schema));
+        let (large_columns, _) = try!(self.get_large_columns(values));
+        let values = try!(self.store_large_values(values.clone(), &large_columns));
+
+        let new_size = try!(get_tuple_storage_size(self.schema.clone(), &mut values.clone()));
+        let old_size = try!(db_page.get_tuple_length(slot));
+        let free_space = try!(db_page.get_free_space());
+
+        if new_size > old_size && new_size - old_size > free_space {
+            // The tuple no longer fits in its page, so move it somewhere else.
+            debug!("Moving updated tuple out of page {}, slot {}.", page_no, slot);
+            try!(db_page.delete_tuple(slot));
+            try!(save_dbpage(&mut self.db_file, &mut db_page));
+            try!(self.update_free_space(&mut db_page));
+            try!(db_page.unpin());
+
+            let mut moved = try!(self.add_tuple(values));
+            try!(moved.unpin());
+            try!(self.free_overflow_pages(overflow_pages));
+            return Ok(moved.get_external_reference());
+        }
+
+        if new_size != old_size {
+            debug!("Resizing tuple at page {}, slot {} from {} to {} bytes.", page_no, slot, old_size, new_size);
+        }
+        let offset = try!(db_page.resize_tuple(slot, new_size));
+        try!(db_page.store_new_tuple(offset, self.schema.clone(), values));
+        try!(save_dbpage(&mut self.db_file, &mut db_page));
+        try!(self.update_free_space(&mut db_page));
+        try!(db_page.unpin());
+        try!(self.free_overflow_pages(overflow_pages));
+        Ok(Some(pointer.clone()))
     }
 
     /// Returns the first tuple in this table file, or `None` if there are no tuples in the file.
@@ -261,7 +639,12 @@ impl HeapTupleFile {
                 }
             }
             let mut db_page = page_result.unwrap();
-            let num_slots = try!(db_page.get_num_slots());
+            // Overflow pages and pages of the free-space map hold no tuples.
+            let num_slots = if try!(is_data_page(&mut db_page)) {
+                try!(db_page.get_num_slots())
+            } else {
+                0
+            };
 
             for slot in 0..num_slots {
                 let offset = try!(db_page.get_slot_value(slot));
@@ -321,7 +704,11 @@ impl HeapTupleFile {
         let mut next_slot = prev_slot + 1;
 
         loop {
-            let num_slots = try!(db_page.get_num_slots());
+            let num_slots = if try!(is_data_page(&mut db_page)) {
+                try!(db_page.get_num_slots())
+            } else {
+                0
+            };
 
             while next_slot < num_slots {
                 let next_offset = try!(db_page.get_slot_value(next_slot));
@@ -363,3 +750,162 @@ impl HeapTupleFile {
         Ok(None)
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::ColumnInfo;
+    use ::expressions::Decimal;
+    use ::storage::{DBFileType, FileManager};
+    use ::storage::storage_manager::buffer_manager;
+
+    fn add(file: &mut HeapTupleFile, i: i32) -> Literal {
+        let values = TupleLiteral::from_iter(vec![Literal::Int(i), Literal::String(format!("{:100}", i))]);
+        let mut tuple = file.add_tuple(values).unwrap();
+        tuple.unpin().unwrap();
+        tuple.get_external_reference().unwrap()
+    }
+
+    fn page_of(pointer: &Literal) -> u16 {
+        match *pointer {
+            Literal::FilePointer { page_no, .. } => page_no,
+            ref other => panic!("Unexpected pointer {}", other),
+        }
+    }
+
+    #[test]
+    fn test_inserts_reuse_free_space() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::Integer, "A"),
+            ColumnInfo::with_name(ColumnType::VarChar { length: 100 }, "B"),
+        ]).unwrap();
+        let mut file = HeapTupleFile::new(db_file, schema).unwrap();
+
+        let pointers: Vec<Literal> = (0..12).map(|i| add(&mut file, i)).collect();
+        let num_pages = get_num_pages(&file.db_file).unwrap();
+        assert!(pointers.iter().any(|pointer| page_of(pointer) > 3));
+
+        // Once the first data page's tuples are deleted, the free-space map points new tuples back
+        // to it instead of appending a page to the file.
+        for pointer in pointers.iter().filter(|pointer| page_of(pointer) == 1) {
+            assert!(file.delete_tuple(pointer).unwrap());
+        }
+        assert_eq!(1, page_of(&add(&mut file, 12)));
+        assert_eq!(num_pages, get_num_pages(&file.db_file).unwrap());
+
+        // Looking through the map leaves none of its pages pinned.
+        let path = file.db_file.file_info.path.clone().unwrap();
+        let fsm_page = file.free_space_map.get_first_page() as u32;
+        assert_eq!(Some(0), buffer_manager().get_pin_count(&(path, fsm_page)));
+    }
+
+    #[test]
+    fn test_overflow_pages_past_page_number_limit() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::Integer, "A"),
+            ColumnInfo::with_name(ColumnType::Text, "B"),
+        ]).unwrap();
+        let mut file = HeapTupleFile::new(db_file, schema).unwrap();
+
+        // Once every page number that fits in two bytes is used, a large value can't be moved out
+        // into new overflow pages.
+        let num_pages = u16::MAX as u64 + 1;
+        file.db_file.get_contents().set_len(num_pages * 512).unwrap();
+        let values = TupleLiteral::from_iter(vec![Literal::Int(1), Literal::String("x".repeat(200))]);
+        match file.add_tuple(values) {
+            Err(e) => assert_eq!(TupleError::FileTooBig(num_pages as u32), e),
+            Ok(_) => panic!("The value shouldn't have been stored"),
+        }
+        assert_eq!(num_pages as u32, get_num_pages(&file.db_file).unwrap());
+    }
+
+    #[test]
+    fn test_data_pages_past_page_number_limit() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::Integer, "A"),
+            ColumnInfo::with_name(ColumnType::VarChar { length: 100 }, "B"),
+        ]).unwrap();
+        let mut file = HeapTupleFile::new(db_file, schema).unwrap();
+        let pointer = add(&mut file, 0);
+
+        // Without room in the existing pages, the tuple would need a page whose number doesn't fit
+        // in a file-pointer.
+        let num_pages = u16::MAX as u64 + 1;
+        file.db_file.get_contents().set_len(num_pages * 512).unwrap();
+        for i in 1..4 {
+            add(&mut file, i);
+        }
+        let values = TupleLiteral::from_iter(vec![Literal::Int(4), Literal::String(format!("{:100}", 4))]);
+        match file.add_tuple(values) {
+            Err(e) => assert_eq!(TupleError::FileTooBig(num_pages as u32), e),
+            Ok(_) => panic!("The tuple shouldn't have been stored"),
+        }
+        assert_eq!(num_pages as u32, get_num_pages(&file.db_file).unwrap());
+
+        // Space freed in existing pages can still be used.
+        assert!(file.delete_tuple(&pointer).unwrap());
+        assert_eq!(page_of(&pointer), page_of(&add(&mut file, 4)));
+    }
+
+    #[test]
+    fn test_numeric_out_of_range() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let column_type = ColumnType::Numeric { scale: 2, precision: 4 };
+        let schema = Schema::with_columns(vec![ColumnInfo::with_name(column_type, "A")]).unwrap();
+        let mut file = HeapTupleFile::new(db_file, schema).unwrap();
+
+        let mut tuple = file.add_tuple(TupleLiteral::from_iter(vec![
+            Literal::Decimal(Decimal::new(1234, 2))])).unwrap();
+        assert_eq!(Ok(Literal::Decimal(Decimal::new(1234, 2))), tuple.get_column_value(0));
+        tuple.unpin().unwrap();
+
+        // 123.4 needs a precision of 5 at scale 2, so it is rejected rather than truncated.
+        let value = Literal::Decimal(Decimal::new(1234, 1));
+        match file.add_tuple(TupleLiteral::from_iter(vec![value.clone()])) {
+            Err(e) => assert_eq!(TupleError::ValueOutOfRange(value, column_type), e),
+            Ok(_) => panic!("The tuple shouldn't have been stored"),
+        }
+        let first = file.get_first_tuple().unwrap().unwrap();
+        assert!(file.get_next_tuple(&first).unwrap().is_none());
+    }
+
+    #[test]
+    fn test_analyze_skips_freed_pages() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let file_manager = FileManager::with_directory(dir.path()).unwrap();
+        let db_file = file_manager.create_dbfile("foo.tbl", DBFileType::HeapTupleFile, 512).unwrap();
+        let schema = Schema::with_columns(vec![
+            ColumnInfo::with_name(ColumnType::Integer, "A"),
+            ColumnInfo::with_name(ColumnType::Text, "B"),
+        ]).unwrap();
+        let mut file = HeapTupleFile::new(db_file, schema).unwrap();
+
+        let mut small = file.add_tuple(TupleLiteral::from_iter(vec![
+            Literal::Int(1), Literal::String("x".into())])).unwrap();
+        small.unpin().unwrap();
+        let mut large = file.add_tuple(TupleLiteral::from_iter(vec![
+            Literal::Int(2), Literal::String("y".repeat(5000))])).unwrap();
+        large.unpin().unwrap();
+        let pointer = large.get_external_reference().unwrap();
+        assert!(get_num_pages(&file.db_file).unwrap() > 10);
+
+        // The freed overflow pages are empty data pages, which don't hold any of the table's data.
+        assert!(file.delete_tuple(&pointer).unwrap());
+        let stats = file.analyze().unwrap();
+        assert_eq!(1, stats.num_tuples);
+        assert_eq!(1, stats.num_data_pages);
+    }
+}
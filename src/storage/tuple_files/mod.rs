@@ -3,6 +3,245 @@
 //! Tuple files can be stored in two different ways: via a heap storage method or a B-tree storage
 //! method.
 
+use ::Schema;
+use ::expressions::Literal;
+use ::relations::TableStats;
+use ::storage::{PinError, Pinnable, Tuple, TupleError, TupleLiteral};
+
+pub mod btree_tuple_file;
 pub mod heap_tuple_file;
 
+pub use self::btree_tuple_file::{BTreeFilePageTuple, BTreeTupleFile};
 pub use self::heap_tuple_file::{HeapFilePageTuple, HeapTupleFile};
+
+/// A tuple file of any of the supported storage methods.
+#[derive(Debug, PartialEq)]
+pub enum TupleFile {
+    /// A tuple file that stores its tuples in no particular order.
+    Heap(HeapTupleFile),
+    /// A tuple file that keeps its tuples ordered by a key.
+    BTree(BTreeTupleFile),
+}
+
+/// A tuple stored in a page of a [`TupleFile`](enum.TupleFile.html).
+pub enum FilePageTuple {
+    /// A tuple from a heap tuple file.
+    Heap(HeapFilePageTuple),
+    /// A tuple from a B<sup>+</sup> tree tuple file.
+    BTree(BTreeFilePageTuple),
+}
+
+impl TupleFile {
+    /// Returns the schema of tuples in the tuple file.
+    pub fn get_schema(&self) -> &Schema {
+        match *self {
+            TupleFile::Heap(ref file) => &file.schema,
+            TupleFile::BTree(ref file) => &file.schema,
+        }
+    }
+
+    /// Returns the statistics of the tuple file, as collected by the last call to
+    /// [`analyze`](#method.analyze).
+    pub fn get_stats(&self) -> &TableStats {
+        match *self {
+            TupleFile::Heap(ref file) => &file.stats,
+            TupleFile::BTree(ref file) => &file.stats,
+        }
+    }
+
+    /// Scans the tuple file to collect its statistics, storing them in the file's header page.
+    pub fn analyze(&mut self) -> Result<TableStats, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.analyze(),
+            TupleFile::BTree(ref mut file) => file.analyze(),
+        }
+    }
+
+    /// Adds the specified tuple into the tuple file, returning a new object corresponding to the
+    /// actual tuple added to the file.
+    ///
+    /// # Arguments
+    /// * tuple - a tuple object containing the values to add to the file
+    pub fn add_tuple<'a, T: Tuple + 'a>(&mut self, tuple: T) -> Result<Box<Tuple + 'a>, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.add_tuple(tuple),
+            TupleFile::BTree(ref mut file) => file.add_tuple(tuple),
+        }
+    }
+
+    /// Returns the first tuple in the tuple file, or `None` if there are no tuples in the file.
+    pub fn get_first_tuple(&mut self) -> Result<Option<FilePageTuple>, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.get_first_tuple().map(|t| t.map(FilePageTuple::Heap)),
+            TupleFile::BTree(ref mut file) => file.get_first_tuple().map(|t| t.map(FilePageTuple::BTree)),
+        }
+    }
+
+    /// Returns the tuple that the given file pointer refers to, as returned by the tuple's
+    /// `get_external_reference` method, or `None` if there is no tuple there.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple.
+    pub fn get_tuple(&mut self, pointer: &Literal) -> Result<Option<FilePageTuple>, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.get_tuple(pointer).map(|t| t.map(FilePageTuple::Heap)),
+            TupleFile::BTree(ref mut file) => file.get_tuple(pointer).map(|t| t.map(FilePageTuple::BTree)),
+        }
+    }
+
+    /// Deletes a tuple from the tuple file, returning whether it was found. Heap files find the
+    /// tuple by its file pointer, while B<sup>+</sup> tree files find a tuple with equal values,
+    /// since their tuples move around as pages are split and merged.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple, as returned by its
+    ///   `get_external_reference` method.
+    /// * values - The values of the tuple.
+    pub fn delete_tuple(&mut self, pointer: &Literal, values: &TupleLiteral) -> Result<bool, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.delete_tuple(pointer),
+            TupleFile::BTree(ref mut file) => file.delete_tuple(values),
+        }
+    }
+
+    /// Replaces the values of a tuple in the tuple file, returning the file pointer to the updated
+    /// tuple, or `None` if the tuple wasn't found. Heap files update the tuple that the file pointer
+    /// refers to, while B<sup>+</sup> tree files delete a tuple with the old values and add the new
+    /// values, since the tuple's key may have changed.
+    ///
+    /// # Arguments
+    /// * pointer - A `Literal::FilePointer` to the tuple, as returned by its
+    ///   `get_external_reference` method.
+    /// * old_values - The current values of the tuple.
+    /// * new_values - The new values of the tuple.
+    pub fn update_tuple(&mut self,
+                        pointer: &Literal,
+                        old_values: &TupleLiteral,
+                        new_values: &TupleLiteral)
+                        -> Result<Option<Literal>, TupleError> {
+        match *self {
+            TupleFile::Heap(ref mut file) => file.update_tuple(pointer, new_values),
+            TupleFile::BTree(ref mut file) => {
+                if !try!(file.delete_tuple(old_values)) {
+                    return Ok(None);
+                }
+                let mut added = try!(file.add_tuple(new_values.clone()));
+                try!(added.unpin());
+                Ok(added.get_external_reference())
+            }
+        }
+    }
+
+    /// Returns the tuple that follows the specified tuple, or `None` if there are no more tuples in
+    /// the file.
+    ///
+    /// # Panics
+    /// This panics if the tuple did not come from a tuple file of the same kind.
+    pub fn get_next_tuple(&mut self, cur_tuple: &FilePageTuple) -> Result<Option<FilePageTuple>, TupleError> {
+        match (self, cur_tuple) {
+            (&mut TupleFile::Heap(ref mut file), &FilePageTuple::Heap(ref tuple)) => {
+                file.get_next_tuple(tuple).map(|t| t.map(FilePageTuple::Heap))
+            }
+            (&mut TupleFile::BTree(ref mut file), &FilePageTuple::BTree(ref tuple)) => {
+                file.get_next_tuple(tuple).map(|t| t.map(FilePageTuple::BTree))
+            }
+            _ => panic!("The tuple does not belong to this kind of tuple file."),
+        }
+    }
+}
+
+impl Pinnable for FilePageTuple {
+    fn pin(&mut self) {
+        match *self {
+            FilePageTuple::Heap(ref mut tuple) => tuple.pin(),
+            FilePageTuple::BTree(ref mut tuple) => tuple.pin(),
+        }
+    }
+
+    fn unpin(&mut self) -> Result<(), PinError> {
+        match *self {
+            FilePageTuple::Heap(ref mut tuple) => tuple.unpin(),
+            FilePageTuple::BTree(ref mut tuple) => tuple.unpin(),
+        }
+    }
+
+    fn get_pin_count(&self) -> u32 {
+        match *self {
+            FilePageTuple::Heap(ref tuple) => tuple.get_pin_count(),
+            FilePageTuple::BTree(ref tuple) => tuple.get_pin_count(),
+        }
+    }
+}
+
+impl Tuple for FilePageTuple {
+    fn is_disk_backed(&self) -> bool {
+        match *self {
+            FilePageTuple::Heap(ref tuple) => tuple.is_disk_backed(),
+            FilePageTuple::BTree(ref tuple) => tuple.is_disk_backed(),
+        }
+    }
+
+    fn is_null_value(&self, col_index: usize) -> Result<bool, TupleError> {
+        match *self {
+            FilePageTuple::Heap(ref tuple) => tuple.is_null_value(col_index),
+            FilePageTuple::BTree(ref tuple) => tuple.is_null_value(col_index),
+        }
+    }
+
+    fn get_column_count(&self) -> usize {
+        match *self {
+            FilePageTuple::Heap(ref tuple) => tuple.get_column_count(),
+            FilePageTuple::BTree(ref tuple) => tuple.get_column_count(),
+        }
+    }
+
+    fn get_column_value(&mut self, col_index: usize) -> Result<Literal, TupleError> {
+        match *self {
+            FilePageTuple::Heap(ref mut tuple) => tuple.get_column_value(col_index),
+            FilePageTuple::BTree(ref mut tuple) => tuple.get_column_value(col_index),
+        }
+    }
+
+    fn get_external_reference(&self) -> Option<Literal> {
+        match *self {
+            FilePageTuple::Heap(ref tuple) => tuple.get_external_reference(),
+            FilePageTuple::BTree(ref tuple) => tuple.get_external_reference(),
+        }
+    }
+}
+
+impl<'a> Tuple for &'a mut FilePageTuple {
+    fn is_disk_backed(&self) -> bool {
+        (**self).is_disk_backed()
+    }
+
+    fn is_null_value(&self, col_index: usize) -> Result<bool, TupleError> {
+        (**self).is_null_value(col_index)
+    }
+
+    fn get_column_count(&self) -> usize {
+        (**self).get_column_count()
+    }
+
+    fn get_column_value(&mut self, col_index: usize) -> Result<Literal, TupleError> {
+        (**self).get_column_value(col_index)
+    }
+
+    fn get_external_reference(&self) -> Option<Literal> {
+        (**self).get_external_reference()
+    }
+}
+
+impl<'a> Pinnable for &'a mut FilePageTuple {
+    fn pin(&mut self) {
+        (**self).pin()
+    }
+
+    fn unpin(&mut self) -> Result<(), PinError> {
+        (**self).unpin()
+    }
+
+    fn get_pin_count(&self) -> u32 {
+        (**self).get_pin_count()
+    }
+}
@@ -78,6 +78,15 @@ impl TupleLiteral {
         }
     }
 
+    /// Replaces the value of a column in the tuple-literal.
+    ///
+    /// # Arguments
+    /// * col_index - The index of the column.
+    /// * value - The new value. This is allowed to be `NULL`.
+    pub fn set_value(&mut self, col_index: usize, value: Literal) {
+        self.values[col_index] = value;
+    }
+
     /// The size of the tuple literal.
     pub fn len(&self) -> usize { self.values.len() }
 }
@@ -158,6 +167,11 @@ impl From<TupleLiteral> for Vec<String> {
                 Literal::String(ref s) => {
                     result.push(s.clone());
                 }
+                // Dates and times are shown without their type name.
+                Literal::Date(d) => result.push(format!("{}", d)),
+                Literal::Time(t) => result.push(format!("{}", t)),
+                Literal::Timestamp(t) => result.push(format!("{}", t)),
+                Literal::Interval(i) => result.push(format!("{}", i)),
                 _ => {
                     result.push(format!("{}", tl.values[i]));
                 }
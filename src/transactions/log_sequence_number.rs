@@ -0,0 +1,81 @@
+//! This module provides the log sequence number, which identifies a record in the write-ahead log.
+
+use std::cmp::Ordering;
+use std::fmt;
+
+use byteorder::{BigEndian, ByteOrder};
+
+/// The number of bytes at the end of every data page that hold the LSN of the last logged change to
+/// the page. Tuple-file formats must not store anything in this part of a page.
+pub const PAGE_LSN_SIZE: usize = 6;
+
+/// A log sequence number (LSN) identifies a record in the write-ahead log, by the number of the log
+/// file the record is in and the offset of the record in that file. LSNs are totally ordered, so
+/// that a later record in the log always has a larger LSN.
+///
+/// The LSN with file number 0 and offset 0 never refers to a record (offset 0 is where the log
+/// file's header is), so it is used to mean "no LSN".
+#[derive(Clone, Copy, Debug, Default, Eq, Hash, PartialEq)]
+pub struct LogSequenceNumber {
+    /// The number of the log file the record is stored in.
+    pub log_file_no: u16,
+    /// The offset of the start of the record in the log file.
+    pub file_offset: u32,
+}
+
+impl LogSequenceNumber {
+    /// Instantiate a new log sequence number.
+    ///
+    /// # Arguments
+    /// * log_file_no - The number of the log file the record is stored in.
+    /// * file_offset - The offset of the start of the record in the log file.
+    pub fn new(log_file_no: u16, file_offset: u32) -> LogSequenceNumber {
+        LogSequenceNumber {
+            log_file_no: log_file_no,
+            file_offset: file_offset,
+        }
+    }
+
+    /// Checks whether this is the "no LSN" value, i.e. it doesn't refer to a log record.
+    pub fn is_none(&self) -> bool {
+        self.log_file_no == 0 && self.file_offset == 0
+    }
+
+    /// Reads the LSN stored at the end of a page's data.
+    ///
+    /// # Arguments
+    /// * page_data - The data of the page.
+    pub fn read_from_page(page_data: &[u8]) -> LogSequenceNumber {
+        let start = page_data.len() - PAGE_LSN_SIZE;
+        LogSequenceNumber::new(BigEndian::read_u16(&page_data[start..]),
+                               BigEndian::read_u32(&page_data[(start + 2)..]))
+    }
+
+    /// Stores the LSN at the end of a page's data.
+    ///
+    /// # Arguments
+    /// * page_data - The data of the page.
+    pub fn write_to_page(&self, page_data: &mut [u8]) {
+        let start = page_data.len() - PAGE_LSN_SIZE;
+        BigEndian::write_u16(&mut page_data[start..], self.log_file_no);
+        BigEndian::write_u32(&mut page_data[(start + 2)..], self.file_offset);
+    }
+}
+
+impl Ord for LogSequenceNumber {
+    fn cmp(&self, other: &LogSequenceNumber) -> Ordering {
+        (self.log_file_no, self.file_offset).cmp(&(other.log_file_no, other.file_offset))
+    }
+}
+
+impl PartialOrd for LogSequenceNumber {
+    fn partial_cmp(&self, other: &LogSequenceNumber) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl fmt::Display for LogSequenceNumber {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "LSN[{:05}:{:08}]", self.log_file_no, self.file_offset)
+    }
+}
@@ -0,0 +1,89 @@
+//! This module contains the classes for transaction processing and recovery.
+//!
+//! Every change to a data page made within a transaction is recorded in the write-ahead log (WAL)
+//! before the page can be written back to its file. Each log record of a page change holds both
+//! the old and the new bytes of the changed parts of the page, so the change can be redone or
+//! undone. The LSN of the last logged change to a page is stored in the last
+//! [`PAGE_LSN_SIZE`](constant.PAGE_LSN_SIZE.html) bytes of the page, and the buffer manager forces
+//! the log up to that LSN before writing the page to disk.
+//!
+//! When a transaction commits, the log is forced to disk, and the LSN of its commit record is saved
+//! in the data directory's [transaction-state file](transaction_state_file/index.html) along with
+//! the ID of the next transaction. When the server starts, the log is used to recover the data
+//! files in the ARIES style: every logged change is redone (unless the page already holds it), and
+//! then the changes of transactions that never committed are undone in reverse order.
+//!
+//! So that the log doesn't grow forever, a checkpoint is taken when a commit leaves no transaction
+//! in progress and enough has been logged since the last one: every cached page is written to its
+//! file, and then the log files are removed, since recovery will never need them.
+
+pub mod log_sequence_number;
+pub mod transaction_manager;
+pub mod transaction_state_file;
+pub mod write_ahead_log;
+
+pub use self::log_sequence_number::{LogSequenceNumber, PAGE_LSN_SIZE};
+pub use self::transaction_manager::TransactionManager;
+pub use self::transaction_state_file::TransactionStateFile;
+pub use self::write_ahead_log::{PageSegment, WALManager, WALRecord, WALRecordType};
+
+use std::error::Error as ErrorTrait;
+use std::io;
+
+use ::storage::file_manager;
+
+/// An error that can occur while logging changes or managing transactions.
+#[derive(Clone, Debug, PartialEq)]
+pub enum Error {
+    /// Some I/O error occurred.
+    IOError(String),
+    /// A write-ahead log file could not be read, because it doesn't have a log file's header.
+    InvalidWALFile(String),
+    /// The transaction-state file could not be read, because it doesn't have the right header.
+    InvalidTxnStateFile(String),
+    /// The log record at the given LSN has a type that doesn't exist.
+    InvalidRecordType(LogSequenceNumber, u8),
+    /// A data file could not be accessed during rollback or recovery.
+    FileManagerError(file_manager::Error),
+    /// Transaction processing was never started for the given data directory.
+    NotStarted(String),
+    /// A transaction was started while another one is in progress.
+    TransactionInProgress,
+    /// A transaction was committed or rolled back, but none is in progress.
+    NoTransactionInProgress,
+}
+
+impl From<io::Error> for Error {
+    fn from(e: io::Error) -> Error {
+        Error::IOError(e.description().into())
+    }
+}
+
+impl From<file_manager::Error> for Error {
+    fn from(error: file_manager::Error) -> Error {
+        Error::FileManagerError(error)
+    }
+}
+
+impl ::std::fmt::Display for Error {
+    fn fmt(&self, f: &mut ::std::fmt::Formatter) -> ::std::fmt::Result {
+        match *self {
+            Error::IOError(ref e) => write!(f, "An IO error occurred: {}", e),
+            Error::InvalidWALFile(ref filename) => {
+                write!(f, "The file {} is not a write-ahead log file.", filename)
+            }
+            Error::InvalidTxnStateFile(ref filename) => {
+                write!(f, "The file {} is not a transaction-state file.", filename)
+            }
+            Error::InvalidRecordType(lsn, record_type) => {
+                write!(f, "The log record at {} has invalid type {}.", lsn, record_type)
+            }
+            Error::FileManagerError(ref e) => write!(f, "{}", e),
+            Error::NotStarted(ref dir) => {
+                write!(f, "Transaction processing has not been started for directory {}.", dir)
+            }
+            Error::TransactionInProgress => write!(f, "A transaction is already in progress."),
+            Error::NoTransactionInProgress => write!(f, "No transaction is in progress."),
+        }
+    }
+}
@@ -0,0 +1,703 @@
+//! This module provides the transaction manager, which tracks the current transaction of a data
+//! directory, logs its changes to pages, and performs rollback and recovery.
+//!
+//! There is one transaction manager for each data directory, kept in a global map like the buffer
+//! manager, since pages are logged when they are saved through the
+//! [`storage_manager`](../../storage/storage_manager/index.html). Changes to pages are only logged
+//! while a transaction is in progress.
+//!
+//! To avoid deadlocks, the transaction managers are never locked while the buffer manager is
+//! being called: the buffer manager forces the log while it is locked, when it writes pages back.
+
+use std::collections::{BTreeMap, HashMap};
+use std::fs::{self, File};
+use std::path::{Path, PathBuf};
+use std::sync::{Mutex, MutexGuard};
+
+use ::storage::{DBFile, FileManager, Pinnable, file_manager};
+use ::storage::storage_manager::{flush_all_pages, load_dbpage, save_dbpage};
+use ::transactions::{Error, LogSequenceNumber, PageSegment, TransactionStateFile, WALManager, WALRecord};
+use ::transactions::write_ahead_log::{MAX_WAL_FILE_SIZE, OFFSET_FIRST_RECORD, find_wal_files, get_wal_file_name};
+
+/// Once this many bytes have been logged since the last checkpoint, a checkpoint is taken when the
+/// next transaction commits.
+pub const CHECKPOINT_LOG_SIZE: u64 = 4 * 1024 * 1024;
+
+lazy_static! {
+    static ref TRANSACTION_MANAGERS: Mutex<HashMap<PathBuf, TransactionManager>> = Mutex::new(HashMap::new());
+}
+
+fn transaction_managers() -> MutexGuard<'static, HashMap<PathBuf, TransactionManager>> {
+    match TRANSACTION_MANAGERS.lock() {
+        Ok(guard) => guard,
+        Err(poisoned) => poisoned.into_inner(),
+    }
+}
+
+/// Runs a function on the transaction manager of a data directory.
+fn with_manager<P, F, T>(dir: P, f: F) -> Result<T, Error>
+    where P: AsRef<Path>,
+          F: FnOnce(&mut TransactionManager) -> Result<T, Error>
+{
+    let mut managers = transaction_managers();
+    match managers.get_mut(dir.as_ref()) {
+        Some(manager) => f(manager),
+        None => Err(Error::NotStarted(dir.as_ref().display().to_string())),
+    }
+}
+
+/// Returns the data directory that a data file is stored in.
+fn get_data_dir(file_path: &Path) -> &Path {
+    file_path.parent().unwrap_or_else(|| Path::new(""))
+}
+
+#[derive(Clone, Copy, Debug, PartialEq)]
+enum TransactionStatus {
+    Active,
+    RollingBack,
+}
+
+#[derive(Debug)]
+struct TransactionInfo {
+    txn_id: u32,
+    status: TransactionStatus,
+    /// The LSN of the last record the transaction wrote, or "no LSN" if it hasn't changed anything.
+    last_lsn: LogSequenceNumber,
+}
+
+/// The transaction manager of a data directory.
+#[derive(Debug)]
+pub struct TransactionManager {
+    wal: WALManager,
+    state: TransactionStateFile,
+    current_txn: Option<TransactionInfo>,
+    checkpoint_size: u64,
+}
+
+impl TransactionManager {
+    /// Instantiate a new transaction manager.
+    ///
+    /// # Arguments
+    /// * dir - The data directory.
+    /// * next_lsn - The LSN the next log record will be written at.
+    /// * state - The transaction state of the data directory.
+    pub fn new<P: AsRef<Path>>(dir: P, next_lsn: LogSequenceNumber, state: TransactionStateFile) -> TransactionManager {
+        TransactionManager {
+            wal: WALManager::new(dir, next_lsn),
+            state: state,
+            current_txn: None,
+            checkpoint_size: CHECKPOINT_LOG_SIZE,
+        }
+    }
+
+    /// Returns the ID of the transaction in progress, if there is one.
+    pub fn get_current_txn_id(&self) -> Option<u32> {
+        self.current_txn.as_ref().map(|txn| txn.txn_id)
+    }
+
+    /// Returns the number of bytes logged since recovery last needed to start reading the log.
+    fn get_log_size(&self) -> u64 {
+        let first_lsn = self.state.first_lsn;
+        let next_lsn = self.wal.get_next_lsn();
+        if next_lsn <= first_lsn {
+            return 0;
+        }
+        (next_lsn.log_file_no - first_lsn.log_file_no) as u64 * MAX_WAL_FILE_SIZE as u64 +
+        next_lsn.file_offset as u64 - first_lsn.file_offset as u64
+    }
+
+    fn begin(&mut self) -> Result<u32, Error> {
+        if self.current_txn.is_some() {
+            return Err(Error::TransactionInProgress);
+        }
+        let txn_id = self.state.next_txn_id;
+        self.state.next_txn_id += 1;
+        debug!("Starting transaction {}.", txn_id);
+        self.current_txn = Some(TransactionInfo {
+            txn_id: txn_id,
+            status: TransactionStatus::Active,
+            last_lsn: LogSequenceNumber::default(),
+        });
+        Ok(txn_id)
+    }
+
+    /// Logs the change to a page made by the current transaction, returning the LSN of the log
+    /// record, or `None` if nothing was logged. The start record of the transaction is written
+    /// before its first change.
+    fn log_page_update(&mut self,
+                       file_name: String,
+                       page_no: u32,
+                       before: &[u8],
+                       after: &[u8])
+                       -> Result<Option<LogSequenceNumber>, Error> {
+        let txn = match self.current_txn {
+            Some(ref mut txn) => txn,
+            None => return Ok(None),
+        };
+        let mut segments = PageSegment::diff(before, after);
+        if segments.is_empty() {
+            return Ok(None);
+        }
+
+        if txn.last_lsn.is_none() {
+            txn.last_lsn = try!(self.wal.write_record(&WALRecord::StartTxn { txn_id: txn.txn_id }));
+        }
+        // Changes made while rolling back are never undone themselves.
+        let redo_only = txn.status == TransactionStatus::RollingBack;
+        if redo_only {
+            for segment in segments.iter_mut() {
+                segment.before.clear();
+            }
+        }
+        let lsn = try!(self.wal.write_record(&WALRecord::UpdatePage {
+            txn_id: txn.txn_id,
+            prev_lsn: txn.last_lsn,
+            redo_only: redo_only,
+            file_name: file_name,
+            page_no: page_no,
+            segments: segments,
+        }));
+        txn.last_lsn = lsn;
+        Ok(Some(lsn))
+    }
+
+    /// Ends the current transaction, writing its commit or abort record if it changed anything.
+    /// When a transaction commits, the log is forced to disk and the transaction state is saved.
+    fn end(&mut self, commit: bool) -> Result<(), Error> {
+        let txn = match self.current_txn.take() {
+            Some(txn) => txn,
+            None => return Err(Error::NoTransactionInProgress),
+        };
+        debug!("Ending transaction {} ({}).", txn.txn_id, if commit { "commit" } else { "abort" });
+        if txn.last_lsn.is_none() {
+            return Ok(());
+        }
+
+        let record = if commit {
+            WALRecord::CommitTxn { txn_id: txn.txn_id, prev_lsn: txn.last_lsn }
+        } else {
+            WALRecord::AbortTxn { txn_id: txn.txn_id, prev_lsn: txn.last_lsn }
+        };
+        let lsn = try!(self.wal.write_record(&record));
+        if commit {
+            try!(self.wal.force(lsn));
+            self.state.last_committed_lsn = lsn;
+            try!(self.state.save());
+        }
+        Ok(())
+    }
+}
+
+/// Applies the logged changes to a page of a data file. When redoing, the change is only applied
+/// if the page doesn't already hold it, and the page's LSN is set to the LSN of the change. When
+/// undoing, the old bytes are restored, which logs a redo-only change since the current
+/// transaction is rolling back.
+fn apply_page_update(file_manager: &FileManager,
+                     files: &mut HashMap<String, DBFile<File>>,
+                     file_name: &str,
+                     page_no: u32,
+                     segments: &[PageSegment],
+                     redo_lsn: Option<LogSequenceNumber>)
+                     -> Result<(), Error> {
+    if !files.contains_key(file_name) {
+        if !file_manager.dbfile_exists(file_name) {
+            warn!("Skipping logged change to page {} of missing file {}.", page_no, file_name);
+            return Ok(());
+        }
+        files.insert(file_name.into(), try!(file_manager.open_dbfile(file_name)));
+    }
+    let dbfile = files.get_mut(file_name).unwrap();
+
+    let mut page = try!(load_dbpage(dbfile, page_no, true));
+    if let Some(lsn) = redo_lsn {
+        if page.get_page_lsn() >= lsn {
+            try!(page.unpin().map_err(file_manager::Error::from));
+            return Ok(());
+        }
+    }
+    for segment in segments.iter() {
+        let data = if redo_lsn.is_some() { &segment.after } else { &segment.before };
+        try!(page.write_at_position(segment.offset as usize, data)
+            .map_err(|_| Error::FileManagerError(file_manager::Error::PageSaveError)));
+    }
+    if let Some(lsn) = redo_lsn {
+        page.set_page_lsn(lsn);
+    }
+    try!(save_dbpage(dbfile, &mut page));
+    try!(page.unpin().map_err(file_manager::Error::from));
+    Ok(())
+}
+
+/// Undoes the changes of the current transaction, following its chain of log records backwards
+/// from the given LSN until reaching `stop_lsn`.
+fn undo_changes(dir: &Path, last_lsn: LogSequenceNumber, stop_lsn: LogSequenceNumber) -> Result<(), Error> {
+    let file_manager = try!(FileManager::with_directory(dir));
+    let mut files = HashMap::new();
+    let mut lsn = last_lsn;
+    while lsn > stop_lsn {
+        let record = try!(with_manager(dir, |manager| manager.wal.read_record(lsn)));
+        if let WALRecord::UpdatePage { redo_only: false, ref file_name, page_no, ref segments, .. } = record {
+            debug!("Undoing change to page {} of {} at {}.", page_no, file_name, lsn);
+            try!(apply_page_update(&file_manager, &mut files, file_name, page_no, segments, None));
+        }
+        lsn = record.get_prev_lsn();
+    }
+    Ok(())
+}
+
+/// Rolls back the current transaction of a data directory, and ends it.
+fn rollback(dir: &Path) -> Result<(), Error> {
+    let last_lsn = try!(with_manager(dir, |manager| {
+        match manager.current_txn {
+            Some(ref mut txn) => {
+                debug!("Rolling back transaction {}.", txn.txn_id);
+                txn.status = TransactionStatus::RollingBack;
+                Ok(txn.last_lsn)
+            }
+            None => Err(Error::NoTransactionInProgress),
+        }
+    }));
+    try!(undo_changes(dir, last_lsn, LogSequenceNumber::default()));
+    with_manager(dir, |manager| manager.end(false))
+}
+
+/// Writes every cached page to its data file, and makes sure the data files of a directory are on
+/// disk.
+fn flush_data_files(dir: &Path) -> Result<(), Error> {
+    try!(flush_all_pages());
+    for entry in try!(fs::read_dir(dir)) {
+        let path = try!(entry).path();
+        if path.is_file() {
+            try!(try!(File::open(path)).sync_all());
+        }
+    }
+    Ok(())
+}
+
+/// Starts up transaction processing for a data directory, recovering the data files from the
+/// write-ahead log if there is one.
+///
+/// Recovery reads the log from the first LSN in the transaction-state file. It first redoes every
+/// logged change that isn't already in its page, and then rolls back the transactions that didn't
+/// commit or abort. Once every page is safely on disk, the old log files are removed, and the
+/// transaction-state file is updated so that the next recovery starts from a new log file.
+///
+/// # Arguments
+/// * dir - The data directory.
+pub fn start_up<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
+    let dir = dir.as_ref();
+    let mut state = match try!(TransactionStateFile::open(dir)) {
+        Some(state) => state,
+        None => TransactionStateFile::new(dir, 1, LogSequenceNumber::new(0, OFFSET_FIRST_RECORD)),
+    };
+
+    let file_nos = try!(find_wal_files(dir));
+    let (records, next_lsn) = match file_nos.last() {
+        Some(&last_file_no) => {
+            let wal = WALManager::new(dir, LogSequenceNumber::default());
+            let recovery_files: Vec<u16> = file_nos.iter()
+                .cloned()
+                .filter(|&file_no| file_no >= state.first_lsn.log_file_no)
+                .collect();
+            let (mut records, end_lsn) = try!(wal.read_all_records(&recovery_files));
+            if end_lsn <= state.last_committed_lsn {
+                warn!("The write-ahead log ends at {}, before the last commit at {}.",
+                      end_lsn,
+                      state.last_committed_lsn);
+            }
+            records.retain(|&(lsn, _)| lsn >= state.first_lsn);
+            (records, LogSequenceNumber::new(last_file_no + 1, OFFSET_FIRST_RECORD))
+        }
+        None => (Vec::new(), state.first_lsn),
+    };
+
+    let mut incomplete_txns = BTreeMap::new();
+    for &(lsn, ref record) in records.iter() {
+        let txn_id = record.get_txn_id();
+        state.next_txn_id = ::std::cmp::max(state.next_txn_id, txn_id + 1);
+        match *record {
+            WALRecord::CommitTxn { .. } |
+            WALRecord::AbortTxn { .. } => incomplete_txns.remove(&txn_id),
+            _ => incomplete_txns.insert(txn_id, lsn),
+        };
+    }
+    transaction_managers().insert(dir.to_path_buf(), TransactionManager::new(dir, next_lsn, state));
+    if file_nos.is_empty() {
+        return with_manager(dir, |manager| manager.state.save());
+    }
+
+    info!("Recovering from {} write-ahead log records.", records.len());
+    let file_manager = try!(FileManager::with_directory(dir));
+    let mut files = HashMap::new();
+    for &(lsn, ref record) in records.iter() {
+        if let WALRecord::UpdatePage { ref file_name, page_no, ref segments, .. } = *record {
+            try!(apply_page_update(&file_manager, &mut files, file_name, page_no, segments, Some(lsn)));
+        }
+    }
+
+    for (&txn_id, &last_lsn) in incomplete_txns.iter() {
+        info!("Rolling back incomplete transaction {}.", txn_id);
+        try!(with_manager(dir, |manager| {
+            manager.current_txn = Some(TransactionInfo {
+                txn_id: txn_id,
+                status: TransactionStatus::Active,
+                last_lsn: last_lsn,
+            });
+            Ok(())
+        }));
+        try!(rollback(dir));
+    }
+    try!(with_manager(dir, |manager| manager.wal.force_all()));
+
+    // Every recovered page must be durable before the old log can be removed.
+    try!(flush_data_files(dir));
+    try!(with_manager(dir, |manager| {
+        manager.state.first_lsn = next_lsn;
+        manager.state.save()
+    }));
+    for &file_no in file_nos.iter() {
+        try!(fs::remove_file(dir.join(get_wal_file_name(file_no))));
+    }
+    Ok(())
+}
+
+/// Starts a new transaction in a data directory, returning its ID.
+///
+/// # Arguments
+/// * dir - The data directory.
+///
+/// # Errors
+/// This will fail if a transaction is already in progress.
+pub fn begin_transaction<P: AsRef<Path>>(dir: P) -> Result<u32, Error> {
+    with_manager(dir, |manager| manager.begin())
+}
+
+/// Commits the current transaction of a data directory, forcing its log records to disk.
+///
+/// # Arguments
+/// * dir - The data directory.
+///
+/// # Errors
+/// This will fail if no transaction is in progress, or the log couldn't be written.
+pub fn commit_transaction<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
+    let dir = dir.as_ref();
+    let needs_checkpoint = try!(with_manager(dir, |manager| {
+        try!(manager.end(true));
+        Ok(manager.get_log_size() >= manager.checkpoint_size)
+    }));
+    if needs_checkpoint {
+        try!(checkpoint(dir));
+    }
+    Ok(())
+}
+
+/// Takes a checkpoint of a data directory when no transaction is in progress: every cached page is
+/// written to disk, and then the write-ahead log is removed, since none of its changes need to be
+/// redone or undone anymore. Returns whether the log was removed, which doesn't happen if a
+/// transaction is in progress or starts while the pages are being written.
+///
+/// # Arguments
+/// * dir - The data directory.
+pub fn checkpoint<P: AsRef<Path>>(dir: P) -> Result<bool, Error> {
+    let dir = dir.as_ref();
+    let start_lsn = try!(with_manager(dir, |manager| {
+        if manager.current_txn.is_some() || manager.get_log_size() == 0 {
+            Ok(None)
+        } else {
+            Ok(Some(manager.wal.get_next_lsn()))
+        }
+    }));
+    let start_lsn = match start_lsn {
+        Some(lsn) => lsn,
+        None => return Ok(false),
+    };
+
+    // The buffer manager forces the log while writing pages, so the transaction manager can't be
+    // locked here.
+    try!(flush_data_files(dir));
+
+    with_manager(dir, |manager| {
+        // Anything logged while the pages were being written may not be on disk yet.
+        if manager.current_txn.is_some() || manager.wal.get_next_lsn() != start_lsn {
+            return Ok(false);
+        }
+        info!("Checkpointing the write-ahead log at {}.", start_lsn);
+        let next_lsn = LogSequenceNumber::new(start_lsn.log_file_no + 1, OFFSET_FIRST_RECORD);
+        manager.wal = WALManager::new(dir, next_lsn);
+        manager.state.first_lsn = next_lsn;
+        try!(manager.state.save());
+        for file_no in try!(find_wal_files(dir)) {
+            if file_no < next_lsn.log_file_no {
+                try!(fs::remove_file(dir.join(get_wal_file_name(file_no))));
+            }
+        }
+        Ok(true)
+    })
+}
+
+/// Rolls back the current transaction of a data directory, undoing all of its changes to pages.
+///
+/// # Arguments
+/// * dir - The data directory.
+///
+/// # Errors
+/// This will fail if no transaction is in progress, or the changes couldn't be undone.
+pub fn rollback_transaction<P: AsRef<Path>>(dir: P) -> Result<(), Error> {
+    rollback(dir.as_ref())
+}
+
+/// Checks whether a transaction is in progress in a data directory.
+///
+/// # Arguments
+/// * dir - The data directory.
+pub fn is_in_transaction<P: AsRef<Path>>(dir: P) -> bool {
+    let managers = transaction_managers();
+    managers.get(dir.as_ref()).map(|manager| manager.current_txn.is_some()) == Some(true)
+}
+
+/// Returns a savepoint in the current transaction of a data directory, which the transaction can
+/// later be rolled back to with [`rollback_to_savepoint`](fn.rollback_to_savepoint.html). The
+/// savepoint is the LSN of the last record the transaction wrote.
+///
+/// # Arguments
+/// * dir - The data directory.
+///
+/// # Errors
+/// This will fail if no transaction is in progress.
+pub fn get_savepoint<P: AsRef<Path>>(dir: P) -> Result<LogSequenceNumber, Error> {
+    with_manager(dir, |manager| {
+        match manager.current_txn {
+            Some(ref txn) => Ok(txn.last_lsn),
+            None => Err(Error::NoTransactionInProgress),
+        }
+    })
+}
+
+/// Undoes the changes the current transaction of a data directory made after a savepoint. The
+/// transaction stays in progress.
+///
+/// # Arguments
+/// * dir - The data directory.
+/// * savepoint - The savepoint to roll back to.
+///
+/// # Errors
+/// This will fail if no transaction is in progress, or the changes couldn't be undone.
+pub fn rollback_to_savepoint<P: AsRef<Path>>(dir: P, savepoint: LogSequenceNumber) -> Result<(), Error> {
+    let dir = dir.as_ref();
+    let set_status = |status: TransactionStatus| {
+        with_manager(dir, |manager| {
+            match manager.current_txn {
+                Some(ref mut txn) => {
+                    txn.status = status;
+                    Ok(txn.last_lsn)
+                }
+                None => Err(Error::NoTransactionInProgress),
+            }
+        })
+    };
+
+    let last_lsn = try!(set_status(TransactionStatus::RollingBack));
+    debug!("Rolling back to savepoint {}.", savepoint);
+    try!(undo_changes(dir, last_lsn, savepoint));
+    set_status(TransactionStatus::Active).map(|_| ())
+}
+
+/// Checks whether changes to a data file are currently being logged, i.e. whether a transaction is
+/// in progress in the file's data directory.
+///
+/// # Arguments
+/// * file_path - The path of the data file.
+pub fn is_logging<P: AsRef<Path>>(file_path: P) -> bool {
+    let managers = transaction_managers();
+    managers.get(get_data_dir(file_path.as_ref()))
+        .map(|manager| manager.current_txn.is_some()) == Some(true)
+}
+
+/// Logs a change to a page of a data file, if a transaction is in progress. Returns the LSN of the
+/// log record, which should be stored in the page, or `None` if nothing was logged.
+///
+/// # Arguments
+/// * file_path - The path of the data file.
+/// * page_no - The number of the changed page.
+/// * before - The data of the page before the change.
+/// * after - The data of the page after the change.
+pub fn log_page_update<P: AsRef<Path>>(file_path: P,
+                                       page_no: u32,
+                                       before: &[u8],
+                                       after: &[u8])
+                                       -> Result<Option<LogSequenceNumber>, Error> {
+    let file_path = file_path.as_ref();
+    let file_name = match file_path.file_name() {
+        Some(name) => name.to_string_lossy().into_owned(),
+        None => return Ok(None),
+    };
+    let mut managers = transaction_managers();
+    match managers.get_mut(get_data_dir(file_path)) {
+        Some(manager) => manager.log_page_update(file_name, page_no, before, after),
+        None => Ok(None),
+    }
+}
+
+/// Forces the write-ahead log of a data file's directory to disk up to the given LSN. This must be
+/// done before a page is written to the data file, so that the page's changes can be undone.
+///
+/// # Arguments
+/// * file_path - The path of the data file.
+/// * lsn - The LSN stored in the page.
+pub fn force_wal<P: AsRef<Path>>(file_path: P, lsn: LogSequenceNumber) -> Result<(), Error> {
+    if lsn.is_none() {
+        return Ok(());
+    }
+    let mut managers = transaction_managers();
+    match managers.get_mut(get_data_dir(file_path.as_ref())) {
+        Some(manager) => manager.wal.force(lsn),
+        None => Ok(()),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+    use ::{ColumnInfo, ColumnType, Schema};
+    use ::expressions::Literal;
+    use ::queries::{FileScanNode, get_plan_results};
+    use ::storage::{TableManager, TupleLiteral};
+    use ::storage::storage_manager::buffer_manager;
+
+    fn create_table(dir: &Path) {
+        let file_manager = FileManager::with_directory(dir).unwrap();
+        let schema = Schema::with_columns(vec![ColumnInfo::with_table_name(ColumnType::Integer, "A", "FOO")])
+            .unwrap();
+        TableManager::new().create_table(&file_manager, "FOO", schema).unwrap();
+        flush_all_pages().unwrap();
+    }
+
+    fn add_tuples(dir: &Path, values: ::std::ops::Range<i32>) {
+        let file_manager = FileManager::with_directory(dir).unwrap();
+        let table = TableManager::new().get_table(&file_manager, "FOO").unwrap();
+        for i in values {
+            table.add_tuple(TupleLiteral::from_iter(vec![Literal::Int(i)])).unwrap();
+        }
+    }
+
+    fn get_values(dir: &Path) -> Vec<Literal> {
+        let file_manager = FileManager::with_directory(dir).unwrap();
+        let table = TableManager::new().get_table(&file_manager, "FOO").unwrap();
+        let mut node = FileScanNode::new(table, None);
+        get_plan_results(&mut node).unwrap().iter().map(|tuple| tuple[0].clone()).collect()
+    }
+
+    /// Simulates a crash by dropping the cached pages of the table without writing them.
+    fn crash(dir: &Path) {
+        buffer_manager().remove_file(dir.join("FOO.tbl"));
+    }
+
+    #[test]
+    fn test_rollback() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        start_up(dir.path()).unwrap();
+        create_table(dir.path());
+
+        begin_transaction(dir.path()).unwrap();
+        assert_eq!(Err(Error::TransactionInProgress), begin_transaction(dir.path()));
+        add_tuples(dir.path(), 0..10);
+        commit_transaction(dir.path()).unwrap();
+
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 10..20);
+        assert_eq!(20, get_values(dir.path()).len());
+        rollback_transaction(dir.path()).unwrap();
+        assert_eq!((0..10).map(Literal::Int).collect::<Vec<_>>(), get_values(dir.path()));
+        assert_eq!(Err(Error::NoTransactionInProgress), rollback_transaction(dir.path()));
+    }
+
+    #[test]
+    fn test_recovery() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        start_up(dir.path()).unwrap();
+        create_table(dir.path());
+
+        // Some of the uncommitted tuples are written to the table file before the crash.
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 0..50);
+        commit_transaction(dir.path()).unwrap();
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 50..60);
+        flush_all_pages().unwrap();
+        add_tuples(dir.path(), 60..70);
+        crash(dir.path());
+        assert_eq!(60, get_values(dir.path()).len());
+
+        start_up(dir.path()).unwrap();
+        assert_eq!((0..50).map(Literal::Int).collect::<Vec<_>>(), get_values(dir.path()));
+        assert_eq!(vec![1], find_wal_files(dir.path()).unwrap());
+
+        // The committed tuples never make it to the table file before the crash.
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 100..110);
+        commit_transaction(dir.path()).unwrap();
+        crash(dir.path());
+        assert_eq!(50, get_values(dir.path()).len());
+
+        start_up(dir.path()).unwrap();
+        let mut expected: Vec<Literal> = (0..50).map(Literal::Int).collect();
+        expected.extend((100..110).map(Literal::Int));
+        assert_eq!(expected, get_values(dir.path()));
+
+        // Recovering again doesn't change anything.
+        start_up(dir.path()).unwrap();
+        assert_eq!(expected, get_values(dir.path()));
+    }
+
+    fn get_wal_size(dir: &Path) -> u64 {
+        find_wal_files(dir).unwrap().iter().map(|&file_no| {
+            fs::metadata(dir.join(get_wal_file_name(file_no))).unwrap().len()
+        }).sum()
+    }
+
+    #[test]
+    fn test_checkpoint() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        start_up(dir.path()).unwrap();
+        create_table(dir.path());
+
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 0..50);
+        assert_eq!(Ok(false), checkpoint(dir.path()));
+        commit_transaction(dir.path()).unwrap();
+        let wal_size = get_wal_size(dir.path());
+        assert!(wal_size > OFFSET_FIRST_RECORD as u64);
+
+        // The log is removed, and the committed tuples survive a crash without it.
+        assert_eq!(Ok(true), checkpoint(dir.path()));
+        assert_eq!(0, get_wal_size(dir.path()));
+        assert_eq!(Ok(false), checkpoint(dir.path()));
+        crash(dir.path());
+        start_up(dir.path()).unwrap();
+        assert_eq!((0..50).map(Literal::Int).collect::<Vec<_>>(), get_values(dir.path()));
+
+        // A checkpoint is taken when a commit takes the log past the checkpoint size.
+        with_manager(dir.path(), |manager| {
+            manager.checkpoint_size = wal_size / 2;
+            Ok(())
+        }).unwrap();
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 50..55);
+        commit_transaction(dir.path()).unwrap();
+        assert!(get_wal_size(dir.path()) > 0);
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 55..100);
+        commit_transaction(dir.path()).unwrap();
+        assert_eq!(0, get_wal_size(dir.path()));
+
+        // Later transactions are still logged and recovered.
+        begin_transaction(dir.path()).unwrap();
+        add_tuples(dir.path(), 100..110);
+        commit_transaction(dir.path()).unwrap();
+        crash(dir.path());
+        start_up(dir.path()).unwrap();
+        assert_eq!((0..110).map(Literal::Int).collect::<Vec<_>>(), get_values(dir.path()));
+    }
+}
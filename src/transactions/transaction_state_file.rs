@@ -0,0 +1,128 @@
+//! This module provides the transaction-state file, which persists the information the transaction
+//! manager needs across restarts.
+//!
+//! The file starts with the usual two-byte database file header, followed by the ID of the next
+//! transaction, the LSN that recovery starts reading the write-ahead log from, and the LSN of the
+//! commit record of the last committed transaction.
+
+use std::fs::{File, OpenOptions};
+use std::io::{self, Cursor};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::storage::DBFileType;
+use ::storage::dbfile::{encode_pagesize, get_default_pagesize};
+use ::transactions::{Error, LogSequenceNumber};
+
+/// The name of the transaction-state file in the data directory.
+pub const TXNSTATE_FILENAME: &'static str = "txnstate.dat";
+
+/// The size of the transaction-state file: the header, the next transaction ID and two LSNs.
+const TXNSTATE_FILE_SIZE: usize = 2 + 4 + 6 + 6;
+
+/// The contents of the transaction-state file of a data directory.
+#[derive(Clone, Debug, PartialEq)]
+pub struct TransactionStateFile {
+    path: PathBuf,
+    /// The ID of the next transaction to start.
+    pub next_txn_id: u32,
+    /// The LSN that recovery starts reading the write-ahead log from. Every change logged before
+    /// this LSN is known to be in the data files.
+    pub first_lsn: LogSequenceNumber,
+    /// The LSN of the commit record of the last committed transaction, or "no LSN" if no
+    /// transaction has committed yet.
+    pub last_committed_lsn: LogSequenceNumber,
+}
+
+fn read_lsn<R: Read>(reader: &mut R) -> io::Result<LogSequenceNumber> {
+    let log_file_no = try!(reader.read_u16::<BigEndian>());
+    let file_offset = try!(reader.read_u32::<BigEndian>());
+    Ok(LogSequenceNumber::new(log_file_no, file_offset))
+}
+
+impl TransactionStateFile {
+    /// Instantiate the transaction state of a data directory. Nothing is written until the state
+    /// is saved.
+    ///
+    /// # Arguments
+    /// * dir - The data directory.
+    /// * next_txn_id - The ID of the next transaction to start.
+    /// * first_lsn - The LSN that recovery starts reading the write-ahead log from.
+    pub fn new<P: AsRef<Path>>(dir: P, next_txn_id: u32, first_lsn: LogSequenceNumber) -> TransactionStateFile {
+        TransactionStateFile {
+            path: dir.as_ref().join(TXNSTATE_FILENAME),
+            next_txn_id: next_txn_id,
+            first_lsn: first_lsn,
+            last_committed_lsn: LogSequenceNumber::default(),
+        }
+    }
+
+    /// Reads the transaction-state file of a data directory, returning `None` if there isn't one.
+    ///
+    /// # Arguments
+    /// * dir - The data directory.
+    pub fn open<P: AsRef<Path>>(dir: P) -> Result<Option<TransactionStateFile>, Error> {
+        let path = dir.as_ref().join(TXNSTATE_FILENAME);
+        if !path.exists() {
+            return Ok(None);
+        }
+
+        let mut data = Vec::new();
+        try!(try!(File::open(&path)).read_to_end(&mut data));
+        if data.len() < TXNSTATE_FILE_SIZE || data[0] != DBFileType::TxnStateFile as u8 {
+            return Err(Error::InvalidTxnStateFile(path.display().to_string()));
+        }
+
+        let mut cursor = Cursor::new(&data[2..]);
+        let next_txn_id = try!(cursor.read_u32::<BigEndian>());
+        let first_lsn = try!(read_lsn(&mut cursor));
+        let last_committed_lsn = try!(read_lsn(&mut cursor));
+        Ok(Some(TransactionStateFile {
+            path: path,
+            next_txn_id: next_txn_id,
+            first_lsn: first_lsn,
+            last_committed_lsn: last_committed_lsn,
+        }))
+    }
+
+    /// Writes the transaction state to the file, and forces it to disk.
+    pub fn save(&self) -> Result<(), Error> {
+        let page_size = try!(encode_pagesize(get_default_pagesize())
+            .map_err(|_| Error::InvalidTxnStateFile(self.path.display().to_string())));
+        let mut data = vec![DBFileType::TxnStateFile as u8, page_size];
+        try!(data.write_u32::<BigEndian>(self.next_txn_id));
+        for lsn in &[self.first_lsn, self.last_committed_lsn] {
+            try!(data.write_u16::<BigEndian>(lsn.log_file_no));
+            try!(data.write_u32::<BigEndian>(lsn.file_offset));
+        }
+
+        let mut file = try!(OpenOptions::new().write(true).create(true).open(&self.path));
+        try!(file.write_all(&data));
+        try!(file.sync_data());
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_save_and_open() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        assert_eq!(Ok(None), TransactionStateFile::open(dir.path()));
+
+        let mut state = TransactionStateFile::new(dir.path(), 5, LogSequenceNumber::new(2, 2));
+        state.save().unwrap();
+        assert_eq!(Ok(Some(state.clone())), TransactionStateFile::open(dir.path()));
+
+        state.next_txn_id = 9;
+        state.last_committed_lsn = LogSequenceNumber::new(2, 400);
+        state.save().unwrap();
+        assert_eq!(Ok(Some(state)), TransactionStateFile::open(dir.path()));
+    }
+}
@@ -0,0 +1,507 @@
+//! This module provides the write-ahead log, which records the changes made to data pages so that
+//! they can be redone or undone.
+//!
+//! The log is stored in a sequence of files named `wal-NNNNN.log` in the data directory. Each file
+//! starts with the usual two-byte database file header, and is followed by log records. Every
+//! record starts with its type and the ID of the transaction that wrote it. All records except the
+//! first record of a transaction also store the LSN of the transaction's previous record, so that
+//! a transaction's changes can be undone by following this chain backwards.
+//!
+//! A page update record stores the name of the file and the number of the page that changed,
+//! followed by a list of segments. Each segment is the offset and length of a changed range of
+//! bytes, followed by the old bytes (except in redo-only records) and then the new bytes.
+
+use std::fs::{self, File, OpenOptions};
+use std::io::{self, Cursor, SeekFrom};
+use std::io::prelude::*;
+use std::path::{Path, PathBuf};
+
+use byteorder::{BigEndian, ReadBytesExt, WriteBytesExt};
+
+use ::storage::{DBFileType, ReadNanoDBExt, WriteNanoDBExt};
+use ::storage::dbfile::{encode_pagesize, get_default_pagesize};
+use ::transactions::{Error, LogSequenceNumber, PAGE_LSN_SIZE};
+
+/// The offset in a log file of the first log record, directly after the file's header.
+pub const OFFSET_FIRST_RECORD: u32 = 2;
+
+/// Once a log file grows past this size, records are written to a new log file.
+pub const MAX_WAL_FILE_SIZE: u32 = 10 * 1024 * 1024;
+
+/// Changed ranges of a page that are separated by fewer than this many unchanged bytes are logged
+/// as one segment, since each segment has four bytes of overhead.
+const MIN_SEGMENT_GAP: usize = 8;
+
+/// Given the number of a log file, returns the name of the file.
+///
+/// # Arguments
+/// * file_no - The number of the log file.
+pub fn get_wal_file_name(file_no: u16) -> String {
+    format!("wal-{:05}.log", file_no)
+}
+
+/// Returns the numbers of the log files in a directory, in increasing order.
+///
+/// # Arguments
+/// * dir - The directory to look in.
+pub fn find_wal_files<P: AsRef<Path>>(dir: P) -> Result<Vec<u16>, Error> {
+    let mut file_nos = Vec::new();
+    for entry in try!(fs::read_dir(dir.as_ref())) {
+        let name = try!(entry).file_name();
+        let name = name.to_string_lossy();
+        if name.starts_with("wal-") && name.ends_with(".log") {
+            if let Ok(file_no) = name[4..(name.len() - 4)].parse::<u16>() {
+                file_nos.push(file_no);
+            }
+        }
+    }
+    file_nos.sort();
+    Ok(file_nos)
+}
+
+/// The types of records in the write-ahead log.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum WALRecordType {
+    /// The first record of a transaction.
+    StartTxn = 1,
+    /// A change to a page that can be redone or undone.
+    UpdatePage = 2,
+    /// A change to a page that can only be redone. These are written while rolling back a
+    /// transaction, and are never undone themselves.
+    UpdatePageRedoOnly = 3,
+    /// The transaction committed.
+    CommitTxn = 4,
+    /// The transaction was rolled back.
+    AbortTxn = 5,
+}
+
+impl WALRecordType {
+    fn from_u8(value: u8) -> Option<WALRecordType> {
+        match value {
+            1 => Some(WALRecordType::StartTxn),
+            2 => Some(WALRecordType::UpdatePage),
+            3 => Some(WALRecordType::UpdatePageRedoOnly),
+            4 => Some(WALRecordType::CommitTxn),
+            5 => Some(WALRecordType::AbortTxn),
+            _ => None,
+        }
+    }
+}
+
+/// A range of bytes in a page that was changed.
+#[derive(Clone, Debug, PartialEq)]
+pub struct PageSegment {
+    /// The offset of the range in the page.
+    pub offset: u16,
+    /// The bytes before the change. This is empty in redo-only records.
+    pub before: Vec<u8>,
+    /// The bytes after the change.
+    pub after: Vec<u8>,
+}
+
+impl PageSegment {
+    /// Computes the segments of a page that differ between two versions of its data. The LSN at
+    /// the end of the page is not compared.
+    ///
+    /// # Arguments
+    /// * before - The data of the page before the change.
+    /// * after - The data of the page after the change.
+    pub fn diff(before: &[u8], after: &[u8]) -> Vec<PageSegment> {
+        let end = after.len() - PAGE_LSN_SIZE;
+        let mut segments = Vec::new();
+        let mut i = 0;
+        while i < end {
+            if before[i] == after[i] {
+                i += 1;
+                continue;
+            }
+
+            let start = i;
+            let mut last_changed = i;
+            while i < end && i - last_changed < MIN_SEGMENT_GAP {
+                if before[i] != after[i] {
+                    last_changed = i;
+                }
+                i += 1;
+            }
+            segments.push(PageSegment {
+                offset: start as u16,
+                before: before[start..(last_changed + 1)].to_vec(),
+                after: after[start..(last_changed + 1)].to_vec(),
+            });
+        }
+        segments
+    }
+}
+
+/// A record in the write-ahead log.
+#[derive(Clone, Debug, PartialEq)]
+pub enum WALRecord {
+    /// The first record of a transaction.
+    StartTxn {
+        /// The ID of the transaction.
+        txn_id: u32,
+    },
+    /// A change to a page, which is undone by restoring the `before` bytes of its segments, or a
+    /// redo-only change if the segments have no `before` bytes.
+    UpdatePage {
+        /// The ID of the transaction.
+        txn_id: u32,
+        /// The LSN of the transaction's previous record.
+        prev_lsn: LogSequenceNumber,
+        /// Whether the change can only be redone.
+        redo_only: bool,
+        /// The name of the changed file.
+        file_name: String,
+        /// The number of the changed page.
+        page_no: u32,
+        /// The changed parts of the page.
+        segments: Vec<PageSegment>,
+    },
+    /// The transaction committed.
+    CommitTxn {
+        /// The ID of the transaction.
+        txn_id: u32,
+        /// The LSN of the transaction's previous record.
+        prev_lsn: LogSequenceNumber,
+    },
+    /// The transaction was rolled back.
+    AbortTxn {
+        /// The ID of the transaction.
+        txn_id: u32,
+        /// The LSN of the transaction's previous record.
+        prev_lsn: LogSequenceNumber,
+    },
+}
+
+fn write_lsn(buffer: &mut Vec<u8>, lsn: LogSequenceNumber) -> io::Result<()> {
+    try!(buffer.write_u16::<BigEndian>(lsn.log_file_no));
+    buffer.write_u32::<BigEndian>(lsn.file_offset)
+}
+
+fn read_lsn<R: Read>(reader: &mut R) -> io::Result<LogSequenceNumber> {
+    let log_file_no = try!(reader.read_u16::<BigEndian>());
+    let file_offset = try!(reader.read_u32::<BigEndian>());
+    Ok(LogSequenceNumber::new(log_file_no, file_offset))
+}
+
+impl WALRecord {
+    /// Returns the type of the record.
+    pub fn get_type(&self) -> WALRecordType {
+        match *self {
+            WALRecord::StartTxn { .. } => WALRecordType::StartTxn,
+            WALRecord::UpdatePage { redo_only: false, .. } => WALRecordType::UpdatePage,
+            WALRecord::UpdatePage { redo_only: true, .. } => WALRecordType::UpdatePageRedoOnly,
+            WALRecord::CommitTxn { .. } => WALRecordType::CommitTxn,
+            WALRecord::AbortTxn { .. } => WALRecordType::AbortTxn,
+        }
+    }
+
+    /// Returns the ID of the transaction that wrote the record.
+    pub fn get_txn_id(&self) -> u32 {
+        match *self {
+            WALRecord::StartTxn { txn_id } |
+            WALRecord::UpdatePage { txn_id, .. } |
+            WALRecord::CommitTxn { txn_id, .. } |
+            WALRecord::AbortTxn { txn_id, .. } => txn_id,
+        }
+    }
+
+    /// Returns the LSN of the transaction's previous record, which is the "no LSN" value for the
+    /// first record of a transaction.
+    pub fn get_prev_lsn(&self) -> LogSequenceNumber {
+        match *self {
+            WALRecord::StartTxn { .. } => LogSequenceNumber::default(),
+            WALRecord::UpdatePage { prev_lsn, .. } |
+            WALRecord::CommitTxn { prev_lsn, .. } |
+            WALRecord::AbortTxn { prev_lsn, .. } => prev_lsn,
+        }
+    }
+
+    /// Serializes the record into the bytes stored in the log.
+    pub fn to_bytes(&self) -> io::Result<Vec<u8>> {
+        let mut buffer = Vec::new();
+        try!(buffer.write_u8(self.get_type() as u8));
+        try!(buffer.write_u32::<BigEndian>(self.get_txn_id()));
+        if let WALRecord::StartTxn { .. } = *self {
+            return Ok(buffer);
+        }
+        try!(write_lsn(&mut buffer, self.get_prev_lsn()));
+
+        if let WALRecord::UpdatePage { redo_only, ref file_name, page_no, ref segments, .. } = *self {
+            try!(buffer.write_varchar255(file_name.as_str()));
+            try!(buffer.write_u32::<BigEndian>(page_no));
+            try!(buffer.write_u16::<BigEndian>(segments.len() as u16));
+            for segment in segments.iter() {
+                try!(buffer.write_u16::<BigEndian>(segment.offset));
+                try!(buffer.write_u16::<BigEndian>(segment.after.len() as u16));
+                if !redo_only {
+                    try!(buffer.write_all(&segment.before));
+                }
+                try!(buffer.write_all(&segment.after));
+            }
+        }
+        Ok(buffer)
+    }
+
+    /// Reads a record from the log. An `UnexpectedEof` error means the record is incomplete, e.g.
+    /// because the server crashed while writing it.
+    ///
+    /// # Arguments
+    /// * reader - The reader positioned at the start of the record.
+    /// * lsn - The LSN of the record, used for error reporting.
+    pub fn read_from<R: Read>(reader: &mut R, lsn: LogSequenceNumber) -> Result<WALRecord, Error> {
+        let type_id = try!(reader.read_u8());
+        let record_type = match WALRecordType::from_u8(type_id) {
+            Some(record_type) => record_type,
+            None => return Err(Error::InvalidRecordType(lsn, type_id)),
+        };
+        let txn_id = try!(reader.read_u32::<BigEndian>());
+        if record_type == WALRecordType::StartTxn {
+            return Ok(WALRecord::StartTxn { txn_id: txn_id });
+        }
+        let prev_lsn = try!(read_lsn(reader));
+
+        Ok(match record_type {
+            WALRecordType::CommitTxn => WALRecord::CommitTxn { txn_id: txn_id, prev_lsn: prev_lsn },
+            WALRecordType::AbortTxn => WALRecord::AbortTxn { txn_id: txn_id, prev_lsn: prev_lsn },
+            _ => {
+                let redo_only = record_type == WALRecordType::UpdatePageRedoOnly;
+                let file_name = try!(reader.read_varchar255());
+                let page_no = try!(reader.read_u32::<BigEndian>());
+                let num_segments = try!(reader.read_u16::<BigEndian>());
+                let mut segments = Vec::new();
+                for _ in 0..num_segments {
+                    let offset = try!(reader.read_u16::<BigEndian>());
+                    let length = try!(reader.read_u16::<BigEndian>()) as usize;
+                    let mut before = vec![0; if redo_only { 0 } else { length }];
+                    try!(reader.read_exact(&mut before));
+                    let mut after = vec![0; length];
+                    try!(reader.read_exact(&mut after));
+                    segments.push(PageSegment {
+                        offset: offset,
+                        before: before,
+                        after: after,
+                    });
+                }
+                WALRecord::UpdatePage {
+                    txn_id: txn_id,
+                    prev_lsn: prev_lsn,
+                    redo_only: redo_only,
+                    file_name: file_name,
+                    page_no: page_no,
+                    segments: segments,
+                }
+            }
+        })
+    }
+}
+
+/// The write-ahead log manager appends records to the log files of a data directory, and forces
+/// them to disk when required.
+#[derive(Debug)]
+pub struct WALManager {
+    dir: PathBuf,
+    current_file: Option<File>,
+    next_lsn: LogSequenceNumber,
+    durable_lsn: LogSequenceNumber,
+}
+
+impl WALManager {
+    /// Instantiate a new WAL manager.
+    ///
+    /// # Arguments
+    /// * dir - The directory the log files are stored in.
+    /// * next_lsn - The LSN the next record will be written at.
+    pub fn new<P: AsRef<Path>>(dir: P, next_lsn: LogSequenceNumber) -> WALManager {
+        WALManager {
+            dir: dir.as_ref().to_path_buf(),
+            current_file: None,
+            next_lsn: next_lsn,
+            durable_lsn: next_lsn,
+        }
+    }
+
+    /// Returns the LSN the next record will be written at.
+    pub fn get_next_lsn(&self) -> LogSequenceNumber {
+        self.next_lsn
+    }
+
+    fn get_file_path(&self, file_no: u16) -> PathBuf {
+        self.dir.join(get_wal_file_name(file_no))
+    }
+
+    /// Opens the log file that the next record will be written to, creating it if necessary. Any
+    /// data past the next LSN (e.g. an incomplete record) is discarded.
+    fn open_current_file(&mut self) -> Result<(), Error> {
+        let path = self.get_file_path(self.next_lsn.log_file_no);
+        let mut file = try!(OpenOptions::new().read(true).write(true).create(true).open(&path));
+        if try!(file.metadata()).len() < OFFSET_FIRST_RECORD as u64 {
+            debug!("Creating write-ahead log file {}.", path.display());
+            let page_size = try!(encode_pagesize(get_default_pagesize())
+                .map_err(|_| Error::InvalidWALFile(path.display().to_string())));
+            try!(file.write_all(&[DBFileType::WriteAheadLogFile as u8, page_size]));
+        }
+        try!(file.set_len(self.next_lsn.file_offset as u64));
+        try!(file.seek(SeekFrom::Start(self.next_lsn.file_offset as u64)));
+        self.current_file = Some(file);
+        Ok(())
+    }
+
+    /// Appends a record to the log, returning its LSN. The record is not guaranteed to be on disk
+    /// until the log is forced past it.
+    ///
+    /// # Arguments
+    /// * record - The record to write.
+    pub fn write_record(&mut self, record: &WALRecord) -> Result<LogSequenceNumber, Error> {
+        let bytes = try!(record.to_bytes());
+
+        if self.next_lsn.file_offset > OFFSET_FIRST_RECORD &&
+           self.next_lsn.file_offset as usize + bytes.len() > MAX_WAL_FILE_SIZE as usize {
+            // Make sure the earlier files are durable, so that forcing only needs the last one.
+            try!(self.force_all());
+            self.next_lsn = LogSequenceNumber::new(self.next_lsn.log_file_no + 1, OFFSET_FIRST_RECORD);
+            self.durable_lsn = self.next_lsn;
+            self.current_file = None;
+        }
+        if self.current_file.is_none() {
+            try!(self.open_current_file());
+        }
+
+        let lsn = self.next_lsn;
+        try!(self.current_file.as_mut().unwrap().write_all(&bytes));
+        self.next_lsn.file_offset += bytes.len() as u32;
+        debug!("Wrote {:?} record for transaction {} at {}.",
+               record.get_type(),
+               record.get_txn_id(),
+               lsn);
+        Ok(lsn)
+    }
+
+    /// Reads the record at the given LSN.
+    ///
+    /// # Arguments
+    /// * lsn - The LSN of the record.
+    pub fn read_record(&self, lsn: LogSequenceNumber) -> Result<WALRecord, Error> {
+        let mut file = try!(File::open(self.get_file_path(lsn.log_file_no)));
+        try!(file.seek(SeekFrom::Start(lsn.file_offset as u64)));
+        WALRecord::read_from(&mut io::BufReader::new(file), lsn)
+    }
+
+    /// Makes sure that every record up to and including the one at the given LSN is on disk.
+    ///
+    /// # Arguments
+    /// * lsn - The LSN of the last record that must be on disk.
+    pub fn force(&mut self, lsn: LogSequenceNumber) -> Result<(), Error> {
+        if lsn < self.durable_lsn {
+            return Ok(());
+        }
+        self.force_all()
+    }
+
+    /// Makes sure that every record written so far is on disk.
+    pub fn force_all(&mut self) -> Result<(), Error> {
+        if let Some(ref file) = self.current_file {
+            debug!("Forcing write-ahead log up to {}.", self.next_lsn);
+            try!(file.sync_data());
+        }
+        self.durable_lsn = self.next_lsn;
+        Ok(())
+    }
+
+    /// Reads every complete record in the given log files, in order. Returns the records with
+    /// their LSNs, and the LSN just past the last complete record. Reading stops at the first
+    /// incomplete or invalid record, since that is where the server crashed while writing.
+    ///
+    /// # Arguments
+    /// * file_nos - The numbers of the log files to read, in increasing order.
+    pub fn read_all_records(&self,
+                            file_nos: &[u16])
+                            -> Result<(Vec<(LogSequenceNumber, WALRecord)>, LogSequenceNumber), Error> {
+        let mut records = Vec::new();
+        let mut end_lsn = LogSequenceNumber::new(0, OFFSET_FIRST_RECORD);
+        for &file_no in file_nos.iter() {
+            let path = self.get_file_path(file_no);
+            let mut data = Vec::new();
+            try!(try!(File::open(&path)).read_to_end(&mut data));
+            if data.len() < OFFSET_FIRST_RECORD as usize ||
+               data[0] != DBFileType::WriteAheadLogFile as u8 {
+                return Err(Error::InvalidWALFile(path.display().to_string()));
+            }
+
+            let mut cursor = Cursor::new(data);
+            cursor.set_position(OFFSET_FIRST_RECORD as u64);
+            end_lsn = LogSequenceNumber::new(file_no, OFFSET_FIRST_RECORD);
+            while (cursor.position() as usize) < cursor.get_ref().len() {
+                match WALRecord::read_from(&mut cursor, end_lsn) {
+                    Ok(record) => {
+                        records.push((end_lsn, record));
+                        end_lsn.file_offset = cursor.position() as u32;
+                    }
+                    Err(e) => {
+                        warn!("Write-ahead log ends with an incomplete record at {}: {}", end_lsn, e);
+                        return Ok((records, end_lsn));
+                    }
+                }
+            }
+        }
+        Ok((records, end_lsn))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use tempdir::TempDir;
+
+    use super::*;
+
+    #[test]
+    fn test_diff() {
+        let before = vec![0; 64];
+        let mut after = before.clone();
+        after[3] = 1;
+        after[5] = 2;
+        after[40] = 3;
+        after[41] = 4;
+        // Changes to the page LSN are not logged.
+        after[63] = 5;
+
+        let segments = PageSegment::diff(&before, &after);
+        assert_eq!(vec![PageSegment { offset: 3, before: vec![0; 3], after: vec![1, 0, 2] },
+                        PageSegment { offset: 40, before: vec![0; 2], after: vec![3, 4] }],
+                   segments);
+        assert!(PageSegment::diff(&before, &before).is_empty());
+    }
+
+    #[test]
+    fn test_write_and_read_records() {
+        let dir = TempDir::new("test_dbfiles").unwrap();
+        let mut wal = WALManager::new(dir.path(), LogSequenceNumber::new(0, OFFSET_FIRST_RECORD));
+
+        let start = WALRecord::StartTxn { txn_id: 3 };
+        let start_lsn = wal.write_record(&start).unwrap();
+        assert_eq!(LogSequenceNumber::new(0, OFFSET_FIRST_RECORD), start_lsn);
+        let update = WALRecord::UpdatePage {
+            txn_id: 3,
+            prev_lsn: start_lsn,
+            redo_only: false,
+            file_name: "FOO.tbl".into(),
+            page_no: 7,
+            segments: vec![PageSegment { offset: 10, before: vec![1, 2], after: vec![3, 4] }],
+        };
+        let update_lsn = wal.write_record(&update).unwrap();
+        let commit = WALRecord::CommitTxn { txn_id: 3, prev_lsn: update_lsn };
+        let commit_lsn = wal.write_record(&commit).unwrap();
+        wal.force(commit_lsn).unwrap();
+
+        assert_eq!(update, wal.read_record(update_lsn).unwrap());
+        assert_eq!(vec![0], find_wal_files(dir.path()).unwrap());
+
+        // An incomplete record at the end of the log is ignored.
+        let end_lsn = wal.get_next_lsn();
+        wal.current_file.as_mut().unwrap().write_all(&[WALRecordType::AbortTxn as u8, 0, 0]).unwrap();
+        let (records, read_end_lsn) = wal.read_all_records(&[0]).unwrap();
+        assert_eq!(vec![(start_lsn, start), (update_lsn, update), (commit_lsn, commit)], records);
+        assert_eq!(end_lsn, read_end_lsn);
+    }
+}